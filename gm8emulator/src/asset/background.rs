@@ -8,3 +8,62 @@ pub struct Background {
     pub height: u32,
     pub atlas_ref: Option<AtlasRef>,
 }
+
+/// Makes the RGBA pixels of a gradient from one colour to another, like background_create_gradient.
+/// The kinds are horizontal, vertical, rectangle, ellipse, double horizontal and double vertical.
+/// Returns None if the image is too big to hold.
+pub fn make_gradient(width: i32, height: i32, col1: i32, col2: i32, kind: i32) -> Option<Vec<u8>> {
+    // how far across from col1 to col2 each pixel is, from 0 to 1
+    let across = |n: i32, len: i32| f64::from(n) / f64::from((len - 1).max(1));
+    let from_centre = |n: i32, len: i32| (across(n, len) * 2.0 - 1.0).abs();
+    let amount = |x: i32, y: i32| match kind {
+        0 => across(x, width),
+        1 => across(y, height),
+        2 => 1.0 - from_centre(x, width).max(from_centre(y, height)),
+        3 => 1.0 - from_centre(x, width).hypot(from_centre(y, height)).min(1.0),
+        4 => 1.0 - from_centre(x, width),
+        5 => 1.0 - from_centre(y, height),
+        _ => 0.0,
+    };
+    let size = usize::try_from(width).ok()?.checked_mul(usize::try_from(height).ok()?)?.checked_mul(4)?;
+    let mut data = Vec::new();
+    data.try_reserve_exact(size).ok()?;
+    for y in 0..height {
+        for x in 0..width {
+            let t = amount(x, y);
+            for shift in [0, 8, 16] {
+                let (c1, c2) = (f64::from((col1 >> shift) & 0xFF), f64::from((col2 >> shift) & 0xFF));
+                data.push((c1 + (c2 - c1) * t).round() as u8);
+            }
+            data.push(0xFF);
+        }
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::make_gradient;
+
+    #[test]
+    fn gradients() {
+        // red (in BGR) to blue, across and down
+        assert_eq!(
+            make_gradient(3, 1, 0x0000FF, 0xFF0000, 0),
+            Some(vec![255, 0, 0, 255, 128, 0, 128, 255, 0, 0, 255, 255]),
+        );
+        assert_eq!(make_gradient(1, 2, 0x0000FF, 0xFF0000, 1), Some(vec![255, 0, 0, 255, 0, 0, 255, 255]));
+        // the double ones go to col2 in the middle and back
+        assert_eq!(
+            make_gradient(3, 1, 0x000000, 0xFFFFFF, 4),
+            Some(vec![0, 0, 0, 255, 255, 255, 255, 255, 0, 0, 0, 255]),
+        );
+        assert_eq!(make_gradient(1, 1, 0x000000, 0xFFFFFF, 2), Some(vec![0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn gradient_too_big() {
+        assert_eq!(make_gradient(-1, 5, 0, 0, 0), None);
+        assert_eq!(make_gradient(i32::MAX, i32::MAX, 0, 0, 0), None);
+    }
+}
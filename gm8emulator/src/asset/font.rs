@@ -1,5 +1,5 @@
 use crate::{
-    asset::Sprite,
+    asset::{sprite, Sprite},
     gml,
     render::{
         atlas::{AtlasBuilder, AtlasRef},
@@ -7,6 +7,7 @@ use crate::{
     },
 };
 use encoding_rs::Encoding;
+use image::{Rgba, RgbaImage};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -58,14 +59,14 @@ impl Font {
     }
 }
 
-pub fn load_default_font(atlases: &mut AtlasBuilder) -> Result<Font, String> {
+/// Reads the glyphs in arimo.dat, which cover 0x20 to 0x7f, as (offset, distance, width, height, alpha values).
+fn default_font_glyphs() -> Vec<(i32, i32, u32, u32, &'static [u8])> {
     // In GM8, the default font is Arial at size 12, but Arial is nonfree,
     // so we instead went for a free alternative called Arimo, under Apache 2.0. https://fonts.google.com/specimen/Arimo
     // arimo.dat was generated by importing Arimo into GM8 and exporting the resulting font data.
     // The `offset` field was tweaked to be closer to Arial's.
-    let data = include_bytes!("../../data/arimo.dat");
-    let mut chars = Vec::with_capacity(0x60);
-    let mut tallest_char_height = 0;
+    let data: &'static [u8] = include_bytes!("../../data/arimo.dat");
+    let mut glyphs = Vec::with_capacity(0x60);
     let mut cursor = 0;
     for _ in 0..0x60 {
         let offset = data[cursor] as i8 as i32;
@@ -73,18 +74,26 @@ pub fn load_default_font(atlases: &mut AtlasBuilder) -> Result<Font, String> {
         let width = data[cursor + 2] as u32;
         let height = data[cursor + 3] as u32;
         cursor += 4;
+        let size = (width * height) as usize;
+        glyphs.push((offset, distance, width, height, &data[cursor..cursor + size]));
+        cursor += size;
+    }
+    glyphs
+}
+
+fn white_with_alpha(alpha: &[u8]) -> Vec<u8> {
+    alpha.iter().flat_map(|&a| [0xFF, 0xFF, 0xFF, a]).collect()
+}
+
+pub fn load_default_font(atlases: &mut AtlasBuilder) -> Result<Font, String> {
+    let mut chars = Vec::with_capacity(0x60);
+    let mut tallest_char_height = 0;
+    for (offset, distance, width, height, alpha) in default_font_glyphs() {
         if height > tallest_char_height {
             tallest_char_height = height;
         }
-        let size = (width * height) as usize;
-        let mut char = Vec::with_capacity(size * 4);
-        for i in 0..size {
-            char.extend_from_slice(&[0xFF, 0xFF, 0xFF]);
-            char.push(data[cursor + i]);
-        }
-        cursor += size;
         let atlas_ref = atlases
-            .texture(width as _, height as _, 0, 0, char.into_boxed_slice())
+            .texture(width as _, height as _, 0, 0, white_with_alpha(alpha).into_boxed_slice())
             .ok_or("Couldn't pack default font")?;
         chars.push(Character { offset, distance, atlas_ref });
     }
@@ -103,6 +112,62 @@ pub fn load_default_font(atlases: &mut AtlasBuilder) -> Result<Font, String> {
     })
 }
 
+/// Makes characters for font_add and font_replace, returning them along with the tallest character's height.
+/// System fonts can't be rendered here, so whatever font was asked for, the default font's glyphs are scaled to the
+/// requested size, and bold and italic are faked by thickening and slanting them. Characters it doesn't have are
+/// left blank. Every character gets its own texture, so the font owns its graphics.
+pub fn create_chars_from_default(
+    size: u32,
+    bold: bool,
+    italic: bool,
+    first: u8,
+    last: u8,
+    renderer: &mut Renderer,
+) -> Result<(Box<[Character]>, u32), String> {
+    let glyphs = default_font_glyphs();
+    let scale = |n: i32| (n * size as i32 + 6).div_euclid(12);
+    let mut chars = Vec::with_capacity(usize::from(last.saturating_sub(first)) + 1);
+    let mut tallest_char_height = 0;
+    for c in first..=last {
+        let glyph = c.checked_sub(0x20).and_then(|i| glyphs.get(usize::from(i))).filter(|g| g.2 * g.3 != 0);
+        let (mut offset, distance) = match glyph {
+            Some(&(offset, distance, ..)) => (scale(offset), scale(distance)),
+            None => (scale(glyphs[0].0), 0), // as wide as a space
+        };
+        let mut image = match glyph {
+            Some(&(_, _, width, height, alpha)) => {
+                let mut image = RgbaImage::from_vec(width, height, white_with_alpha(alpha)).unwrap();
+                sprite::scale(&mut image, (scale(width as i32) as u32).max(1), (scale(height as i32) as u32).max(1));
+                image
+            },
+            None => RgbaImage::new(1, 1),
+        };
+        let (width, height) = image.dimensions();
+        if glyph.is_some() && bold {
+            image = RgbaImage::from_fn(width + 1, height, |x, y| {
+                let alpha = |x: u32| if x < width { image.get_pixel(x, y)[3] } else { 0 };
+                Rgba([0xFF, 0xFF, 0xFF, alpha(x).max(x.checked_sub(1).map(alpha).unwrap_or(0))])
+            });
+            offset += 1;
+        }
+        if glyph.is_some() && italic && height > 1 {
+            let (width, slant) = (image.width(), height / 5);
+            image = RgbaImage::from_fn(width + slant, height, |x, y| {
+                let shift = slant * (height - 1 - y) / (height - 1);
+                match x.checked_sub(shift).filter(|&x| x < width) {
+                    Some(x) => *image.get_pixel(x, y),
+                    None => Rgba([0xFF, 0xFF, 0xFF, 0]),
+                }
+            });
+        }
+        tallest_char_height = tallest_char_height.max(height);
+        let (width, height) = image.dimensions();
+        let atlas_ref = renderer.upload_sprite(image.into_raw().into_boxed_slice(), width as _, height as _, 0, 0)?;
+        chars.push(Character { offset, distance, atlas_ref });
+    }
+    Ok((chars.into_boxed_slice(), tallest_char_height))
+}
+
 pub fn create_chars_from_sprite(sprite: &Sprite, prop: bool, sep: i32, renderer: &Renderer) -> Box<[Character]> {
     let mut chars = Vec::with_capacity(sprite.frames.len());
     if prop {
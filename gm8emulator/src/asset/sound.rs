@@ -1,5 +1,5 @@
 use crate::{
-    game::audio::{Mp3Handle, WavHandle},
+    game::audio::{MidiHandle, Mp3Handle, WavHandle},
     gml,
     math::Real,
 };
@@ -17,5 +17,6 @@ pub struct Sound {
 pub enum FileType {
     Mp3(Mp3Handle),
     Wav(WavHandle),
+    Midi(MidiHandle),
     None,
 }
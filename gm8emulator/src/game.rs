@@ -1,19 +1,28 @@
 pub mod audio;
 pub mod background;
+pub mod dialog;
 pub mod draw;
+pub mod dump;
 pub mod events;
 pub mod external;
 pub mod gm_save;
+pub mod highscore;
 pub mod includedfile;
 pub mod model;
 pub mod movement;
+pub mod mplay;
 pub mod particle;
 pub mod pathfinding;
 pub mod platform;
+pub mod profiler;
 pub mod recording;
+pub mod registry;
 pub mod replay;
 pub mod savestate;
+pub mod screenshot;
+pub mod splash;
 pub mod surface;
+pub mod tracer;
 pub mod transition;
 pub mod view;
 
@@ -35,7 +44,7 @@ use crate::{
     },
     game::gm_save::GMSave,
     game::replay::FrameRng,
-    gml::{self, ds, ev, file, rand::Random, runtime::Instruction, Compiler, Context},
+    gml::{self, ds, ev, file, rand::Random, runtime::Instruction, vfs, Compiler, Context},
     handleman::{HandleArray, HandleList},
     input::{self, Input},
     instance::{DummyFieldHolder, Instance, InstanceState},
@@ -65,7 +74,6 @@ use std::{
     fs::File,
     io::Write,
     path::PathBuf,
-    process::{Child, Command, Stdio},
     rc::Rc,
     time::{Duration, Instant},
 };
@@ -75,8 +83,10 @@ pub struct Game {
     pub compiler: Compiler,
     pub text_files: HandleArray<file::TextHandle, 32>,
     pub binary_files: HandleArray<file::BinaryHandle, 32>,
+    pub vfs: vfs::FileSystem,
     pub rand: Random,
     pub input: Input,
+    pub joysticks: platform::Joysticks, // only read in normal play, replays have their own joystick inputs
     pub assets: Assets,
     pub event_holders: [IndexMap<u32, Rc<RefCell<Vec<ID>>>>; 12],
     pub custom_draw_objects: HashSet<ID>,
@@ -86,6 +96,7 @@ pub struct Game {
 
     pub library_init_strings: Vec<Box<[u8]>>,
     pub extension_functions: Vec<Option<ExtensionFunction>>,
+    pub extension_function_names: Vec<String>, // "package/file/function" for each extension function
     pub extension_initializers: Vec<usize>,
     pub extension_finalizers: Vec<usize>,
 
@@ -123,6 +134,10 @@ pub struct Game {
     pub draw_alpha: Real,
     pub draw_halign: draw::Halign,
     pub draw_valign: draw::Valign,
+    pub message_settings: dialog::MessageSettings,
+    pub splash_settings: splash::SplashSettings,
+    pub splash_overlay: Option<splash::SplashOverlay>,
+    pub game_info: splash::GameInfo,
     pub surfaces: Vec<Option<surface::Surface>>,
     pub surface_target: Option<i32>,
     pub models: Vec<Option<model::Model>>,
@@ -162,19 +177,27 @@ pub struct Game {
     pub open_ini: Option<(ini::Ini, gml::String)>, // keep the filename for writing
     pub open_file: Option<file::TextHandle>,       // for legacy file functions from GM <= 5.1
     pub file_finder: Option<Box<dyn Iterator<Item = PathBuf>>>,
+    pub registry: registry::Registry,
+    pub registry_file: Option<PathBuf>, // if set, registry changes get written here
+    pub highscores: highscore::Highscores,
     pub spoofed_time_nanos: Option<u128>, // use this instead of real time if this is set
     pub parameters: Vec<String>,
     pub encoding: &'static Encoding,
 
     pub esc_close_game: bool,
+    pub f1_help_menu: bool,
 
     pub play_type: PlayType,
     pub stored_events: VecDeque<replay::Event>,
     pub frame_limiter: bool,   // whether to limit FPS of gameplay by room_speed
     pub frame_limit_at: usize, // on which frame to start limiting FPS
-    pub ffmpeg_dumper: Option<Child>,
+    pub dumper: Option<dump::Dumper>,
+    pub screenshotter: Option<screenshot::Screenshotter>,
 
     pub audio: audio::AudioManager,
+    pub mplay: mplay::Multiplayer,
+    pub profiler: profiler::Profiler,
+    pub tracer: tracer::Tracer,
 
     // winit windowing
     pub window: Window,
@@ -183,6 +206,7 @@ pub struct Game {
     pub window_cursor_gml: i32,
     pub window_icons: bool,
     pub window_inner_size: (u32, u32),
+    pub ui_window_size: (u32, u32), // size of the TAS UI window in record mode, where dialogs get drawn
     pub window_offset_spoof: (i32, i32),
     pub window_is_logical_dpi: bool,
     pub window_sizeable: bool,
@@ -289,8 +313,9 @@ impl Game {
         encoding: &'static Encoding,
         frame_limiter: bool,
         frame_limit_at: usize,
-        dump_audiovideo: bool,
+        dump_settings: Option<dump::DumpSettings>,
         play_type: PlayType,
+        mut externals: external::ExternalManager,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         // Parse file path
         let mut file_path2 = file_path.clone();
@@ -329,6 +354,7 @@ impl Game {
             constants,
             extensions,
             fonts,
+            help_dialog,
             included_files,
             last_instance_id,
             last_tile_id,
@@ -352,6 +378,16 @@ impl Game {
             gm8exe::GameVersion::GameMaker8_1 => Version::GameMaker8_1,
         };
 
+        let game_info = splash::GameInfo {
+            caption: help_dialog.caption.into(),
+            colour: (help_dialog.bg_colour.as_decimal() & 0xFFFFFF) as i32,
+            new_window: help_dialog.new_window,
+            size: (help_dialog.width as i32, help_dialog.height as i32),
+            border: help_dialog.border,
+            freeze: help_dialog.freeze_game,
+            text: help_dialog.info.into(),
+        };
+
         // If there are no rooms, you can't build a GM8 game. Fatal error.
         // We need a lot of the initialization info from the first room,
         // the window size, and title, etc. is based on it.
@@ -438,7 +474,7 @@ impl Game {
                         },
                     },
                 };
-                let mut i = IncludedFile {
+                IncludedFile {
                     name: match decode_str_maybe(i.file_name.0.to_vec()) {
                         Some(s) => s,
                         None => {
@@ -450,12 +486,9 @@ impl Game {
                     overwrite: i.overwrite_file,
                     free_after_export: i.free_memory,
                     remove_at_end: i.remove_at_end,
-                };
-                i.export(temp_directory.clone(), program_directory.to_string().into())?;
-                Ok(i)
+                }
             })
-            .collect::<Result<Vec<_>, std::io::Error>>()
-            .expect("failed to extract included files");
+            .collect::<Vec<_>>();
 
         // Set up a GML compiler
         let mut compiler = Compiler::new();
@@ -513,14 +546,15 @@ impl Game {
         let mut extension_finalizers = Vec::new();
         for extension in extensions.iter() {
             for file in extension.files.iter() {
+                // A file's init and final functions are called by name, so they have to be one of its own functions.
+                // GameMaker takes the first one with that name, and skips it if there isn't one.
+                let find = |name: &PascalString| {
+                    file.functions.iter().position(|f| !name.0.is_empty() && f.name.0 == name.0).map(|i| fn_index + i)
+                };
+                extension_initializers.extend(find(&file.initializer));
+                extension_finalizers.extend(find(&file.finalizer));
                 for function in file.functions.iter() {
                     compiler.register_extension_function(function.name.0.as_ref().into(), fn_index);
-                    if function.name.0 == file.initializer.0 {
-                        extension_initializers.push(fn_index);
-                    }
-                    if function.name.0 == file.finalizer.0 {
-                        extension_finalizers.push(fn_index);
-                    }
                     fn_index += 1;
                 }
                 for constant in file.consts.iter() {
@@ -588,33 +622,8 @@ impl Game {
         }
 
         let window = builder.build()?;
-        let ffmpeg_dumper = dump_audiovideo.then(|| {
-            Command::new("ffmpeg")
-                .arg("-y")
-                .arg("-f")
-                .arg("rawvideo")
-                .arg("-pixel_format")
-                .arg("rgba")
-                .arg("-video_size")
-                .arg(format!("{}x{}", width, height))
-                .arg("-framerate")
-                .arg("50")
-                .arg("-an")
-                .arg("-i")
-                .arg("-")
-                .arg("-c:v")
-                .arg("libx264rgb")
-                .arg("-preset")
-                .arg("veryslow")
-                .arg("-qp")
-                .arg("0")
-                .arg("dump.mkv")
-                .stdin(Stdio::piped())
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .spawn()
-                .expect("Failed to open FFmpeg stdin")
-        });
+        let dump_audiovideo = dump_settings.is_some();
+        let dumper = dump_settings.map(dump::Dumper::new).transpose()?;
         // Set up audio manager
         let mut audio = audio::AudioManager::new(play_type != PlayType::Record, dump_audiovideo);
 
@@ -636,19 +645,21 @@ impl Game {
 
         let default_font = asset::font::load_default_font(&mut atlases)?;
 
-        let mut externals = external::ExternalManager::new(play_type == PlayType::Record);
-
         // Code compiling starts here. The order in which things are compiled is important for
         // keeping savestates compatible. This isn't 100% accurate right now, but it's mostly right.
 
         let mut extension_functions = Vec::with_capacity(
             extensions.iter().map(|x| x.files.iter().map(|f| f.functions.len()).sum::<usize>()).sum::<usize>(),
         );
+        let mut extension_function_names = Vec::with_capacity(extension_functions.capacity());
         for extension in extensions.iter() {
             temp_directory.push(&*String::from_utf8_lossy(extension.folder_name.0.as_ref()));
             std::fs::create_dir_all(&temp_directory)?;
 
             for file in extension.files.iter() {
+                extension_function_names.extend(
+                    file.functions.iter().map(|f| format!("{}/{}/{}", extension.name, file.name, f.name)),
+                );
                 match file.kind {
                     FileKind::DynamicLibrary => {
                         // DLL - save this to disk then define all the externals in it
@@ -667,7 +678,7 @@ impl Game {
                                 Version::GameMaker8_0 => encoding,
                                 Version::GameMaker8_1 => encoding_rs::UTF_8,
                             });
-                            match externals.define(external::dll::ExternalSignature {
+                            let signature = external::dll::ExternalSignature {
                                 dll: dll.to_string(),
                                 symbol: sym.to_string(),
                                 call_conv: match function.convention {
@@ -687,7 +698,8 @@ impl Game {
                                     FunctionValueKind::GMReal => external::dll::ValueType::Real,
                                     FunctionValueKind::GMString => external::dll::ValueType::Str,
                                 },
-                            }) {
+                            };
+                            match externals.define(signature, &mut compiler) {
                                 Ok(id) => extension_functions.push(Some(ExtensionFunction::Dll(sym.into(), id))),
                                 Err(e) => {
                                     println!(
@@ -745,9 +757,9 @@ impl Game {
                             }
                         }
                     },
-                    FileKind::ActionLibrary => (), // Lib - don't think we need to do anything with this
-                    FileKind::Other => {
-                        // Other - just save this to disk
+                    FileKind::ActionLibrary | FileKind::Other => {
+                        // Lib or other - just save this to disk, it might be used by one of the DLLs
+                        // (action libraries are only used in the editor, but GameMaker extracts them anyway)
                         temp_directory.push(&*String::from_utf8_lossy(file.name.0.as_ref()));
                         let mut f = File::create(&temp_directory)?;
                         f.write_all(&file.contents)?;
@@ -782,10 +794,18 @@ impl Game {
                                 data,
                                 sound_id as i32,
                                 b.volume,
+                                u32::from(b.fx.chorus) * audio::effects::CHORUS
+                                    | u32::from(b.fx.echo) * audio::effects::ECHO
+                                    | u32::from(b.fx.flanger) * audio::effects::FLANGER
+                                    | u32::from(b.fx.gargle) * audio::effects::GARGLE
+                                    | u32::from(b.fx.reverb) * audio::effects::REVERB,
                                 b.kind == SoundKind::ThreeDimensional,
                                 b.kind == SoundKind::Multimedia,
                             ) {
-                                Some(x) => FileType::Wav(x),
+                                Some(mut x) => {
+                                    x.set_spatial(|s| s.pan = b.pan);
+                                    FileType::Wav(x)
+                                },
                                 None => {
                                     println!(
                                         "WARNING: invalid wav data in sound '{}'",
@@ -794,6 +814,16 @@ impl Game {
                                     FileType::None
                                 },
                             },
+                            b".mid" | b".midi" => match audio.add_midi(data, sound_id as i32) {
+                                Some(x) => FileType::Midi(x),
+                                None => {
+                                    println!(
+                                        "WARNING: invalid midi data in sound '{}'",
+                                        String::from_utf8_lossy(b.name.0.as_ref())
+                                    );
+                                    FileType::None
+                                },
+                            },
                             _ => FileType::None,
                         },
                         None => FileType::None,
@@ -1228,16 +1258,19 @@ impl Game {
             compiler,
             text_files: HandleArray::new(),
             binary_files: HandleArray::new(),
+            vfs: vfs::FileSystem::new(PathBuf::from(program_directory)),
             rand,
             renderer: renderer,
             background_colour: settings.clear_colour.into(),
             library_init_strings: library_init_strings.into_iter().map(|x| x.0).collect(),
             extension_functions,
+            extension_function_names,
             extension_initializers,
             extension_finalizers,
             externals,
             surface_fix: false,
             input: Input::new(),
+            joysticks: platform::Joysticks::new(),
             assets: Assets { backgrounds, fonts, objects, paths, rooms, scripts, sprites, sounds, timelines, triggers },
             event_holders,
             custom_draw_objects,
@@ -1280,6 +1313,10 @@ impl Game {
             draw_alpha: Real::from(1.0),
             draw_halign: draw::Halign::Left,
             draw_valign: draw::Valign::Top,
+            message_settings: Default::default(),
+            splash_settings: Default::default(),
+            splash_overlay: None,
+            game_info,
             surfaces: Vec::new(),
             surface_target: None,
             models: Vec::new(),
@@ -1309,15 +1346,20 @@ impl Game {
             open_ini: None,
             open_file: None,
             file_finder: None,
+            registry: registry::Registry::new(),
+            registry_file: None,
+            highscores: highscore::Highscores::new(),
             spoofed_time_nanos: Some(0),
             frame_limiter,
             frame_limit_at,
-            ffmpeg_dumper,
+            dumper,
+            screenshotter: None,
             fps: 0,
             frame_counter: 0,
             parameters: game_arguments,
             encoding,
             esc_close_game: settings.esc_close_game,
+            f1_help_menu: settings.f1_help_menu,
             score_capt_d: true,
             has_set_show_score: false,
             lives_capt_d: false,
@@ -1325,6 +1367,9 @@ impl Game {
             error_occurred: false,
             error_last: "".to_string().into(),
             audio,
+            mplay: mplay::Multiplayer::default(),
+            profiler: profiler::Profiler::new(false),
+            tracer: tracer::Tracer::new(),
             window,
             window_border,
             window_icons,
@@ -1341,6 +1386,7 @@ impl Game {
             window_caption: room1_caption.clone(),
             window_cursor_gml: gml::mappings::constants::CR_DEFAULT as _,
             window_inner_size: (width, height),
+            ui_window_size: (width, height),
             window_is_logical_dpi: false,
             window_offset_spoof: (0, 0),
             window_sizeable: settings.allow_resize,
@@ -1606,6 +1652,7 @@ impl Game {
         // Update some stored vars
         let mut room_state = room_state;
         std::mem::swap(&mut self.room, &mut room_state);
+        self.trace_room_change(room_id);
         if self.room.persistent && !self.game_start {
             self.stored_rooms.push(room_state);
         }
@@ -1661,6 +1708,10 @@ impl Game {
             self.room.instance_list.insert(instance);
         }
 
+        for (handle, _) in &new_handles {
+            self.trace_instance_created(*handle);
+        }
+
         for (handle, instance) in &new_handles {
             if self.room.instance_list.get(*handle).is_active() {
                 if self.swap_creation_events {
@@ -1715,19 +1766,7 @@ impl Game {
             Ok(())
         } else {
             if self.play_type != PlayType::Record {
-                let w: i32 = self.window_inner_size.0.try_into().unwrap();
-                let h: i32 = self.window_inner_size.1.try_into().unwrap();
-                let pixels = self.renderer.get_pixels(0, 0, w, h);
-                if self.ffmpeg_dumper.is_some() {
-                    self.ffmpeg_dumper
-                        .as_mut()
-                        .unwrap()
-                        .stdin
-                        .as_mut()
-                        .expect("Failed to open stdin")
-                        .write_all(&pixels)
-                        .unwrap();
-                }
+                self.dump_room_start();
             }
             // Draw "frame 0", perform transition if applicable, and then return
             if self.auto_draw {
@@ -1750,7 +1789,6 @@ impl Game {
                     const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000u64 / 120);
                     let mut current_time = Instant::now();
                     let perspective = self.renderer.get_perspective();
-                    let mut current_frame_time: u32 = 119;
                     for i in 0..self.transition_steps + 1 {
                         let progress = Real::from(i) / self.transition_steps.into();
                         if self.surface_fix {
@@ -1766,7 +1804,7 @@ impl Game {
                         transition(self, trans_surf_old, trans_surf_new, width as _, height as _, progress)?;
                         if self.play_type != PlayType::Record {
                             self.renderer.present(width, height, self.scaling);
-                            self.dump_audiovideo_frame(&mut current_frame_time, 120);
+                            self.dump_transition_frame();
 
                             let diff = current_time.elapsed();
                             if let Some(dur) = FRAME_TIME.checked_sub(diff) {
@@ -1816,9 +1854,11 @@ impl Game {
         use std::io::Read;
         self.input.keyboard_clear_all();
         self.input.mouse_clear_all();
-        let mut file = std::fs::File::open(path)
-            .map(std::io::BufReader::new)
+        let data = self
+            .vfs
+            .read(&path.to_string_lossy())
             .map_err(|e| gml::Error::FunctionError("game_load".into(), format!("{}", e)))?;
+        let mut file = data.as_slice();
         let mut magnum = [0u8; 4];
         file.read(&mut magnum).map_err(|e| gml::Error::FunctionError("game_load".into(), format!("{}", e)))?;
         if magnum != [0x1d, 0x02, 0x00, 0x00] {
@@ -1834,13 +1874,39 @@ impl Game {
         Ok(Default::default())
     }
 
+    /// Applies the current step of any sound_fade in progress, based on the game clock.
+    fn update_sound_fades(&mut self) {
+        let nanos = self.spoofed_time_nanos.unwrap_or_else(gml::datetime::now_as_nanos);
+        for (sound_id, volume) in self.audio.update_fades(nanos) {
+            if let Some(sound) = self.assets.sounds.get_asset_mut(sound_id) {
+                use asset::sound::FileType;
+                match &mut sound.handle {
+                    FileType::Wav(handle) => handle.set_volume(volume),
+                    FileType::Mp3(handle) => handle.set_volume(volume),
+                    FileType::Midi(handle) => handle.set_volume(volume),
+                    FileType::None => (),
+                }
+            }
+        }
+    }
+
     /// Runs a frame loop and draws the screen. Exits immediately, without waiting for any FPS limitation.
     pub fn frame(&mut self) -> gml::Result<()> {
+        self.profiler.frame_begin();
+        self.tracer.frame_begin();
+
         if self.esc_close_game && self.input.keyboard_lastkey() == input::Button::Escape as u8 {
             self.scene_change = Some(SceneChange::End);
             return Ok(());
         }
 
+        if self.f1_help_menu && self.input.keyboard_check_pressed(input::Button::F1 as u8) {
+            self.show_game_info()?;
+        }
+        self.update_splash_overlay();
+        self.update_sound_fades();
+        self.mplay_live(|mplay| mplay.poll());
+
         // Update xprevious and yprevious for all instances
         let mut iter = self.room.instance_list.iter_by_drawing();
         while let Some(instance) = iter.next(&self.room.instance_list).map(|x| self.room.instance_list.get(x)) {
@@ -1866,8 +1932,9 @@ impl Game {
         while let Some(handle) = iter.next(&self.room.instance_list) {
             let instance = self.room.instance_list.get(handle);
             let object_index = instance.object_index.get();
+            let timeline_index = instance.timeline_index.get();
             if instance.timeline_running.get() {
-                if let Some(timeline) = self.assets.timelines.get_asset(instance.timeline_index.get()) {
+                if let Some(timeline) = self.assets.timelines.get_asset(timeline_index) {
                     let moments = timeline.moments.clone();
                     let timeline_len = Real::from(*moments.borrow().keys().max().unwrap_or(&0));
 
@@ -1883,11 +1950,12 @@ impl Game {
                                     instance.timeline_position.set(new_position)
                                 }
 
-                                for (_, tree) in moments
+                                for (moment, tree) in moments
                                     .borrow()
                                     .iter()
                                     .filter(|(&x, _)| Real::from(x) >= old_position && Real::from(x) < new_position)
                                 {
+                                    self.trace_timeline_moment(handle, timeline_index, *moment);
                                     self.execute_tree(tree.clone(), handle, handle, 0, 0, object_index)?;
                                 }
                             },
@@ -1898,12 +1966,13 @@ impl Game {
                                     instance.timeline_position.set(new_position)
                                 }
 
-                                for (_, tree) in moments
+                                for (moment, tree) in moments
                                     .borrow()
                                     .iter()
                                     .filter(|(&x, _)| Real::from(x) > new_position && Real::from(x) <= old_position)
                                     .rev()
                                 {
+                                    self.trace_timeline_moment(handle, timeline_index, *moment);
                                     self.execute_tree(tree.clone(), handle, handle, 0, 0, object_index)?;
                                 }
                             },
@@ -2063,12 +2132,84 @@ impl Game {
                         _ => (),
                     }
                 }
+                self.poll_joysticks();
             },
             _ => (),
         }
     }
 
+    /// Brings the joysticks' state up to date with the ones actually plugged in.
+    fn poll_joysticks(&mut self) {
+        for id in 0..input::JOYSTICK_COUNT {
+            let state = self.joysticks.poll(id);
+            self.input.joystick_set(id, state);
+        }
+    }
+
+    /// Calls an external function by its ID. Depending on the result mode, the results of calls to code on the host
+    /// get stored as replay events, or are taken from them without making the call.
     pub fn call_external(&mut self, id: i32, context: &mut Context, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let mode = self.externals.result_mode();
+        let (dll, symbol) = match self.externals.get_external(id) {
+            Some(ext)
+                if mode != external::ResultMode::Live
+                    && ext.call.is_host()
+                    && args.len() == ext.signature.type_args.len() =>
+            {
+                (external::dll_name(&ext.signature.dll).to_string(), ext.signature.symbol.clone())
+            },
+            _ => return self.call_external_live(id, context, args),
+        };
+        self.host_result(&dll, &symbol, args, |game| game.call_external_live(id, context, args))
+    }
+
+    /// Gets the result of something that depends on the host, such as a call into a real DLL. Depending on the
+    /// result mode, `live` is run and its result is stored as a replay event, or the result is taken from the replay.
+    pub fn host_result(
+        &mut self,
+        dll: &str,
+        symbol: &str,
+        args: &[gml::Value],
+        live: impl FnOnce(&mut Self) -> gml::Result<gml::Value>,
+    ) -> gml::Result<gml::Value> {
+        match self.externals.result_mode() {
+            external::ResultMode::Live => live(self),
+            external::ResultMode::Record => {
+                let result = live(self)?;
+                let event = replay::Event::External {
+                    dll: dll.into(),
+                    symbol: symbol.into(),
+                    args: args.to_vec(),
+                    result: result.clone(),
+                };
+                self.stored_events.push_back(event);
+                Ok(result)
+            },
+            external::ResultMode::Replay => match self.stored_events.pop_front() {
+                Some(replay::Event::External { dll: d, symbol: s, args: a, result })
+                    if d.eq_ignore_ascii_case(dll) && s == symbol && a == args =>
+                {
+                    Ok(result)
+                },
+                event => {
+                    let expected = match event {
+                        Some(replay::Event::External { dll: d, symbol: s, args: a, .. }) => {
+                            external::describe_call(&d, &s, &a)
+                        },
+                        Some(event) => format!("{:?}", event),
+                        None => "nothing".into(),
+                    };
+                    Err(gml::Error::ReplayError(format!(
+                        "external call {} diverged from the replay, which has {} stored here",
+                        external::describe_call(dll, symbol, args),
+                        expected,
+                    )))
+                },
+            },
+        }
+    }
+
+    fn call_external_live(&mut self, id: i32, context: &mut Context, args: &[gml::Value]) -> gml::Result<gml::Value> {
         use external::dll;
         if let Some(external) = self.externals.get_external(id) {
             if args.len() != external.signature.type_args.len() {
@@ -2098,12 +2239,39 @@ impl Game {
                     let args = convert_args();
                     Ok(self.externals.call_ipc(id as _, &args).into())
                 },
+                external::Call::Gml(instrs) => {
+                    let instrs = instrs.clone();
+                    self.call_external_gml(&instrs, context, args)
+                },
+                external::Call::Plugin(func) => Ok(func.call(args)),
+                external::Call::Replayed => unreachable!(),
+                external::Call::Interpreted(_) => {
+                    let args = convert_args();
+                    let symbol = external.signature.symbol.clone();
+                    self.externals.call_interpreted(id as _, &args).map_err(|e| gml::Error::ExternalFunction(symbol, e))
+                },
             }
         } else {
             Ok(Default::default()) // unfortunately required
         }
     }
 
+    /// Runs a GML stub standing in for an external function, passing it the call's arguments.
+    fn call_external_gml(
+        &mut self,
+        instrs: &[Instruction],
+        context: &mut Context,
+        args: &[gml::Value],
+    ) -> gml::Result<gml::Value> {
+        let mut new_args: [gml::Value; 16] = Default::default();
+        for (src, dest) in args.iter().zip(new_args.iter_mut()) {
+            *dest = src.clone();
+        }
+        let mut new_context = Context::copy_with_args(context, new_args, args.len());
+        self.execute(instrs, &mut new_context)?;
+        Ok(new_context.return_value)
+    }
+
     /// Runs an ExtensionFunction by its ID
     pub fn run_extension_function(
         &mut self,
@@ -2130,6 +2298,28 @@ impl Game {
         }
     }
 
+    /// Lists every extension function and how calling it works, one line each.
+    pub fn extension_function_report(&self) -> Vec<String> {
+        self.extension_functions
+            .iter()
+            .zip(&self.extension_function_names)
+            .map(|(function, name)| match function {
+                Some(ExtensionFunction::Dll(symbol, id)) => match self.externals.get_external(*id) {
+                    Some(external) => format!(
+                        "{}: {} ({} in {})",
+                        name,
+                        external.call.kind(),
+                        symbol,
+                        external::dll_name(&external.signature.dll)
+                    ),
+                    None => format!("{}: freed", name),
+                },
+                Some(ExtensionFunction::Gml(_)) => format!("{}: GML", name),
+                None => format!("{}: dummied (failed to load)", name),
+            })
+            .collect()
+    }
+
     /// Starts the game, loading the first room. Does not need to be called immediately before loading a savestate.
     pub fn init(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         // Library initialization code
@@ -2189,16 +2379,13 @@ impl Game {
 
         let mut time_now = Instant::now();
         let mut time_last = time_now;
-        let mut current_frame_time: u32 = 0;
         loop {
             self.process_window_events();
 
             self.frame()?;
-            self.dump_audiovideo_frame(&mut current_frame_time, self.room.speed);
+            self.dump_frame();
             if let Some(SceneChange::End) = self.scene_change {
-                if let Some(dumper) = self.ffmpeg_dumper.take() {
-                    self.stop_audiovisual_dump(dumper);
-                }
+                self.finish_dump();
             }
             handle_scene_change!(self);
 
@@ -2232,25 +2419,6 @@ impl Game {
         }
     }
 
-    fn dump_audiovideo_frame(&mut self, current_frame_time: &mut u32, game_speed: u32) {
-        if let Some(ffmpeg_dumper) = self.ffmpeg_dumper.as_mut() {
-            while *current_frame_time < 50 {
-                if self.scene_change.is_none() {
-                    let w: i32 = self.window_inner_size.0.try_into().unwrap();
-                    let h: i32 = self.window_inner_size.1.try_into().unwrap();
-                    let pixels = self.renderer.get_pixels(0, 0, w, h);
-
-                    let stdin = ffmpeg_dumper.stdin.as_mut().expect("Failed to open stdin");
-
-                    stdin.write_all(&pixels).unwrap();
-                }
-                *current_frame_time += game_speed;
-                self.audio.dump_audio();
-            }
-            *current_frame_time -= 50;
-        }
-    }
-
     pub fn set_input_from_frame(&mut self, frame: &crate::game::replay::Frame) {
         for ev in frame.events.iter() {
             self.stored_events.push_back(ev.clone());
@@ -2280,6 +2448,16 @@ impl Game {
                 replay::Input::MouseRelease(b) => self.input.mouse_release(*b as i8, true),
                 replay::Input::MouseWheelUp => self.input.mouse_scroll_up(),
                 replay::Input::MouseWheelDown => self.input.mouse_scroll_down(),
+                replay::Input::JoystickConnect { id, name, axes, buttons, has_pov } => {
+                    self.input.joystick_connect(usize::from(*id), name.clone(), *axes, *buttons, *has_pov)
+                },
+                replay::Input::JoystickDisconnect(id) => self.input.joystick_disconnect(usize::from(*id)),
+                replay::Input::JoystickButtonPress(id, b) => self.input.joystick_button_press(usize::from(*id), *b),
+                replay::Input::JoystickButtonRelease(id, b) => self.input.joystick_button_release(usize::from(*id), *b),
+                replay::Input::JoystickAxis(id, axis, pos) => {
+                    self.input.joystick_move_axis(usize::from(*id), usize::from(*axis), *pos)
+                },
+                replay::Input::JoystickPov(id, pov) => self.input.joystick_move_pov(usize::from(*id), *pov),
             }
         }
     }
@@ -2290,11 +2468,33 @@ impl Game {
         replay: Replay,
         output_bin: Option<PathBuf>,
         start_save_path: Option<&PathBuf>,
+        profile_path: Option<PathBuf>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let result = self.play_replay(&replay, output_bin.as_ref(), start_save_path);
+
+        // a runtime error is when a profile or trace is most useful, so write them out however the replay ended
+        if let Some(path) = profile_path {
+            if let Err(e) = self.save_profile(&path) {
+                eprintln!("Failed to write profile to {:?}: {}", path, e);
+            }
+        }
+        if let Err(e) = self.tracer.stop() {
+            eprintln!("Failed to write trace file: {}", e);
+        }
+        result
+    }
+
+    fn play_replay(
+        &mut self,
+        replay: &Replay,
+        output_bin: Option<&PathBuf>,
+        start_save_path: Option<&PathBuf>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let mut frame_count: usize = 0;
         self.rand.set_seed(replay.start_seed);
         self.spoofed_time_nanos = Some(replay.start_time);
-        let mut current_frame_time: u32 = 0;
+        self.externals.set_result_mode(replay.external_result_mode(PlayType::Replay));
+        self.registry = registry::Registry::from_text(&replay.registry)?;
 
         // the tas ui creates some sprites, so as a hotfix we need to generate them here too
         // TODO don't
@@ -2307,13 +2507,16 @@ impl Game {
             let mut save_buffer = savestate::Buffer::new();
             match SaveState::from_file(start_save_path.unwrap(), &mut save_buffer) {
                 Ok(state) => {
-                    let (rep, ren) = state.clone().load_into(&mut self);
+                    let (rep, ren) = state.clone().load_into(self);
                     if !replay.contains_part(&rep) {
                         panic!("Savestate is not part of replay");
                     }
 
                     frame_count = rep.frame_count();
                     clean_state = state.clean_state;
+                    if let Some(dumper) = self.dumper.as_mut() {
+                        dumper.skip_to(frame_count);
+                    }
                     self.renderer.set_state(&ren);
                 },
                 Err(e) => {
@@ -2329,12 +2532,12 @@ impl Game {
         }
 
         let mut time_now = Instant::now();
-        return loop {
+        let result = loop {
             self.window.poll_events();
             self.input.mouse_step();
 
             if self.frame_limit_at > 0 && frame_count == self.frame_limit_at || frame_count == replay.frame_count() {
-                if let Some(bin) = &output_bin {
+                if let Some(bin) = output_bin {
                     if start_save_path.is_some() {
                         // Store the current framebuffer since it's used by the savestate. Only matters if there already is a framebuffer stored which is the case when loading a savestate.
                         self.renderer.resize_framebuffer(
@@ -2346,7 +2549,7 @@ impl Game {
                     let render_state = self.renderer.state();
                     let mut new_replay = replay.clone();
                     new_replay.truncate_frames(frame_count);
-                    match SaveState::from(&mut self, new_replay, render_state, clean_state)
+                    match SaveState::from(self, new_replay, render_state, clean_state)
                         .save_to_file(bin, &mut savestate::Buffer::new())
                     {
                         Ok(()) => {
@@ -2355,8 +2558,8 @@ impl Game {
                         Err(e) => break Err(format!("Error saving to {:?}: {:?}", output_bin, e).into()),
                     }
                 }
-                if let Some(dumper) = self.ffmpeg_dumper.take() {
-                    self.stop_audiovisual_dump(dumper);
+                if self.dumper.is_some() {
+                    self.finish_dump();
                     break Ok(());
                 }
             }
@@ -2375,7 +2578,8 @@ impl Game {
             }
 
             self.frame()?;
-            self.dump_audiovideo_frame(&mut current_frame_time, self.room.speed);
+            self.dump_frame();
+            self.replay_screenshot(frame_count);
 
             match self.scene_change {
                 Some(SceneChange::Room(id)) => self.load_room(id)?,
@@ -2417,29 +2621,13 @@ impl Game {
 
             frame_count += 1;
         };
-    }
 
-    fn stop_audiovisual_dump(&mut self, dumper: Child) {
-        dumper.wait_with_output().expect("video dumper should close");
-        self.audio.stop_audio_dump();
-        //combine audio and video dump into one file
-        Command::new("ffmpeg")
-            .arg("-y")
-            .arg("-i")
-            .arg("dump.mkv")
-            .arg("-i")
-            .arg("dump.flac")
-            .arg("-c")
-            .arg("copy")
-            .arg("--")
-            .arg("tas recording.mkv")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::null())
-            .stderr(Stdio::inherit())
-            .spawn()
-            .expect("Failed to open FFmpeg stdin")
-            .wait_with_output()
-            .expect("ffmpeg dumper should close");
+        match &self.screenshotter {
+            Some(screenshotter) if result.is_ok() && screenshotter.mismatches() > 0 => {
+                Err(format!("{} frames didn't match their reference images", screenshotter.mismatches()).into())
+            },
+            _ => result,
+        }
     }
 
     // Gets the mouse position in room coordinates
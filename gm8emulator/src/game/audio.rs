@@ -1,8 +1,7 @@
+mod midi;
 mod mixer;
 mod mp3;
 use serde::{Deserialize, Serialize};
-use std::io::Write;
-use std::process::{self, Child, Stdio};
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::{
     collections::HashMap,
@@ -23,13 +22,28 @@ use udon::{
 };
 
 use self::{
-    mixer::{Mixer, MixerHandle},
+    midi::MidiPlayer,
+    mixer::{effects::SoundEffects, Mixer, MixerHandle},
     mp3::Mp3Player,
 };
+use rustysynth::SoundFont;
+
+pub use self::mixer::{effects, Spatial};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Mp3Handle {
     player: Mp3Player,
+    params: Arc<SoundParams>,
+    volume: f64,
+    id: i32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MidiHandle {
+    file: Arc<[u8]>,
+    length: f64, // in seconds, at normal tempo
+    params: Arc<SoundParams>,
+    volume: f64,
     id: i32,
 }
 
@@ -37,7 +51,9 @@ pub struct Mp3Handle {
 pub struct WavHandle {
     player: WavPlayer,
     params: Arc<SoundParams>,
-    _use_3d: bool,
+    volume: f64,
+    effects: SoundEffects,
+    spatial: Spatial,
     exclusive: bool,
     id: i32,
 }
@@ -45,6 +61,17 @@ pub struct WavHandle {
 #[derive(Serialize, Deserialize)]
 pub struct SoundParams {
     pub volume: AtomicU32,
+    pub left: AtomicU32,
+    pub right: AtomicU32,
+}
+
+/// A volume envelope going linearly from one GML volume to another, timed by the game clock.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Fade {
+    from: f64,
+    to: f64,
+    start: u128,
+    duration: u128,
 }
 
 pub struct AudioManager {
@@ -57,7 +84,11 @@ pub struct AudioManager {
     global_volume: Arc<AtomicU32>,
     end_times: HashMap<i32, Option<u128>>,
     multimedia_end: Option<(i32, Option<u128>)>,
-    audio_dumper: Option<Child>,
+    multimedia_midi: bool,
+    midi_tempo: Arc<AtomicU32>,
+    soundfont: Option<Arc<SoundFont>>,
+    soundfont_warned: bool,
+    fades: HashMap<i32, Fade>,
 }
 pub struct InterprocessSource {
     reciever: Receiver<Sample>,
@@ -98,24 +129,6 @@ impl AudioManager {
         let (mixer, mixer_handle) = Mixer::new(sample_rate, channel_count, global_volume.clone());
         let (sample_sender, sample_reciever) = mpsc::channel();
         let interprocess_source = InterprocessSource::new(sample_reciever, channel_count, sample_rate);
-        let audio_dumper = do_dump_audio.then(|| {
-            process::Command::new("ffmpeg")
-                .arg("-y")
-                .arg("-f")
-                .arg("f32le")
-                .arg("-ar")
-                .arg(sample_rate.to_string())
-                .arg("-ac")
-                .arg(channel_count.to_string())
-                .arg("-i")
-                .arg("-")
-                .arg("dump.flac")
-                .stdin(Stdio::piped())
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .spawn()
-                .expect("Failed to open FFmpeg stdin")
-        });
         if do_dump_audio {
             std::thread::spawn(move || {
                 let stream = session.open_output_stream(device).unwrap();
@@ -131,7 +144,11 @@ impl AudioManager {
                 global_volume,
                 end_times: HashMap::new(),
                 multimedia_end: None,
-                audio_dumper: audio_dumper,
+                multimedia_midi: false,
+                midi_tempo: Arc::new(AtomicU32::from(1.0f32.to_bits())),
+                soundfont: None,
+                soundfont_warned: false,
+                fades: HashMap::new(),
             };
         } else {
             std::thread::spawn(move || {
@@ -148,36 +165,40 @@ impl AudioManager {
                 global_volume,
                 end_times: HashMap::new(),
                 multimedia_end: None,
-                audio_dumper: audio_dumper,
+                multimedia_midi: false,
+                midi_tempo: Arc::new(AtomicU32::from(1.0f32.to_bits())),
+                soundfont: None,
+                soundfont_warned: false,
+                fades: HashMap::new(),
             }
         }
     }
-    pub fn dump_audio(&mut self) {
+    /// The channel count and sample rate of the samples returned by dump_audio().
+    pub fn dump_format(&self) -> (u16, u32) {
+        (self.mixer_channel_count.into(), self.mixer_sample_rate.into())
+    }
+
+    /// Mixes the next `frames` frames of audio when dumping, passing them on to the audio device as well.
+    /// When not dumping, the mixer runs on its own, so this returns nothing.
+    pub fn dump_audio(&mut self, frames: usize) -> Vec<Sample> {
         if let Some(mixer) = &mut self.mixer {
-            const AUDIO_SAMPLES_PER_FRAME: usize = 48000 / 50 * 2;
-            let mut audio_output: [Sample; AUDIO_SAMPLES_PER_FRAME] = [0.0; AUDIO_SAMPLES_PER_FRAME];
-            mixer.write_samples(&mut audio_output);
-
-            let stdin = self.audio_dumper.as_mut().unwrap().stdin.as_mut().expect("Failed to open stdin");
-            unsafe {
-                let samples = std::slice::from_raw_parts(audio_output.as_ptr() as *const u8, audio_output.len() * 4);
-                stdin.write_all(samples).unwrap();
-            }
+            let mut samples = vec![0.0; frames * usize::from(u16::from(self.mixer_channel_count))];
+            mixer.write_samples(&mut samples);
             if let Some(sample_sender) = &self.sample_sender {
-                audio_output.iter().for_each(|s| {
+                samples.iter().for_each(|s| {
                     sample_sender.send(*s).unwrap();
                 })
             }
+            samples
+        } else {
+            Vec::new()
         }
     }
 
-    pub fn stop_audio_dump(&mut self) {
-        if let Some(dumper) = self.audio_dumper.take() {
-            dumper.wait_with_output().expect("audio dumper should close");
-        }
-    }
     pub fn add_mp3(&mut self, file: Box<[u8]>, sound_id: i32) -> Option<Mp3Handle> {
-        Mp3Player::new(file).map(|player| Mp3Handle { player, id: sound_id }).ok()
+        Mp3Player::new(file)
+            .map(|player| Mp3Handle { player, params: SoundParams::new(1.0), volume: 1.0, id: sound_id })
+            .ok()
     }
 
     pub fn add_wav(
@@ -185,46 +206,111 @@ impl AudioManager {
         file: Box<[u8]>,
         sound_id: i32,
         volume: f64,
+        effects: u32,
         use_3d: bool,
         exclusive: bool,
     ) -> Option<WavHandle> {
         WavPlayer::new(file)
             .map(|player| WavHandle {
                 player,
-                params: Arc::new(SoundParams { volume: AtomicU32::new(make_volume(volume).to_bits()) }),
-                _use_3d: use_3d,
+                params: SoundParams::new(volume),
+                volume,
+                effects: SoundEffects { enabled: effects, ..Default::default() },
+                spatial: Spatial::new(use_3d),
                 exclusive,
                 id: sound_id,
             })
             .ok()
     }
 
+    /// Loads the SoundFont used to synthesize MIDI music. Without one, MIDIs are timed as usual but silent.
+    pub fn load_soundfont(&mut self, file: &[u8]) -> Result<(), String> {
+        self.soundfont = Some(midi::load_soundfont(file)?);
+        Ok(())
+    }
+
+    pub fn add_midi(&mut self, file: Box<[u8]>, sound_id: i32) -> Option<MidiHandle> {
+        midi::length(&file)
+            .map(|length| MidiHandle {
+                file: file.into(),
+                length,
+                params: SoundParams::new(1.0),
+                volume: 1.0,
+                id: sound_id,
+            })
+            .ok()
+    }
+
+    pub fn play_midi(&mut self, handle: &MidiHandle, start_time: u128) {
+        let tempo = f64::from(f32::from_bits(self.midi_tempo.load(Ordering::Acquire)));
+        let end_time = (handle.length / tempo * 1_000_000_000.0) as u128 + start_time;
+        self.multimedia_end = Some((handle.id, Some(end_time)));
+        self.multimedia_midi = true;
+        self.add_midi_source(handle, false);
+    }
+
+    pub fn loop_midi(&mut self, handle: &MidiHandle) {
+        self.multimedia_end = Some((handle.id, None));
+        self.multimedia_midi = true;
+        self.add_midi_source(handle, true);
+    }
+
+    fn add_midi_source(&mut self, handle: &MidiHandle, looping: bool) {
+        if !self.do_output {
+            return
+        }
+        match &self.soundfont {
+            Some(soundfont) => {
+                let tempo = self.midi_tempo.clone();
+                if let Ok(player) = MidiPlayer::new(soundfont, &handle.file, self.mixer_sample_rate, tempo, looping) {
+                    let _ = self.mixer_handle.add_exclusive(
+                        Rechanneler::new(player, self.mixer_channel_count),
+                        handle.params.clone(),
+                        handle.id,
+                    );
+                }
+            },
+            None if !self.soundfont_warned => {
+                eprintln!("WARNING: no SoundFont loaded, so MIDI music will be silent (see --soundfont)");
+                self.soundfont_warned = true;
+            },
+            None => (),
+        }
+    }
+
+    /// Sets the tempo factor of MIDI music, rescaling the end time of any that's already playing.
+    pub fn set_midi_tempo(&mut self, tempo: f64, current_time: u128) {
+        let tempo = tempo.max(0.01);
+        let old_tempo = f64::from(f32::from_bits(self.midi_tempo.swap((tempo as f32).to_bits(), Ordering::AcqRel)));
+        if let (true, Some((_, Some(end_time)))) = (self.multimedia_midi, &mut self.multimedia_end) {
+            if *end_time > current_time {
+                let remaining = (*end_time - current_time) as f64 * old_tempo / tempo;
+                *end_time = current_time + remaining as u128;
+            }
+        }
+    }
+
     pub fn play_mp3(&mut self, handle: &Mp3Handle, start_time: u128) {
-        let end_time = length_to_ns(
-            handle.player.length(),
-            handle.player.sample_rate().into(),
-            1, // mp3 length() already takes channels into account
-        ) + start_time;
+        let end_time = handle.length() + start_time;
         self.multimedia_end = Some((handle.id, Some(end_time)));
+        self.multimedia_midi = false;
         if self.do_output {
             let _ = self.mixer_handle.add_exclusive(
                 Rechanneler::new(
                     Resampler::new(handle.player.clone(), self.mixer_sample_rate),
                     self.mixer_channel_count,
                 ),
+                handle.params.clone(),
                 handle.id,
             );
         }
     }
 
     pub fn play_wav(&mut self, handle: &WavHandle, start_time: u128) {
-        let end_time = length_to_ns(
-            handle.player.length(),
-            handle.player.sample_rate().into(),
-            handle.player.channel_count().into(),
-        ) + start_time;
+        let end_time = handle.length() + start_time;
         if handle.exclusive {
             self.multimedia_end = Some((handle.id, Some(end_time)));
+            self.multimedia_midi = false;
         } else if self.end_times.get(&handle.id) != Some(&None) {
             self.end_times.insert(handle.id, Some(end_time));
         }
@@ -236,6 +322,7 @@ impl AudioManager {
                         Resampler::new(handle.player.clone(), self.mixer_sample_rate),
                         self.mixer_channel_count,
                     ),
+                    handle.params.clone(),
                     handle.id,
                 );
             } else {
@@ -245,6 +332,7 @@ impl AudioManager {
                         self.mixer_channel_count,
                     ),
                     handle.params.clone(),
+                    handle.effects.clone(),
                     handle.id,
                 );
             }
@@ -253,12 +341,14 @@ impl AudioManager {
 
     pub fn loop_mp3(&mut self, handle: &Mp3Handle) {
         self.multimedia_end = Some((handle.id, None));
+        self.multimedia_midi = false;
         if self.do_output {
             let _ = self.mixer_handle.add_exclusive(
                 Cycle::new(Rechanneler::new(
                     Resampler::new(handle.player.clone(), self.mixer_sample_rate),
                     self.mixer_channel_count,
                 )),
+                handle.params.clone(),
                 handle.id,
             );
         }
@@ -267,6 +357,7 @@ impl AudioManager {
     pub fn loop_wav(&mut self, handle: &WavHandle) {
         if handle.exclusive {
             self.multimedia_end = Some((handle.id, None));
+            self.multimedia_midi = false;
         } else {
             self.end_times.insert(handle.id, None);
         }
@@ -278,6 +369,7 @@ impl AudioManager {
                         Resampler::new(handle.player.clone(), self.mixer_sample_rate),
                         self.mixer_channel_count,
                     )),
+                    handle.params.clone(),
                     handle.id,
                 );
             } else {
@@ -287,6 +379,7 @@ impl AudioManager {
                         self.mixer_channel_count,
                     )),
                     handle.params.clone(),
+                    handle.effects.clone(),
                     handle.id,
                 );
             }
@@ -303,6 +396,37 @@ impl AudioManager {
         }
     }
 
+    /// Starts fading a sound from its current volume to another one over `duration` milliseconds.
+    /// The fade is driven by `update_fades`, so it follows the game clock rather than the audio device.
+    pub fn fade(&mut self, sound_id: i32, from: f64, to: f64, duration: u32, current_time: u128) {
+        let duration = u128::from(duration) * 1_000_000;
+        self.fades.insert(sound_id, Fade { from, to, start: current_time, duration });
+    }
+
+    pub fn cancel_fade(&mut self, sound_id: i32) {
+        self.fades.remove(&sound_id);
+    }
+
+    /// Advances all fades to the given time, returning the volume each faded sound should now have.
+    /// Fades which have reached their target are removed after reporting it.
+    pub fn update_fades(&mut self, current_time: u128) -> Vec<(i32, f64)> {
+        let volumes = self
+            .fades
+            .iter()
+            .map(|(&id, fade)| {
+                let elapsed = current_time.saturating_sub(fade.start);
+                let volume = if elapsed >= fade.duration {
+                    fade.to
+                } else {
+                    fade.from + (fade.to - fade.from) * (elapsed as f64 / fade.duration as f64)
+                };
+                (id, volume)
+            })
+            .collect();
+        self.fades.retain(|_, fade| current_time.saturating_sub(fade.start) < fade.duration);
+        volumes
+    }
+
     pub fn stop_all(&mut self) {
         self.end_times.clear();
         self.multimedia_end = None;
@@ -338,6 +462,9 @@ impl AudioManager {
             global_volume: self.global_volume.clone(),
             end_times: self.end_times.clone(),
             multimedia_end: self.multimedia_end,
+            multimedia_midi: self.multimedia_midi,
+            midi_tempo: self.midi_tempo.clone(),
+            fades: self.fades.clone(),
         }
     }
 
@@ -345,13 +472,99 @@ impl AudioManager {
         self.global_volume = state.global_volume;
         self.end_times = state.end_times;
         self.multimedia_end = state.multimedia_end;
+        self.multimedia_midi = state.multimedia_midi;
+        self.midi_tempo.store(state.midi_tempo.load(Ordering::Acquire), Ordering::Release);
+        self.fades = state.fades;
+    }
+}
+
+impl SoundParams {
+    fn new(volume: f64) -> Arc<Self> {
+        Arc::new(Self {
+            volume: AtomicU32::new(make_volume(volume).to_bits()),
+            left: AtomicU32::new(1.0f32.to_bits()),
+            right: AtomicU32::new(1.0f32.to_bits()),
+        })
+    }
+}
+
+impl Mp3Handle {
+    pub fn volume(&self) -> f64 {
+        self.volume
+    }
+
+    /// How long the sound takes to play once, in nanoseconds.
+    pub fn length(&self) -> u128 {
+        // mp3 length() already takes channels into account
+        length_to_ns(self.player.length(), self.player.sample_rate().into(), 1)
+    }
+
+    /// A copy of the sound under another mixer ID, with its own volume so that it can be adjusted separately.
+    pub fn duplicate(&self, id: i32) -> Self {
+        Self { params: SoundParams::new(self.volume), id, ..self.clone() }
+    }
+
+    pub fn set_volume(&mut self, vol: f64) {
+        self.volume = vol;
+        self.params.volume.store(make_volume(vol).to_bits(), Ordering::Release);
+    }
+}
+
+impl MidiHandle {
+    pub fn volume(&self) -> f64 {
+        self.volume
+    }
+
+    /// How long the sound takes to play once at normal tempo, in nanoseconds.
+    pub fn length(&self) -> u128 {
+        (self.length * 1_000_000_000.0) as u128
+    }
+
+    /// A copy of the sound under another mixer ID, with its own volume so that it can be adjusted separately.
+    pub fn duplicate(&self, id: i32) -> Self {
+        Self { params: SoundParams::new(self.volume), id, ..self.clone() }
+    }
+
+    pub fn set_volume(&mut self, vol: f64) {
+        self.volume = vol;
+        self.params.volume.store(make_volume(vol).to_bits(), Ordering::Release);
     }
 }
 
 impl WavHandle {
-    pub fn set_volume(&self, vol: f64) {
+    pub fn volume(&self) -> f64 {
+        self.volume
+    }
+
+    /// How long the sound takes to play once, in nanoseconds.
+    pub fn length(&self) -> u128 {
+        length_to_ns(self.player.length(), self.player.sample_rate().into(), self.player.channel_count().into())
+    }
+
+    /// A copy of the sound under another mixer ID, with its own volume and pan so they can be adjusted separately.
+    pub fn duplicate(&self, id: i32) -> Self {
+        let mut handle = Self { params: SoundParams::new(self.volume), id, ..self.clone() };
+        handle.set_spatial(|_| ());
+        handle
+    }
+
+    pub fn set_volume(&mut self, vol: f64) {
+        self.volume = vol;
         self.params.volume.store(make_volume(vol).to_bits(), Ordering::Release);
     }
+
+    /// The sound's effect settings, which get used from the next time it's played.
+    pub fn effects_mut(&mut self) -> &mut SoundEffects {
+        &mut self.effects
+    }
+
+    /// Changes the sound's pan or 3D parameters, which unlike effects also applies to it while it's playing.
+    pub fn set_spatial(&mut self, f: impl FnOnce(&mut Spatial)) {
+        f(&mut self.spatial);
+        let (left, right) = self.spatial.gains();
+        self.params.left.store(left.to_bits(), Ordering::Release);
+        self.params.right.store(right.to_bits(), Ordering::Release);
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -359,6 +572,9 @@ pub struct AudioState {
     global_volume: Arc<AtomicU32>,
     end_times: HashMap<i32, Option<u128>>,
     multimedia_end: Option<(i32, Option<u128>)>,
+    multimedia_midi: bool,
+    midi_tempo: Arc<AtomicU32>,
+    fades: HashMap<i32, Fade>,
 }
 
 fn length_to_ns(sample_count: usize, sample_rate: u32, channels: u16) -> u128 {
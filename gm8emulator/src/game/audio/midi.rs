@@ -0,0 +1,79 @@
+use rustysynth::{MidiFile, MidiFileSequencer, SoundFont, Synthesizer, SynthesizerSettings};
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+use udon::source::{ChannelCount, Sample, SampleRate, Source};
+
+/// Plays a MIDI file through a SoundFont synthesizer, at the tempo set by sound_background_tempo().
+pub struct MidiPlayer {
+    sequencer: MidiFileSequencer,
+    midi: Arc<MidiFile>,
+    tempo: Arc<AtomicU32>,
+    looping: bool,
+    sample_rate: SampleRate,
+    left: Vec<f32>,
+    right: Vec<f32>,
+}
+
+pub enum Error {
+    InvalidFile,
+    Synthesizer,
+}
+
+/// Gets the length of a MIDI file in seconds at normal tempo, which also checks that it can be parsed.
+pub fn length(file: &[u8]) -> Result<f64, Error> {
+    MidiFile::new(&mut &file[..]).map(|midi| midi.get_length()).map_err(|_| Error::InvalidFile)
+}
+
+pub fn load_soundfont(file: &[u8]) -> Result<Arc<SoundFont>, String> {
+    SoundFont::new(&mut &file[..]).map(Arc::new).map_err(|e| e.to_string())
+}
+
+impl MidiPlayer {
+    pub fn new(
+        soundfont: &Arc<SoundFont>,
+        file: &[u8],
+        sample_rate: SampleRate,
+        tempo: Arc<AtomicU32>,
+        looping: bool,
+    ) -> Result<Self, Error> {
+        let midi = Arc::new(MidiFile::new(&mut &file[..]).map_err(|_| Error::InvalidFile)?);
+        let settings = SynthesizerSettings::new(u32::from(sample_rate) as i32);
+        let synthesizer = Synthesizer::new(soundfont, &settings).map_err(|_| Error::Synthesizer)?;
+        let mut sequencer = MidiFileSequencer::new(synthesizer);
+        sequencer.set_speed(f64::from(f32::from_bits(tempo.load(Ordering::Acquire))));
+        sequencer.play(&midi, looping);
+        Ok(Self { sequencer, midi, tempo, looping, sample_rate, left: Vec::new(), right: Vec::new() })
+    }
+}
+
+impl Source for MidiPlayer {
+    fn channel_count(&self) -> ChannelCount {
+        ChannelCount::new(2).unwrap()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    fn write_samples(&mut self, buffer: &mut [Sample]) -> usize {
+        if !self.looping && self.sequencer.get_position() >= self.midi.get_length() {
+            return 0
+        }
+        let frames = buffer.len() / 2;
+        self.left.resize(frames, 0.0);
+        self.right.resize(frames, 0.0);
+        self.sequencer.set_speed(f64::from(f32::from_bits(self.tempo.load(Ordering::Acquire))));
+        self.sequencer.render(&mut self.left, &mut self.right);
+        for ((frame, left), right) in buffer.chunks_exact_mut(2).zip(self.left.iter()).zip(self.right.iter()) {
+            frame[0] = *left;
+            frame[1] = *right;
+        }
+        frames * 2
+    }
+
+    fn reset(&mut self) {
+        self.sequencer.play(&self.midi, self.looping);
+    }
+}
@@ -1,4 +1,8 @@
-use super::SoundParams;
+pub mod effects;
+
+use self::effects::{EffectChain, SoundEffects};
+use super::{make_volume, SoundParams};
+use serde::{Deserialize, Serialize};
 
 use std::{
     process::Child,
@@ -17,16 +21,16 @@ const INIT_CAPACITY: usize = 16;
 pub struct Mixer {
     channels: ChannelCount,
     sample_rate: SampleRate,
-    sources: Vec<(Box<dyn Source + Send + 'static>, Arc<SoundParams>, i32)>,
-    exclusive_source: Option<(Box<dyn Source + Send + 'static>, i32)>,
+    sources: Vec<(Box<dyn Source + Send + 'static>, Arc<SoundParams>, EffectChain, i32)>,
+    exclusive_source: Option<(Box<dyn Source + Send + 'static>, Arc<SoundParams>, i32)>,
     global_volume: Arc<AtomicU32>,
     input_buffer: Vec<Sample>,
     receiver: Receiver<Command>,
 }
 
 enum Command {
-    Add { source: Box<dyn Source + Send + 'static>, params: Arc<SoundParams>, id: i32 },
-    AddExclusive { source: Box<dyn Source + Send + 'static>, id: i32 },
+    Add { source: Box<dyn Source + Send + 'static>, params: Arc<SoundParams>, effects: SoundEffects, id: i32 },
+    AddExclusive { source: Box<dyn Source + Send + 'static>, params: Arc<SoundParams>, id: i32 },
     Stop(i32),
     StopAll,
 }
@@ -67,11 +71,15 @@ impl Source for Mixer {
         // Check for new incoming commands
         while let Ok(cmd) = self.receiver.try_recv() {
             match cmd {
-                Command::Add { source, params, id } => self.sources.push((source, params, id)),
-                Command::AddExclusive { source, id } => self.exclusive_source = Some((source, id)),
+                Command::Add { source, params, effects, id } => {
+                    let channels = usize::from(u16::from(self.channels));
+                    let chain = EffectChain::new(&effects, u32::from(self.sample_rate), channels);
+                    self.sources.push((source, params, chain, id))
+                },
+                Command::AddExclusive { source, params, id } => self.exclusive_source = Some((source, params, id)),
                 Command::Stop(id) => {
-                    self.sources.retain(|(_, _, x)| *x != id);
-                    if let Some((_, x)) = &self.exclusive_source {
+                    self.sources.retain(|(_, _, _, x)| *x != id);
+                    if let Some((_, _, x)) = &self.exclusive_source {
                         if *x == id {
                             self.exclusive_source = None;
                         }
@@ -84,8 +92,10 @@ impl Source for Mixer {
             }
         }
 
-        if let Some((source, _)) = &mut self.exclusive_source {
+        if let Some((source, params, _)) = &mut self.exclusive_source {
             let count = source.write_samples(buffer);
+            let volume = f32::from_bits(params.volume.load(Ordering::Acquire));
+            buffer[..count].iter_mut().for_each(|x| *x *= volume);
             if buffer.len() != count {
                 buffer[count..].iter_mut().for_each(|x| *x = 0.0);
                 self.exclusive_source = None;
@@ -98,12 +108,23 @@ impl Source for Mixer {
         input_buffer.resize_with(buffer.len(), Default::default);
         let global_volume = f32::from_bits(self.global_volume.load(Ordering::Acquire));
 
-        RetainMut::retain_mut(&mut self.sources, |(source, params, _)| {
-            let volume = f32::from_bits(params.volume.load(Ordering::Acquire));
+        let channels = usize::from(u16::from(self.channels));
+
+        RetainMut::retain_mut(&mut self.sources, |(source, params, effects, _)| {
+            let volume = f32::from_bits(params.volume.load(Ordering::Acquire)) * global_volume;
+            let left = f32::from_bits(params.left.load(Ordering::Acquire));
+            let right = f32::from_bits(params.right.load(Ordering::Acquire));
+            // a mono device can't pan, so it only gets the distance attenuation
+            let gains = if channels >= 2 { [left, right] } else { [left.max(right); 2] };
             let count = source.write_samples(input_buffer);
+            if !effects.is_empty() {
+                effects.process(&mut input_buffer[..count]);
+            }
 
-            for (in_sample, out_sample) in input_buffer.iter().take(count).copied().zip(buffer.iter_mut()) {
-                *out_sample += in_sample * volume * global_volume;
+            let samples = input_buffer.iter().take(count).copied().zip(buffer.iter_mut());
+            for (i, (in_sample, out_sample)) in samples.enumerate() {
+                let gain = gains.get(i % channels).copied().unwrap_or(1.0);
+                *out_sample += in_sample * volume * gain;
             }
 
             count == input_buffer.len()
@@ -126,15 +147,26 @@ impl Source for Mixer {
 }
 
 impl MixerHandle {
-    /// Adds a sound to be mixed, along with its ID and atomic params
-    pub fn add(&self, source: impl Source + Send + 'static, params: Arc<SoundParams>, id: i32) -> Result<(), Error> {
-        let command = Command::Add { source: Box::new(source), params, id };
+    /// Adds a sound to be mixed, along with its ID, atomic params and the effects to put on it
+    pub fn add(
+        &self,
+        source: impl Source + Send + 'static,
+        params: Arc<SoundParams>,
+        effects: SoundEffects,
+        id: i32,
+    ) -> Result<(), Error> {
+        let command = Command::Add { source: Box::new(source), params, effects, id };
         self.0.send(command).map_err(|_| Error::SendError)
     }
 
-    /// Adds an exclusive sound
-    pub fn add_exclusive(&self, source: impl Source + Send + 'static, id: i32) -> Result<(), Error> {
-        let command = Command::AddExclusive { source: Box::new(source), id };
+    /// Adds an exclusive sound, along with its ID and atomic params
+    pub fn add_exclusive(
+        &self,
+        source: impl Source + Send + 'static,
+        params: Arc<SoundParams>,
+        id: i32,
+    ) -> Result<(), Error> {
+        let command = Command::AddExclusive { source: Box::new(source), params, id };
         self.0.send(command).map_err(|_| Error::SendError)
     }
 
@@ -149,6 +181,89 @@ impl MixerHandle {
     }
 }
 
+/// Where a sound is in the stereo field or in 3D space, relative to a listener at the origin facing +z.
+/// This is a cut-down DirectSound3D: there's no doppler, so the velocity is only remembered, and rolloff is always 1.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Spatial {
+    pub use_3d: bool,
+    pub pan: f64,
+    pub position: [f64; 3],
+    pub velocity: [f64; 3],
+    pub min_distance: f64,
+    pub max_distance: f64,
+    pub cone_orientation: [f64; 3],
+    pub cone_inside: f64,         // degrees
+    pub cone_outside: f64,        // degrees
+    pub cone_outside_volume: f64, // hundredths of a decibel, 0 to -10000
+}
+
+impl Spatial {
+    pub fn new(use_3d: bool) -> Self {
+        Self {
+            use_3d,
+            pan: 0.0,
+            position: [0.0; 3],
+            velocity: [0.0; 3],
+            min_distance: 1.0,
+            max_distance: 1000000000.0,
+            cone_orientation: [0.0, 0.0, 1.0],
+            cone_inside: 360.0,
+            cone_outside: 360.0,
+            cone_outside_volume: 0.0,
+        }
+    }
+
+    /// The gains of the left and right channels. 3D sounds ignore the pan, like DirectSound3D buffers do.
+    pub fn gains(&self) -> (f32, f32) {
+        if !self.use_3d {
+            return pan_gains(self.pan)
+        }
+        let [x, y, z] = self.position;
+        let distance = (x * x + y * y + z * z).sqrt();
+        let attenuation = if distance > self.min_distance {
+            self.min_distance / distance.min(self.max_distance)
+        } else {
+            1.0
+        };
+        let cone = self.cone_attenuation(distance);
+        let pan = if distance > 0.0 { x / distance } else { 0.0 };
+        let (left, right) = pan_gains(pan);
+        let gain = (attenuation * cone) as f32;
+        (left * gain, right * gain)
+    }
+
+    /// How much the sound's cone attenuates it, based on the angle between its orientation and the listener.
+    fn cone_attenuation(&self, distance: f64) -> f64 {
+        let [ox, oy, oz] = self.cone_orientation;
+        let length = (ox * ox + oy * oy + oz * oz).sqrt();
+        if distance <= 0.0 || length <= 0.0 {
+            return 1.0
+        }
+        let [x, y, z] = self.position;
+        let cos = -(x * ox + y * oy + z * oz) / (distance * length);
+        let angle = cos.clamp(-1.0, 1.0).acos().to_degrees();
+        let (inside, outside) = (self.cone_inside / 2.0, self.cone_outside.max(self.cone_inside) / 2.0);
+        let hundredths = if angle <= inside {
+            0.0
+        } else if angle >= outside {
+            self.cone_outside_volume
+        } else {
+            self.cone_outside_volume * (angle - inside) / (outside - inside)
+        };
+        10f64.powf(hundredths / 2000.0)
+    }
+}
+
+/// DirectSound pans by turning down the opposite channel, which we do on the same curve as the volume.
+fn pan_gains(pan: f64) -> (f32, f32) {
+    let pan = pan.clamp(-1.0, 1.0);
+    if pan < 0.0 {
+        (1.0, make_volume(1.0 + pan))
+    } else {
+        (make_volume(1.0 - pan), 1.0)
+    }
+}
+
 trait RetainMut<T> {
     fn retain_mut(&mut self, f: impl FnMut(&mut T) -> bool);
 }
@@ -0,0 +1,628 @@
+//! DSP versions of the DirectSound effects GM8 can put on a sound.
+//!
+//! Parameters use the same units and ranges as the DirectSound structs they mimic (DSFXChorus and so on),
+//! and out-of-range values are clamped the same way the setter functions do. The defaults are DirectSound's.
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use udon::source::Sample;
+
+pub const CHORUS: u32 = 1;
+pub const ECHO: u32 = 2;
+pub const FLANGER: u32 = 4;
+pub const GARGLE: u32 = 8;
+pub const REVERB: u32 = 16;
+pub const COMPRESSOR: u32 = 32;
+pub const EQUALIZER: u32 = 64;
+
+/// The effect settings of a sound: which effects are enabled, and the parameters of each of them.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SoundEffects {
+    pub enabled: u32,
+    pub chorus: Chorus,
+    pub echo: Echo,
+    pub flanger: Flanger,
+    pub gargle: Gargle,
+    pub reverb: Reverb,
+    pub compressor: Compressor,
+    pub equalizer: Equalizer,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Chorus {
+    pub wet_dry: f64,   // 0 to 100 percent
+    pub depth: f64,     // 0 to 100 percent
+    pub feedback: f64,  // -99 to 99 percent
+    pub frequency: f64, // 0 to 10 Hz
+    pub sine: bool,     // otherwise triangle
+    pub delay: f64,     // 0 to 20 ms
+    pub phase: i32,     // 0 to 4, meaning -180 to 180 degrees
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Echo {
+    pub wet_dry: f64,     // 0 to 100 percent
+    pub feedback: f64,    // 0 to 100 percent
+    pub left_delay: f64,  // 1 to 2000 ms
+    pub right_delay: f64, // 1 to 2000 ms
+    pub pan_delay: bool,  // swaps the channels on each repeat
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Flanger {
+    pub wet_dry: f64,   // 0 to 100 percent
+    pub depth: f64,     // 0 to 100 percent
+    pub feedback: f64,  // -99 to 99 percent
+    pub frequency: f64, // 0 to 10 Hz
+    pub sine: bool,     // otherwise triangle
+    pub delay: f64,     // 0 to 4 ms
+    pub phase: i32,     // 0 to 4, meaning -180 to 180 degrees
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Gargle {
+    pub rate: f64,    // 1 to 1000 Hz
+    pub square: bool, // otherwise triangle
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Reverb {
+    pub gain: f64,     // -96 to 0 dB
+    pub mix: f64,      // -96 to 0 dB
+    pub time: f64,     // 0.001 to 3000 ms
+    pub hf_ratio: f64, // 0.001 to 0.999
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Compressor {
+    pub gain: f64,      // -60 to 60 dB
+    pub attack: f64,    // 0.01 to 500 ms
+    pub release: f64,   // 50 to 3000 ms
+    pub threshold: f64, // -60 to 0 dB
+    pub ratio: f64,     // 1 to 100
+    pub delay: f64,     // 0 to 4 ms
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Equalizer {
+    pub center: f64,    // 80 to 16000 Hz
+    pub bandwidth: f64, // 1 to 36 semitones
+    pub gain: f64,      // -15 to 15 dB
+}
+
+impl Default for Chorus {
+    fn default() -> Self {
+        Self { wet_dry: 50.0, depth: 10.0, feedback: 25.0, frequency: 1.1, sine: true, delay: 16.0, phase: 3 }
+    }
+}
+
+impl Default for Echo {
+    fn default() -> Self {
+        Self { wet_dry: 50.0, feedback: 50.0, left_delay: 500.0, right_delay: 500.0, pan_delay: false }
+    }
+}
+
+impl Default for Flanger {
+    fn default() -> Self {
+        Self { wet_dry: 50.0, depth: 100.0, feedback: -50.0, frequency: 0.25, sine: true, delay: 2.0, phase: 2 }
+    }
+}
+
+impl Default for Gargle {
+    fn default() -> Self {
+        Self { rate: 20.0, square: false }
+    }
+}
+
+impl Default for Reverb {
+    fn default() -> Self {
+        Self { gain: 0.0, mix: 0.0, time: 1000.0, hf_ratio: 0.001 }
+    }
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self { gain: 0.0, attack: 10.0, release: 200.0, threshold: -20.0, ratio: 3.0, delay: 4.0 }
+    }
+}
+
+impl Default for Equalizer {
+    fn default() -> Self {
+        Self { center: 8000.0, bandwidth: 12.0, gain: 0.0 }
+    }
+}
+
+impl Chorus {
+    pub fn clamped(self) -> Self {
+        Self {
+            wet_dry: self.wet_dry.clamp(0.0, 100.0),
+            depth: self.depth.clamp(0.0, 100.0),
+            feedback: self.feedback.clamp(-99.0, 99.0),
+            frequency: self.frequency.clamp(0.0, 10.0),
+            sine: self.sine,
+            delay: self.delay.clamp(0.0, 20.0),
+            phase: self.phase.clamp(0, 4),
+        }
+    }
+}
+
+impl Echo {
+    pub fn clamped(self) -> Self {
+        Self {
+            wet_dry: self.wet_dry.clamp(0.0, 100.0),
+            feedback: self.feedback.clamp(0.0, 100.0),
+            left_delay: self.left_delay.clamp(1.0, 2000.0),
+            right_delay: self.right_delay.clamp(1.0, 2000.0),
+            pan_delay: self.pan_delay,
+        }
+    }
+}
+
+impl Flanger {
+    pub fn clamped(self) -> Self {
+        Self {
+            wet_dry: self.wet_dry.clamp(0.0, 100.0),
+            depth: self.depth.clamp(0.0, 100.0),
+            feedback: self.feedback.clamp(-99.0, 99.0),
+            frequency: self.frequency.clamp(0.0, 10.0),
+            sine: self.sine,
+            delay: self.delay.clamp(0.0, 4.0),
+            phase: self.phase.clamp(0, 4),
+        }
+    }
+}
+
+impl Gargle {
+    pub fn clamped(self) -> Self {
+        Self { rate: self.rate.clamp(1.0, 1000.0), square: self.square }
+    }
+}
+
+impl Reverb {
+    pub fn clamped(self) -> Self {
+        Self {
+            gain: self.gain.clamp(-96.0, 0.0),
+            mix: self.mix.clamp(-96.0, 0.0),
+            time: self.time.clamp(0.001, 3000.0),
+            hf_ratio: self.hf_ratio.clamp(0.001, 0.999),
+        }
+    }
+}
+
+impl Compressor {
+    pub fn clamped(self) -> Self {
+        Self {
+            gain: self.gain.clamp(-60.0, 60.0),
+            attack: self.attack.clamp(0.01, 500.0),
+            release: self.release.clamp(50.0, 3000.0),
+            threshold: self.threshold.clamp(-60.0, 0.0),
+            ratio: self.ratio.clamp(1.0, 100.0),
+            delay: self.delay.clamp(0.0, 4.0),
+        }
+    }
+}
+
+impl Equalizer {
+    pub fn clamped(self) -> Self {
+        Self {
+            center: self.center.clamp(80.0, 16000.0),
+            bandwidth: self.bandwidth.clamp(1.0, 36.0),
+            gain: self.gain.clamp(-15.0, 15.0),
+        }
+    }
+}
+
+/// One effect in a chain, processing interleaved frames of samples in place.
+trait Effect {
+    fn process(&mut self, frame: &mut [Sample]);
+}
+
+/// The running state of a sound's effects, created when the sound starts playing.
+pub struct EffectChain {
+    channels: usize,
+    effects: Vec<Box<dyn Effect + Send>>,
+}
+
+impl EffectChain {
+    pub fn new(settings: &SoundEffects, sample_rate: u32, channels: usize) -> Self {
+        let rate = sample_rate as f32;
+        let mut effects: Vec<Box<dyn Effect + Send>> = Vec::new();
+        // DirectSound runs the effects in the order they were passed to SetFX, which GM8 does in flag order
+        if settings.enabled & CHORUS != 0 {
+            effects.push(Box::new(Modulation::new(settings.chorus.into(), rate, channels)));
+        }
+        if settings.enabled & ECHO != 0 {
+            effects.push(Box::new(EchoState::new(settings.echo, rate, channels)));
+        }
+        if settings.enabled & FLANGER != 0 {
+            effects.push(Box::new(Modulation::new(settings.flanger.into(), rate, channels)));
+        }
+        if settings.enabled & GARGLE != 0 {
+            effects.push(Box::new(GargleState::new(settings.gargle, rate)));
+        }
+        if settings.enabled & REVERB != 0 {
+            effects.push(Box::new(ReverbState::new(settings.reverb, rate, channels)));
+        }
+        if settings.enabled & COMPRESSOR != 0 {
+            effects.push(Box::new(CompressorState::new(settings.compressor, rate, channels)));
+        }
+        if settings.enabled & EQUALIZER != 0 {
+            effects.push(Box::new(EqualizerState::new(settings.equalizer, rate, channels)));
+        }
+        Self { channels: channels.max(1), effects }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    pub fn process(&mut self, buffer: &mut [Sample]) {
+        for frame in buffer.chunks_exact_mut(self.channels) {
+            for effect in self.effects.iter_mut() {
+                effect.process(frame);
+            }
+        }
+    }
+}
+
+/// A ring buffer which can be read at a fractional number of samples in the past.
+struct DelayLine {
+    buffer: Vec<f32>,
+    pos: usize,
+}
+
+impl DelayLine {
+    fn new(len: usize) -> Self {
+        Self { buffer: vec![0.0; len.max(2)], pos: 0 }
+    }
+
+    fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        let delay = delay.clamp(0.0, (len - 2) as f32);
+        let whole = delay as usize;
+        let frac = delay - whole as f32;
+        let a = self.buffer[(self.pos + len - 1 - whole) % len];
+        let b = self.buffer[(self.pos + len - 2 - whole) % len];
+        a + (b - a) * frac
+    }
+
+    fn write(&mut self, sample: f32) {
+        self.buffer[self.pos] = sample;
+        self.pos = (self.pos + 1) % self.buffer.len();
+    }
+}
+
+/// A low frequency oscillator going from -1 to 1, starting at the given phase (in cycles).
+struct Lfo {
+    phase: f32,
+    step: f32,
+}
+
+impl Lfo {
+    fn new(frequency: f32, sample_rate: f32, phase: f32) -> Self {
+        Self { phase: phase.rem_euclid(1.0), step: frequency / sample_rate }
+    }
+
+    fn sine(&self) -> f32 {
+        (self.phase * 2.0 * PI).sin()
+    }
+
+    fn triangle(&self) -> f32 {
+        1.0 - 4.0 * (self.phase - 0.5).abs()
+    }
+
+    fn advance(&mut self) {
+        self.phase = (self.phase + self.step).fract();
+    }
+}
+
+/// Chorus and flanger: a short delay swept by an LFO and fed back into itself.
+struct Modulation {
+    lines: Vec<DelayLine>,
+    lfos: Vec<Lfo>,
+    sine: bool,
+    wet: f32,
+    feedback: f32,
+    delay: f32,
+    depth: f32,
+}
+
+/// The parameters chorus and flanger have in common, plus the longest base delay each one allows.
+struct ModulationSettings {
+    wet_dry: f64,
+    depth: f64,
+    feedback: f64,
+    frequency: f64,
+    sine: bool,
+    delay: f64,
+    phase: i32,
+    max_delay: f64,
+}
+
+impl From<Chorus> for ModulationSettings {
+    fn from(c: Chorus) -> Self {
+        let Chorus { wet_dry, depth, feedback, frequency, sine, delay, phase } = c;
+        Self { wet_dry, depth, feedback, frequency, sine, delay, phase, max_delay: 20.0 }
+    }
+}
+
+impl From<Flanger> for ModulationSettings {
+    fn from(f: Flanger) -> Self {
+        let Flanger { wet_dry, depth, feedback, frequency, sine, delay, phase } = f;
+        Self { wet_dry, depth, feedback, frequency, sine, delay, phase, max_delay: 4.0 }
+    }
+}
+
+impl Modulation {
+    fn new(settings: ModulationSettings, sample_rate: f32, channels: usize) -> Self {
+        // the delay can swing up to twice its base value at full depth
+        let len = (settings.max_delay as f32 * 2.0 * sample_rate / 1000.0) as usize + 4;
+        let phase_offset = (settings.phase - 2) as f32 / 4.0;
+        Self {
+            lines: (0..channels).map(|_| DelayLine::new(len)).collect(),
+            lfos: (0..channels)
+                .map(|c| Lfo::new(settings.frequency as f32, sample_rate, if c % 2 == 1 { phase_offset } else { 0.0 }))
+                .collect(),
+            sine: settings.sine,
+            wet: (settings.wet_dry / 100.0) as f32,
+            feedback: (settings.feedback / 100.0) as f32,
+            delay: settings.delay as f32 * sample_rate / 1000.0,
+            depth: (settings.depth / 100.0) as f32,
+        }
+    }
+}
+
+impl Effect for Modulation {
+    fn process(&mut self, frame: &mut [Sample]) {
+        for ((sample, line), lfo) in frame.iter_mut().zip(self.lines.iter_mut()).zip(self.lfos.iter_mut()) {
+            let sweep = if self.sine { lfo.sine() } else { lfo.triangle() };
+            let delayed = line.read(self.delay * (1.0 + self.depth * sweep));
+            line.write(*sample + delayed * self.feedback);
+            *sample = *sample * (1.0 - self.wet) + delayed * self.wet;
+            lfo.advance();
+        }
+    }
+}
+
+struct EchoState {
+    lines: Vec<DelayLine>,
+    delays: Vec<f32>,
+    delayed: Vec<f32>,
+    wet: f32,
+    feedback: f32,
+    pan_delay: bool,
+}
+
+impl EchoState {
+    fn new(echo: Echo, sample_rate: f32, channels: usize) -> Self {
+        let delays = (0..channels)
+            .map(|c| (if c % 2 == 1 { echo.right_delay } else { echo.left_delay }) as f32 * sample_rate / 1000.0)
+            .collect::<Vec<_>>();
+        Self {
+            lines: delays.iter().map(|d| DelayLine::new(*d as usize + 2)).collect(),
+            delayed: vec![0.0; channels],
+            delays,
+            wet: (echo.wet_dry / 100.0) as f32,
+            feedback: (echo.feedback / 100.0) as f32,
+            pan_delay: echo.pan_delay,
+        }
+    }
+}
+
+impl Effect for EchoState {
+    fn process(&mut self, frame: &mut [Sample]) {
+        for ((delayed, line), delay) in self.delayed.iter_mut().zip(self.lines.iter()).zip(self.delays.iter()) {
+            *delayed = line.read(*delay);
+        }
+        let delayed = &self.delayed;
+        for (c, sample) in frame.iter_mut().enumerate() {
+            // with pan delay on, each channel's echo gets fed into the other one, so it bounces between them
+            let source = if self.pan_delay && delayed.len() >= 2 { c ^ 1 } else { c };
+            let feed = delayed.get(source).copied().unwrap_or(delayed[c]);
+            self.lines[c].write(*sample + feed * self.feedback);
+            *sample = *sample * (1.0 - self.wet) + delayed[c] * self.wet;
+        }
+    }
+}
+
+/// Gargle: amplitude modulation by a unipolar triangle or square wave.
+struct GargleState {
+    lfo: Lfo,
+    square: bool,
+}
+
+impl GargleState {
+    fn new(gargle: Gargle, sample_rate: f32) -> Self {
+        Self { lfo: Lfo::new(gargle.rate as f32, sample_rate, 0.0), square: gargle.square }
+    }
+}
+
+impl Effect for GargleState {
+    fn process(&mut self, frame: &mut [Sample]) {
+        let gain = if self.square {
+            if self.lfo.phase < 0.5 { 1.0 } else { 0.0 }
+        } else {
+            (self.lfo.triangle() + 1.0) / 2.0
+        };
+        frame.iter_mut().for_each(|s| *s *= gain);
+        self.lfo.advance();
+    }
+}
+
+// Comb and allpass lengths from the usual Schroeder-Moorer reverb, in samples at 44.1kHz
+const COMB_LENGTHS: [usize; 4] = [1116, 1188, 1277, 1356];
+const ALLPASS_LENGTHS: [usize; 2] = [556, 441];
+const STEREO_SPREAD: usize = 23;
+
+struct Comb {
+    line: DelayLine,
+    len: f32,
+    feedback: f32,
+    damp: f32,
+    filter: f32,
+}
+
+struct Allpass {
+    line: DelayLine,
+    len: f32,
+}
+
+/// Reverb: parallel combs into series allpasses per channel, with the comb feedback tuned to the reverb time and
+/// a lowpass in each loop so that high frequencies die away after `time * hf_ratio` instead.
+struct ReverbState {
+    combs: Vec<Vec<Comb>>,
+    allpasses: Vec<Vec<Allpass>>,
+    gain: f32,
+    mix: f32,
+}
+
+impl ReverbState {
+    fn new(reverb: Reverb, sample_rate: f32, channels: usize) -> Self {
+        let scale = sample_rate / 44100.0;
+        let time = reverb.time as f32 / 1000.0;
+        let decay = |len: f32, time: f32| 10f32.powf(-3.0 * len / (sample_rate * time));
+        let combs = (0..channels)
+            .map(|c| {
+                COMB_LENGTHS
+                    .iter()
+                    .map(|len| {
+                        let len = ((len + STEREO_SPREAD * (c % 2)) as f32 * scale).max(1.0);
+                        let feedback = decay(len, time);
+                        let hf_feedback = decay(len, time * reverb.hf_ratio as f32);
+                        Comb {
+                            line: DelayLine::new(len as usize + 2),
+                            len,
+                            feedback,
+                            damp: (1.0 - hf_feedback / feedback.max(f32::EPSILON)).clamp(0.0, 1.0),
+                            filter: 0.0,
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let allpasses = (0..channels)
+            .map(|c| {
+                ALLPASS_LENGTHS
+                    .iter()
+                    .map(|len| {
+                        let len = ((len + STEREO_SPREAD * (c % 2)) as f32 * scale).max(1.0);
+                        Allpass { line: DelayLine::new(len as usize + 2), len }
+                    })
+                    .collect()
+            })
+            .collect();
+        Self {
+            combs,
+            allpasses,
+            gain: 10f32.powf(reverb.gain as f32 / 20.0),
+            mix: 10f32.powf(reverb.mix as f32 / 20.0),
+        }
+    }
+}
+
+impl Effect for ReverbState {
+    fn process(&mut self, frame: &mut [Sample]) {
+        for ((sample, combs), allpasses) in frame.iter_mut().zip(self.combs.iter_mut()).zip(self.allpasses.iter_mut()) {
+            let input = *sample * self.gain;
+            let mut wet = 0.0;
+            for comb in combs.iter_mut() {
+                let delayed = comb.line.read(comb.len - 1.0);
+                comb.filter = delayed * (1.0 - comb.damp) + comb.filter * comb.damp;
+                comb.line.write(input + comb.filter * comb.feedback);
+                wet += delayed;
+            }
+            wet /= combs.len() as f32;
+            for allpass in allpasses.iter_mut() {
+                let delayed = allpass.line.read(allpass.len - 1.0);
+                allpass.line.write(wet + delayed * 0.5);
+                wet = delayed - wet * 0.5;
+            }
+            *sample = input + wet * self.mix;
+        }
+    }
+}
+
+/// Compressor: a linked peak detector running ahead of the signal by the predelay.
+struct CompressorState {
+    lines: Vec<DelayLine>,
+    delay: f32,
+    attack: f32,
+    release: f32,
+    envelope: f32,
+    threshold: f32,
+    slope: f32,
+    gain: f32,
+}
+
+impl CompressorState {
+    fn new(compressor: Compressor, sample_rate: f32, channels: usize) -> Self {
+        let coefficient = |ms: f64| (-1.0 / (ms as f32 / 1000.0 * sample_rate)).exp();
+        let delay = compressor.delay as f32 * sample_rate / 1000.0;
+        Self {
+            lines: (0..channels).map(|_| DelayLine::new(delay as usize + 2)).collect(),
+            delay,
+            attack: coefficient(compressor.attack),
+            release: coefficient(compressor.release),
+            envelope: -96.0,
+            threshold: compressor.threshold as f32,
+            slope: 1.0 - 1.0 / compressor.ratio as f32,
+            gain: compressor.gain as f32,
+        }
+    }
+}
+
+impl Effect for CompressorState {
+    fn process(&mut self, frame: &mut [Sample]) {
+        let peak = frame.iter().fold(0.0f32, |acc, s| acc.max(s.abs()));
+        let level = 20.0 * peak.max(1e-5).log10();
+        let coefficient = if level > self.envelope { self.attack } else { self.release };
+        self.envelope = level + (self.envelope - level) * coefficient;
+        let reduction = (self.envelope - self.threshold).max(0.0) * self.slope;
+        let gain = 10f32.powf((self.gain - reduction) / 20.0);
+        for (sample, line) in frame.iter_mut().zip(self.lines.iter_mut()) {
+            line.write(*sample);
+            *sample = line.read(self.delay) * gain;
+        }
+    }
+}
+
+/// Parametric equalizer: a single peaking biquad per channel.
+struct EqualizerState {
+    coefficients: [f32; 5],
+    history: Vec<[f32; 4]>,
+}
+
+impl EqualizerState {
+    fn new(equalizer: Equalizer, sample_rate: f32, channels: usize) -> Self {
+        // DirectSound refuses a centre above a third of the sample rate
+        let center = (equalizer.center as f32).min(sample_rate / 3.0);
+        let a = 10f32.powf(equalizer.gain as f32 / 40.0);
+        let w0 = 2.0 * PI * center / sample_rate;
+        let octaves = equalizer.bandwidth as f32 / 12.0;
+        let alpha = w0.sin() * ((2f32.ln() / 2.0) * octaves * w0 / w0.sin()).sinh();
+        let a0 = 1.0 + alpha / a;
+        let coefficients = [
+            (1.0 + alpha * a) / a0,
+            (-2.0 * w0.cos()) / a0,
+            (1.0 - alpha * a) / a0,
+            (-2.0 * w0.cos()) / a0,
+            (1.0 - alpha / a) / a0,
+        ];
+        Self { coefficients, history: vec![[0.0; 4]; channels] }
+    }
+}
+
+impl Effect for EqualizerState {
+    fn process(&mut self, frame: &mut [Sample]) {
+        let [b0, b1, b2, a1, a2] = self.coefficients;
+        for (sample, [x1, x2, y1, y2]) in frame.iter_mut().zip(self.history.iter_mut()) {
+            let x = *sample;
+            let y = b0 * x + b1 * *x1 + b2 * *x2 - a1 * *y1 - a2 * *y2;
+            *x2 = *x1;
+            *x1 = x;
+            *y2 = *y1;
+            *y1 = y;
+            *sample = y;
+        }
+    }
+}
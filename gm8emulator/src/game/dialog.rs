@@ -0,0 +1,866 @@
+use crate::{
+    game::{draw, replay, Game, GetAsset, PlayType},
+    gml::{self, Value},
+    input::{self, Button},
+    math::Real,
+    render::Scaling,
+    types::ID,
+};
+use ramen::event::Event;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+pub(super) const PADDING: i32 = 12;
+pub(super) const CAPTION_HEIGHT: i32 = 20;
+const BUTTON_WIDTH: i32 = 75;
+const BUTTON_HEIGHT: i32 = 23;
+pub(super) const MIN_WIDTH: i32 = 200;
+const SWATCH_SIZE: i32 = 20;
+
+const COLOUR_FACE: i32 = 0xF0F0F0;
+pub(super) const COLOUR_BORDER: i32 = 0x646464;
+pub(super) const COLOUR_CAPTION: i32 = 0x800000;
+const COLOUR_HIGHLIGHT: i32 = 0xD77800;
+
+/// The basic colours offered by the Windows colour picker, in the same order, as BGR.
+const PALETTE: [i32; 48] = [
+    0x8080FF, 0x80FFFF, 0x80FF80, 0x80FF00, 0xFFFF80, 0xFF8000, 0xC080FF, 0xFF80FF, //
+    0x0000FF, 0x00FFFF, 0x00FF80, 0x40FF00, 0xFFFF00, 0xC08000, 0xC08080, 0xFF00FF, //
+    0x404080, 0x4080FF, 0x00FF00, 0x808000, 0x804000, 0xFF8080, 0x400080, 0x8000FF, //
+    0x000080, 0x0080FF, 0x008000, 0x408000, 0xFF0000, 0xA00000, 0x800080, 0xFF0080, //
+    0x000040, 0x004080, 0x004000, 0x404000, 0x800000, 0x400000, 0x400040, 0x800040, //
+    0x000000, 0x008080, 0x408080, 0x808080, 0x808040, 0xC0C0C0, 0x400040, 0xFFFFFF, //
+];
+const PALETTE_COLUMNS: i32 = 8;
+
+/// A font chosen with one of the message_*_font functions. GM8 can use any font installed on the system,
+/// but we can only draw the fonts which are in the game, so the closest matching font resource is used instead,
+/// or the default font if there isn't one with the same name and style.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MessageFont {
+    pub name: gml::String,
+    pub size: i32,
+    pub colour: i32,
+    pub style: i32,
+    pub charset: i32,
+}
+
+/// Settings for the built-in dialogs, which can be changed with the message_* functions.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MessageSettings {
+    pub background: ID,
+    pub button: ID,
+    pub alpha: Real,
+    pub text_font: MessageFont,
+    pub button_font: MessageFont,
+    pub input_font: MessageFont,
+    pub mouse_colour: i32,
+    pub input_colour: i32,
+    pub position: (i32, i32),
+    pub size: (i32, i32),
+    pub show_caption: bool,
+    pub caption: gml::String,
+}
+
+impl MessageFont {
+    pub fn new(colour: i32) -> Self {
+        Self { name: "Arial".into(), size: 10, colour, style: 0, charset: 1 }
+    }
+}
+
+impl Default for MessageSettings {
+    fn default() -> Self {
+        Self {
+            background: -1,
+            button: -1,
+            alpha: Real::from(1.0),
+            text_font: MessageFont::new(0),
+            button_font: MessageFont::new(0),
+            input_font: MessageFont::new(0),
+            mouse_colour: COLOUR_CAPTION,
+            input_colour: 0xFFFFFF,
+            position: (-1, -1),
+            size: (-1, -1),
+            show_caption: true,
+            caption: "".into(),
+        }
+    }
+}
+
+/// Something that happened while a dialog was open, with mouse positions already translated to the game's view.
+#[derive(Clone, Copy)]
+pub(super) enum DialogInput {
+    Key(u8),
+    Char(char),
+    MouseMove(i32, i32),
+    MouseDown(i32, i32),
+    MouseUp(i32, i32),
+    Close,
+}
+
+/// A modal dialog which takes over the game window until it's dismissed.
+pub(super) trait Modal {
+    type Output;
+
+    /// Handles an input, returning a result if the dialog should close.
+    fn handle(&mut self, game: &mut Game, input: DialogInput) -> Option<Self::Output>;
+
+    /// Called once per frame while the dialog is open, for dialogs which close on their own.
+    fn tick(&mut self) -> Option<Self::Output> {
+        None
+    }
+
+    fn draw(&self, game: &mut Game);
+}
+
+pub(super) type Rect = (i32, i32, i32, i32);
+
+pub(super) fn contains((x, y, w, h): Rect, (px, py): (i32, i32)) -> bool {
+    px >= x && py >= y && px < x + w && py < y + h
+}
+
+/// Draws a filled rectangle with a one pixel border.
+pub(super) fn draw_box(game: &mut Game, (x, y, w, h): Rect, fill: i32, border: i32, alpha: f64) {
+    let (x1, y1, x2, y2) = (f64::from(x), f64::from(y), f64::from(x + w - 1), f64::from(y + h - 1));
+    game.renderer.draw_rectangle(x1, y1, x2, y2, fill, alpha);
+    if border != fill {
+        game.renderer.draw_rectangle_outline(x1, y1, x2, y2, border, alpha);
+    }
+}
+
+/// A button label, with the '&' marking its keyboard shortcut (if any) removed.
+struct Label {
+    text: gml::String,
+    shortcut: Option<u8>,
+}
+
+impl Label {
+    fn new(label: &[u8]) -> Self {
+        let mut text = Vec::with_capacity(label.len());
+        let mut shortcut = None;
+        let mut iter = label.iter().copied();
+        while let Some(c) = iter.next() {
+            if c == b'&' {
+                match iter.next() {
+                    Some(c) => {
+                        if c != b'&' && shortcut.is_none() && c.is_ascii_alphanumeric() {
+                            shortcut = Some(c.to_ascii_uppercase());
+                        }
+                        text.push(c);
+                    },
+                    None => break,
+                }
+            } else {
+                text.push(c);
+            }
+        }
+        Self { text: text.into(), shortcut }
+    }
+}
+
+/// How a message box was closed.
+#[derive(Clone, Copy, PartialEq)]
+enum Outcome {
+    Button(usize),
+    Cancel,
+}
+
+/// A message box with some text and buttons, and optionally a text field or a colour palette.
+struct MessageBox {
+    caption: gml::String,
+    text: gml::String,
+    buttons: Vec<Label>,
+    input: Option<String>,
+    colour: Option<i32>,
+    mouse: (i32, i32),
+    pressed: Option<usize>,
+}
+
+struct MessageLayout {
+    bounds: Rect,
+    text: (i32, i32),
+    text_width: i32,
+    input: Option<Rect>,
+    palette: Option<(i32, i32)>,
+    buttons: Vec<Rect>,
+}
+
+impl MessageBox {
+    fn new(caption: gml::String, text: gml::String, buttons: Vec<Label>) -> Self {
+        Self { caption, text, buttons, input: None, colour: None, mouse: (-1, -1), pressed: None }
+    }
+
+    fn with_input(mut self, default: String) -> Self {
+        self.input = Some(default);
+        self
+    }
+
+    fn with_palette(mut self, default: i32) -> Self {
+        self.colour = Some(default);
+        self
+    }
+
+    fn button_size(&self, game: &mut Game) -> (i32, i32) {
+        match game.assets.sprites.get_asset(game.message_settings.button) {
+            Some(sprite) => (sprite.width as i32, sprite.height as i32),
+            None => {
+                let font = game.message_settings.button_font.clone();
+                let widest = self.buttons.iter().map(|b| game.dialog_text_size(&font, &b.text, None).0).max();
+                (BUTTON_WIDTH.max(widest.unwrap_or(0) + PADDING * 2), BUTTON_HEIGHT)
+            },
+        }
+    }
+
+    fn layout(&self, game: &mut Game) -> MessageLayout {
+        let settings = game.message_settings.clone();
+        let (screen_w, screen_h) = (game.unscaled_width as i32, game.unscaled_height as i32);
+        let (button_w, button_h) = self.button_size(game);
+        let buttons_w = self.buttons.len() as i32 * (button_w + PADDING) - PADDING;
+        let palette_w = PALETTE_COLUMNS * SWATCH_SIZE;
+        let caption_h = if settings.show_caption { CAPTION_HEIGHT } else { 0 };
+
+        let width = if settings.size.0 > 0 {
+            settings.size.0
+        } else {
+            let max_text_w = (screen_w - PADDING * 4).max(MIN_WIDTH);
+            let text_w = game.dialog_text_size(&settings.text_font, &self.text, Some(max_text_w)).0;
+            (text_w.max(buttons_w).max(if self.colour.is_some() { palette_w } else { 0 }) + PADDING * 2).max(MIN_WIDTH)
+        };
+        let text_width = width - PADDING * 2;
+        let text_h = game.dialog_text_size(&settings.text_font, &self.text, Some(text_width)).1;
+        let input_h = match self.input {
+            Some(_) => game.dialog_text_size(&settings.input_font, &"|".into(), None).1 + 6 + PADDING,
+            None => 0,
+        };
+        let palette_h = match self.colour {
+            Some(_) => (PALETTE.len() as i32 / PALETTE_COLUMNS) * SWATCH_SIZE + SWATCH_SIZE + PADDING * 2,
+            None => 0,
+        };
+        let height = if settings.size.1 > 0 {
+            settings.size.1
+        } else {
+            caption_h + PADDING + text_h + input_h + palette_h + PADDING + button_h + PADDING
+        };
+        let x = if settings.position.0 < 0 { (screen_w - width) / 2 } else { settings.position.0 };
+        let y = if settings.position.1 < 0 { (screen_h - height) / 2 } else { settings.position.1 };
+
+        let mut cursor_y = y + caption_h + PADDING + text_h;
+        let input = self.input.as_ref().map(|_| {
+            let rect = (x + PADDING, cursor_y + PADDING, text_width, input_h - PADDING);
+            cursor_y += input_h;
+            rect
+        });
+        let palette = self.colour.map(|_| ((x + (width - palette_w) / 2), cursor_y + PADDING));
+        let buttons_x = x + (width - buttons_w) / 2;
+        let buttons_y = y + height - PADDING - button_h;
+        let buttons = (0..self.buttons.len() as i32)
+            .map(|i| (buttons_x + i * (button_w + PADDING), buttons_y, button_w, button_h))
+            .collect();
+        MessageLayout {
+            bounds: (x, y, width, height),
+            text: (x + PADDING, y + caption_h + PADDING),
+            text_width,
+            input,
+            palette,
+            buttons,
+        }
+    }
+
+    fn swatch_at(palette: (i32, i32), pos: (i32, i32)) -> Option<i32> {
+        (0..PALETTE.len() as i32)
+            .find(|i| {
+                let rect = (
+                    palette.0 + (i % PALETTE_COLUMNS) * SWATCH_SIZE,
+                    palette.1 + (i / PALETTE_COLUMNS) * SWATCH_SIZE,
+                    SWATCH_SIZE,
+                    SWATCH_SIZE,
+                );
+                contains(rect, pos)
+            })
+            .map(|i| PALETTE[i as usize])
+    }
+}
+
+impl Modal for MessageBox {
+    type Output = Outcome;
+
+    fn handle(&mut self, game: &mut Game, input: DialogInput) -> Option<Outcome> {
+        match input {
+            DialogInput::Key(vk) if vk == Button::Escape as u8 => return Some(Outcome::Cancel),
+            DialogInput::Key(vk) if vk == Button::Return as u8 => return Some(Outcome::Button(0)),
+            DialogInput::Key(vk) if vk == Button::Backspace as u8 => {
+                if let Some(text) = self.input.as_mut() {
+                    text.pop();
+                }
+            },
+            DialogInput::Key(vk) if self.input.is_none() => {
+                if let Some(i) = self.buttons.iter().position(|b| b.shortcut == Some(vk)) {
+                    return Some(Outcome::Button(i))
+                }
+            },
+            DialogInput::Key(_) => (),
+            DialogInput::Char(c) => {
+                if let Some(text) = self.input.as_mut() {
+                    if !c.is_control() {
+                        text.push(c);
+                    }
+                }
+            },
+            DialogInput::MouseMove(x, y) => self.mouse = (x, y),
+            DialogInput::MouseDown(x, y) => {
+                self.mouse = (x, y);
+                let layout = self.layout(game);
+                self.pressed = layout.buttons.iter().position(|b| contains(*b, self.mouse));
+                if let Some(colour) = layout.palette.and_then(|p| Self::swatch_at(p, self.mouse)) {
+                    self.colour = Some(colour);
+                }
+            },
+            DialogInput::MouseUp(x, y) => {
+                self.mouse = (x, y);
+                if let Some(pressed) = self.pressed.take() {
+                    if contains(self.layout(game).buttons[pressed], self.mouse) {
+                        return Some(Outcome::Button(pressed))
+                    }
+                }
+            },
+            DialogInput::Close => return Some(Outcome::Cancel),
+        }
+        None
+    }
+
+    fn draw(&self, game: &mut Game) {
+        let settings = game.message_settings.clone();
+        let alpha = settings.alpha.into();
+        let layout = self.layout(game);
+        let (x, y, w, h) = layout.bounds;
+
+        let background = game
+            .assets
+            .backgrounds
+            .get_asset(settings.background)
+            .and_then(|b| b.atlas_ref.map(|a| (a, b.width.max(1), b.height.max(1))));
+        match background {
+            Some((atlas_ref, bg_w, bg_h)) => {
+                let (xscale, yscale) = (f64::from(w) / f64::from(bg_w), f64::from(h) / f64::from(bg_h));
+                game.renderer.draw_sprite(atlas_ref, x.into(), y.into(), xscale, yscale, 0.0, 0xFFFFFF, alpha);
+            },
+            None => draw_box(game, layout.bounds, COLOUR_FACE, COLOUR_BORDER, alpha),
+        }
+
+        if settings.show_caption {
+            draw_box(game, (x + 1, y + 1, w - 2, CAPTION_HEIGHT - 1), COLOUR_CAPTION, COLOUR_CAPTION, alpha);
+            let font = MessageFont { colour: 0xFFFFFF, ..settings.text_font.clone() };
+            let caption_h = game.dialog_text_size(&font, &self.caption, None).1;
+            game.dialog_draw_text(&font, x + 6, y + (CAPTION_HEIGHT - caption_h) / 2, &self.caption, None, alpha);
+        }
+
+        let (text_x, text_y) = layout.text;
+        game.dialog_draw_text(&settings.text_font, text_x, text_y, &self.text, Some(layout.text_width), alpha);
+
+        if let (Some(rect), Some(text)) = (layout.input, self.input.as_ref()) {
+            draw_box(game, rect, settings.input_colour, COLOUR_BORDER, alpha);
+            let text = game.dialog_string(&format!("{}|", text));
+            game.dialog_draw_text(&settings.input_font, rect.0 + 3, rect.1 + 3, &text, None, alpha);
+        }
+
+        if let (Some((px, py)), Some(colour)) = (layout.palette, self.colour) {
+            for (i, swatch) in PALETTE.iter().copied().enumerate() {
+                let sx = px + (i as i32 % PALETTE_COLUMNS) * SWATCH_SIZE;
+                let sy = py + (i as i32 / PALETTE_COLUMNS) * SWATCH_SIZE;
+                let border = if swatch == colour { 0 } else { COLOUR_FACE };
+                draw_box(game, (sx, sy, SWATCH_SIZE, SWATCH_SIZE), border, border, alpha);
+                draw_box(game, (sx + 2, sy + 2, SWATCH_SIZE - 4, SWATCH_SIZE - 4), swatch, swatch, alpha);
+            }
+            let preview_y = py + (PALETTE.len() as i32 / PALETTE_COLUMNS) * SWATCH_SIZE + PADDING / 2;
+            draw_box(game, (px, preview_y, PALETTE_COLUMNS * SWATCH_SIZE, SWATCH_SIZE), colour, COLOUR_BORDER, alpha);
+        }
+
+        for (i, (label, rect)) in self.buttons.iter().zip(layout.buttons.iter().copied()).enumerate() {
+            let (bx, by, bw, bh) = rect;
+            let hovering = contains(rect, self.mouse);
+            let pressed = hovering && self.pressed == Some(i);
+            let frame = if pressed {
+                2
+            } else if hovering {
+                1
+            } else {
+                0
+            };
+            let button_sprite = game
+                .assets
+                .sprites
+                .get_asset(settings.button)
+                .and_then(|s| s.frames.get(frame).or_else(|| s.frames.first()))
+                .map(|f| f.atlas_ref);
+            match button_sprite {
+                Some(atlas_ref) => {
+                    game.renderer.draw_sprite(atlas_ref, bx.into(), by.into(), 1.0, 1.0, 0.0, 0xFFFFFF, alpha)
+                },
+                None => {
+                    let face = if pressed { 0xD8D8D8 } else { 0xE1E1E1 };
+                    let border = if hovering { COLOUR_HIGHLIGHT } else { 0xADADAD };
+                    draw_box(game, rect, face, border, alpha);
+                },
+            }
+            let font = if hovering {
+                MessageFont { colour: settings.mouse_colour, ..settings.button_font.clone() }
+            } else {
+                settings.button_font.clone()
+            };
+            let (tw, th) = game.dialog_text_size(&font, &label.text, None);
+            game.dialog_draw_text(&font, bx + (bw - tw) / 2, by + (bh - th) / 2, &label.text, None, alpha);
+        }
+    }
+}
+
+/// A popup menu, as shown by show_menu. The result is the index of the chosen item, if any.
+struct Menu {
+    items: Vec<gml::String>,
+    position: (i32, i32),
+    selected: Option<usize>,
+}
+
+impl Menu {
+    fn is_separator(item: &gml::String) -> bool {
+        item.as_ref() == b"-"
+    }
+
+    fn item_rects(&self, game: &mut Game) -> Vec<Rect> {
+        let font = game.message_settings.text_font.clone();
+        let line_h = game.dialog_text_size(&font, &"|".into(), None).1 + 6;
+        let width = self.items.iter().map(|i| game.dialog_text_size(&font, i, None).0).max().unwrap_or(0) + 32;
+        let height = line_h * self.items.len() as i32;
+        let (screen_w, screen_h) = (game.unscaled_width as i32, game.unscaled_height as i32);
+        let x = self.position.0.min(screen_w - width - 2).max(0);
+        let y = self.position.1.min(screen_h - height - 2).max(0);
+        (0..self.items.len() as i32).map(|i| (x + 1, y + 1 + i * line_h, width, line_h)).collect()
+    }
+
+    /// Finds the item at a position, ignoring separators.
+    fn item_at(&self, game: &mut Game, pos: (i32, i32)) -> Option<usize> {
+        self.item_rects(game).iter().position(|r| contains(*r, pos)).filter(|i| !Self::is_separator(&self.items[*i]))
+    }
+
+    fn step_selection(&mut self, forward: bool) {
+        let count = self.items.len();
+        let mut index = self.selected.unwrap_or(if forward { count - 1 } else { 0 });
+        for _ in 0..count {
+            index = if forward { (index + 1) % count } else { (index + count - 1) % count };
+            if !Self::is_separator(&self.items[index]) {
+                self.selected = Some(index);
+                break
+            }
+        }
+    }
+}
+
+impl Modal for Menu {
+    type Output = Option<usize>;
+
+    fn handle(&mut self, game: &mut Game, input: DialogInput) -> Option<Option<usize>> {
+        match input {
+            DialogInput::Key(vk) if vk == Button::Escape as u8 => return Some(None),
+            DialogInput::Key(vk) if vk == Button::Return as u8 => return Some(self.selected),
+            DialogInput::Key(vk) if vk == Button::UpArrow as u8 => self.step_selection(false),
+            DialogInput::Key(vk) if vk == Button::DownArrow as u8 => self.step_selection(true),
+            DialogInput::Key(_) | DialogInput::Char(_) => (),
+            DialogInput::MouseMove(x, y) => self.selected = self.item_at(game, (x, y)),
+            DialogInput::MouseDown(x, y) => {
+                if !self.item_rects(game).iter().any(|r| contains(*r, (x, y))) {
+                    return Some(None)
+                }
+            },
+            DialogInput::MouseUp(x, y) => {
+                if let Some(i) = self.item_at(game, (x, y)) {
+                    return Some(Some(i))
+                }
+            },
+            DialogInput::Close => return Some(None),
+        }
+        None
+    }
+
+    fn draw(&self, game: &mut Game) {
+        let settings = game.message_settings.clone();
+        let alpha = settings.alpha.into();
+        let rects = self.item_rects(game);
+        if let (Some(first), Some(last)) = (rects.first().copied(), rects.last().copied()) {
+            let bounds = (first.0 - 1, first.1 - 1, first.2 + 2, last.1 + last.3 - first.1 + 2);
+            draw_box(game, bounds, COLOUR_FACE, COLOUR_BORDER, alpha);
+        }
+        for (i, (item, rect)) in self.items.iter().zip(rects.into_iter()).enumerate() {
+            let (x, y, w, h) = rect;
+            if Self::is_separator(item) {
+                let mid = f64::from(y + h / 2);
+                let (x1, x2) = (f64::from(x + 2), f64::from(x + w - 3));
+                game.renderer.draw_line(x1, mid, x2, mid, None, COLOUR_BORDER, COLOUR_BORDER, alpha);
+                continue
+            }
+            let font = if self.selected == Some(i) {
+                draw_box(game, rect, COLOUR_HIGHLIGHT, COLOUR_HIGHLIGHT, alpha);
+                MessageFont { colour: 0xFFFFFF, ..settings.text_font.clone() }
+            } else {
+                settings.text_font.clone()
+            };
+            game.dialog_draw_text(&font, x + 16, y + 3, item, None, alpha);
+        }
+    }
+}
+
+impl Game {
+    /// Finds the font resource to use in place of a font chosen with one of the message_*_font functions.
+    fn dialog_font_id(&self, font: &MessageFont) -> ID {
+        let (bold, italic) = (font.style & 1 != 0, font.style & 2 != 0);
+        self.assets
+            .fonts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, f)| f.as_ref().map(|f| (i, f)))
+            .filter(|(_, f)| {
+                f.sys_name.eq_ignore_ascii_case(font.name.as_ref()) && f.bold == bold && f.italic == italic
+            })
+            .min_by_key(|(_, f)| (f.size as i32 - font.size).abs())
+            .map(|(i, _)| i as ID)
+            .unwrap_or(-1)
+    }
+
+    pub(super) fn dialog_text_size(
+        &mut self,
+        font: &MessageFont,
+        text: &gml::String,
+        max_width: Option<i32>,
+    ) -> (i32, i32) {
+        let font_id = self.dialog_font_id(font);
+        let old_font = std::mem::replace(&mut self.draw_font_id, font_id);
+        let size = self.get_string_size(text.clone(), None, max_width);
+        self.draw_font_id = old_font;
+        size
+    }
+
+    pub(super) fn dialog_draw_text(
+        &mut self,
+        font: &MessageFont,
+        x: i32,
+        y: i32,
+        text: &gml::String,
+        max_width: Option<i32>,
+        alpha: f64,
+    ) {
+        let font_id = self.dialog_font_id(font);
+        let old_font = std::mem::replace(&mut self.draw_font_id, font_id);
+        let old_halign = std::mem::replace(&mut self.draw_halign, draw::Halign::Left);
+        let old_valign = std::mem::replace(&mut self.draw_valign, draw::Valign::Top);
+        let c = font.colour;
+        self.draw_string(
+            x.into(),
+            y.into(),
+            text.clone(),
+            None,
+            max_width,
+            Real::from(1.0),
+            Real::from(1.0),
+            Real::from(0.0),
+            Some((c, c, c, c)),
+            alpha.into(),
+        );
+        self.draw_font_id = old_font;
+        self.draw_halign = old_halign;
+        self.draw_valign = old_valign;
+    }
+
+    /// Converts text typed into a dialog into a GML string.
+    pub(super) fn dialog_string(&self, text: &str) -> gml::String {
+        match self.encode_str_maybe(text) {
+            Some(bytes) => bytes.as_ref().into(),
+            None => text.into(),
+        }
+    }
+
+    /// The caption to show on a dialog: the one set with message_caption, or the window caption if that's empty.
+    pub(super) fn dialog_caption(&self) -> gml::String {
+        if self.message_settings.caption.as_ref().is_empty() {
+            self.dialog_string(&self.get_window_title())
+        } else {
+            self.message_settings.caption.clone()
+        }
+    }
+
+    /// Translates a position in the window to a position on the game's framebuffer, undoing any scaling.
+    fn window_to_framebuffer(&self, x: i32, y: i32) -> (i32, i32) {
+        let (window_w, window_h) = self.dialog_window_size();
+        let (window_w, window_h) = (window_w as i32, window_h as i32);
+        let (fb_w, fb_h) = (self.unscaled_width as i32, self.unscaled_height as i32);
+        let (left, top, w, h) = match self.scaling {
+            Scaling::Fixed(scale) => {
+                let w = (f64::from(fb_w) * scale) as i32;
+                let h = (f64::from(fb_h) * scale) as i32;
+                ((window_w - w) / 2, (window_h - h) / 2, w, h)
+            },
+            Scaling::Aspect(_) if fb_w > 0 && fb_h > 0 => {
+                let fixed_width = window_h * fb_w / fb_h;
+                if fixed_width < window_w {
+                    ((window_w - fixed_width) / 2, 0, fixed_width, window_h)
+                } else {
+                    let fixed_height = window_w * fb_h / fb_w;
+                    (0, (window_h - fixed_height) / 2, window_w, fixed_height)
+                }
+            },
+            Scaling::Aspect(_) => (0, 0, fb_w, fb_h),
+            Scaling::Full => (0, 0, window_w, window_h),
+        };
+        ((x - left) * fb_w / w.max(1), (y - top) * fb_h / h.max(1))
+    }
+
+    /// The size of the window dialogs are presented to. While recording, this is the TAS UI's window.
+    fn dialog_window_size(&self) -> (u32, u32) {
+        if self.play_type == PlayType::Record { self.ui_window_size } else { self.window_inner_size }
+    }
+
+    /// Shows a modal dialog over the game window, blocking until it's dismissed like GM8 does.
+    pub(super) fn run_modal<M: Modal>(&mut self, modal: &mut M) -> M::Output {
+        const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000u64 / 60);
+        let (width, height) = (self.unscaled_width as i32, self.unscaled_height as i32);
+
+        // Keep a copy of what's on screen to draw the dialog on top of, and to restore afterwards
+        self.renderer.flush_queue();
+        self.renderer.reset_target();
+        let renderer_state = self.renderer.state();
+        let mut pixels = self.renderer.get_pixels(0, 0, width, height);
+        pixels.chunks_exact_mut(4).for_each(|px| px[3] = 255);
+        let backdrop = self.renderer.upload_sprite(pixels, width, height, 0, 0).ok();
+        let draw_backdrop = |game: &mut Self| {
+            game.renderer.set_view(0, 0, width, height, 0.0, 0, 0, width, height);
+            if let Some(atlas_ref) = backdrop {
+                game.renderer.draw_sprite(atlas_ref, 0.0, 0.0, 1.0, 1.0, 0.0, 0xFFFFFF, 1.0);
+            }
+        };
+
+        let mut mouse = (-1, -1);
+        let result = 'outer: loop {
+            self.window.poll_events();
+            let events = self.window.events().into_iter().copied().collect::<Vec<_>>();
+            for event in events {
+                let input = match event {
+                    Event::KeyboardDown(key) => Some(DialogInput::Key(input::ramen2vk(key))),
+                    Event::Input(c) => Some(DialogInput::Char(c)),
+                    Event::MouseMove((x, y)) => {
+                        mouse = self.window_to_framebuffer(x as i32, y as i32);
+                        Some(DialogInput::MouseMove(mouse.0, mouse.1))
+                    },
+                    Event::MouseDown(_) => Some(DialogInput::MouseDown(mouse.0, mouse.1)),
+                    Event::MouseUp(_) => Some(DialogInput::MouseUp(mouse.0, mouse.1)),
+                    Event::Resize((w, h)) if self.play_type == PlayType::Record => {
+                        self.ui_window_size = (w as _, h as _);
+                        None
+                    },
+                    Event::Resize((w, h)) => {
+                        self.window_inner_size = (w as _, h as _);
+                        None
+                    },
+                    Event::CloseRequest => {
+                        if self.play_type == PlayType::Normal {
+                            self.close_requested = true;
+                        }
+                        Some(DialogInput::Close)
+                    },
+                    _ => None,
+                };
+                if let Some(result) = input.and_then(|input| modal.handle(self, input)) {
+                    break 'outer result
+                }
+            }
+            if let Some(result) = modal.tick() {
+                break result
+            }
+
+            draw_backdrop(self);
+            modal.draw(self);
+            let (window_w, window_h) = self.dialog_window_size();
+            self.renderer.present(window_w, window_h, self.scaling);
+            gml::datetime::sleep(FRAME_TIME);
+        };
+
+        draw_backdrop(self);
+        self.renderer.flush_queue();
+        if let Some(atlas_ref) = backdrop {
+            self.renderer.delete_sprite(atlas_ref);
+        }
+        self.renderer.set_state(&renderer_state);
+        if let Some(surf) = self.surface_target.and_then(|id| self.surfaces.get_asset(id)) {
+            self.renderer.set_target(surf.atlas_ref);
+        }
+        result
+    }
+
+    /// Shows a dialog, or takes its result from the replay when replaying.
+    /// In record mode, the result is stored in the replay so it doesn't need to be shown again.
+    pub(super) fn dialog_result(
+        &mut self,
+        function: &str,
+        show: impl FnOnce(&mut Self) -> Value,
+        to_event: fn(Value) -> replay::Event,
+        from_event: fn(replay::Event) -> Option<Value>,
+    ) -> gml::Result<Value> {
+        match self.play_type {
+            PlayType::Normal => Ok(show(self)),
+            PlayType::Record => {
+                let value = show(self);
+                self.stored_events.push_back(to_event(value.clone()));
+                Ok(value)
+            },
+            PlayType::Replay => self
+                .stored_events
+                .pop_front()
+                .and_then(from_event)
+                .ok_or_else(|| gml::Error::ReplayError(function.into())),
+        }
+    }
+
+    pub fn dialog_show_message(&mut self, text: gml::String) -> gml::Result<Value> {
+        self.dialog_result(
+            "show_message",
+            |game| {
+                game.run_modal(&mut MessageBox::new(game.dialog_caption(), text, vec![Label::new(b"OK")]));
+                Default::default()
+            },
+            |_| replay::Event::ShowMessage,
+            |ev| matches!(ev, replay::Event::ShowMessage).then(Default::default),
+        )
+    }
+
+    /// Shows a message with up to three buttons, returning the number of the button pressed, or 0 for Escape.
+    /// Empty buttons aren't shown.
+    pub fn dialog_show_message_ext(&mut self, text: gml::String, buttons: [gml::String; 3]) -> gml::Result<Value> {
+        self.dialog_result(
+            "show_message_ext",
+            |game| {
+                let shown = (0..3).filter(|i| !buttons[*i].as_ref().is_empty()).collect::<Vec<_>>();
+                let labels = shown.iter().map(|i| Label::new(buttons[*i].as_ref())).collect();
+                match game.run_modal(&mut MessageBox::new(game.dialog_caption(), text, labels)) {
+                    Outcome::Button(i) => shown.get(i).map(|b| b + 1).unwrap_or(0).into(),
+                    Outcome::Cancel => 0.into(),
+                }
+            },
+            replay::Event::ShowMessageExt,
+            |ev| if let replay::Event::ShowMessageExt(v) = ev { Some(v) } else { None },
+        )
+    }
+
+    pub fn dialog_show_question(&mut self, text: gml::String) -> gml::Result<Value> {
+        self.dialog_result(
+            "show_question",
+            |game| {
+                let mut message_box =
+                    MessageBox::new(game.dialog_caption(), text, vec![Label::new(b"&Yes"), Label::new(b"&No")]);
+                let outcome = game.run_modal(&mut message_box);
+                (outcome == Outcome::Button(0)).into()
+            },
+            replay::Event::ShowQuestion,
+            |ev| if let replay::Event::ShowQuestion(v) = ev { Some(v) } else { None },
+        )
+    }
+
+    /// Asks the player to type something in, returning what they typed, or None if they cancelled.
+    pub(super) fn dialog_input(&mut self, caption: gml::String, text: gml::String, default: String) -> Option<String> {
+        let mut message_box =
+            MessageBox::new(caption, text, vec![Label::new(b"OK"), Label::new(b"Cancel")]).with_input(default);
+        match self.run_modal(&mut message_box) {
+            Outcome::Button(0) => message_box.input,
+            _ => None,
+        }
+    }
+
+    pub fn dialog_get_integer(&mut self, text: gml::String, default: Value) -> gml::Result<Value> {
+        self.dialog_result(
+            "get_integer",
+            |game| {
+                let default_text = game.decode_str(default.repr().as_ref()).into_owned();
+                match game.dialog_input(game.dialog_caption(), text, default_text) {
+                    Some(s) => s.trim().parse::<f64>().map(Value::from).unwrap_or(default),
+                    None => default,
+                }
+            },
+            replay::Event::GetInteger,
+            |ev| if let replay::Event::GetInteger(v) = ev { Some(v) } else { None },
+        )
+    }
+
+    pub fn dialog_get_string(&mut self, text: gml::String, default: Value) -> gml::Result<Value> {
+        self.dialog_result(
+            "get_string",
+            |game| {
+                let default_text = game.decode_str(default.repr().as_ref()).into_owned();
+                match game.dialog_input(game.dialog_caption(), text, default_text) {
+                    Some(s) => game.dialog_string(&s).into(),
+                    None => default,
+                }
+            },
+            replay::Event::GetString,
+            |ev| if let replay::Event::GetString(v) = ev { Some(v) } else { None },
+        )
+    }
+
+    /// Asks for a file or directory name. There's no file browser, so the path just has to be typed in.
+    pub fn dialog_get_path(
+        &mut self,
+        function: &str,
+        caption: &str,
+        text: gml::String,
+        default: gml::String,
+    ) -> gml::Result<Value> {
+        self.dialog_result(
+            function,
+            |game| {
+                let default_text = game.decode_str(default.as_ref()).into_owned();
+                match game.dialog_input(caption.into(), text, default_text) {
+                    Some(s) => game.dialog_string(&s).into(),
+                    None => "".into(),
+                }
+            },
+            replay::Event::GetPath,
+            |ev| if let replay::Event::GetPath(v) = ev { Some(v) } else { None },
+        )
+    }
+
+    pub fn dialog_get_colour(&mut self, default: i32) -> gml::Result<Value> {
+        self.dialog_result(
+            "get_color",
+            |game| {
+                let mut message_box =
+                    MessageBox::new("Color".into(), "".into(), vec![Label::new(b"OK"), Label::new(b"Cancel")])
+                        .with_palette(default);
+                match game.run_modal(&mut message_box) {
+                    Outcome::Button(0) => message_box.colour.unwrap_or(default).into(),
+                    _ => (-1).into(),
+                }
+            },
+            replay::Event::GetColour,
+            |ev| if let replay::Event::GetColour(v) = ev { Some(v) } else { None },
+        )
+    }
+
+    /// Shows a popup menu at the given window position. `items` is separated by '|', and items which are just '-'
+    /// are separators. Returns the index of the chosen item, or `default` if nothing was chosen.
+    pub fn dialog_show_menu(
+        &mut self,
+        function: &str,
+        x: i32,
+        y: i32,
+        items: gml::String,
+        default: Value,
+    ) -> gml::Result<Value> {
+        self.dialog_result(
+            function,
+            |game| {
+                let items = items.as_ref().split(|c| *c == b'|').map(gml::String::from).collect::<Vec<_>>();
+                let position = game.window_to_framebuffer(x, y);
+                match game.run_modal(&mut Menu { items, position, selected: None }) {
+                    Some(i) => i.into(),
+                    None => default,
+                }
+            },
+            replay::Event::ShowMenu,
+            |ev| if let replay::Event::ShowMenu(v) = ev { Some(v) } else { None },
+        )
+    }
+}
@@ -216,6 +216,9 @@ impl Game {
             self.unscaled_height as _,
         );
 
+        // Draw the splash screen or game information if one is being shown while the game runs
+        self.draw_splash_overlay();
+
         // Apply room caption
         let title = self.get_window_title();
         if self.play_type != PlayType::Record {
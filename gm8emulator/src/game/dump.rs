@@ -0,0 +1,626 @@
+//! Audio and video dumping, either to a PNG sequence and a WAV file in-process, or through an external ffmpeg.
+
+use crate::game::Game;
+use image::{imageops, RgbaImage};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+};
+use udon::source::Sample;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Writes numbered PNG frames and a WAV file into a directory, with no external programs needed.
+    Builtin,
+    /// Pipes raw frames and samples into ffmpeg, then muxes them into one file.
+    Ffmpeg,
+}
+
+#[derive(Clone, Copy)]
+pub enum Framerate {
+    /// Dumps at a constant framerate, duplicating or dropping game frames to match it.
+    Fixed(u32),
+    /// Dumps each game frame exactly once, timed by the room speed it ran at.
+    Game,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Resize {
+    /// Stretches frames to the size of the first one.
+    Scale,
+    /// Centres frames on a black background the size of the first one, cropping them if they're bigger.
+    Pad,
+}
+
+/// How the ffmpeg backend encodes its output. Video frames all have to be the same size, which is the size
+/// of the first one, so `resize` decides what happens to frames after the window changes size.
+pub struct FfmpegSettings {
+    pub video_codec: String,
+    pub video_args: Vec<String>,
+    pub audio_codec: String,
+    pub container: Option<String>, // ffmpeg guesses from the output's extension if this isn't set
+    pub mux: bool,
+    pub resize: Resize,
+}
+
+impl Default for FfmpegSettings {
+    fn default() -> Self {
+        Self {
+            video_codec: "libx264rgb".into(),
+            video_args: ["-preset", "veryslow", "-qp", "0"].iter().map(|s| s.to_string()).collect(),
+            audio_codec: "flac".into(),
+            container: None,
+            mux: true,
+            resize: Resize::Scale,
+        }
+    }
+}
+
+pub struct DumpSettings {
+    pub backend: Backend,
+    pub path: PathBuf,
+    pub first_frame: usize,
+    pub last_frame: Option<usize>,
+    pub framerate: Framerate,
+    pub scale: f64,
+    pub ffmpeg: FfmpegSettings,
+}
+
+impl DumpSettings {
+    pub fn new(backend: Backend) -> Self {
+        let path = match backend {
+            Backend::Builtin => "dump".into(),
+            Backend::Ffmpeg => "tas recording.mkv".into(),
+        };
+        Self {
+            backend,
+            path,
+            first_frame: 0,
+            last_frame: None,
+            framerate: Framerate::Fixed(50),
+            scale: 1.0,
+            ffmpeg: Default::default(),
+        }
+    }
+
+    /// Where the ffmpeg backend encodes one of its streams before muxing them, such as "video.mkv".
+    fn stream_path(&self, suffix: &str) -> PathBuf {
+        self.path.with_extension(suffix)
+    }
+}
+
+/// Parses a frame range for --dump-frames: "START", "START-END" or "-END", all inclusive.
+pub fn parse_frames(s: &str) -> Result<(usize, Option<usize>), String> {
+    let parse = |s: &str| s.trim().parse::<usize>().map_err(|e| format!("invalid frame '{}': {}", s, e));
+    match s.split_once('-') {
+        Some((first, last)) => {
+            let first = if first.trim().is_empty() { 0 } else { parse(first)? };
+            let last = if last.trim().is_empty() { None } else { Some(parse(last)?) };
+            match last {
+                Some(last) if last < first => Err(format!("frame range {} ends before it starts", s)),
+                _ => Ok((first, last)),
+            }
+        },
+        None => Ok((parse(s)?, None)),
+    }
+}
+
+/// Parses a framerate policy for --dump-fps: either "game" or a number of frames per second.
+pub fn parse_framerate(s: &str) -> Result<Framerate, String> {
+    match s {
+        "game" => Ok(Framerate::Game),
+        _ => match s.parse::<u32>() {
+            Ok(fps) if fps > 0 => Ok(Framerate::Fixed(fps)),
+            _ => Err(format!("invalid framerate '{}', expected a positive number or 'game'", s)),
+        },
+    }
+}
+
+pub fn parse_resize(s: &str) -> Result<Resize, String> {
+    match s {
+        "scale" => Ok(Resize::Scale),
+        "pad" => Ok(Resize::Pad),
+        _ => Err(format!("invalid resize mode '{}', expected 'scale' or 'pad'", s)),
+    }
+}
+
+/// Fits a frame into the given size, for when the window size changes in the middle of an ffmpeg dump.
+fn fit_frame(image: RgbaImage, (width, height): (u32, u32), resize: Resize) -> RgbaImage {
+    if image.dimensions() == (width, height) {
+        return image
+    }
+    match resize {
+        Resize::Scale => imageops::resize(&image, width, height, imageops::FilterType::Nearest),
+        Resize::Pad => {
+            let mut frame = RgbaImage::from_pixel(width, height, image::Rgba([0, 0, 0, 255]));
+            let offset_x = (i64::from(width) - i64::from(image.width())) / 2;
+            let offset_y = (i64::from(height) - i64::from(image.height())) / 2;
+            for (x, y, pixel) in image.enumerate_pixels() {
+                let (frame_x, frame_y) = (i64::from(x) + offset_x, i64::from(y) + offset_y);
+                if (0..i64::from(width)).contains(&frame_x) && (0..i64::from(height)).contains(&frame_y) {
+                    frame.put_pixel(frame_x as u32, frame_y as u32, *pixel);
+                }
+            }
+            frame
+        },
+    }
+}
+
+/// A WAV file of 32-bit float samples, whose header gets filled in when it's finished.
+struct WavWriter {
+    file: BufWriter<File>,
+    data_len: u32,
+}
+
+impl WavWriter {
+    fn create(path: &Path, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let block_align = channels * 4;
+        file.write_all(b"RIFF\0\0\0\0WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&3u16.to_le_bytes())?; // WAVE_FORMAT_IEEE_FLOAT
+        file.write_all(&channels.to_le_bytes())?;
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&32u16.to_le_bytes())?;
+        file.write_all(b"data\0\0\0\0")?;
+        Ok(Self { file, data_len: 0 })
+    }
+
+    fn write(&mut self, samples: &[Sample]) -> io::Result<()> {
+        // the sizes in the header are 32-bit, and the RIFF one also counts the 36 bytes of header before the data
+        let data_len = samples
+            .len()
+            .checked_mul(4)
+            .and_then(|len| u32::try_from(len).ok())
+            .and_then(|len| self.data_len.checked_add(len))
+            .filter(|len| len.checked_add(36).is_some())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "the audio is too long to fit in a WAV file"))?;
+        for sample in samples {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_len = data_len;
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&(36 + self.data_len).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&self.data_len.to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+enum Output {
+    Builtin { wav: Option<WavWriter> },
+    Ffmpeg {
+        video: Option<(Child, u32)>, // the current segment's ffmpeg and its framerate
+        segments: Vec<PathBuf>,
+        audio: Option<Child>,
+        size: Option<(u32, u32)>,
+    },
+    Finished,
+}
+
+pub struct Dumper {
+    settings: DumpSettings,
+    frame: usize,
+    frame_time: u32,
+    elapsed_nanos: u128,
+    audio_frames: u128,
+    video_frames: usize,
+    output: Output,
+}
+
+impl Dumper {
+    pub fn new(settings: DumpSettings) -> io::Result<Self> {
+        let output = match settings.backend {
+            Backend::Builtin => {
+                fs::create_dir_all(&settings.path)?;
+                Output::Builtin { wav: None }
+            },
+            Backend::Ffmpeg => Output::Ffmpeg { video: None, segments: Vec::new(), audio: None, size: None },
+        };
+        Ok(Self {
+            settings,
+            frame: 0,
+            frame_time: 0,
+            elapsed_nanos: 0,
+            audio_frames: 0,
+            video_frames: 0,
+            output,
+        })
+    }
+
+    /// Sets the number of the next game frame, for when a replay starts from a savestate.
+    pub fn skip_to(&mut self, frame: usize) {
+        self.frame = frame;
+    }
+
+    fn in_range(&self) -> bool {
+        self.frame >= self.settings.first_frame && !matches!(self.settings.last_frame, Some(last) if self.frame > last)
+    }
+
+    /// Moves the clock forward by one output frame at the given rate.
+    fn advance_clock(&mut self, rate: u32) {
+        self.elapsed_nanos += 1_000_000_000 / u128::from(rate.max(1));
+    }
+
+    /// How many frames of audio are due, so that the audio lines up with the video frames written so far.
+    fn audio_frames_due(&mut self, sample_rate: u32) -> usize {
+        let target = self.elapsed_nanos * u128::from(sample_rate) / 1_000_000_000;
+        let due = target.saturating_sub(self.audio_frames);
+        self.audio_frames = target.max(self.audio_frames);
+        due as usize
+    }
+
+    fn write_video(&mut self, pixels: Box<[u8]>, width: u32, height: u32, rate: u32) -> io::Result<()> {
+        let mut image = match RgbaImage::from_raw(width, height, pixels.into_vec()) {
+            Some(image) => image,
+            None => return Ok(()),
+        };
+        // the window's alpha channel is meaningless, so don't let it make frames transparent
+        image.pixels_mut().for_each(|p| p[3] = 255);
+        if self.settings.scale != 1.0 {
+            let scaled_w = ((f64::from(width) * self.settings.scale).round() as u32).max(1);
+            let scaled_h = ((f64::from(height) * self.settings.scale).round() as u32).max(1);
+            image = imageops::resize(&image, scaled_w, scaled_h, imageops::FilterType::Nearest);
+        }
+        let index = self.video_frames;
+        self.video_frames += 1;
+        match &mut self.output {
+            Output::Builtin { .. } => {
+                let path = self.settings.path.join(format!("{:06}.png", index));
+                image.save(&path).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+            },
+            Output::Ffmpeg { video, segments, size, .. } => {
+                let ffmpeg = &self.settings.ffmpeg;
+                let (width, height) = *size.get_or_insert(image.dimensions());
+                // ffmpeg needs a constant framerate for raw input, so when the room speed changes the video gets
+                // continued in a new segment at the new rate, and the segments get joined together at the end
+                if matches!(video, Some((_, segment_rate)) if *segment_rate != rate) {
+                    video.take().unwrap().0.wait_with_output()?;
+                }
+                if video.is_none() {
+                    let path = self.settings.stream_path(&format!("video{}.mkv", segments.len()));
+                    let child = Command::new("ffmpeg")
+                        .arg("-y")
+                        .arg("-f")
+                        .arg("rawvideo")
+                        .arg("-pixel_format")
+                        .arg("rgba")
+                        .arg("-video_size")
+                        .arg(format!("{}x{}", width, height))
+                        .arg("-framerate")
+                        .arg(rate.to_string())
+                        .arg("-an")
+                        .arg("-i")
+                        .arg("-")
+                        .arg("-c:v")
+                        .arg(&ffmpeg.video_codec)
+                        .args(&ffmpeg.video_args)
+                        .arg(&path)
+                        .stdin(Stdio::piped())
+                        .stdout(Stdio::null())
+                        .stderr(Stdio::null())
+                        .spawn()?;
+                    *video = Some((child, rate));
+                    segments.push(path);
+                }
+                let image = fit_frame(image, (width, height), ffmpeg.resize);
+                let stdin = video.as_mut().and_then(|(c, _)| c.stdin.as_mut()).expect("Failed to open stdin");
+                stdin.write_all(image.as_raw())
+            },
+            Output::Finished => Ok(()),
+        }
+    }
+
+    fn write_audio(&mut self, samples: &[Sample], channels: u16, sample_rate: u32) -> io::Result<()> {
+        match &mut self.output {
+            Output::Builtin { wav } => {
+                if wav.is_none() {
+                    *wav = Some(WavWriter::create(&self.settings.path.join("audio.wav"), channels, sample_rate)?);
+                }
+                wav.as_mut().unwrap().write(samples)
+            },
+            Output::Ffmpeg { audio, .. } => {
+                if audio.is_none() {
+                    *audio = Some(
+                        Command::new("ffmpeg")
+                            .arg("-y")
+                            .arg("-f")
+                            .arg("f32le")
+                            .arg("-ar")
+                            .arg(sample_rate.to_string())
+                            .arg("-ac")
+                            .arg(channels.to_string())
+                            .arg("-i")
+                            .arg("-")
+                            .arg("-c:a")
+                            .arg(&self.settings.ffmpeg.audio_codec)
+                            .arg(self.settings.stream_path("audio.mka"))
+                            .stdin(Stdio::piped())
+                            .stdout(Stdio::null())
+                            .stderr(Stdio::null())
+                            .spawn()?,
+                    );
+                }
+                let stdin = audio.as_mut().and_then(|c| c.stdin.as_mut()).expect("Failed to open stdin");
+                let bytes = samples.iter().flat_map(|s| s.to_le_bytes()).collect::<Vec<_>>();
+                stdin.write_all(&bytes)
+            },
+            Output::Finished => Ok(()),
+        }
+    }
+
+    /// Closes the output files, after which nothing more gets written. Does nothing if it's already finished.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match std::mem::replace(&mut self.output, Output::Finished) {
+            Output::Builtin { wav } => wav.map_or(Ok(()), WavWriter::finish),
+            Output::Ffmpeg { video, segments, audio, .. } => {
+                let mut streams = Vec::new();
+                if let Some((video, _)) = video {
+                    video.wait_with_output()?;
+                }
+                if !segments.is_empty() {
+                    let video_path = self.settings.stream_path("video.mkv");
+                    self.join_segments(&segments, &video_path)?;
+                    streams.push(video_path);
+                }
+                if let Some(audio) = audio {
+                    audio.wait_with_output()?;
+                    streams.push(self.settings.stream_path("audio.mka"));
+                }
+                if !self.settings.ffmpeg.mux || streams.is_empty() {
+                    return Ok(())
+                }
+                // combine audio and video dump into one file
+                let mut command = Command::new("ffmpeg");
+                command.arg("-y");
+                for stream in streams.iter() {
+                    command.arg("-i").arg(stream);
+                }
+                command.arg("-c").arg("copy");
+                if let Some(container) = &self.settings.ffmpeg.container {
+                    command.arg("-f").arg(container);
+                }
+                let output = command
+                    .arg("--")
+                    .arg(&self.settings.path)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::inherit())
+                    .spawn()?
+                    .wait_with_output()?;
+                if output.status.success() {
+                    for stream in streams.iter() {
+                        fs::remove_file(stream).ok();
+                    }
+                    Ok(())
+                } else {
+                    Err(io::Error::new(io::ErrorKind::Other, "ffmpeg couldn't mux the audio and video together"))
+                }
+            },
+            Output::Finished => Ok(()),
+        }
+    }
+
+    /// Joins the video segments made at each framerate into one file, keeping each segment's timing.
+    fn join_segments(&self, segments: &[PathBuf], path: &Path) -> io::Result<()> {
+        if let [segment] = segments {
+            return fs::rename(segment, path)
+        }
+        let list_path = self.settings.stream_path("video.txt");
+        let mut list = String::new();
+        for segment in segments {
+            let segment = fs::canonicalize(segment)?;
+            list.push_str(&format!("file '{}'\n", segment.to_string_lossy().replace('\'', "'\\''")));
+        }
+        fs::write(&list_path, list)?;
+        let output = Command::new("ffmpeg")
+            .arg("-y")
+            .arg("-f")
+            .arg("concat")
+            .arg("-safe")
+            .arg("0")
+            .arg("-i")
+            .arg(&list_path)
+            .arg("-c")
+            .arg("copy")
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()?
+            .wait_with_output()?;
+        if output.status.success() {
+            fs::remove_file(&list_path).ok();
+            for segment in segments {
+                fs::remove_file(segment).ok();
+            }
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::Other, "ffmpeg couldn't join the video segments together"))
+        }
+    }
+}
+
+impl Game {
+    /// Dumps the frame that was just drawn, along with the audio that plays until the next one.
+    pub fn dump_frame(&mut self) {
+        self.dump_audiovideo(self.room.speed, true);
+    }
+
+    /// Like dump_frame, but for the steps of a room transition, which run at 120 FPS and aren't game frames.
+    pub fn dump_transition_frame(&mut self) {
+        self.dump_audiovideo(120, false);
+    }
+
+    fn dump_audiovideo(&mut self, game_speed: u32, advance: bool) {
+        let dumper = match self.dumper.as_mut() {
+            Some(dumper) => dumper,
+            None => return,
+        };
+        let in_range = dumper.in_range();
+        let (output_rate, count) = match dumper.settings.framerate {
+            Framerate::Fixed(fps) => {
+                let mut count = 0;
+                while dumper.frame_time < fps {
+                    dumper.frame_time += game_speed;
+                    count += 1;
+                }
+                dumper.frame_time -= fps;
+                (fps, count)
+            },
+            Framerate::Game => (game_speed, 1),
+        };
+        let (channels, sample_rate) = self.audio.dump_format();
+        for _ in 0..count {
+            let mut result = Ok(());
+            if in_range && self.scene_change.is_none() {
+                let (w, h) = self.window_inner_size;
+                let pixels = self.renderer.get_pixels(0, 0, w as i32, h as i32);
+                result = dumper.write_video(pixels, w, h, output_rate);
+            }
+            // the audio gets pulled from the mixer even when it's not being written, so that it keeps time
+            dumper.advance_clock(output_rate);
+            let samples = self.audio.dump_audio(dumper.audio_frames_due(sample_rate));
+            if in_range {
+                result = result.and_then(|()| dumper.write_audio(&samples, channels, sample_rate));
+            }
+            if let Err(e) = result {
+                eprintln!("Failed to write to the dump, stopping it: {}", e);
+                dumper.finish().ok();
+                return
+            }
+        }
+        if advance {
+            dumper.frame += 1;
+            if matches!(dumper.settings.last_frame, Some(last) if dumper.frame > last) {
+                self.finish_dump();
+            }
+        }
+    }
+
+    /// Writes a frame of video only, for the screen as it is before a new room's first frame gets drawn.
+    pub fn dump_room_start(&mut self) {
+        if let Some(dumper) = self.dumper.as_mut() {
+            if dumper.in_range() {
+                let (w, h) = self.window_inner_size;
+                let pixels = self.renderer.get_pixels(0, 0, w as i32, h as i32);
+                let rate = match dumper.settings.framerate {
+                    Framerate::Fixed(fps) => fps,
+                    Framerate::Game => self.room.speed,
+                };
+                if let Err(e) = dumper.write_video(pixels, w, h, rate) {
+                    eprintln!("Failed to write to the dump, stopping it: {}", e);
+                    dumper.finish().ok();
+                }
+            }
+        }
+    }
+
+    /// Finishes writing the dump, if there is one. The audio keeps getting pulled through it afterwards.
+    pub fn finish_dump(&mut self) {
+        if let Some(dumper) = self.dumper.as_mut() {
+            if let Err(e) = dumper.finish() {
+                eprintln!("Failed to finish the dump: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames() {
+        assert_eq!(parse_frames("30"), Ok((30, None)));
+        assert_eq!(parse_frames("10-20"), Ok((10, Some(20))));
+        assert_eq!(parse_frames(" 10 - 10 "), Ok((10, Some(10))));
+        assert_eq!(parse_frames("-20"), Ok((0, Some(20))));
+        assert_eq!(parse_frames("10-"), Ok((10, None)));
+        assert!(parse_frames("20-10").is_err());
+        assert!(parse_frames("ten").is_err());
+        assert!(parse_frames("1-2-3").is_err());
+    }
+
+    #[test]
+    fn framerates() {
+        assert!(matches!(parse_framerate("game"), Ok(Framerate::Game)));
+        assert!(matches!(parse_framerate("60"), Ok(Framerate::Fixed(60))));
+        assert!(parse_framerate("0").is_err());
+        assert!(parse_framerate("-30").is_err());
+        assert!(parse_framerate("29.97").is_err());
+    }
+
+    #[test]
+    fn fitting_frames() {
+        let red = image::Rgba([255, 0, 0, 255]);
+        let black = image::Rgba([0, 0, 0, 255]);
+        let image = RgbaImage::from_pixel(2, 2, red);
+        assert_eq!(fit_frame(image.clone(), (2, 2), Resize::Pad), image);
+
+        let scaled = fit_frame(image.clone(), (4, 2), Resize::Scale);
+        assert_eq!(scaled.dimensions(), (4, 2));
+        assert!(scaled.pixels().all(|p| *p == red));
+
+        // padding centres the frame
+        let padded = fit_frame(image.clone(), (4, 4), Resize::Pad);
+        for (x, y, pixel) in padded.enumerate_pixels() {
+            let inside = (1..3).contains(&x) && (1..3).contains(&y);
+            assert_eq!(*pixel, if inside { red } else { black }, "pixel at {}, {}", x, y);
+        }
+
+        // and crops it if it's bigger
+        let mut image = RgbaImage::from_pixel(4, 4, black);
+        image.put_pixel(1, 1, red);
+        let cropped = fit_frame(image, (2, 2), Resize::Pad);
+        assert_eq!(cropped.get_pixel(0, 0), &red);
+        assert_eq!(cropped.get_pixel(1, 1), &black);
+    }
+
+    #[test]
+    fn wav_file() {
+        let path = std::env::temp_dir().join(format!("gm8emulator-test-{}.wav", std::process::id()));
+        let mut wav = WavWriter::create(&path, 2, 44100).unwrap();
+        wav.write(&[0.5, -0.5, 1.0, 0.0]).unwrap();
+        wav.write(&[0.25, 0.25]).unwrap();
+        wav.finish().unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).ok();
+
+        let u16_at = |i: usize| u16::from_le_bytes(bytes[i..i + 2].try_into().unwrap());
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        assert_eq!(bytes.len(), 44 + 6 * 4);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(4), 36 + 6 * 4);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u16_at(20), 3);
+        assert_eq!(u16_at(22), 2);
+        assert_eq!(u32_at(24), 44100);
+        assert_eq!(u32_at(28), 44100 * 8);
+        assert_eq!(u16_at(32), 8);
+        assert_eq!(u16_at(34), 32);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(40), 6 * 4);
+        assert_eq!(f32::from_le_bytes(bytes[48..52].try_into().unwrap()), -0.5);
+    }
+
+    #[test]
+    fn wav_file_too_long() {
+        let path = std::env::temp_dir().join(format!("gm8emulator-test-{}-long.wav", std::process::id()));
+        let mut wav = WavWriter::create(&path, 1, 44100).unwrap();
+        wav.data_len = u32::MAX - 36 - 4;
+        assert!(wav.write(&[0.0]).is_ok());
+        assert!(wav.write(&[0.0]).is_err());
+        assert_eq!(wav.data_len, u32::MAX - 36);
+        drop(wav);
+        fs::remove_file(&path).ok();
+    }
+}
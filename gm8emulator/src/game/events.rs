@@ -1,8 +1,8 @@
 use crate::{
     asset::trigger::TriggerTime,
-    game::{Game, GetAsset},
+    game::{profiler::ProfileKey, Game, GetAsset},
     gml,
-    input::MouseButton,
+    input::{MouseButton, JOYSTICK_COUNT},
     instance::Instance,
     types::ID,
 };
@@ -77,12 +77,49 @@ impl Game {
                 }
             };
 
-            self.execute_tree(event, instance, other, event_id, event_sub as _, object_id)
+            self.trace_event(event_id, event_sub, instance, other);
+            self.profiler.enter(ProfileKey::Event { object: object_id, event_type: event_id, event_number: event_sub });
+            let result = self.execute_tree(event, instance, other, event_id, event_sub as _, object_id);
+            self.profiler.exit();
+            result
         } else {
             Ok(())
         }
     }
 
+    /// Gets a human-readable name for an event type and sub-event, such as "Begin Step" or "Alarm 3".
+    pub fn event_name(&self, event_id: usize, event_sub: u32) -> String {
+        let object_name = |id: u32| {
+            self.assets
+                .objects
+                .get_asset(id as ID)
+                .map(|x| self.decode_str(x.name.as_ref()).into_owned())
+                .unwrap_or_else(|| format!("<object {}>", id))
+        };
+        match event_id {
+            gml::ev::CREATE => "Create".into(),
+            gml::ev::DESTROY => "Destroy".into(),
+            gml::ev::ALARMS => format!("Alarm {}", event_sub),
+            gml::ev::STEP => match event_sub {
+                1 => "Begin Step".into(),
+                2 => "End Step".into(),
+                _ => "Step".into(),
+            },
+            gml::ev::COLLISION => format!("Collision with {}", object_name(event_sub)),
+            gml::ev::KEYBOARD => format!("Keyboard {}", event_sub),
+            gml::ev::MOUSE => format!("Mouse {}", event_sub),
+            gml::ev::OTHER => format!("Other {}", event_sub),
+            gml::ev::DRAW => "Draw".into(),
+            gml::ev::KEYPRESS => format!("Key Press {}", event_sub),
+            gml::ev::KEYRELEASE => format!("Key Release {}", event_sub),
+            gml::ev::TRIGGER => match self.assets.triggers.get_asset(event_sub as ID) {
+                Some(trigger) => format!("Trigger {}", self.decode_str(trigger.name.as_ref())),
+                None => format!("Trigger {}", event_sub),
+            },
+            _ => format!("Event {},{}", event_id, event_sub),
+        }
+    }
+
     /// Runs room end followed by game end events for all instances. Should be called only when the game ends.
     pub fn run_game_end_events(&mut self) -> gml::Result<()> {
         // Reset this so the events will run
@@ -417,6 +454,32 @@ impl Game {
             self.run_object_event(gml::ev::MOUSE, 61, None)?;
         }
 
+        // Joystick directions and buttons, which are 16-28 for joystick 1 and 31-43 for joystick 2
+        for id in 0..JOYSTICK_COUNT {
+            let joystick = match self.input.joystick(id) {
+                Some(joystick) if joystick.connected => joystick,
+                _ => continue,
+            };
+            let base = 16 + 15 * id as u32;
+            let (x, y) = (joystick.axis(0), joystick.axis(1));
+            let buttons = (0..8).filter(|&b| joystick.button(b)).map(u32::from).collect::<Vec<_>>();
+            if x < -0.5 {
+                self.run_object_event(gml::ev::MOUSE, base, None)?;
+            }
+            if x > 0.5 {
+                self.run_object_event(gml::ev::MOUSE, base + 1, None)?;
+            }
+            if y < -0.5 {
+                self.run_object_event(gml::ev::MOUSE, base + 2, None)?;
+            }
+            if y > 0.5 {
+                self.run_object_event(gml::ev::MOUSE, base + 3, None)?;
+            }
+            for button in buttons {
+                self.run_object_event(gml::ev::MOUSE, base + 5 + button, None)?;
+            }
+        }
+
         Ok(())
     }
 
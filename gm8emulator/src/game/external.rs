@@ -1,11 +1,22 @@
 pub mod dll;
 mod dummy;
+mod emulated;
+mod plugin;
+pub mod stub;
 pub mod win32;
 mod wow64;
+mod x86;
 
-use crate::{gml, gml::Function, types::ID};
+use crate::{
+    gml,
+    gml::{runtime::Instruction, Compiler, Function},
+    types::ID,
+};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 #[cfg(not(all(target_os = "windows", target_arch = "x86")))]
 use dummy as native;
@@ -24,6 +35,43 @@ pub enum Call {
     Emulated(Function),
     Native(NativeExternal),
     Ipc(ipc::IpcExternal),
+    Gml(Rc<[Instruction]>),
+    Plugin(plugin::PluginFunction),
+    /// A plugin stub during a replay, which isn't loaded since its results come from the replay.
+    Replayed,
+    Interpreted(x86::InterpretedExternal),
+}
+
+impl Call {
+    /// Whether this calls code on the host, which can return something different depending on the machine.
+    pub fn is_host(&self) -> bool {
+        matches!(self, Call::Native(_) | Call::Ipc(_) | Call::Plugin(_) | Call::Replayed)
+    }
+
+    /// How the call is made, for listing externals.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Call::Dummy(_) => "dummied",
+            Call::Emulated(_) => "emulated",
+            Call::Native(_) => "native",
+            Call::Ipc(_) => "IPC",
+            Call::Gml(_) => "stubbed with GML",
+            Call::Plugin(_) => "plugin",
+            Call::Replayed => "plugin, replayed",
+            Call::Interpreted(_) => "interpreted",
+        }
+    }
+}
+
+/// What happens to the results of calls to code on the host, so a replay doesn't depend on the machine it was made on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResultMode {
+    /// Results are used as they are.
+    Live,
+    /// Results are stored as replay events.
+    Record,
+    /// Results are taken from replay events instead of making the call.
+    Replay,
 }
 
 pub struct External {
@@ -34,39 +82,109 @@ pub struct External {
 pub struct ExternalManager {
     externals: Vec<Option<External>>,
     dummy_audio: bool,
+    emulated: emulated::EmulatedState,
+    sockets: emulated::Sockets,
+    stubs: Vec<stub::Stub>,
+    plugin_dir: PathBuf,
+    plugins: plugin::Plugins,
+    result_mode: ResultMode,
 
     native_manager: native::NativeManager,
     ipc_manager: ipc::IpcManager,
+    interpreter: x86::InterpreterManager,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ExternalState {
     signatures: Vec<Option<dll::ExternalSignature>>,
+    emulated: emulated::EmulatedState,
+    interpreter: Option<x86::Machine>,
 }
 
 impl ExternalManager {
-    pub fn new(dummy_audio: bool) -> Self {
+    /// Creates the manager with the user's stubs, which take priority over everything else.
+    /// Plugin stubs' libraries are found relative to `plugin_dir`, and aren't loaded at all if results are replayed.
+    pub fn new(dummy_audio: bool, stubs: Vec<stub::Stub>, plugin_dir: PathBuf, result_mode: ResultMode) -> Self {
         Self {
             externals: Vec::new(),
             dummy_audio,
+            emulated: Default::default(),
+            sockets: Default::default(),
+            stubs,
+            plugin_dir,
+            plugins: plugin::Plugins::new(),
+            result_mode,
             native_manager: native::NativeManager::new(),
             ipc_manager: ipc::IpcManager::new(),
+            interpreter: Default::default(),
         }
     }
 
-    fn make_call(&mut self, signature: &dll::ExternalSignature) -> Result<Call, String> {
+    pub fn stubs(&self) -> &[stub::Stub] {
+        &self.stubs
+    }
+
+    pub fn set_result_mode(&mut self, mode: ResultMode) {
+        self.result_mode = mode;
+    }
+
+    pub fn result_mode(&self) -> ResultMode {
+        self.result_mode
+    }
+
+    fn make_call(&mut self, signature: &dll::ExternalSignature, compiler: &mut Compiler) -> Result<Call, String> {
+        let dll = dll_name(&signature.dll);
+        if let Some(stub) = self.stubs.iter().find(|s| s.matches(dll, &signature.symbol)) {
+            return match &stub.kind {
+                stub::StubKind::Constant(value) => Ok(Call::Dummy(value.clone())),
+                stub::StubKind::Gml(code) => compiler
+                    .compile(code.as_bytes())
+                    .map(Call::Gml)
+                    .map_err(|e| format!("couldn't compile the GML stub: {}", e.message)),
+                stub::StubKind::Plugin { .. } if self.result_mode == ResultMode::Replay => Ok(Call::Replayed),
+                stub::StubKind::Plugin { library, symbol } => {
+                    self.plugins.function(&self.plugin_dir.join(library).to_string_lossy(), symbol).map(Call::Plugin)
+                },
+            }
+        }
+        if emulated::is_emulated(dll) {
+            return Ok(match emulated::lookup(dll, &signature.symbol) {
+                Some(function) => Call::Emulated(function),
+                None => Call::Dummy(gml::Value::Real(0.into())),
+            })
+        }
         if let Some(dummy) = self.should_dummy(&signature) {
             return Ok(Call::Dummy(dummy))
         }
         if cfg!(all(target_os = "windows", target_arch = "x86")) {
             Ok(Call::Native(self.native_manager.define(&signature)?))
+        } else if cfg!(target_os = "windows") {
+            // WoW64 runs DLLs for real, so the interpreter is only for when the IPC host can't be used
+            self.ipc_manager.define(&signature).map(Call::Ipc).or_else(|e| {
+                self.interpreter
+                    .define(&signature)
+                    .map(Call::Interpreted)
+                    .map_err(|reason| format!("{} (it couldn't be interpreted either: {})", e, reason))
+            })
         } else {
-            Ok(Call::Ipc(self.ipc_manager.define(&signature)?))
+            // DLLs that only need what the interpreter provides don't need Windows at all,
+            // and the others only get interpreted if there's no other way to run them
+            match self.interpreter.define(&signature) {
+                Ok(external) if external.is_self_contained() => Ok(Call::Interpreted(external)),
+                Ok(external) => {
+                    Ok(self.ipc_manager.define(&signature).map(Call::Ipc).unwrap_or(Call::Interpreted(external)))
+                },
+                Err(reason) => self
+                    .ipc_manager
+                    .define(&signature)
+                    .map(Call::Ipc)
+                    .map_err(|e| format!("{} (it couldn't be interpreted either: {})", e, reason)),
+            }
         }
     }
 
-    pub fn define(&mut self, signature: dll::ExternalSignature) -> Result<ID, String> {
-        let external = External { call: self.make_call(&signature)?, signature };
+    pub fn define(&mut self, signature: dll::ExternalSignature, compiler: &mut Compiler) -> Result<ID, String> {
+        let external = External { call: self.make_call(&signature, compiler)?, signature };
         if let Some((id, cell)) = self.externals.iter_mut().enumerate().find(|(_, o)| o.is_none()) {
             *cell = Some(external);
             Ok(id as ID)
@@ -95,6 +213,13 @@ impl ExternalManager {
         }
     }
 
+    pub fn call_interpreted(&mut self, id: usize, args: &[dll::Value]) -> Result<gml::Value, String> {
+        match &self.externals[id].as_ref().unwrap().call {
+            Call::Interpreted(ext) => self.interpreter.call(ext, args).map(Into::into),
+            _ => unreachable!(),
+        }
+    }
+
     pub fn free(&mut self, dll: &str) {
         for option in &mut self.externals {
             if let Some(external) = option.as_ref() {
@@ -111,37 +236,27 @@ impl ExternalManager {
 
     pub fn save_state(&self) -> ExternalState {
         let signatures = self.externals.iter().map(|o| o.as_ref().map(|e| e.signature.clone())).collect();
-        ExternalState { signatures }
+        ExternalState { signatures, emulated: self.emulated.clone(), interpreter: self.interpreter.save_state() }
     }
 
-    pub fn load_state(&mut self, mut state: ExternalState) {
+    pub fn load_state(&mut self, mut state: ExternalState, compiler: &mut Compiler) {
+        self.emulated = state.emulated;
+        // the DLLs are already loaded in the saved process, so this has to come before the calls are remade
+        self.interpreter.load_state(state.interpreter);
         self.externals.clear();
         for opt in state.signatures.drain(..) {
-            let external = opt.map(|s| External { call: self.make_call(&s).unwrap(), signature: s });
+            let external = opt.map(|s| External { call: self.make_call(&s, compiler).unwrap(), signature: s });
             self.externals.push(external);
         }
     }
 
     fn should_dummy(&self, signature: &dll::ExternalSignature) -> Option<gml::Value> {
-        let dll = &signature.dll;
+        let dll = dll_name(&signature.dll);
         let sym = &signature.symbol;
-        let dll = Path::new(dll).file_name().and_then(|oss| oss.to_str()).unwrap_or(dll);
 
         let mut dummy = None;
         if self.dummy_audio {
-            if dll.eq_ignore_ascii_case("gmfmodsimple.dll") {
-                if sym == "FMODSoundAdd" {
-                    dummy = Some(gml::Value::Real(1.into()));
-                } else {
-                    dummy = Some(gml::Value::Real(0.into()));
-                }
-            } else if dll.eq_ignore_ascii_case("ssound.dll") || dll.eq_ignore_ascii_case("supersound.dll") {
-                if sym == "SS_Init" {
-                    dummy = Some(gml::Value::Str("Yes".into()));
-                } else {
-                    dummy = Some(gml::Value::Real(0.into()));
-                }
-            } else if dll.eq_ignore_ascii_case("sgaudio.dll") || dll.eq_ignore_ascii_case("sxms-3.dll") {
+            if dll.eq_ignore_ascii_case("sgaudio.dll") || dll.eq_ignore_ascii_case("sxms-3.dll") {
                 dummy = Some(gml::Value::Real(0.into()));
             } else if dll.eq_ignore_ascii_case("caster.dll") {
                 if sym == "caster_error_message" || sym == "caster_version" {
@@ -162,3 +277,14 @@ impl ExternalManager {
         dummy
     }
 }
+
+/// The file name of a DLL, without the directory it was loaded from.
+pub fn dll_name(dll: &str) -> &str {
+    Path::new(dll).file_name().and_then(|oss| oss.to_str()).unwrap_or(dll)
+}
+
+/// Describes a call for error messages, like `symbol(1, "two") in dll.dll`.
+pub fn describe_call(dll: &str, symbol: &str, args: &[gml::Value]) -> String {
+    let args = args.iter().map(ToString::to_string).collect::<Vec<_>>();
+    format!("{}({}) in {}", symbol, args.join(", "), dll)
+}
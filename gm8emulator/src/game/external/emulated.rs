@@ -0,0 +1,269 @@
+//! Rust implementations of popular DLLs, which get used in place of the real ones.
+//! Unlike native or IPC externals these work on every platform and go into savestates. They're deterministic, except
+//! for 39dll's sockets, whose results get stored in replays the same way as a real DLL's.
+
+mod dll39;
+mod gmfmodsimple;
+mod supersound;
+
+use crate::{
+    asset::sound::FileType,
+    game::Game,
+    gml::{self, datetime, Function},
+};
+use lewton::inside_ogg::OggStreamReader;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+pub use dll39::Sockets;
+
+/// Whether a DLL is emulated. Any of its functions that aren't implemented just return 0.
+pub fn is_emulated(dll: &str) -> bool {
+    gmfmodsimple::is_dll(dll) || supersound::is_dll(dll) || dll39::is_dll(dll)
+}
+
+/// Finds the emulated implementation of a function, if there is one.
+pub fn lookup(dll: &str, symbol: &str) -> Option<Function> {
+    if gmfmodsimple::is_dll(dll) {
+        gmfmodsimple::lookup(symbol)
+    } else if supersound::is_dll(dll) {
+        supersound::lookup(symbol)
+    } else if dll39::is_dll(dll) {
+        dll39::lookup(symbol)
+    } else {
+        None
+    }
+}
+
+/// The state of all emulated DLLs, apart from their sockets.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct EmulatedState {
+    channels: Vec<Option<Channel>>,
+    fmod: gmfmodsimple::State,
+    dll39_buffers: dll39::Buffers,
+}
+
+/// A sound which can be played through the audio manager. Each one has its own mixer ID, volume and pan.
+#[derive(Clone, Serialize, Deserialize)]
+struct Channel {
+    handle: FileType,
+    start: Option<u128>,
+    looping: bool,
+    volume: f64,
+    pan: f64,
+    // the GMFMODSimple sound this is an instance of, in which case it gets freed once it's finished
+    fmod_sound: Option<usize>,
+}
+
+impl Channel {
+    /// How long the sound takes to play once, in nanoseconds.
+    fn length(&self) -> u128 {
+        match &self.handle {
+            FileType::Mp3(handle) => handle.length(),
+            FileType::Wav(handle) => handle.length(),
+            FileType::Midi(handle) => handle.length(),
+            FileType::None => 0,
+        }
+    }
+
+    /// How far into the sound playback is, in nanoseconds.
+    fn position(&self, now: u128) -> u128 {
+        let length = self.length();
+        match self.start {
+            Some(_) if length == 0 => 0,
+            Some(start) if self.looping => now.saturating_sub(start) % length,
+            Some(start) => now.saturating_sub(start).min(length),
+            None => 0,
+        }
+    }
+
+    /// Whether the sound has played through to its end.
+    fn finished(&self, now: u128) -> bool {
+        match self.start {
+            Some(start) => !self.looping && now.saturating_sub(start) >= self.length(),
+            None => false,
+        }
+    }
+
+    /// Applies a volume to the sound, on top of the channel's own volume.
+    fn apply_volume(&mut self, gain: f64) {
+        let volume = (self.volume * gain).clamp(0.0, 1.0);
+        match &mut self.handle {
+            FileType::Mp3(handle) => handle.set_volume(volume),
+            FileType::Wav(handle) => handle.set_volume(volume),
+            FileType::Midi(handle) => handle.set_volume(volume),
+            FileType::None => (),
+        }
+    }
+
+    /// Pans the sound between -1 (left) and 1 (right). Only WAVs can be panned.
+    fn set_pan(&mut self, pan: f64) {
+        let pan = pan.clamp(-1.0, 1.0);
+        self.pan = pan;
+        if let FileType::Wav(handle) = &mut self.handle {
+            handle.set_spatial(|s| s.pan = pan);
+        }
+    }
+}
+
+/// Channels use negative mixer IDs so they can't clash with sound assets.
+fn mixer_id(channel: usize) -> i32 {
+    -(channel as i32) - 1
+}
+
+/// Reads a handle argument, which starts at 1 and may be passed as a string. Returns the index it refers to.
+fn handle_arg(args: &[gml::Value], index: usize) -> Option<usize> {
+    let handle = match args.get(index)? {
+        gml::Value::Real(x) => x.round().to_i32(),
+        gml::Value::Str(s) => s.decode_utf8().trim().parse().ok()?,
+    };
+    usize::try_from(handle).ok()?.checked_sub(1)
+}
+
+fn real_arg(args: &[gml::Value], index: usize) -> f64 {
+    args.get(index).cloned().map(f64::from).unwrap_or(0.0)
+}
+
+fn bytes_arg(args: &[gml::Value], index: usize) -> gml::String {
+    args.get(index).cloned().map(gml::String::from).unwrap_or_else(|| "".into())
+}
+
+fn string_arg(args: &[gml::Value], index: usize) -> String {
+    bytes_arg(args, index).decode_utf8().into_owned()
+}
+
+/// Decodes an Ogg Vorbis file into a 16-bit PCM WAV file, which the mixer can play.
+fn ogg_to_wav(data: &[u8]) -> Option<Box<[u8]>> {
+    let mut reader = OggStreamReader::new(Cursor::new(data)).ok()?;
+    let channels = u16::from(reader.ident_hdr.audio_channels);
+    let sample_rate = reader.ident_hdr.audio_sample_rate;
+    let mut samples = Vec::new();
+    while let Some(packet) = reader.read_dec_packet_itl().ok()? {
+        samples.extend(packet);
+    }
+    let data_len = u32::try_from(samples.len() * 2).ok()?;
+    let block_align = channels * 2;
+    let mut wav = Vec::with_capacity(44 + samples.len() * 2);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&data_len.checked_add(36)?.to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // WAVE_FORMAT_PCM
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * u32::from(block_align)).to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    Some(wav.into_boxed_slice())
+}
+
+impl Game {
+    fn emulated_now(&self) -> u128 {
+        self.spoofed_time_nanos.unwrap_or_else(datetime::now_as_nanos)
+    }
+
+    /// Loads a sound file for an emulated DLL, going by its contents rather than its extension like they do.
+    /// Ogg Vorbis files get decoded up front, since the mixer doesn't play them. Tracker modules aren't supported.
+    fn emulated_load_sound(&mut self, path: &str) -> Option<FileType> {
+        let data = match self.vfs.read(path) {
+            Ok(data) => data.into_boxed_slice(),
+            Err(e) => {
+                eprintln!("Warning: couldn't load sound {}: {}", path, e);
+                return None
+            },
+        };
+        let sound = match data.get(..4) {
+            Some(b"RIFF") => self.audio.add_wav(data, 0, 1.0, 0, false, false).map(FileType::Wav),
+            Some(b"MThd") => self.audio.add_midi(data, 0).map(FileType::Midi),
+            Some(b"OggS") => {
+                ogg_to_wav(&data).and_then(|wav| self.audio.add_wav(wav, 0, 1.0, 0, false, false)).map(FileType::Wav)
+            },
+            _ => self.audio.add_mp3(data, 0).map(FileType::Mp3),
+        };
+        if sound.is_none() {
+            eprintln!("Warning: sound {} isn't a WAV, MIDI, Ogg Vorbis or MP3 file that can be played", path);
+        }
+        sound
+    }
+
+    /// Makes a new channel for a sound, reusing the slot of a freed or finished GMFMODSimple instance if possible.
+    fn emulated_add_channel(&mut self, handle: &FileType, fmod_sound: Option<usize>) -> usize {
+        let now = self.emulated_now();
+        let channels = &mut self.externals.emulated.channels;
+        let index = channels
+            .iter()
+            .position(|c| match c {
+                Some(channel) => channel.fmod_sound.is_some() && channel.finished(now),
+                None => true,
+            })
+            .unwrap_or(channels.len());
+        let id = mixer_id(index);
+        let handle = match handle {
+            FileType::Mp3(handle) => FileType::Mp3(handle.duplicate(id)),
+            FileType::Wav(handle) => FileType::Wav(handle.duplicate(id)),
+            FileType::Midi(handle) => FileType::Midi(handle.duplicate(id)),
+            FileType::None => FileType::None,
+        };
+        let channel = Channel { handle, start: None, looping: false, volume: 1.0, pan: 0.0, fmod_sound };
+        if index == channels.len() {
+            channels.push(Some(channel));
+        } else {
+            self.audio.stop_sound(id);
+            channels[index] = Some(channel);
+        }
+        index
+    }
+
+    fn emulated_channel(&self, index: usize) -> Option<&Channel> {
+        self.externals.emulated.channels.get(index).and_then(Option::as_ref)
+    }
+
+    fn emulated_channel_mut(&mut self, index: usize) -> Option<&mut Channel> {
+        self.externals.emulated.channels.get_mut(index).and_then(Option::as_mut)
+    }
+
+    /// Starts a channel from the beginning, stopping it first if it was already playing.
+    fn emulated_play(&mut self, index: usize, looping: bool) {
+        let now = self.emulated_now();
+        self.audio.stop_sound(mixer_id(index));
+        if let Some(channel) = self.externals.emulated.channels.get_mut(index).and_then(Option::as_mut) {
+            channel.start = Some(now);
+            channel.looping = looping;
+            match (&channel.handle, looping) {
+                (FileType::Mp3(handle), false) => self.audio.play_mp3(handle, now),
+                (FileType::Mp3(handle), true) => self.audio.loop_mp3(handle),
+                (FileType::Wav(handle), false) => self.audio.play_wav(handle, now),
+                (FileType::Wav(handle), true) => self.audio.loop_wav(handle),
+                (FileType::Midi(handle), false) => self.audio.play_midi(handle, now),
+                (FileType::Midi(handle), true) => self.audio.loop_midi(handle),
+                (FileType::None, _) => (),
+            }
+        }
+    }
+
+    fn emulated_stop(&mut self, index: usize) {
+        if let Some(channel) = self.externals.emulated.channels.get_mut(index).and_then(Option::as_mut) {
+            channel.start = None;
+            self.audio.stop_sound(mixer_id(index));
+        }
+    }
+
+    fn emulated_free(&mut self, index: usize) {
+        self.emulated_stop(index);
+        if let Some(cell) = self.externals.emulated.channels.get_mut(index) {
+            *cell = None;
+        }
+    }
+
+    fn emulated_playing(&self, index: usize) -> bool {
+        match self.emulated_channel(index) {
+            Some(channel) => channel.start.is_some() && self.audio.sound_playing(mixer_id(index), self.emulated_now()),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,892 @@
+//! 39dll, a networking library whose buffers also get used for all sorts of binary data.
+//! Buffers go into savestates, but sockets can't, so they stay open across savestate loads.
+//! Anything that depends on the network is stored in replays like a real DLL's results, so playback doesn't need it.
+
+use super::{bytes_arg, real_arg};
+use crate::{
+    game::{external::ResultMode, Game},
+    gml::{self, network, Function},
+    handleman::{HandleList, HandleManager},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+};
+
+/// The name socket results are stored under in replays, whichever name the game loaded it by.
+const DLL_NAME: &str = "39dll.dll";
+
+pub fn is_dll(dll: &str) -> bool {
+    // it's often shipped under a versioned name, like 39dll2.dll
+    let lower = dll.to_ascii_lowercase();
+    lower.starts_with("39dll") && lower.ends_with(".dll")
+}
+
+pub fn lookup(symbol: &str) -> Option<Function> {
+    Some(Function::Engine(match symbol {
+        "writebyte" => Game::dll39_writebyte,
+        "writeshort" => Game::dll39_writeshort,
+        "writeushort" => Game::dll39_writeushort,
+        "writeint" => Game::dll39_writeint,
+        "writeuint" => Game::dll39_writeuint,
+        "writefloat" => Game::dll39_writefloat,
+        "writedouble" => Game::dll39_writedouble,
+        "writechars" => Game::dll39_writechars,
+        "writestring" => Game::dll39_writestring,
+        "readbyte" => Game::dll39_readbyte,
+        "readshort" => Game::dll39_readshort,
+        "readushort" => Game::dll39_readushort,
+        "readint" => Game::dll39_readint,
+        "readuint" => Game::dll39_readuint,
+        "readfloat" => Game::dll39_readfloat,
+        "readdouble" => Game::dll39_readdouble,
+        "readchars" => Game::dll39_readchars,
+        "readstring" => Game::dll39_readstring,
+        "clearbuffer" => Game::dll39_clearbuffer,
+        "buffsize" => Game::dll39_buffsize,
+        "bytesleft" => Game::dll39_bytesleft,
+        "getpos" => Game::dll39_getpos,
+        "setpos" => Game::dll39_setpos,
+        "createbuffer" => Game::dll39_createbuffer,
+        "freebuffer" => Game::dll39_freebuffer,
+        "bufferexists" => Game::dll39_bufferexists,
+        "copybuffer" => Game::dll39_copybuffer,
+        "copybuffer2" => Game::dll39_copybuffer2,
+        "md5string" => Game::dll39_md5string,
+        "md5buffer" => Game::dll39_md5buffer,
+        "adler32" => Game::dll39_adler32,
+        "tcpconnect" => Game::dll39_tcpconnect,
+        "tcplisten" => Game::dll39_tcplisten,
+        "tcpaccept" => Game::dll39_tcpaccept,
+        "tcpip" => Game::dll39_tcpip,
+        "tcpconnected" => Game::dll39_tcpconnected,
+        "udpconnect" => Game::dll39_udpconnect,
+        "setnagle" => Game::dll39_setnagle,
+        "setformat" => Game::dll39_setformat,
+        "setsync" => Game::dll39_setsync,
+        "closesocket" => Game::dll39_closesocket,
+        "socklasterror" => Game::dll39_socklasterror,
+        "sendmessage" => Game::dll39_sendmessage,
+        "receivemessage" => Game::dll39_receivemessage,
+        "peekmessage" => Game::dll39_peekmessage,
+        "lastinIP" => Game::dll39_lastinip,
+        "lastinPort" => Game::dll39_lastinport,
+        "myhost" => Game::dll39_myhost,
+        "hostip" => Game::dll39_hostip,
+        "compareip" => Game::dll39_compareip,
+        "iptouint" => Game::dll39_iptouint,
+        "uinttoip" => Game::dll39_uinttoip,
+        "getsocketid" => Game::dll39_getsocketid,
+        "sockstart" | "sockexit" | "netconnected" => Game::dll39_ok,
+        _ => return None,
+    }))
+}
+
+/// A growable byte buffer. Writes always go on the end, and reads go on from wherever the last one stopped.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Buffer {
+    data: Vec<u8>,
+    read_pos: usize,
+}
+
+impl Buffer {
+    pub fn write(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Reads some bytes, or none at all if there aren't enough left, in which case the buffer is read to the end.
+    pub fn read(&mut self, len: usize) -> Option<&[u8]> {
+        let start = self.read_pos.min(self.data.len());
+        if self.data.len() - start < len {
+            self.read_pos = self.data.len();
+            return None
+        }
+        self.read_pos = start + len;
+        Some(&self.data[start..self.read_pos])
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.read(N).map(|bytes| bytes.try_into().unwrap())
+    }
+
+    /// Reads up to the next null byte, skipping over it.
+    pub fn read_string(&mut self) -> Vec<u8> {
+        let start = self.read_pos.min(self.data.len());
+        let rest = &self.data[start..];
+        match rest.iter().position(|&b| b == 0) {
+            Some(len) => {
+                self.read_pos = start + len + 1;
+                rest[..len].to_vec()
+            },
+            None => {
+                self.read_pos = self.data.len();
+                rest.to_vec()
+            },
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.read_pos = 0;
+    }
+
+    pub fn bytes_left(&self) -> usize {
+        self.data.len().saturating_sub(self.read_pos)
+    }
+}
+
+/// 39dll's buffers. Buffer 0 always exists, as it's the one games use when they don't ask for another.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Buffers(HandleList<Buffer>);
+
+impl Default for Buffers {
+    fn default() -> Self {
+        let mut buffers = HandleList::new();
+        buffers.put(Buffer::default());
+        Self(buffers)
+    }
+}
+
+/// How messages over a TCP socket are delimited.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Each message starts with its length as a 16-bit integer.
+    Binary,
+    /// Each message ends with a separator string.
+    Text,
+    /// No delimiting at all, messages are however many bytes are asked for.
+    Raw,
+}
+
+enum Kind {
+    Stream(TcpStream),
+    Listener(TcpListener),
+    Udp(UdpSocket),
+}
+
+struct Socket {
+    kind: Kind,
+    framing: Framing,
+    last_error: i32,
+}
+
+struct Framing {
+    format: Format,
+    separator: Vec<u8>,
+    // bytes received over TCP that haven't made a whole message yet
+    pending: Vec<u8>,
+    closed: bool,
+}
+
+impl Framing {
+    /// Takes the first whole message out of the received bytes, if there is one.
+    fn take_message(&mut self, len: usize, peek: bool) -> Option<Vec<u8>> {
+        let (start, end, consumed) = match self.format {
+            Format::Binary => {
+                let size = usize::from(u16::from_le_bytes(self.pending.get(..2)?.try_into().unwrap()));
+                if self.pending.len() < 2 + size {
+                    return None
+                }
+                (2, 2 + size, 2 + size)
+            },
+            Format::Text => {
+                let sep = &self.separator;
+                let end = self.pending.windows(sep.len().max(1)).position(|w| w == sep.as_slice())?;
+                (0, end, end + sep.len())
+            },
+            Format::Raw if len == 0 && !self.pending.is_empty() => (0, self.pending.len(), self.pending.len()),
+            Format::Raw if len != 0 && self.pending.len() >= len => (0, len, len),
+            Format::Raw => return None,
+        };
+        let message = self.pending[start..end].to_vec();
+        if !peek {
+            self.pending.drain(..consumed);
+        }
+        Some(message)
+    }
+}
+
+impl Socket {
+    fn new(kind: Kind) -> Self {
+        let framing =
+            Framing { format: Format::Binary, separator: b"\r\n".to_vec(), pending: Vec::new(), closed: false };
+        Self { kind, framing, last_error: 0 }
+    }
+
+    fn fail(&mut self, error: io::Error) -> i32 {
+        fail(&mut self.last_error, error)
+    }
+}
+
+/// Remembers an error for socklasterror, returning -1 for convenience.
+fn fail(last_error: &mut i32, error: io::Error) -> i32 {
+    *last_error = error.raw_os_error().unwrap_or(-1);
+    -1
+}
+
+/// 39dll's sockets, which are numbered from 1.
+pub struct Sockets {
+    sockets: HandleList<Socket>,
+    last_in: Option<SocketAddr>,
+}
+
+impl Default for Sockets {
+    fn default() -> Self {
+        Self { sockets: HandleList::new(), last_in: None }
+    }
+}
+
+fn is_would_block(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::WouldBlock
+}
+
+impl Sockets {
+    fn add(&mut self, kind: Kind) -> i32 {
+        self.sockets.put(Socket::new(kind)) + 1
+    }
+
+    fn get_mut(&mut self, id: i32) -> Option<&mut Socket> {
+        self.sockets.get_mut(id - 1)
+    }
+
+    /// Connects to a TCP server, returning the new socket or -1 if it couldn't connect.
+    pub fn tcp_connect(&mut self, host: &str, port: u16, nonblocking: bool) -> i32 {
+        match TcpStream::connect((host, port)).and_then(|s| s.set_nonblocking(nonblocking).map(|_| s)) {
+            Ok(stream) => self.add(Kind::Stream(stream)),
+            Err(_) => -1,
+        }
+    }
+
+    pub fn tcp_listen(&mut self, port: u16, nonblocking: bool) -> i32 {
+        match TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).and_then(|l| l.set_nonblocking(nonblocking).map(|_| l)) {
+            Ok(listener) => self.add(Kind::Listener(listener)),
+            Err(_) => -1,
+        }
+    }
+
+    /// Accepts a connection on a listening socket, returning the new socket or -1 if nobody's connecting.
+    pub fn tcp_accept(&mut self, id: i32, nonblocking: bool) -> i32 {
+        let accepted = match self.get_mut(id) {
+            Some(Socket { kind: Kind::Listener(listener), .. }) => listener.accept(),
+            _ => return -1,
+        };
+        match accepted.and_then(|(s, _)| s.set_nonblocking(nonblocking).map(|_| s)) {
+            Ok(stream) => self.add(Kind::Stream(stream)),
+            Err(e) if is_would_block(&e) => -1,
+            Err(e) => self.get_mut(id).map(|s| s.fail(e)).unwrap_or(-1),
+        }
+    }
+
+    pub fn udp_bind(&mut self, port: u16, nonblocking: bool) -> i32 {
+        match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)).and_then(|s| s.set_nonblocking(nonblocking).map(|_| s)) {
+            Ok(socket) => self.add(Kind::Udp(socket)),
+            Err(_) => -1,
+        }
+    }
+
+    pub fn close(&mut self, id: i32) {
+        self.sockets.delete(id - 1);
+    }
+
+    pub fn set_format(&mut self, id: i32, format: Format, separator: &[u8]) {
+        if let Some(socket) = self.get_mut(id) {
+            socket.framing.format = format;
+            socket.framing.separator = separator.to_vec();
+        }
+    }
+
+    pub fn set_nonblocking(&mut self, id: i32, nonblocking: bool) {
+        if let Some(socket) = self.get_mut(id) {
+            let _ = match &socket.kind {
+                Kind::Stream(stream) => stream.set_nonblocking(nonblocking),
+                Kind::Listener(listener) => listener.set_nonblocking(nonblocking),
+                Kind::Udp(udp) => udp.set_nonblocking(nonblocking),
+            };
+        }
+    }
+
+    pub fn set_nagle(&mut self, id: i32, nagle: bool) {
+        if let Some(Socket { kind: Kind::Stream(stream), .. }) = self.get_mut(id) {
+            let _ = stream.set_nodelay(!nagle);
+        }
+    }
+
+    pub fn connected(&mut self, id: i32) -> bool {
+        match self.get_mut(id) {
+            Some(Socket { kind: Kind::Stream(stream), framing, .. }) => !framing.closed && stream.peer_addr().is_ok(),
+            _ => false,
+        }
+    }
+
+    pub fn peer_ip(&mut self, id: i32) -> Option<IpAddr> {
+        match self.get_mut(id) {
+            Some(Socket { kind: Kind::Stream(stream), .. }) => stream.peer_addr().ok().map(|a| a.ip()),
+            _ => None,
+        }
+    }
+
+    pub fn last_error(&mut self, id: i32) -> i32 {
+        self.get_mut(id).map(|s| s.last_error).unwrap_or(0)
+    }
+
+    pub fn last_in(&self) -> Option<SocketAddr> {
+        self.last_in
+    }
+
+    /// Sends a message, formatted for the socket if it's TCP. UDP sockets send it to the given address.
+    /// Returns how many bytes were sent, or -1 if it failed.
+    pub fn send(&mut self, id: i32, host: &str, port: u16, message: &[u8]) -> i32 {
+        let socket = match self.get_mut(id) {
+            Some(socket) => socket,
+            None => return -1,
+        };
+        let result = match &mut socket.kind {
+            Kind::Stream(stream) => {
+                let mut data = Vec::with_capacity(message.len() + 2);
+                match socket.framing.format {
+                    Format::Binary => {
+                        data.extend_from_slice(&(message.len() as u16).to_le_bytes());
+                        data.extend_from_slice(message);
+                    },
+                    Format::Text => {
+                        data.extend_from_slice(message);
+                        data.extend_from_slice(&socket.framing.separator);
+                    },
+                    Format::Raw => data.extend_from_slice(message),
+                }
+                stream.write_all(&data).map(|_| data.len())
+            },
+            Kind::Udp(udp) => udp.send_to(message, (host, port)),
+            Kind::Listener(_) => return -1,
+        };
+        match result {
+            Ok(len) => len as i32,
+            Err(e) => socket.fail(e),
+        }
+    }
+
+    /// Receives a message, and doesn't take it out of the socket if peeking. On failure, returns 0 if the
+    /// connection was closed, or -1 if there's no message yet.
+    pub fn receive(&mut self, id: i32, len: usize, peek: bool) -> Result<Vec<u8>, i32> {
+        let Socket { kind, framing, last_error } = self.sockets.get_mut(id - 1).ok_or(-1)?;
+        let mut chunk = [0u8; 4096];
+        match kind {
+            Kind::Stream(stream) => loop {
+                if let Some(message) = framing.take_message(len, peek) {
+                    return Ok(message)
+                }
+                if framing.closed {
+                    return Err(0)
+                }
+                match stream.read(&mut chunk) {
+                    Ok(0) => framing.closed = true,
+                    Ok(n) => framing.pending.extend_from_slice(&chunk[..n]),
+                    Err(e) if is_would_block(&e) => return Err(-1),
+                    Err(e) => {
+                        framing.closed = true;
+                        return Err(fail(last_error, e).max(0))
+                    },
+                }
+            },
+            Kind::Udp(udp) => {
+                let mut datagram = vec![0u8; 65536];
+                let received = if peek { udp.peek_from(&mut datagram) } else { udp.recv_from(&mut datagram) };
+                match received {
+                    Ok((n, from)) => {
+                        datagram.truncate(n);
+                        self.last_in = Some(from);
+                        Ok(datagram)
+                    },
+                    Err(e) if is_would_block(&e) => Err(-1),
+                    Err(e) => Err(fail(last_error, e)),
+                }
+            },
+            Kind::Listener(_) => Err(-1),
+        }
+    }
+}
+
+/// Resolves a host name to an IPv4 address.
+fn resolve(host: &str) -> Option<Ipv4Addr> {
+    (host, 0).to_socket_addrs().ok()?.find_map(|a| match a.ip() {
+        IpAddr::V4(ip) => Some(ip),
+        IpAddr::V6(_) => None,
+    })
+}
+
+/// Checks an IP against a mask like "192.168.*.*".
+fn compare_ip(ip: &str, mask: &str) -> bool {
+    let (ip, mask) = (ip.split('.').collect::<Vec<_>>(), mask.split('.').collect::<Vec<_>>());
+    ip.len() == mask.len() && ip.iter().zip(&mask).all(|(i, m)| *m == "*" || i == m)
+}
+
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    let constants: Vec<u32> = (0..64).map(|i| (f64::from(i + 1).sin().abs() * 4294967296.0) as u32).collect();
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for chunk in message.chunks_exact(64) {
+        let words: Vec<u32> = chunk.chunks_exact(4).map(|w| u32::from_le_bytes(w.try_into().unwrap())).collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f.wrapping_add(a).wrapping_add(constants[i]).wrapping_add(words[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(SHIFTS[i / 16 * 4 + i % 4]));
+        }
+        for (s, x) in state.iter_mut().zip([a, b, c, d]) {
+            *s = s.wrapping_add(x);
+        }
+    }
+
+    let mut digest = [0u8; 16];
+    for (out, s) in digest.chunks_exact_mut(4).zip(state) {
+        out.copy_from_slice(&s.to_le_bytes());
+    }
+    digest
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+impl Game {
+    fn dll39_buffer(&mut self, args: &[gml::Value], index: usize) -> Option<&mut Buffer> {
+        self.externals.emulated.dll39_buffers.0.get_mut(real_arg(args, index) as i32)
+    }
+
+    /// Appends bytes to the buffer given by an argument, returning how many were written.
+    fn dll39_write(&mut self, args: &[gml::Value], index: usize, bytes: &[u8]) -> gml::Result<gml::Value> {
+        match self.dll39_buffer(args, index) {
+            Some(buffer) => {
+                buffer.write(bytes);
+                Ok(bytes.len().into())
+            },
+            None => Ok(0.into()),
+        }
+    }
+
+    fn dll39_read<const N: usize>(&mut self, args: &[gml::Value]) -> Option<[u8; N]> {
+        self.dll39_buffer(args, 0).and_then(Buffer::read_array)
+    }
+
+    fn dll39_ok(&mut self, _args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(1.into())
+    }
+
+    fn dll39_writebyte(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_write(args, 1, &[real_arg(args, 0) as i64 as u8])
+    }
+
+    fn dll39_writeshort(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_write(args, 1, &(real_arg(args, 0) as i64 as i16).to_le_bytes())
+    }
+
+    fn dll39_writeushort(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_write(args, 1, &(real_arg(args, 0) as i64 as u16).to_le_bytes())
+    }
+
+    fn dll39_writeint(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_write(args, 1, &(real_arg(args, 0) as i64 as i32).to_le_bytes())
+    }
+
+    fn dll39_writeuint(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_write(args, 1, &(real_arg(args, 0) as i64 as u32).to_le_bytes())
+    }
+
+    fn dll39_writefloat(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_write(args, 1, &(real_arg(args, 0) as f32).to_le_bytes())
+    }
+
+    fn dll39_writedouble(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_write(args, 1, &real_arg(args, 0).to_le_bytes())
+    }
+
+    fn dll39_writechars(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_write(args, 1, bytes_arg(args, 0).as_ref())
+    }
+
+    fn dll39_writestring(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let mut bytes = bytes_arg(args, 0).as_ref().to_vec();
+        bytes.push(0);
+        self.dll39_write(args, 1, &bytes)
+    }
+
+    fn dll39_readbyte(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.dll39_read(args).map(u8::from_le_bytes).unwrap_or(0).into())
+    }
+
+    fn dll39_readshort(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.dll39_read(args).map(|b| i32::from(i16::from_le_bytes(b))).unwrap_or(0).into())
+    }
+
+    fn dll39_readushort(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.dll39_read(args).map(|b| i32::from(u16::from_le_bytes(b))).unwrap_or(0).into())
+    }
+
+    fn dll39_readint(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.dll39_read(args).map(i32::from_le_bytes).unwrap_or(0).into())
+    }
+
+    fn dll39_readuint(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.dll39_read(args).map(u32::from_le_bytes).unwrap_or(0).into())
+    }
+
+    fn dll39_readfloat(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.dll39_read(args).map(f32::from_le_bytes).map(f64::from).unwrap_or(0.0).into())
+    }
+
+    fn dll39_readdouble(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.dll39_read(args).map(f64::from_le_bytes).unwrap_or(0.0).into())
+    }
+
+    fn dll39_readchars(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let len = real_arg(args, 0).max(0.0) as usize;
+        let chars = self.dll39_buffer(args, 1).and_then(|b| b.read(len)).map(<[u8]>::to_vec).unwrap_or_default();
+        Ok(chars.into())
+    }
+
+    fn dll39_readstring(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.dll39_buffer(args, 0).map(Buffer::read_string).unwrap_or_default().into())
+    }
+
+    fn dll39_clearbuffer(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        if let Some(buffer) = self.dll39_buffer(args, 0) {
+            buffer.clear();
+        }
+        Ok(1.into())
+    }
+
+    fn dll39_buffsize(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.dll39_buffer(args, 0).map(|b| b.data.len()).unwrap_or(0).into())
+    }
+
+    fn dll39_bytesleft(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.dll39_buffer(args, 0).map(|b| b.bytes_left()).unwrap_or(0).into())
+    }
+
+    /// Gets the write position if the first argument is 0, otherwise the read position.
+    fn dll39_getpos(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let write = real_arg(args, 0) == 0.0;
+        Ok(self.dll39_buffer(args, 1).map(|b| if write { b.data.len() } else { b.read_pos }).unwrap_or(0).into())
+    }
+
+    fn dll39_setpos(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let pos = real_arg(args, 0).max(0.0) as usize;
+        if let Some(buffer) = self.dll39_buffer(args, 1) {
+            buffer.read_pos = pos;
+        }
+        Ok(1.into())
+    }
+
+    fn dll39_createbuffer(&mut self, _args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.externals.emulated.dll39_buffers.0.put(Buffer::default()).into())
+    }
+
+    fn dll39_freebuffer(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let id = real_arg(args, 0) as i32;
+        // buffer 0 can't be freed
+        Ok((id != 0 && self.externals.emulated.dll39_buffers.0.delete(id)).into())
+    }
+
+    fn dll39_bufferexists(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.dll39_buffer(args, 0).is_some().into())
+    }
+
+    /// Appends the whole of one buffer onto another.
+    fn dll39_copybuffer(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let data = match self.dll39_buffer(args, 1) {
+            Some(source) => source.data.clone(),
+            None => return Ok(0.into()),
+        };
+        self.dll39_write(args, 0, &data)
+    }
+
+    /// Appends part of one buffer onto another.
+    fn dll39_copybuffer2(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let start = real_arg(args, 1).max(0.0) as usize;
+        let len = real_arg(args, 2).max(0.0) as usize;
+        let data = match self.dll39_buffer(args, 3) {
+            Some(source) => {
+                let start = start.min(source.data.len());
+                source.data[start..(start + len).min(source.data.len())].to_vec()
+            },
+            None => return Ok(0.into()),
+        };
+        self.dll39_write(args, 0, &data)
+    }
+
+    fn dll39_md5string(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(hex::encode(md5(bytes_arg(args, 0).as_ref())).into())
+    }
+
+    fn dll39_md5buffer(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(hex::encode(md5(self.dll39_buffer(args, 0).map(|b| b.data.as_slice()).unwrap_or_default())).into())
+    }
+
+    fn dll39_adler32(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(adler32(self.dll39_buffer(args, 0).map(|b| b.data.as_slice()).unwrap_or_default()).into())
+    }
+
+    /// Does something with the sockets that depends on the network, returning its result. Like a real DLL's result,
+    /// it's stored in the replay when recording, and on playback it's taken from there without touching the network.
+    fn dll39_network(
+        &mut self,
+        symbol: &str,
+        args: &[gml::Value],
+        live: impl FnOnce(&mut Sockets) -> gml::Value,
+    ) -> gml::Result<gml::Value> {
+        self.host_result(DLL_NAME, symbol, args, |game| Ok(live(&mut game.externals.sockets)))
+    }
+
+    /// Does something with the sockets that has no result, unless a replay is playing, as there's no network then.
+    fn dll39_live(&mut self, live: impl FnOnce(&mut Sockets)) -> gml::Result<gml::Value> {
+        if self.externals.result_mode() != ResultMode::Replay {
+            live(&mut self.externals.sockets);
+        }
+        Ok(1.into())
+    }
+
+    fn dll39_tcpconnect(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let host = bytes_arg(args, 0).decode_utf8().into_owned();
+        let nonblocking = real_arg(args, 2) != 0.0;
+        self.dll39_network("tcpconnect", args, |sockets| {
+            sockets.tcp_connect(&host, real_arg(args, 1) as u16, nonblocking).into()
+        })
+    }
+
+    fn dll39_tcplisten(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let nonblocking = real_arg(args, 2) != 0.0;
+        self.dll39_network("tcplisten", args, |sockets| {
+            sockets.tcp_listen(real_arg(args, 0) as u16, nonblocking).into()
+        })
+    }
+
+    fn dll39_tcpaccept(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let nonblocking = real_arg(args, 1) != 0.0;
+        self.dll39_network("tcpaccept", args, |sockets| {
+            sockets.tcp_accept(real_arg(args, 0) as i32, nonblocking).into()
+        })
+    }
+
+    fn dll39_tcpip(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_network("tcpip", args, |sockets| {
+            sockets.peer_ip(real_arg(args, 0) as i32).map(|ip| ip.to_string()).unwrap_or_default().into()
+        })
+    }
+
+    fn dll39_tcpconnected(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_network("tcpconnected", args, |sockets| sockets.connected(real_arg(args, 0) as i32).into())
+    }
+
+    fn dll39_udpconnect(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let nonblocking = real_arg(args, 1) != 0.0;
+        self.dll39_network("udpconnect", args, |sockets| sockets.udp_bind(real_arg(args, 0) as u16, nonblocking).into())
+    }
+
+    fn dll39_setnagle(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_live(|sockets| sockets.set_nagle(real_arg(args, 0) as i32, real_arg(args, 1) != 0.0))
+    }
+
+    fn dll39_setformat(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let format = match real_arg(args, 1) as i32 {
+            0 => Format::Binary,
+            1 => Format::Text,
+            _ => Format::Raw,
+        };
+        self.dll39_live(|sockets| sockets.set_format(real_arg(args, 0) as i32, format, bytes_arg(args, 2).as_ref()))
+    }
+
+    fn dll39_setsync(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_live(|sockets| sockets.set_nonblocking(real_arg(args, 0) as i32, real_arg(args, 1) != 0.0))
+    }
+
+    fn dll39_closesocket(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_live(|sockets| sockets.close(real_arg(args, 0) as i32))
+    }
+
+    fn dll39_socklasterror(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_network("socklasterror", args, |sockets| sockets.last_error(real_arg(args, 0) as i32).into())
+    }
+
+    fn dll39_sendmessage(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let host = bytes_arg(args, 1).decode_utf8().into_owned();
+        let message = match self.dll39_buffer(args, 3) {
+            Some(buffer) => buffer.data.clone(),
+            None => return Ok((-1).into()),
+        };
+        let id = real_arg(args, 0) as i32;
+        self.dll39_network("sendmessage", args, |sockets| {
+            sockets.send(id, &host, real_arg(args, 2) as u16, &message).into()
+        })
+    }
+
+    /// Receives a message into a buffer, replacing what was in it.
+    fn dll39_receive(&mut self, args: &[gml::Value], peek: bool) -> gml::Result<gml::Value> {
+        let symbol = if peek { "peekmessage" } else { "receivemessage" };
+        let len = real_arg(args, 1).max(0.0) as usize;
+        // the message itself is the stored result, so playback can fill the buffer with it
+        let id = real_arg(args, 0) as i32;
+        let result = self.dll39_network(symbol, args, |sockets| match sockets.receive(id, len, peek) {
+            Ok(message) => message.as_slice().into(),
+            Err(code) => code.into(),
+        })?;
+        let message = match result {
+            gml::Value::Str(message) => message,
+            code => return Ok(code),
+        };
+        match self.dll39_buffer(args, 2) {
+            Some(buffer) => {
+                buffer.clear();
+                buffer.write(message.as_ref());
+                Ok(message.as_ref().len().into())
+            },
+            None => Ok((-1).into()),
+        }
+    }
+
+    fn dll39_receivemessage(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_receive(args, false)
+    }
+
+    fn dll39_peekmessage(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_receive(args, true)
+    }
+
+    fn dll39_lastinip(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_network("lastinIP", args, |sockets| {
+            sockets.last_in().map(|a| a.ip().to_string()).unwrap_or_default().into()
+        })
+    }
+
+    fn dll39_lastinport(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_network("lastinPort", args, |sockets| {
+            sockets.last_in().map(|a| i32::from(a.port())).unwrap_or(0).into()
+        })
+    }
+
+    fn dll39_myhost(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_network("myhost", args, |_| {
+            network::get_local_ip().unwrap_or_else(|_| Ipv4Addr::LOCALHOST.into()).to_string().into()
+        })
+    }
+
+    fn dll39_hostip(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.dll39_network("hostip", args, |_| {
+            resolve(&bytes_arg(args, 0).decode_utf8()).map(|ip| ip.to_string()).unwrap_or_default().into()
+        })
+    }
+
+    fn dll39_compareip(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(compare_ip(&bytes_arg(args, 0).decode_utf8(), &bytes_arg(args, 1).decode_utf8()).into())
+    }
+
+    /// Converts an IP to an integer the way inet_addr does, so the first part ends up in the lowest byte.
+    fn dll39_iptouint(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let ip = bytes_arg(args, 0).decode_utf8().parse::<Ipv4Addr>();
+        Ok(ip.map(|ip| u32::from_le_bytes(ip.octets())).unwrap_or(u32::MAX).into())
+    }
+
+    fn dll39_uinttoip(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(Ipv4Addr::from((real_arg(args, 0) as i64 as u32).to_le_bytes()).to_string().into())
+    }
+
+    fn dll39_getsocketid(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(real_arg(args, 0).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adler32, md5, Buffer, Format, Sockets};
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, UdpSocket},
+    };
+
+    #[test]
+    fn buffer_roundtrip() {
+        let mut buffer = Buffer::default();
+        buffer.write(&300i16.to_le_bytes());
+        buffer.write(b"hello\0world");
+        assert_eq!(buffer.read(2), Some(&300i16.to_le_bytes()[..]));
+        assert_eq!(buffer.read_string(), b"hello");
+        assert_eq!(buffer.bytes_left(), 5);
+        assert_eq!(buffer.read(6), None);
+        assert_eq!(buffer.bytes_left(), 0);
+    }
+
+    #[test]
+    fn hashes() {
+        assert_eq!(hex::encode(md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(
+            hex::encode(md5(b"The quick brown fox jumps over the lazy dog")),
+            "9e107d9d372bb6826bd81d3542a419d6"
+        );
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    }
+
+    #[test]
+    fn tcp_loopback() {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = server.local_addr().unwrap().port();
+        let mut sockets = Sockets::default();
+        let client = sockets.tcp_connect("127.0.0.1", port, false);
+        assert!(client > 0);
+        let (mut peer, _) = server.accept().unwrap();
+
+        assert_eq!(sockets.send(client, "", 0, b"ping"), 6);
+        let mut received = [0u8; 6];
+        peer.read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"\x04\x00ping");
+
+        peer.write_all(b"\x04\x00pong\x02\x00hi").unwrap();
+        assert_eq!(sockets.receive(client, 0, true), Ok(b"pong".to_vec()));
+        assert_eq!(sockets.receive(client, 0, false), Ok(b"pong".to_vec()));
+        assert_eq!(sockets.receive(client, 0, false), Ok(b"hi".to_vec()));
+
+        sockets.set_format(client, Format::Text, b"\n");
+        peer.write_all(b"line\n").unwrap();
+        assert_eq!(sockets.receive(client, 0, false), Ok(b"line".to_vec()));
+
+        drop(peer);
+        assert_eq!(sockets.receive(client, 0, false), Err(0));
+        assert!(!sockets.connected(client));
+    }
+
+    #[test]
+    fn udp_loopback() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = server.local_addr().unwrap().port();
+        let mut sockets = Sockets::default();
+        let client = sockets.udp_bind(0, false);
+        assert_eq!(sockets.send(client, "127.0.0.1", port, b"ping"), 4);
+        let mut received = [0u8; 16];
+        let (len, from) = server.recv_from(&mut received).unwrap();
+        assert_eq!(&received[..len], b"ping");
+
+        server.send_to(b"pong", from).unwrap();
+        assert_eq!(sockets.receive(client, 0, false), Ok(b"pong".to_vec()));
+        assert_eq!(sockets.last_in(), Some(server.local_addr().unwrap()));
+    }
+}
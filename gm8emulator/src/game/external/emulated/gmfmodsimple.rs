@@ -0,0 +1,275 @@
+//! GMFMODSimple, a wrapper around FMOD. Sounds get played as instances, which each have their own volume and pan.
+
+use super::{handle_arg, real_arg, string_arg};
+use crate::{
+    asset::sound::FileType,
+    game::Game,
+    gml::{self, Function},
+};
+use serde::{Deserialize, Serialize};
+
+pub fn is_dll(dll: &str) -> bool {
+    dll.eq_ignore_ascii_case("gmfmodsimple.dll")
+}
+
+pub fn lookup(symbol: &str) -> Option<Function> {
+    Some(Function::Engine(match symbol {
+        "FMODinit" | "FMODUpdate" => Game::fmod_ok,
+        "FMODfree" => Game::fmod_free,
+        "FMODAllStop" => Game::fmod_all_stop,
+        "FMODSoundAdd" => Game::fmod_sound_add,
+        "FMODSoundFree" => Game::fmod_sound_free,
+        "FMODSoundPlay" => Game::fmod_sound_play,
+        "FMODSoundLoop" => Game::fmod_sound_loop,
+        "FMODSoundGetLength" => Game::fmod_sound_get_length,
+        "FMODSoundSetMaxVolume" => Game::fmod_sound_set_max_volume,
+        "FMODSoundGetMaxVolume" => Game::fmod_sound_get_max_volume,
+        "FMODInstanceStop" => Game::fmod_instance_stop,
+        "FMODInstanceIsPlaying" => Game::fmod_instance_is_playing,
+        "FMODInstanceGetSound" => Game::fmod_instance_get_sound,
+        "FMODInstanceSetVolume" => Game::fmod_instance_set_volume,
+        "FMODInstanceGetVolume" => Game::fmod_instance_get_volume,
+        "FMODInstanceSetPan" => Game::fmod_instance_set_pan,
+        "FMODInstanceGetPan" => Game::fmod_instance_get_pan,
+        "FMODInstanceGetPosition" => Game::fmod_instance_get_position,
+        "FMODInstanceSetPaused" => Game::fmod_instance_set_paused,
+        "FMODInstanceGetPaused" => Game::fmod_instance_get_paused,
+        "FMODMasterSetVolume" => Game::fmod_master_set_volume,
+        "FMODMasterGetVolume" => Game::fmod_master_get_volume,
+        _ => return None,
+    }))
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct State {
+    sounds: Vec<Option<Sound>>,
+    master_volume: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Sound {
+    handle: FileType,
+    max_volume: f64,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self { sounds: Vec::new(), master_volume: 1.0 }
+    }
+}
+
+impl Game {
+    fn fmod_sound(&self, args: &[gml::Value]) -> Option<&Sound> {
+        handle_arg(args, 0).and_then(|id| self.externals.emulated.fmod.sounds.get(id)).and_then(Option::as_ref)
+    }
+
+    /// Finds the channel an instance handle refers to, as long as it's still an instance of a GMFMODSimple sound.
+    fn fmod_instance(&self, args: &[gml::Value]) -> Option<usize> {
+        let index = handle_arg(args, 0)?;
+        self.emulated_channel(index).filter(|c| c.fmod_sound.is_some()).map(|_| index)
+    }
+
+    /// Works out how loud an instance should be from its own volume, its sound's and the master volume.
+    fn fmod_apply_volume(&mut self, index: usize) {
+        let fmod = &self.externals.emulated.fmod;
+        if let Some(channel) = self.externals.emulated.channels.get_mut(index).and_then(Option::as_mut) {
+            let max_volume = channel
+                .fmod_sound
+                .and_then(|id| fmod.sounds.get(id))
+                .and_then(Option::as_ref)
+                .map(|s| s.max_volume)
+                .unwrap_or(1.0);
+            channel.apply_volume(max_volume * fmod.master_volume);
+        }
+    }
+
+    fn fmod_instances(&self) -> Vec<usize> {
+        let channels = self.externals.emulated.channels.iter().enumerate();
+        channels.filter(|(_, c)| matches!(c, Some(c) if c.fmod_sound.is_some())).map(|(i, _)| i).collect()
+    }
+
+    fn fmod_ok(&mut self, _args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(1.into())
+    }
+
+    fn fmod_all_stop(&mut self, _args: &[gml::Value]) -> gml::Result<gml::Value> {
+        for index in self.fmod_instances() {
+            self.emulated_free(index);
+        }
+        Ok(1.into())
+    }
+
+    fn fmod_free(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.fmod_all_stop(args)?;
+        self.externals.emulated.fmod.sounds.clear();
+        Ok(1.into())
+    }
+
+    fn fmod_sound_add(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let handle = match self.emulated_load_sound(&string_arg(args, 0)) {
+            Some(handle) => handle,
+            None => return Ok(0.into()),
+        };
+        let sounds = &mut self.externals.emulated.fmod.sounds;
+        let sound = Some(Sound { handle, max_volume: 1.0 });
+        let id = match sounds.iter().position(Option::is_none) {
+            Some(id) => {
+                sounds[id] = sound;
+                id
+            },
+            None => {
+                sounds.push(sound);
+                sounds.len() - 1
+            },
+        };
+        Ok((id + 1).into())
+    }
+
+    fn fmod_sound_free(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        if let Some(id) = handle_arg(args, 0).filter(|&id| id < self.externals.emulated.fmod.sounds.len()) {
+            for index in self.fmod_instances() {
+                if matches!(self.emulated_channel(index), Some(c) if c.fmod_sound == Some(id)) {
+                    self.emulated_free(index);
+                }
+            }
+            self.externals.emulated.fmod.sounds[id] = None;
+        }
+        Ok(1.into())
+    }
+
+    /// Makes an instance of a sound and starts it, unless it should start off paused.
+    fn fmod_start(&mut self, args: &[gml::Value], looping: bool) -> gml::Result<gml::Value> {
+        let (id, handle) = match (handle_arg(args, 0), self.fmod_sound(args)) {
+            (Some(id), Some(sound)) => (id, sound.handle.clone()),
+            _ => return Ok(0.into()),
+        };
+        let index = self.emulated_add_channel(&handle, Some(id));
+        self.fmod_apply_volume(index);
+        if let Some(channel) = self.emulated_channel_mut(index) {
+            channel.looping = looping;
+        }
+        if !args.get(1).map(gml::Value::is_truthy).unwrap_or(false) {
+            self.emulated_play(index, looping);
+        }
+        Ok((index + 1).into())
+    }
+
+    fn fmod_sound_play(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.fmod_start(args, false)
+    }
+
+    fn fmod_sound_loop(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.fmod_start(args, true)
+    }
+
+    /// The length of a sound in milliseconds.
+    fn fmod_sound_get_length(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let length = match self.fmod_sound(args).map(|s| &s.handle) {
+            Some(FileType::Mp3(handle)) => handle.length(),
+            Some(FileType::Wav(handle)) => handle.length(),
+            Some(FileType::Midi(handle)) => handle.length(),
+            Some(FileType::None) | None => 0,
+        };
+        Ok(((length / 1_000_000) as f64).into())
+    }
+
+    fn fmod_sound_set_max_volume(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        if let Some(id) = handle_arg(args, 0) {
+            if let Some(Some(sound)) = self.externals.emulated.fmod.sounds.get_mut(id) {
+                sound.max_volume = real_arg(args, 1).clamp(0.0, 1.0);
+                for index in self.fmod_instances() {
+                    self.fmod_apply_volume(index);
+                }
+            }
+        }
+        Ok(1.into())
+    }
+
+    fn fmod_sound_get_max_volume(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.fmod_sound(args).map(|s| s.max_volume).unwrap_or(0.0).into())
+    }
+
+    fn fmod_instance_stop(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        if let Some(index) = self.fmod_instance(args) {
+            self.emulated_free(index);
+        }
+        Ok(1.into())
+    }
+
+    fn fmod_instance_is_playing(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.fmod_instance(args).map(|index| self.emulated_playing(index)).unwrap_or(false).into())
+    }
+
+    fn fmod_instance_get_sound(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let sound = self.fmod_instance(args).and_then(|index| self.emulated_channel(index)?.fmod_sound);
+        Ok(sound.map(|id| id + 1).unwrap_or(0).into())
+    }
+
+    fn fmod_instance_set_volume(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        if let Some(index) = self.fmod_instance(args) {
+            if let Some(channel) = self.emulated_channel_mut(index) {
+                channel.volume = real_arg(args, 1).clamp(0.0, 1.0);
+            }
+            self.fmod_apply_volume(index);
+        }
+        Ok(1.into())
+    }
+
+    fn fmod_instance_get_volume(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let channel = self.fmod_instance(args).and_then(|index| self.emulated_channel(index));
+        Ok(channel.map(|c| c.volume).unwrap_or(0.0).into())
+    }
+
+    fn fmod_instance_set_pan(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let pan = real_arg(args, 1);
+        if let Some(channel) = self.fmod_instance(args).and_then(|index| self.emulated_channel_mut(index)) {
+            channel.set_pan(pan);
+        }
+        Ok(1.into())
+    }
+
+    fn fmod_instance_get_pan(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let channel = self.fmod_instance(args).and_then(|index| self.emulated_channel(index));
+        Ok(channel.map(|c| c.pan).unwrap_or(0.0).into())
+    }
+
+    /// How far through the sound an instance is, from 0 to 1.
+    fn fmod_instance_get_position(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let now = self.emulated_now();
+        let position = match self.fmod_instance(args).and_then(|index| self.emulated_channel(index)) {
+            Some(channel) if channel.length() > 0 => channel.position(now) as f64 / channel.length() as f64,
+            _ => 0.0,
+        };
+        Ok(position.into())
+    }
+
+    /// Unpauses an instance that was started paused. The audio manager can't pause a sound that's already
+    /// playing, so pausing does nothing.
+    fn fmod_instance_set_paused(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        if let Some(index) = self.fmod_instance(args) {
+            let paused = args.get(1).map(gml::Value::is_truthy).unwrap_or(false);
+            if let Some(channel) = self.emulated_channel(index).filter(|c| !paused && c.start.is_none()) {
+                let looping = channel.looping;
+                self.emulated_play(index, looping);
+            }
+        }
+        Ok(1.into())
+    }
+
+    fn fmod_instance_get_paused(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let channel = self.fmod_instance(args).and_then(|index| self.emulated_channel(index));
+        Ok(matches!(channel, Some(c) if c.start.is_none()).into())
+    }
+
+    fn fmod_master_set_volume(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        self.externals.emulated.fmod.master_volume = real_arg(args, 0).clamp(0.0, 1.0);
+        for index in self.fmod_instances() {
+            self.fmod_apply_volume(index);
+        }
+        Ok(1.into())
+    }
+
+    fn fmod_master_get_volume(&mut self, _args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.externals.emulated.fmod.master_volume.into())
+    }
+}
@@ -0,0 +1,127 @@
+//! SuperSound, a DirectSound wrapper. Each loaded sound plays on its own, and handles are passed around as strings.
+//! Volumes go from 0 to 10000, pans from -10000 to 10000, and positions and lengths are in milliseconds.
+
+use super::{handle_arg, real_arg, string_arg};
+use crate::{
+    game::Game,
+    gml::{self, Function},
+};
+
+pub fn is_dll(dll: &str) -> bool {
+    dll.eq_ignore_ascii_case("ssound.dll") || dll.eq_ignore_ascii_case("supersound.dll")
+}
+
+pub fn lookup(symbol: &str) -> Option<Function> {
+    Some(Function::Engine(match symbol {
+        "SS_Init" | "SS_Unload" => Game::ss_init,
+        "SS_LoadSound" => Game::ss_load_sound,
+        "SS_FreeSound" => Game::ss_free_sound,
+        "SS_PlaySound" => Game::ss_play_sound,
+        "SS_LoopSound" => Game::ss_loop_sound,
+        "SS_StopSound" => Game::ss_stop_sound,
+        "SS_IsSoundPlaying" => Game::ss_is_sound_playing,
+        "SS_IsSoundLooping" => Game::ss_is_sound_looping,
+        "SS_SetSoundVol" => Game::ss_set_sound_vol,
+        "SS_GetSoundVol" => Game::ss_get_sound_vol,
+        "SS_SetSoundPan" => Game::ss_set_sound_pan,
+        "SS_GetSoundPan" => Game::ss_get_sound_pan,
+        "SS_GetSoundPosition" => Game::ss_get_sound_position,
+        "SS_GetSoundLength" => Game::ss_get_sound_length,
+        _ => return None,
+    }))
+}
+
+impl Game {
+    /// Finds the channel a sound handle refers to, as long as it's a SuperSound one.
+    fn ss_sound(&self, args: &[gml::Value]) -> Option<usize> {
+        let index = handle_arg(args, 0)?;
+        self.emulated_channel(index).filter(|c| c.fmod_sound.is_none()).map(|_| index)
+    }
+
+    fn ss_init(&mut self, _args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok("Yes".into())
+    }
+
+    /// Returns the new sound's handle, or "0" if it couldn't be loaded.
+    fn ss_load_sound(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        match self.emulated_load_sound(&string_arg(args, 0)) {
+            Some(handle) => Ok((self.emulated_add_channel(&handle, None) + 1).to_string().into()),
+            None => Ok("0".into()),
+        }
+    }
+
+    fn ss_free_sound(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        if let Some(index) = self.ss_sound(args) {
+            self.emulated_free(index);
+        }
+        Ok(1.into())
+    }
+
+    fn ss_play_sound(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        if let Some(index) = self.ss_sound(args) {
+            self.emulated_play(index, false);
+        }
+        Ok(1.into())
+    }
+
+    fn ss_loop_sound(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        if let Some(index) = self.ss_sound(args) {
+            self.emulated_play(index, true);
+        }
+        Ok(1.into())
+    }
+
+    fn ss_stop_sound(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        if let Some(index) = self.ss_sound(args) {
+            self.emulated_stop(index);
+        }
+        Ok(1.into())
+    }
+
+    fn ss_is_sound_playing(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        Ok(self.ss_sound(args).map(|index| self.emulated_playing(index)).unwrap_or(false).into())
+    }
+
+    fn ss_is_sound_looping(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let channel = self.ss_sound(args).and_then(|index| self.emulated_channel(index));
+        Ok(matches!(channel, Some(c) if c.start.is_some() && c.looping).into())
+    }
+
+    fn ss_set_sound_vol(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let volume = real_arg(args, 1) / 10000.0;
+        if let Some(channel) = self.ss_sound(args).and_then(|index| self.emulated_channel_mut(index)) {
+            channel.volume = volume.clamp(0.0, 1.0);
+            channel.apply_volume(1.0);
+        }
+        Ok(1.into())
+    }
+
+    fn ss_get_sound_vol(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let channel = self.ss_sound(args).and_then(|index| self.emulated_channel(index));
+        Ok(channel.map(|c| (c.volume * 10000.0).round()).unwrap_or(0.0).into())
+    }
+
+    fn ss_set_sound_pan(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let pan = real_arg(args, 1) / 10000.0;
+        if let Some(channel) = self.ss_sound(args).and_then(|index| self.emulated_channel_mut(index)) {
+            channel.set_pan(pan);
+        }
+        Ok(1.into())
+    }
+
+    fn ss_get_sound_pan(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let channel = self.ss_sound(args).and_then(|index| self.emulated_channel(index));
+        Ok(channel.map(|c| (c.pan * 10000.0).round()).unwrap_or(0.0).into())
+    }
+
+    fn ss_get_sound_position(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let now = self.emulated_now();
+        let channel = self.ss_sound(args).and_then(|index| self.emulated_channel(index));
+        Ok(channel.map(|c| (c.position(now) / 1_000_000) as f64).unwrap_or(0.0).into())
+    }
+
+    fn ss_get_sound_length(&mut self, args: &[gml::Value]) -> gml::Result<gml::Value> {
+        let channel = self.ss_sound(args).and_then(|index| self.emulated_channel(index));
+        Ok(channel.map(|c| (c.length() / 1_000_000) as f64).unwrap_or(0.0).into())
+    }
+}
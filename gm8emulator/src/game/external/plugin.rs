@@ -0,0 +1,150 @@
+//! Loading stub implementations of external functions out of native plugins.
+//!
+//! A plugin is a shared library exporting functions with the C signature
+//! `GmValue f(unsigned int argc, const GmValue *argv)`, where
+//! `struct GmValue { unsigned int is_string; double real; const char *string; }`.
+//! Strings are null-terminated, and a returned string only needs to stay valid until the plugin is next called.
+
+use crate::gml;
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_void},
+    ptr,
+};
+
+#[repr(C)]
+struct PluginValue {
+    is_string: u32,
+    real: f64,
+    string: *const c_char,
+}
+
+type RawFunction = unsafe extern "C" fn(u32, *const PluginValue) -> PluginValue;
+
+#[derive(Clone, Copy)]
+pub struct PluginFunction(RawFunction);
+
+impl PluginFunction {
+    pub fn call(&self, args: &[gml::Value]) -> gml::Value {
+        // interior nulls can't be passed, so strings get cut off at the first one like they would in a real DLL
+        let strings = args
+            .iter()
+            .map(|arg| match arg {
+                gml::Value::Str(s) => {
+                    let bytes = s.as_ref();
+                    Some(CString::new(&bytes[..bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len())]).unwrap())
+                },
+                gml::Value::Real(_) => None,
+            })
+            .collect::<Vec<_>>();
+        let values = args
+            .iter()
+            .zip(&strings)
+            .map(|(arg, string)| PluginValue {
+                is_string: string.is_some().into(),
+                real: arg.as_real().map(f64::from).unwrap_or(0.0),
+                string: string.as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null()),
+            })
+            .collect::<Vec<_>>();
+        let result = unsafe { (self.0)(values.len() as u32, values.as_ptr()) };
+        if result.is_string != 0 && !result.string.is_null() {
+            gml::Value::from(unsafe { CStr::from_ptr(result.string) }.to_bytes())
+        } else {
+            gml::Value::from(result.real)
+        }
+    }
+}
+
+/// Keeps plugins loaded for as long as their functions might get called.
+pub struct Plugins {
+    libraries: HashMap<String, Library>,
+}
+
+impl Plugins {
+    pub fn new() -> Self {
+        Self { libraries: HashMap::new() }
+    }
+
+    pub fn function(&mut self, library: &str, symbol: &str) -> Result<PluginFunction, String> {
+        if !self.libraries.contains_key(library) {
+            let loaded = Library::open(library).ok_or_else(|| format!("failed to load plugin '{}'", library))?;
+            self.libraries.insert(library.to_string(), loaded);
+        }
+        let symbol_c = CString::new(symbol).map_err(|_| format!("invalid plugin function name '{}'", symbol))?;
+        let function = self.libraries[library].symbol(&symbol_c);
+        if function.is_null() {
+            return Err(format!("plugin '{}' has no function '{}'", library, symbol))
+        }
+        Ok(PluginFunction(unsafe { std::mem::transmute::<*const c_void, RawFunction>(function) }))
+    }
+}
+
+#[cfg(unix)]
+struct Library(*mut c_void);
+
+#[cfg(unix)]
+impl Library {
+    fn open(path: &str) -> Option<Self> {
+        let path = CString::new(path).ok()?;
+        let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() { None } else { Some(Self(handle)) }
+    }
+
+    fn symbol(&self, symbol: &CStr) -> *const c_void {
+        unsafe { libc::dlsym(self.0, symbol.as_ptr()) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Library {
+    fn drop(&mut self) {
+        unsafe { libc::dlclose(self.0) };
+    }
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn LoadLibraryW(lpFileName: *const u16) -> *mut c_void;
+    fn GetProcAddress(hModule: *mut c_void, lpProcName: *const c_char) -> *const c_void;
+    fn FreeLibrary(hModule: *mut c_void) -> i32;
+}
+
+#[cfg(windows)]
+struct Library(*mut c_void);
+
+#[cfg(windows)]
+impl Library {
+    fn open(path: &str) -> Option<Self> {
+        use std::os::windows::ffi::OsStrExt;
+        let path = std::ffi::OsStr::new(path).encode_wide().chain(std::iter::once(0)).collect::<Vec<_>>();
+        let handle = unsafe { LoadLibraryW(path.as_ptr()) };
+        if handle.is_null() { None } else { Some(Self(handle)) }
+    }
+
+    fn symbol(&self, symbol: &CStr) -> *const c_void {
+        unsafe { GetProcAddress(self.0, symbol.as_ptr()) }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Library {
+    fn drop(&mut self) {
+        unsafe { FreeLibrary(self.0) };
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+struct Library;
+
+#[cfg(not(any(unix, windows)))]
+impl Library {
+    fn open(_path: &str) -> Option<Self> {
+        None
+    }
+
+    fn symbol(&self, _symbol: &CStr) -> *const c_void {
+        ptr::null()
+    }
+}
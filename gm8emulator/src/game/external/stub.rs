@@ -0,0 +1,80 @@
+//! Stand-in implementations of external functions, configured by the user for DLLs the emulator doesn't know.
+//!
+//! They're read from an ini where each section is a DLL and each key is one of its functions, for example:
+//! ```ini
+//! [steam_api.dll]
+//! SteamInit = 1
+//! SteamUserName = "Player"
+//! SteamScore = gml: return argument0 * 2
+//! SteamAchievement = plugin: stubs.so:achievement
+//! ```
+//! Plugin paths are relative to the ini, and are stored that way so replays don't depend on where they were made.
+
+use crate::gml;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum StubKind {
+    Constant(gml::Value),
+    Gml(String),
+    Plugin { library: String, symbol: String },
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Stub {
+    pub dll: String,
+    pub symbol: String,
+    pub kind: StubKind,
+}
+
+impl Stub {
+    pub fn matches(&self, dll: &str, symbol: &str) -> bool {
+        self.dll.eq_ignore_ascii_case(dll) && self.symbol == symbol
+    }
+}
+
+/// Loads stubs from an ini file.
+pub fn load(path: &Path) -> Result<Vec<Stub>, String> {
+    // quotes are kept to tell strings apart from numbers, and backslashes so Windows paths work
+    let options = ini::ParseOption { enabled_quote: false, enabled_escape: false };
+    let ini = ini::Ini::load_from_file_opt(path, options)
+        .map_err(|e| format!("couldn't load '{}': {}", path.to_string_lossy(), e))?;
+    let mut stubs = Vec::new();
+    for (dll, properties) in ini.iter() {
+        let dll = match dll {
+            Some(dll) => dll,
+            None if properties.is_empty() => continue,
+            None => {
+                return Err(format!("stubs in '{}' must go in a section named after their DLL", path.to_string_lossy()))
+            },
+        };
+        for (symbol, value) in properties.iter() {
+            let kind = parse_kind(value).map_err(|e| format!("{} in [{}]: {}", symbol, dll, e))?;
+            stubs.push(Stub { dll: dll.into(), symbol: symbol.into(), kind });
+        }
+    }
+    Ok(stubs)
+}
+
+fn parse_kind(value: &str) -> Result<StubKind, String> {
+    let value = value.trim();
+    if let Some(code) = value.strip_prefix("gml:") {
+        Ok(StubKind::Gml(code.trim().into()))
+    } else if let Some(plugin) = value.strip_prefix("plugin:") {
+        // split on the last colon, since there can be one in a Windows path
+        match plugin.trim().rsplit_once(':') {
+            Some((library, symbol)) if !library.is_empty() && !symbol.is_empty() => {
+                Ok(StubKind::Plugin { library: library.trim().into(), symbol: symbol.trim().into() })
+            },
+            _ => Err(format!("expected 'plugin: LIBRARY:FUNCTION', got '{}'", value)),
+        }
+    } else if let Some(string) = value.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Ok(StubKind::Constant(string.into()))
+    } else {
+        match value.parse::<f64>() {
+            Ok(real) => Ok(StubKind::Constant(real.into())),
+            Err(_) => Err(format!("expected a number, a quoted string, 'gml:' or 'plugin:', got '{}'", value)),
+        }
+    }
+}
@@ -0,0 +1,484 @@
+//! Runs DLLs that don't need Windows by loading them into an interpreted 32-bit x86 process.
+//! On Windows it's only used when the WoW64 IPC host can't load a DLL, as that runs DLLs the way GM8 does.
+//!
+//! Lots of extension DLLs are small maths or string helpers which only touch the OS through kernel32 and the
+//! C runtime, so a PE loader, an interpreter and stand-ins for those are enough to run them on any platform.
+//! Calling a function that needs an import the interpreter doesn't provide fails with an error naming it.
+//! The whole process goes into savestates, so DLLs with global state stay in sync with the game.
+
+mod cpu;
+mod imports;
+mod memory;
+mod pe;
+
+use super::dll;
+use cpu::{Cpu, EAX, ESP};
+use memory::{Heap, Memory};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const STACK_BASE: u32 = 0x0010_0000;
+const STACK_SIZE: u32 = 0x0010_0000;
+const HEAP_BASE: u32 = 0x4000_0000;
+const HEAP_LIMIT: u32 = 0x1000_0000;
+/// The thread and process environment blocks, which code finds through the fs segment.
+const TEB: u32 = 0x7FFD_E000;
+const PEB: u32 = TEB + 0x1000;
+/// Where calls into the guest return to. Nothing is mapped there, so it can't be reached any other way.
+const RETURN_ADDRESS: u32 = 0x7FFE_0000;
+/// How many instructions a call can run before it's assumed to be stuck.
+const STEP_LIMIT: u64 = 1_000_000_000;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Loaded {
+    module: pe::Module,
+    path: String,
+    /// Whether every import of this DLL, and of the DLLs it loaded, was provided.
+    self_contained: bool,
+}
+
+/// An interpreted process, with all the DLLs that have been loaded into it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Machine {
+    cpu: Cpu,
+    memory: Memory,
+    heap: Heap,
+    modules: Vec<Loaded>,
+    thunks: Vec<(String, pe::ImportName)>,
+    host: imports::HostState,
+    steps: u64,
+}
+
+/// Strips the directory from a DLL path.
+fn dll_file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+/// Strips the directory and the .dll extension, which Windows lets you leave off, from a DLL path.
+fn module_name(path: &str) -> &str {
+    let name = dll_file_name(path);
+    match name.len().checked_sub(4) {
+        Some(stem) if name.is_char_boundary(stem) && name[stem..].eq_ignore_ascii_case(".dll") => &name[..stem],
+        _ => name,
+    }
+}
+
+/// Whether two DLL names refer to the same file, like Windows would see it.
+fn same_module(a: &str, b: &str) -> bool {
+    module_name(a).eq_ignore_ascii_case(module_name(b))
+}
+
+/// Finds a DLL in a directory, ignoring case since the DLL was made for Windows.
+fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    let exact = dir.join(name);
+    if exact.is_file() {
+        return Some(exact)
+    }
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .find(|entry| same_module(&entry.file_name().to_string_lossy(), name))
+        .map(|entry| entry.path())
+}
+
+impl Machine {
+    fn new() -> Result<Self, String> {
+        let mut memory = Memory::default();
+        memory.map(STACK_BASE, STACK_SIZE)?;
+        memory.map(HEAP_BASE, Heap::INITIAL_SIZE)?;
+        memory.map(TEB, 0x2000)?;
+        memory.write_u32(TEB, u32::MAX)?; // end of the exception handler chain
+        memory.write_u32(TEB + 0x4, STACK_BASE + STACK_SIZE)?;
+        memory.write_u32(TEB + 0x8, STACK_BASE)?;
+        memory.write_u32(TEB + 0x18, TEB)?;
+        memory.write_u32(TEB + 0x20, imports::PROCESS_ID)?;
+        memory.write_u32(TEB + 0x24, imports::THREAD_ID)?;
+        memory.write_u32(TEB + 0x30, PEB)?;
+        Ok(Self {
+            cpu: Cpu::new(STACK_BASE + STACK_SIZE - 16, TEB),
+            memory,
+            heap: Heap::new(HEAP_BASE, HEAP_LIMIT),
+            modules: Vec::new(),
+            thunks: Vec::new(),
+            host: Default::default(),
+            steps: 0,
+        })
+    }
+
+    /// Calls a function in the guest with the given stack arguments, returning eax.
+    /// This can happen while another call is running, such as when a host function calls back into the guest.
+    fn call(&mut self, addr: u32, args: &[u32]) -> Result<u32, String> {
+        let (esp, eip) = (self.cpu.regs[ESP], self.cpu.eip);
+        let result = self.call_inner(addr, args);
+        self.cpu.regs[ESP] = esp;
+        self.cpu.eip = eip;
+        result
+    }
+
+    fn call_inner(&mut self, addr: u32, args: &[u32]) -> Result<u32, String> {
+        for arg in args.iter().rev() {
+            self.push(*arg)?;
+        }
+        self.push(RETURN_ADDRESS)?;
+        self.cpu.eip = addr;
+        let limit = self.steps + STEP_LIMIT;
+        while self.cpu.eip != RETURN_ADDRESS {
+            if self.steps >= limit {
+                return Err(format!("gave up after running {} instructions", STEP_LIMIT))
+            }
+            match imports::thunk_index(self.cpu.eip, self.thunks.len()) {
+                Some(index) => imports::call_host(self, index)?,
+                None => self.step()?,
+            }
+        }
+        Ok(self.cpu.regs[EAX])
+    }
+
+    /// Gets the address of a thunk for an import, making one if it's new.
+    fn thunk(&mut self, dll: &str, name: pe::ImportName) -> u32 {
+        let index = match self.thunks.iter().position(|(d, n)| same_module(d, dll) && *n == name) {
+            Some(index) => index,
+            None => {
+                self.thunks.push((dll.into(), name));
+                self.thunks.len() - 1
+            },
+        };
+        imports::THUNK_BASE + index as u32
+    }
+
+    fn find_module(&self, name: &str) -> Option<usize> {
+        self.modules.iter().position(|m| same_module(&m.module.name, name))
+    }
+
+    /// Loads a DLL, unless one with the same name already is, returning its index in the module list.
+    fn load(&mut self, path: &Path) -> Result<usize, String> {
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        if let Some(index) = self.find_module(&name) {
+            return Ok(index)
+        }
+        let data = std::fs::read(path).map_err(|e| format!("couldn't read {}: {}", path.to_string_lossy(), e))?;
+        self.load_image(path, &name, &data)
+    }
+
+    fn load_image(&mut self, path: &Path, name: &str, data: &[u8]) -> Result<usize, String> {
+        let (module, imports) =
+            pe::load(&mut self.memory, name, data).map_err(|e| format!("couldn't load {}: {}", name, e))?;
+        let (base, entry, index) = (module.base, module.entry, self.modules.len());
+        let path = path.to_string_lossy().into_owned();
+        self.modules.push(Loaded { module, path: path.clone(), self_contained: true });
+        let mut self_contained = true;
+        for import in imports {
+            let addr = match self.resolve(Path::new(&path), &import, &mut self_contained)? {
+                Some(addr) => addr,
+                None => {
+                    self_contained = false;
+                    self.thunk(&import.dll, import.name)
+                },
+            };
+            self.memory.write_u32(import.slot, addr)?;
+        }
+        self.modules[index].self_contained = self_contained;
+        if let Some(entry) = entry {
+            // DllMain(instance, DLL_PROCESS_ATTACH, reserved)
+            let error = match self.call(entry, &[base, 1, 0]) {
+                Ok(0) => Some("DllMain returned 0".into()),
+                Ok(_) => None,
+                Err(e) => Some(e),
+            };
+            if let Some(error) = error {
+                self.modules.remove(index);
+                return Err(format!("{} failed to initialise: {}", name, error))
+            }
+        }
+        Ok(index)
+    }
+
+    /// Finds what an import should be bound to: a host function, or a DLL next to the one importing it.
+    fn resolve(
+        &mut self,
+        importer: &Path,
+        import: &pe::Import,
+        self_contained: &mut bool,
+    ) -> Result<Option<u32>, String> {
+        if let Some(addr) = imports::resolve(self, &import.dll, &import.name)? {
+            return Ok(Some(addr))
+        }
+        if imports::is_host_dll(&import.dll) {
+            return Ok(None)
+        }
+        let dir = importer.parent().unwrap_or_else(|| Path::new(""));
+        let index = match self.find_module(&import.dll) {
+            Some(index) => index,
+            None => match find_file(dir, dll_file_name(&import.dll)) {
+                Some(path) => self.load(&path)?,
+                None => return Ok(None),
+            },
+        };
+        let loaded = &self.modules[index];
+        *self_contained &= loaded.self_contained;
+        Ok(loaded.module.export(&import.name))
+    }
+}
+
+/// A function in an interpreted DLL.
+#[derive(Clone)]
+pub struct InterpretedExternal {
+    address: u32,
+    type_return: dll::ValueType,
+    self_contained: bool,
+}
+
+impl InterpretedExternal {
+    /// Whether everything the function's DLL imports is provided, so it should work as long as it doesn't crash.
+    pub fn is_self_contained(&self) -> bool {
+        self.self_contained
+    }
+}
+
+/// Owns the interpreted process, which is only started once a DLL is loaded into it.
+#[derive(Default)]
+pub struct InterpreterManager {
+    machine: Option<Machine>,
+}
+
+impl InterpreterManager {
+    pub fn define(&mut self, signature: &dll::ExternalSignature) -> Result<InterpretedExternal, String> {
+        let machine = match &mut self.machine {
+            Some(machine) => machine,
+            None => self.machine.insert(Machine::new()?),
+        };
+        let index = machine.load(Path::new(&signature.dll))?;
+        let loaded = &machine.modules[index];
+        let address = loaded
+            .module
+            .export(&pe::ImportName::Name(signature.symbol.clone()))
+            .ok_or_else(|| format!("{} has no function called {}", loaded.module.name, signature.symbol))?;
+        Ok(InterpretedExternal { address, type_return: signature.type_return, self_contained: loaded.self_contained })
+    }
+
+    pub fn call(&mut self, external: &InterpretedExternal, args: &[dll::Value]) -> Result<dll::Value, String> {
+        let machine = self.machine.as_mut().ok_or("the interpreter isn't running")?;
+        let mut stack = Vec::with_capacity(args.len() * 2);
+        let mut strings = Vec::new();
+        for arg in args {
+            match arg {
+                dll::Value::Real(x) => {
+                    let bits = x.to_bits();
+                    stack.extend_from_slice(&[bits as u32, (bits >> 32) as u32]);
+                },
+                dll::Value::Str(s) => {
+                    // laid out like a Delphi string, since some DLLs read the length before the pointer
+                    let bytes = s.as_slice();
+                    let addr = machine.heap.alloc(&mut machine.memory, bytes.len() as u32 + 9).ok_or("out of memory")?;
+                    machine.memory.write_u32(addr, u32::MAX)?;
+                    machine.memory.write_u32(addr + 4, bytes.len() as u32)?;
+                    machine.memory.write(addr + 8, bytes)?;
+                    machine.memory.write_u8(addr + 8 + bytes.len() as u32, 0)?;
+                    strings.push(addr);
+                    stack.push(addr + 8);
+                },
+            }
+        }
+        let result = machine.call(external.address, &stack);
+        for addr in strings {
+            machine.heap.free(addr);
+        }
+        let eax = match result {
+            Ok(eax) => eax,
+            Err(e) => {
+                machine.cpu.fpu.reset();
+                return Err(e)
+            },
+        };
+        let fpu = &mut machine.cpu.fpu;
+        Ok(match external.type_return {
+            dll::ValueType::Real => dll::Value::Real(if fpu.is_empty() { 0.0 } else { fpu.pop() }),
+            dll::ValueType::Str if eax == 0 => dll::Value::Str(dll::PascalString::empty()),
+            dll::ValueType::Str => dll::Value::Str(dll::PascalString::new(&machine.memory.read_cstr(eax)?)),
+        })
+    }
+
+    pub fn save_state(&self) -> Option<Machine> {
+        self.machine.clone()
+    }
+
+    pub fn load_state(&mut self, machine: Option<Machine>) {
+        self.machine = machine;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a DLL with one section holding the code, followed by an export table and an import table.
+    fn build_dll(code: &[u8], exports: &[(&str, u32)], imports: &[(&str, &str)]) -> Vec<u8> {
+        const BASE: u32 = 0x1000_0000;
+        const SECTION: u32 = 0x1000;
+        let mut section = code.to_vec();
+        let align = |section: &mut Vec<u8>| section.resize((section.len() + 3) & !3, 0);
+        let rva = |section: &Vec<u8>| SECTION + section.len() as u32;
+        let put = |section: &mut Vec<u8>, offset: u32, value: u32| {
+            let offset = (offset - SECTION) as usize;
+            section[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+
+        align(&mut section);
+        let export_dir = rva(&section);
+        section.resize(section.len() + 40, 0);
+        let functions = rva(&section);
+        for (_, offset) in exports {
+            section.extend_from_slice(&(SECTION + offset).to_le_bytes());
+        }
+        let names = rva(&section);
+        section.resize(section.len() + exports.len() * 4, 0);
+        let name_ordinals = rva(&section);
+        for i in 0..exports.len() as u16 {
+            section.extend_from_slice(&i.to_le_bytes());
+        }
+        for (i, (name, _)) in exports.iter().enumerate() {
+            let addr = rva(&section);
+            put(&mut section, names + i as u32 * 4, addr);
+            section.extend_from_slice(name.as_bytes());
+            section.push(0);
+        }
+        align(&mut section);
+        put(&mut section, export_dir + 20, exports.len() as u32);
+        put(&mut section, export_dir + 24, exports.len() as u32);
+        put(&mut section, export_dir + 28, functions);
+        put(&mut section, export_dir + 32, names);
+        put(&mut section, export_dir + 36, name_ordinals);
+
+        // one descriptor per import for simplicity, each with a single slot
+        let import_dir = rva(&section);
+        section.resize(section.len() + (imports.len() + 1) * 20, 0);
+        for (i, (dll, name)) in imports.iter().enumerate() {
+            let descriptor = import_dir + i as u32 * 20;
+            let slots = rva(&section);
+            section.resize(section.len() + 8, 0);
+            let dll_name = rva(&section);
+            section.extend_from_slice(dll.as_bytes());
+            section.push(0);
+            align(&mut section);
+            let hint = rva(&section);
+            section.extend_from_slice(&[0, 0]);
+            section.extend_from_slice(name.as_bytes());
+            section.push(0);
+            align(&mut section);
+            put(&mut section, slots, hint);
+            put(&mut section, descriptor + 12, dll_name);
+            put(&mut section, descriptor + 16, slots);
+        }
+        section.resize((section.len() + 0x1FF) & !0x1FF, 0);
+
+        let mut image = vec![0u8; 0x200];
+        image[0..2].copy_from_slice(b"MZ");
+        image[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        let mut header = b"PE\0\0".to_vec();
+        header.extend_from_slice(&0x14Cu16.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&[0; 12]);
+        header.extend_from_slice(&224u16.to_le_bytes());
+        header.extend_from_slice(&0x2102u16.to_le_bytes());
+        let mut optional = vec![0u8; 224];
+        optional[0..2].copy_from_slice(&0x10Bu16.to_le_bytes());
+        optional[28..32].copy_from_slice(&BASE.to_le_bytes());
+        optional[32..36].copy_from_slice(&0x1000u32.to_le_bytes());
+        optional[36..40].copy_from_slice(&0x200u32.to_le_bytes());
+        optional[56..60].copy_from_slice(&(SECTION + section.len() as u32).to_le_bytes());
+        optional[60..64].copy_from_slice(&0x200u32.to_le_bytes());
+        optional[92..96].copy_from_slice(&16u32.to_le_bytes());
+        optional[96..100].copy_from_slice(&export_dir.to_le_bytes());
+        optional[100..104].copy_from_slice(&(import_dir - export_dir).to_le_bytes());
+        optional[104..108].copy_from_slice(&import_dir.to_le_bytes());
+        optional[108..112].copy_from_slice(&((imports.len() as u32 + 1) * 20).to_le_bytes());
+        header.extend_from_slice(&optional);
+        let mut section_header = b".text\0\0\0".to_vec();
+        for value in &[section.len() as u32, SECTION, section.len() as u32, 0x200, 0, 0, 0, 0xE000_0020] {
+            section_header.extend_from_slice(&value.to_le_bytes());
+        }
+        header.extend_from_slice(&section_header[..40]);
+        image[0x80..0x80 + header.len()].copy_from_slice(&header);
+        image.extend_from_slice(&section);
+        image
+    }
+
+    fn define(
+        manager: &mut InterpreterManager,
+        dll: &[u8],
+        symbol: &str,
+        type_return: dll::ValueType,
+    ) -> InterpretedExternal {
+        let machine = manager.machine.insert(Machine::new().unwrap());
+        let index = machine.load_image(Path::new("test.dll"), "test.dll", dll).unwrap();
+        let loaded = &machine.modules[index];
+        let address = loaded.module.export(&pe::ImportName::Name(symbol.into())).unwrap();
+        InterpretedExternal { address, type_return, self_contained: loaded.self_contained }
+    }
+
+    #[test]
+    fn real_arithmetic() {
+        // fld qword [esp+4]; fadd st0, st0; ret
+        let dll = build_dll(&[0xDD, 0x44, 0x24, 0x04, 0xD8, 0xC0, 0xC3], &[("double_it", 0)], &[]);
+        let mut manager = InterpreterManager::default();
+        let external = define(&mut manager, &dll, "double_it", dll::ValueType::Real);
+        assert!(external.is_self_contained());
+        match manager.call(&external, &[dll::Value::Real(1.25)]).unwrap() {
+            dll::Value::Real(x) => assert_eq!(x, 2.5),
+            dll::Value::Str(_) => panic!("expected a real"),
+        }
+    }
+
+    #[test]
+    fn string_import() {
+        // push dword [esp+4]; call [strlen]; add esp, 4; push eax; fild dword [esp]; pop eax; ret
+        let mut code =
+            vec![0xFF, 0x74, 0x24, 0x04, 0xFF, 0x15, 0, 0, 0, 0, 0x83, 0xC4, 0x04, 0x50, 0xDB, 0x04, 0x24, 0x58, 0xC3];
+        code.resize(0x20, 0x90);
+        let dll = build_dll(&code, &[("length", 0)], &[("msvcrt.dll", "strlen")]);
+        // the import slot is the first thing after the import descriptors, which are after the exports
+        let slot = 0x1000_0000 + dll_slot(&dll);
+        let mut dll = dll;
+        dll[0x206..0x20A].copy_from_slice(&slot.to_le_bytes());
+        let mut manager = InterpreterManager::default();
+        let external = define(&mut manager, &dll, "length", dll::ValueType::Real);
+        assert!(external.is_self_contained());
+        match manager.call(&external, &[dll::Value::Str(dll::PascalString::new(b"hello"))]).unwrap() {
+            dll::Value::Real(x) => assert_eq!(x, 5.0),
+            dll::Value::Str(_) => panic!("expected a real"),
+        }
+    }
+
+    /// Finds the RVA of the first import slot by reading the import directory back out of the headers.
+    fn dll_slot(dll: &[u8]) -> u32 {
+        let read = |offset: usize| u32::from_le_bytes(dll[offset..offset + 4].try_into().unwrap());
+        let import_dir = read(0x80 + 24 + 104);
+        read(0x200 + (import_dir - 0x1000) as usize + 16)
+    }
+
+    #[test]
+    fn unsupported_import() {
+        // call [CreateFileA]; ret
+        let mut code = vec![0xFF, 0x15, 0, 0, 0, 0, 0xC3];
+        code.resize(0x10, 0x90);
+        let mut dll = build_dll(&code, &[("open", 0)], &[("KERNEL32.dll", "CreateFileA")]);
+        let slot = 0x1000_0000 + dll_slot(&dll);
+        dll[0x202..0x206].copy_from_slice(&slot.to_le_bytes());
+        let mut manager = InterpreterManager::default();
+        let external = define(&mut manager, &dll, "open", dll::ValueType::Real);
+        assert!(!external.is_self_contained());
+        let error = manager.call(&external, &[]).err().unwrap();
+        assert!(error.contains("KERNEL32.dll!CreateFileA"), "{}", error);
+        // the process is still usable afterwards
+        assert!(manager.call(&external, &[]).is_err());
+    }
+
+    #[test]
+    fn printf_reals() {
+        assert_eq!(imports::format_real(1.5, b'e', 6, false), "1.500000e+000");
+        assert_eq!(imports::format_real(0.0001, b'g', 6, false), "0.0001");
+        assert_eq!(imports::format_real(1e20, b'G', 6, false), "1E+020");
+        assert_eq!(imports::format_real(2.0, b'f', 2, false), "2.00");
+        assert_eq!(imports::format_real(f64::INFINITY, b'f', 6, false), "1.#INF");
+    }
+}
@@ -0,0 +1,1361 @@
+//! The interpreter itself, covering the integer, x87 and scalar SSE instructions compilers emit for plain code.
+//! Anything privileged, 16-bit addressing and the packed SSE instructions aren't supported.
+
+use super::Machine;
+use serde::{Deserialize, Serialize};
+
+pub const EAX: usize = 0;
+pub const ECX: usize = 1;
+pub const EDX: usize = 2;
+pub const EBX: usize = 3;
+pub const ESP: usize = 4;
+pub const EBP: usize = 5;
+pub const ESI: usize = 6;
+pub const EDI: usize = 7;
+
+const CF: u32 = 0x1;
+const PF: u32 = 0x4;
+const AF: u32 = 0x10;
+const ZF: u32 = 0x40;
+const SF: u32 = 0x80;
+const DF: u32 = 0x400;
+const OF: u32 = 0x800;
+// flags which popfd can change: the arithmetic ones, the direction flag and the ID flag used to detect cpuid
+const WRITABLE_FLAGS: u32 = 0x20_0CD5;
+
+// x87 condition codes
+const C0: u16 = 0x100;
+const C1: u16 = 0x200;
+const C2: u16 = 0x400;
+const C3: u16 = 0x4000;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Cpu {
+    pub regs: [u32; 8],
+    pub eip: u32,
+    pub fs_base: u32,
+    pub fpu: Fpu,
+    flags: u32,
+    xmm: [u128; 8],
+    mxcsr: u32,
+}
+
+impl Cpu {
+    pub fn new(esp: u32, fs_base: u32) -> Self {
+        let mut regs = [0; 8];
+        regs[ESP] = esp;
+        Self { regs, eip: 0, fs_base, fpu: Fpu::new(), flags: 0x202, xmm: [0; 8], mxcsr: 0x1F80 }
+    }
+}
+
+/// The x87 register stack, in double precision rather than extended precision.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Fpu {
+    regs: [f64; 8],
+    empty: u8,
+    top: usize,
+    status: u16,
+    control: u16,
+}
+
+impl Fpu {
+    fn new() -> Self {
+        Self { regs: [0.0; 8], empty: 0xFF, top: 0, status: 0, control: 0x27F }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn push(&mut self, value: f64) {
+        self.top = (self.top + 7) & 7;
+        self.regs[self.top] = value;
+        self.empty &= !(1 << self.top);
+    }
+
+    pub fn pop(&mut self) -> f64 {
+        let value = self.regs[self.top];
+        self.empty |= 1 << self.top;
+        self.top = (self.top + 1) & 7;
+        value
+    }
+
+    /// Whether st(0) is empty, for example because a function that was meant to return a real didn't.
+    pub fn is_empty(&self) -> bool {
+        self.empty & (1 << self.top) != 0
+    }
+
+    pub fn st(&self, i: usize) -> f64 {
+        self.regs[(self.top + i) & 7]
+    }
+
+    fn set(&mut self, i: usize, value: f64) {
+        let reg = (self.top + i) & 7;
+        self.regs[reg] = value;
+        self.empty &= !(1 << reg);
+    }
+
+    fn status_word(&self) -> u16 {
+        (self.status & !0x3800) | ((self.top as u16) << 11)
+    }
+
+    fn set_condition(&mut self, condition: u16) {
+        self.status = (self.status & !(C0 | C1 | C2 | C3)) | condition;
+    }
+
+    fn compare(&mut self, a: f64, b: f64) {
+        self.set_condition(if a > b {
+            0
+        } else if a < b {
+            C0
+        } else if a == b {
+            C3
+        } else {
+            C0 | C2 | C3
+        });
+    }
+
+    fn round(&self, value: f64) -> f64 {
+        round(value, (self.control >> 10) & 3)
+    }
+}
+
+/// Rounds in one of the modes from the x87 control word or MXCSR.
+fn round(value: f64, mode: u16) -> f64 {
+    match mode {
+        0 => {
+            let rounded = value.round();
+            if (rounded - value).abs() == 0.5 { 2.0 * (value / 2.0).round() } else { rounded }
+        },
+        1 => value.floor(),
+        2 => value.ceil(),
+        _ => value.trunc(),
+    }
+}
+
+// out of range conversions give the "integer indefinite" value, like the real thing
+fn to_i16(value: f64) -> u16 {
+    if (-32768.0..32768.0).contains(&value) { value as i16 as u16 } else { 0x8000 }
+}
+
+fn to_i32(value: f64) -> u32 {
+    if (-2147483648.0..2147483648.0).contains(&value) { value as i32 as u32 } else { 0x8000_0000 }
+}
+
+fn to_i64(value: f64) -> u64 {
+    if (-9223372036854775808.0..9223372036854775808.0).contains(&value) { value as i64 as u64 } else { 1 << 63 }
+}
+
+fn to_f80(value: f64) -> [u8; 10] {
+    let bits = value.to_bits();
+    let sign = ((bits >> 63) as u16) << 15;
+    let exponent = ((bits >> 52) & 0x7FF) as i32;
+    let fraction = bits & ((1 << 52) - 1);
+    let (exponent, mantissa) = match exponent {
+        0 if fraction == 0 => (0, 0),
+        0 => {
+            let shift = fraction.leading_zeros();
+            (16383 + 63 - 1074 - shift as i32, fraction << shift)
+        },
+        0x7FF => (0x7FFF, (1 << 63) | (fraction << 11)),
+        _ => (exponent - 1023 + 16383, (1 << 63) | (fraction << 11)),
+    };
+    let mut bytes = [0; 10];
+    bytes[..8].copy_from_slice(&mantissa.to_le_bytes());
+    bytes[8..].copy_from_slice(&(sign | exponent as u16).to_le_bytes());
+    bytes
+}
+
+fn from_f80(bytes: [u8; 10]) -> f64 {
+    let mut mantissa = [0; 8];
+    mantissa.copy_from_slice(&bytes[..8]);
+    let mantissa = u64::from_le_bytes(mantissa);
+    let top = u16::from_le_bytes([bytes[8], bytes[9]]);
+    let exponent = i32::from(top & 0x7FFF);
+    let value = if exponent == 0x7FFF {
+        if mantissa << 1 == 0 { f64::INFINITY } else { f64::NAN }
+    } else {
+        // in two steps so tiny values don't underflow before the mantissa is applied
+        let scale = exponent - 16383 - 63;
+        mantissa as f64 * 2f64.powi(scale / 2) * 2f64.powi(scale - scale / 2)
+    };
+    if top & 0x8000 != 0 { -value } else { value }
+}
+
+fn mask(size: u32) -> u32 {
+    match size {
+        1 => 0xFF,
+        2 => 0xFFFF,
+        _ => !0,
+    }
+}
+
+fn sign_bit(size: u32) -> u32 {
+    1 << (size * 8 - 1)
+}
+
+fn sign_extend(value: u32, size: u32) -> i32 {
+    match size {
+        1 => value as u8 as i8 as i32,
+        2 => value as u16 as i16 as i32,
+        _ => value as i32,
+    }
+}
+
+fn low_mask(size: u32) -> u128 {
+    if size >= 16 { !0 } else { (1 << (size * 8)) - 1 }
+}
+
+#[derive(Clone, Copy)]
+enum Rm {
+    Reg(usize),
+    Mem(u32),
+}
+
+#[derive(Default)]
+struct Prefixes {
+    opsize: bool,
+    rep: u8,
+    segment: u32,
+}
+
+impl Machine {
+    /// Runs one instruction.
+    pub(super) fn step(&mut self) -> Result<(), String> {
+        let start = self.cpu.eip;
+        self.steps += 1;
+        let mut prefixes = Prefixes::default();
+        let op = loop {
+            match self.fetch8()? {
+                0x66 => prefixes.opsize = true,
+                rep @ (0xF2 | 0xF3) => prefixes.rep = rep,
+                0x64 => prefixes.segment = self.cpu.fs_base,
+                0x26 | 0x2E | 0x36 | 0x3E | 0x65 | 0xF0 => (),
+                op => break op,
+            }
+        };
+        if self.execute(op, &prefixes)? { Ok(()) } else { Err(self.unsupported(start)) }
+    }
+
+    fn unsupported(&self, start: u32) -> String {
+        let bytes = (0..8)
+            .filter_map(|i| self.memory.read_u8(start.wrapping_add(i)).ok())
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>();
+        format!("unsupported instruction at {:#010x} ({})", start, bytes.join(" "))
+    }
+
+    fn fetch8(&mut self) -> Result<u8, String> {
+        let value = self.memory.read_u8(self.cpu.eip)?;
+        self.cpu.eip = self.cpu.eip.wrapping_add(1);
+        Ok(value)
+    }
+
+    fn fetch16(&mut self) -> Result<u16, String> {
+        let value = self.memory.read_u16(self.cpu.eip)?;
+        self.cpu.eip = self.cpu.eip.wrapping_add(2);
+        Ok(value)
+    }
+
+    fn fetch32(&mut self) -> Result<u32, String> {
+        let value = self.memory.read_u32(self.cpu.eip)?;
+        self.cpu.eip = self.cpu.eip.wrapping_add(4);
+        Ok(value)
+    }
+
+    fn fetch_imm(&mut self, size: u32) -> Result<u32, String> {
+        match size {
+            1 => self.fetch8().map(u32::from),
+            2 => self.fetch16().map(u32::from),
+            _ => self.fetch32(),
+        }
+    }
+
+    fn fetch_rel8(&mut self) -> Result<u32, String> {
+        Ok(self.fetch8()? as i8 as u32)
+    }
+
+    pub(super) fn push(&mut self, value: u32) -> Result<(), String> {
+        let esp = self.cpu.regs[ESP].wrapping_sub(4);
+        self.memory.write_u32(esp, value)?;
+        self.cpu.regs[ESP] = esp;
+        Ok(())
+    }
+
+    pub(super) fn pop(&mut self) -> Result<u32, String> {
+        let value = self.memory.read_u32(self.cpu.regs[ESP])?;
+        self.cpu.regs[ESP] = self.cpu.regs[ESP].wrapping_add(4);
+        Ok(value)
+    }
+
+    fn get_reg(&self, reg: usize, size: u32) -> u32 {
+        match size {
+            1 if reg < 4 => self.cpu.regs[reg] & 0xFF,
+            1 => (self.cpu.regs[reg - 4] >> 8) & 0xFF,
+            2 => self.cpu.regs[reg] & 0xFFFF,
+            _ => self.cpu.regs[reg],
+        }
+    }
+
+    fn set_reg(&mut self, reg: usize, size: u32, value: u32) {
+        let regs = &mut self.cpu.regs;
+        match size {
+            1 if reg < 4 => regs[reg] = (regs[reg] & !0xFF) | (value & 0xFF),
+            1 => regs[reg - 4] = (regs[reg - 4] & !0xFF00) | ((value & 0xFF) << 8),
+            2 => regs[reg] = (regs[reg] & !0xFFFF) | (value & 0xFFFF),
+            _ => regs[reg] = value,
+        }
+    }
+
+    /// Decodes a ModRM byte (and any SIB byte and displacement), returning its reg field and what it addresses.
+    fn modrm(&mut self, prefixes: &Prefixes) -> Result<(usize, Rm), String> {
+        let modrm = self.fetch8()?;
+        let (mode, reg, rm) = (modrm >> 6, usize::from((modrm >> 3) & 7), usize::from(modrm & 7));
+        if mode == 3 {
+            return Ok((reg, Rm::Reg(rm)))
+        }
+        let mut addr = if rm == 4 {
+            let sib = self.fetch8()?;
+            let (scale, index, base) = (sib >> 6, usize::from((sib >> 3) & 7), usize::from(sib & 7));
+            let index = if index == 4 { 0 } else { self.cpu.regs[index] << scale };
+            let base = if base == 5 && mode == 0 { self.fetch32()? } else { self.cpu.regs[base] };
+            base.wrapping_add(index)
+        } else if rm == 5 && mode == 0 {
+            self.fetch32()?
+        } else {
+            self.cpu.regs[rm]
+        };
+        match mode {
+            1 => addr = addr.wrapping_add(self.fetch_rel8()?),
+            2 => addr = addr.wrapping_add(self.fetch32()?),
+            _ => (),
+        }
+        Ok((reg, Rm::Mem(addr.wrapping_add(prefixes.segment))))
+    }
+
+    fn read_rm(&self, rm: Rm, size: u32) -> Result<u32, String> {
+        match rm {
+            Rm::Reg(reg) => Ok(self.get_reg(reg, size)),
+            Rm::Mem(addr) => match size {
+                1 => self.memory.read_u8(addr).map(u32::from),
+                2 => self.memory.read_u16(addr).map(u32::from),
+                _ => self.memory.read_u32(addr),
+            },
+        }
+    }
+
+    fn write_rm(&mut self, rm: Rm, size: u32, value: u32) -> Result<(), String> {
+        match rm {
+            Rm::Reg(reg) => {
+                self.set_reg(reg, size, value);
+                Ok(())
+            },
+            Rm::Mem(addr) => match size {
+                1 => self.memory.write_u8(addr, value as u8),
+                2 => self.memory.write_u16(addr, value as u16),
+                _ => self.memory.write_u32(addr, value),
+            },
+        }
+    }
+
+    fn flag(&self, flag: u32) -> bool {
+        self.cpu.flags & flag != 0
+    }
+
+    fn set_flag(&mut self, flag: u32, on: bool) {
+        if on {
+            self.cpu.flags |= flag;
+        } else {
+            self.cpu.flags &= !flag;
+        }
+    }
+
+    fn set_result_flags(&mut self, result: u32, size: u32) {
+        self.set_flag(ZF, result & mask(size) == 0);
+        self.set_flag(SF, result & sign_bit(size) != 0);
+        self.set_flag(PF, (result as u8).count_ones() & 1 == 0);
+    }
+
+    /// Does one of the eight basic arithmetic operations in the order they're encoded:
+    /// add, or, adc, sbb, and, sub, xor, cmp. Operands must already be truncated to the size.
+    fn alu(&mut self, op: usize, a: u32, b: u32, size: u32) -> u32 {
+        let (mask, sign) = (mask(size), sign_bit(size));
+        let result = match op {
+            0 | 2 => {
+                let carry = u64::from(op == 2 && self.flag(CF));
+                let full = u64::from(a) + u64::from(b) + carry;
+                let result = full as u32 & mask;
+                self.set_flag(CF, full > u64::from(mask));
+                self.set_flag(OF, (a ^ result) & (b ^ result) & sign != 0);
+                self.set_flag(AF, (a ^ b ^ result) & 0x10 != 0);
+                result
+            },
+            3 | 5 | 7 => {
+                let borrow = u64::from(op == 3 && self.flag(CF));
+                let result = a.wrapping_sub(b).wrapping_sub(borrow as u32) & mask;
+                self.set_flag(CF, u64::from(a) < u64::from(b) + borrow);
+                self.set_flag(OF, (a ^ b) & (a ^ result) & sign != 0);
+                self.set_flag(AF, (a ^ b ^ result) & 0x10 != 0);
+                result
+            },
+            _ => {
+                self.cpu.flags &= !(CF | OF | AF);
+                match op {
+                    1 => a | b,
+                    4 => a & b,
+                    _ => a ^ b,
+                }
+            },
+        };
+        self.set_result_flags(result, size);
+        result
+    }
+
+    /// Increments or decrements, which don't touch the carry flag.
+    fn inc_dec(&mut self, value: u32, dec: bool, size: u32) -> u32 {
+        let carry = self.flag(CF);
+        let result = self.alu(if dec { 5 } else { 0 }, value, 1, size);
+        self.set_flag(CF, carry);
+        result
+    }
+
+    /// Does one of the shifts and rotates in the order they're encoded: rol, ror, rcl, rcr, shl, shr, sal, sar.
+    fn shift(&mut self, op: usize, value: u32, count: u32, size: u32) -> u32 {
+        let (bits, mask, sign) = (size * 8, mask(size), sign_bit(size));
+        let count = count & 31;
+        if count == 0 {
+            return value
+        }
+        match op {
+            0 | 1 => {
+                let count = count % bits;
+                let result = match (count, op) {
+                    (0, _) => value,
+                    (_, 0) => ((value << count) | (value >> (bits - count))) & mask,
+                    _ => ((value >> count) | (value << (bits - count))) & mask,
+                };
+                if op == 0 {
+                    self.set_flag(CF, result & 1 != 0);
+                    self.set_flag(OF, (result & sign != 0) != (result & 1 != 0));
+                } else {
+                    self.set_flag(CF, result & sign != 0);
+                    self.set_flag(OF, (result & sign != 0) != (result & (sign >> 1) != 0));
+                }
+                result
+            },
+            2 | 3 => {
+                let (mut result, mut carry) = (value, self.flag(CF));
+                for _ in 0..count % (bits + 1) {
+                    if op == 2 {
+                        let out = result & sign != 0;
+                        result = ((result << 1) | u32::from(carry)) & mask;
+                        carry = out;
+                    } else {
+                        let out = result & 1 != 0;
+                        result = (result >> 1) | if carry { sign } else { 0 };
+                        carry = out;
+                    }
+                }
+                self.set_flag(CF, carry);
+                if op == 2 {
+                    self.set_flag(OF, (result & sign != 0) != carry);
+                } else {
+                    self.set_flag(OF, (result & sign != 0) != (result & (sign >> 1) != 0));
+                }
+                result
+            },
+            4 | 6 => {
+                let full = u64::from(value) << count;
+                let result = full as u32 & mask;
+                self.set_flag(CF, (full >> bits) & 1 != 0);
+                self.set_flag(OF, (result & sign != 0) != self.flag(CF));
+                self.set_result_flags(result, size);
+                result
+            },
+            5 => {
+                let result = value >> count;
+                self.set_flag(CF, (value >> (count - 1)) & 1 != 0);
+                self.set_flag(OF, value & sign != 0);
+                self.set_result_flags(result, size);
+                result
+            },
+            _ => {
+                let signed = i64::from(sign_extend(value, size));
+                let result = (signed >> count) as u32 & mask;
+                self.set_flag(CF, (signed >> (count - 1)) & 1 != 0);
+                self.set_flag(OF, false);
+                self.set_result_flags(result, size);
+                result
+            },
+        }
+    }
+
+    fn imul(&mut self, a: u32, b: u32, size: u32) -> u32 {
+        let full = i64::from(sign_extend(a, size)) * i64::from(sign_extend(b, size));
+        let result = full as u32 & mask(size);
+        let overflow = i64::from(sign_extend(result, size)) != full;
+        self.set_flag(CF, overflow);
+        self.set_flag(OF, overflow);
+        result
+    }
+
+    /// Evaluates one of the sixteen condition codes used by jcc, setcc and cmovcc.
+    fn condition(&self, code: u8) -> bool {
+        let result = match code >> 1 {
+            0 => self.flag(OF),
+            1 => self.flag(CF),
+            2 => self.flag(ZF),
+            3 => self.flag(CF) || self.flag(ZF),
+            4 => self.flag(SF),
+            5 => self.flag(PF),
+            6 => self.flag(SF) != self.flag(OF),
+            _ => self.flag(ZF) || self.flag(SF) != self.flag(OF),
+        };
+        result != (code & 1 != 0)
+    }
+
+    fn jump(&mut self, rel: u32, condition: bool) {
+        if condition {
+            self.cpu.eip = self.cpu.eip.wrapping_add(rel);
+        }
+    }
+
+    /// Sets ZF, PF and CF from a floating point comparison, like comisd and fcomi do.
+    fn set_compare_flags(&mut self, a: f64, b: f64) {
+        let (zero, parity, carry) = if a.is_nan() || b.is_nan() {
+            (true, true, true)
+        } else if a < b {
+            (false, false, true)
+        } else {
+            (a == b, false, false)
+        };
+        self.cpu.flags &= !(OF | SF | AF);
+        self.set_flag(ZF, zero);
+        self.set_flag(PF, parity);
+        self.set_flag(CF, carry);
+    }
+
+    /// Executes a one-byte opcode, returning false if it isn't supported.
+    fn execute(&mut self, op: u8, prefixes: &Prefixes) -> Result<bool, String> {
+        let osize = if prefixes.opsize { 2 } else { 4 };
+        // most instructions come in a byte-sized form and a full-sized form, picked by the lowest bit
+        let size = if op & 1 == 0 { 1 } else { osize };
+        match op {
+            0x0F => return self.execute_0f(prefixes),
+            0x00..=0x3F if op & 7 < 4 => {
+                let (reg, rm) = self.modrm(prefixes)?;
+                let (a, b) = if op & 2 == 0 {
+                    (self.read_rm(rm, size)?, self.get_reg(reg, size))
+                } else {
+                    (self.get_reg(reg, size), self.read_rm(rm, size)?)
+                };
+                let result = self.alu(usize::from(op >> 3), a, b, size);
+                if op >> 3 != 7 {
+                    if op & 2 == 0 {
+                        self.write_rm(rm, size, result)?;
+                    } else {
+                        self.set_reg(reg, size, result);
+                    }
+                }
+            },
+            0x00..=0x3F if op & 7 < 6 => {
+                let b = self.fetch_imm(size)?;
+                let result = self.alu(usize::from(op >> 3), self.get_reg(EAX, size), b, size);
+                if op >> 3 != 7 {
+                    self.set_reg(EAX, size, result);
+                }
+            },
+            0x40..=0x4F => {
+                let reg = usize::from(op & 7);
+                let result = self.inc_dec(self.get_reg(reg, osize), op >= 0x48, osize);
+                self.set_reg(reg, osize, result);
+            },
+            0x50..=0x57 => self.push(self.cpu.regs[usize::from(op & 7)])?,
+            0x58..=0x5F => {
+                let value = self.pop()?;
+                self.cpu.regs[usize::from(op & 7)] = value;
+            },
+            0x60 => {
+                let esp = self.cpu.regs[ESP];
+                for reg in 0..8 {
+                    self.push(if reg == ESP { esp } else { self.cpu.regs[reg] })?;
+                }
+            },
+            0x61 => {
+                for reg in (0..8).rev() {
+                    let value = self.pop()?;
+                    if reg != ESP {
+                        self.cpu.regs[reg] = value;
+                    }
+                }
+            },
+            0x68 => {
+                let value = self.fetch32()?;
+                self.push(value)?;
+            },
+            0x6A => {
+                let value = self.fetch_rel8()?;
+                self.push(value)?;
+            },
+            0x69 | 0x6B => {
+                let (reg, rm) = self.modrm(prefixes)?;
+                let a = self.read_rm(rm, osize)?;
+                let b = if op == 0x6B { self.fetch_rel8()? } else { self.fetch_imm(osize)? };
+                let result = self.imul(a, b, osize);
+                self.set_reg(reg, osize, result);
+            },
+            0x70..=0x7F => {
+                let rel = self.fetch_rel8()?;
+                self.jump(rel, self.condition(op & 15));
+            },
+            0x80..=0x83 => {
+                let (op2, rm) = self.modrm(prefixes)?;
+                let a = self.read_rm(rm, size)?;
+                let b = if op == 0x81 { self.fetch_imm(size)? } else { self.fetch_rel8()? & mask(size) };
+                let result = self.alu(op2, a, b, size);
+                if op2 != 7 {
+                    self.write_rm(rm, size, result)?;
+                }
+            },
+            0x84 | 0x85 => {
+                let (reg, rm) = self.modrm(prefixes)?;
+                self.alu(4, self.read_rm(rm, size)?, self.get_reg(reg, size), size);
+            },
+            0x86 | 0x87 => {
+                let (reg, rm) = self.modrm(prefixes)?;
+                let a = self.read_rm(rm, size)?;
+                self.write_rm(rm, size, self.get_reg(reg, size))?;
+                self.set_reg(reg, size, a);
+            },
+            0x88 | 0x89 => {
+                let (reg, rm) = self.modrm(prefixes)?;
+                self.write_rm(rm, size, self.get_reg(reg, size))?;
+            },
+            0x8A | 0x8B => {
+                let (reg, rm) = self.modrm(prefixes)?;
+                let value = self.read_rm(rm, size)?;
+                self.set_reg(reg, size, value);
+            },
+            0x8D => match self.modrm(&Prefixes::default())? {
+                (reg, Rm::Mem(addr)) => self.set_reg(reg, osize, addr),
+                (_, Rm::Reg(_)) => return Ok(false),
+            },
+            0x8F => {
+                // the address is worked out after esp has been incremented
+                let value = self.pop()?;
+                let (_, rm) = self.modrm(prefixes)?;
+                self.write_rm(rm, 4, value)?;
+            },
+            0x90 => (),
+            0x91..=0x97 => {
+                let reg = usize::from(op & 7);
+                let (a, b) = (self.get_reg(EAX, osize), self.get_reg(reg, osize));
+                self.set_reg(EAX, osize, b);
+                self.set_reg(reg, osize, a);
+            },
+            0x98 => {
+                if prefixes.opsize {
+                    self.set_reg(EAX, 2, sign_extend(self.get_reg(EAX, 1), 1) as u32);
+                } else {
+                    self.cpu.regs[EAX] = sign_extend(self.get_reg(EAX, 2), 2) as u32;
+                }
+            },
+            0x99 => {
+                let negative = self.get_reg(EAX, osize) & sign_bit(osize) != 0;
+                self.set_reg(EDX, osize, if negative { !0 } else { 0 });
+            },
+            0x9C => self.push(self.cpu.flags)?,
+            0x9D => {
+                let value = self.pop()?;
+                self.cpu.flags = (value & WRITABLE_FLAGS) | 0x202;
+            },
+            0x9E => {
+                let ah = self.get_reg(4, 1);
+                self.cpu.flags = (self.cpu.flags & !0xD5) | (ah & 0xD5);
+            },
+            0x9F => self.set_reg(4, 1, (self.cpu.flags & 0xD5) | 2),
+            0xA0..=0xA3 => {
+                let size = if op & 1 == 0 { 1 } else { osize };
+                let addr = Rm::Mem(self.fetch32()?.wrapping_add(prefixes.segment));
+                if op & 2 == 0 {
+                    let value = self.read_rm(addr, size)?;
+                    self.set_reg(EAX, size, value);
+                } else {
+                    self.write_rm(addr, size, self.get_reg(EAX, size))?;
+                }
+            },
+            0xA4..=0xA7 | 0xAA..=0xAF => self.string_op(op, prefixes, size)?,
+            0xA8 | 0xA9 => {
+                let b = self.fetch_imm(size)?;
+                self.alu(4, self.get_reg(EAX, size), b, size);
+            },
+            0xB0..=0xB7 => {
+                let value = self.fetch_imm(1)?;
+                self.set_reg(usize::from(op & 7), 1, value);
+            },
+            0xB8..=0xBF => {
+                let value = self.fetch_imm(osize)?;
+                self.set_reg(usize::from(op & 7), osize, value);
+            },
+            0xC0 | 0xC1 | 0xD0..=0xD3 => {
+                let (op2, rm) = self.modrm(prefixes)?;
+                let count = match op {
+                    0xC0 | 0xC1 => u32::from(self.fetch8()?),
+                    0xD0 | 0xD1 => 1,
+                    _ => self.cpu.regs[ECX] & 0xFF,
+                };
+                let result = self.shift(op2, self.read_rm(rm, size)?, count, size);
+                self.write_rm(rm, size, result)?;
+            },
+            0xC2 => {
+                let bytes = self.fetch16()?;
+                self.cpu.eip = self.pop()?;
+                self.cpu.regs[ESP] = self.cpu.regs[ESP].wrapping_add(u32::from(bytes));
+            },
+            0xC3 => self.cpu.eip = self.pop()?,
+            0xC6 | 0xC7 => {
+                let (_, rm) = self.modrm(prefixes)?;
+                let value = self.fetch_imm(size)?;
+                self.write_rm(rm, size, value)?;
+            },
+            0xC9 => {
+                self.cpu.regs[ESP] = self.cpu.regs[EBP];
+                self.cpu.regs[EBP] = self.pop()?;
+            },
+            0xD8..=0xDF => return self.x87(op, prefixes),
+            0xE2 => {
+                let rel = self.fetch_rel8()?;
+                self.cpu.regs[ECX] = self.cpu.regs[ECX].wrapping_sub(1);
+                self.jump(rel, self.cpu.regs[ECX] != 0);
+            },
+            0xE3 => {
+                let rel = self.fetch_rel8()?;
+                self.jump(rel, self.cpu.regs[ECX] == 0);
+            },
+            0xE8 => {
+                let rel = self.fetch32()?;
+                self.push(self.cpu.eip)?;
+                self.jump(rel, true);
+            },
+            0xE9 => {
+                let rel = self.fetch32()?;
+                self.jump(rel, true);
+            },
+            0xEB => {
+                let rel = self.fetch_rel8()?;
+                self.jump(rel, true);
+            },
+            0xF5 => self.cpu.flags ^= CF,
+            0xF6 | 0xF7 => return self.group3(prefixes, size),
+            0xF8 => self.set_flag(CF, false),
+            0xF9 => self.set_flag(CF, true),
+            0xFC => self.set_flag(DF, false),
+            0xFD => self.set_flag(DF, true),
+            0xFE | 0xFF => {
+                let (op2, rm) = self.modrm(prefixes)?;
+                match op2 {
+                    0 | 1 => {
+                        let result = self.inc_dec(self.read_rm(rm, size)?, op2 == 1, size);
+                        self.write_rm(rm, size, result)?;
+                    },
+                    2 if op == 0xFF => {
+                        let target = self.read_rm(rm, 4)?;
+                        self.push(self.cpu.eip)?;
+                        self.cpu.eip = target;
+                    },
+                    4 if op == 0xFF => self.cpu.eip = self.read_rm(rm, 4)?,
+                    6 if op == 0xFF => self.push(self.read_rm(rm, 4)?)?,
+                    _ => return Ok(false),
+                }
+            },
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// test, not, neg, mul, imul, div and idiv.
+    fn group3(&mut self, prefixes: &Prefixes, size: u32) -> Result<bool, String> {
+        let (op, rm) = self.modrm(prefixes)?;
+        let value = self.read_rm(rm, size)?;
+        let bits = size * 8;
+        match op {
+            0 | 1 => {
+                let b = self.fetch_imm(size)?;
+                self.alu(4, value, b, size);
+            },
+            2 => self.write_rm(rm, size, !value & mask(size))?,
+            3 => {
+                let result = self.alu(5, 0, value, size);
+                self.write_rm(rm, size, result)?;
+            },
+            4 | 5 => {
+                let acc = self.get_reg(EAX, size);
+                let (full, overflow) = if op == 4 {
+                    let full = u64::from(value) * u64::from(acc);
+                    (full, full >> bits != 0)
+                } else {
+                    let full = i64::from(sign_extend(value, size)) * i64::from(sign_extend(acc, size));
+                    (full as u64, full != i64::from(sign_extend(full as u32, size)))
+                };
+                if size == 1 {
+                    self.set_reg(EAX, 2, full as u32);
+                } else {
+                    self.set_reg(EAX, size, full as u32);
+                    self.set_reg(EDX, size, (full >> bits) as u32);
+                }
+                self.set_flag(CF, overflow);
+                self.set_flag(OF, overflow);
+            },
+            _ => {
+                if value == 0 {
+                    return Err("integer division by zero".into())
+                }
+                let dividend = if size == 1 {
+                    u64::from(self.get_reg(EAX, 2))
+                } else {
+                    (u64::from(self.get_reg(EDX, size)) << bits) | u64::from(self.get_reg(EAX, size))
+                };
+                let (quotient, remainder) = if op == 6 {
+                    let quotient = dividend / u64::from(value);
+                    if quotient > u64::from(mask(size)) {
+                        return Err("integer division overflow".into())
+                    }
+                    (quotient as u32, (dividend % u64::from(value)) as u32)
+                } else {
+                    let dividend = ((dividend << (64 - 2 * bits)) as i64) >> (64 - 2 * bits);
+                    let divisor = i64::from(sign_extend(value, size));
+                    let limit = 1i64 << (bits - 1);
+                    match dividend.checked_div(divisor) {
+                        Some(quotient) if quotient >= -limit && quotient < limit => {
+                            (quotient as u32 & mask(size), (dividend % divisor) as u32 & mask(size))
+                        },
+                        _ => return Err("integer division overflow".into()),
+                    }
+                };
+                if size == 1 {
+                    self.set_reg(EAX, 1, quotient);
+                    self.set_reg(4, 1, remainder);
+                } else {
+                    self.set_reg(EAX, size, quotient);
+                    self.set_reg(EDX, size, remainder);
+                }
+            },
+        }
+        Ok(true)
+    }
+
+    /// movs, cmps, stos, lods and scas, with or without a rep prefix.
+    fn string_op(&mut self, op: u8, prefixes: &Prefixes, size: u32) -> Result<(), String> {
+        let step = if self.flag(DF) { size.wrapping_neg() } else { size };
+        let compares = matches!(op, 0xA6 | 0xA7 | 0xAE | 0xAF);
+        loop {
+            if prefixes.rep != 0 && self.cpu.regs[ECX] == 0 {
+                break
+            }
+            let (esi, edi) = (self.cpu.regs[ESI], self.cpu.regs[EDI]);
+            let source = Rm::Mem(esi.wrapping_add(prefixes.segment));
+            match op {
+                0xA4 | 0xA5 => {
+                    let value = self.read_rm(source, size)?;
+                    self.write_rm(Rm::Mem(edi), size, value)?;
+                },
+                0xA6 | 0xA7 => {
+                    let (a, b) = (self.read_rm(source, size)?, self.read_rm(Rm::Mem(edi), size)?);
+                    self.alu(7, a, b, size);
+                },
+                0xAA | 0xAB => self.write_rm(Rm::Mem(edi), size, self.get_reg(EAX, size))?,
+                0xAC | 0xAD => {
+                    let value = self.read_rm(source, size)?;
+                    self.set_reg(EAX, size, value);
+                },
+                _ => {
+                    let b = self.read_rm(Rm::Mem(edi), size)?;
+                    self.alu(7, self.get_reg(EAX, size), b, size);
+                },
+            }
+            if !matches!(op, 0xAA..=0xAB | 0xAE..=0xAF) {
+                self.cpu.regs[ESI] = esi.wrapping_add(step);
+            }
+            if !matches!(op, 0xAC..=0xAD) {
+                self.cpu.regs[EDI] = edi.wrapping_add(step);
+            }
+            if prefixes.rep == 0 {
+                break
+            }
+            self.cpu.regs[ECX] = self.cpu.regs[ECX].wrapping_sub(1);
+            if compares && self.flag(ZF) != (prefixes.rep == 0xF3) {
+                break
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes an opcode starting with 0F, returning false if it isn't supported.
+    fn execute_0f(&mut self, prefixes: &Prefixes) -> Result<bool, String> {
+        let op = self.fetch8()?;
+        let osize = if prefixes.opsize { 2 } else { 4 };
+        match op {
+            // prefetches and multi-byte nops
+            0x18 | 0x1F => {
+                self.modrm(prefixes)?;
+            },
+            0x31 => {
+                // the "timestamp" counts instructions, so it's the same on every run
+                self.cpu.regs[EAX] = self.steps as u32;
+                self.cpu.regs[EDX] = (self.steps >> 32) as u32;
+            },
+            0xA2 => {
+                // a basic family 6 CPU with no SSE, so runtime checks pick plain x87 and integer code paths
+                let (a, b, c, d) = match self.cpu.regs[EAX] {
+                    0 => (1, u32::from_le_bytes(*b"Genu"), u32::from_le_bytes(*b"ntel"), u32::from_le_bytes(*b"ineI")),
+                    1 => (0x633, 0, 0, 0x8111),
+                    _ => (0, 0, 0, 0),
+                };
+                self.cpu.regs[EAX] = a;
+                self.cpu.regs[EBX] = b;
+                self.cpu.regs[ECX] = c;
+                self.cpu.regs[EDX] = d;
+            },
+            0x40..=0x4F => {
+                let (reg, rm) = self.modrm(prefixes)?;
+                let value = self.read_rm(rm, osize)?;
+                if self.condition(op & 15) {
+                    self.set_reg(reg, osize, value);
+                }
+            },
+            0x80..=0x8F => {
+                let rel = self.fetch32()?;
+                self.jump(rel, self.condition(op & 15));
+            },
+            0x90..=0x9F => {
+                let (_, rm) = self.modrm(prefixes)?;
+                self.write_rm(rm, 1, self.condition(op & 15).into())?;
+            },
+            0xA3 | 0xAB | 0xB3 | 0xBB | 0xBA => {
+                let (reg, rm) = self.modrm(prefixes)?;
+                let (kind, offset) = match op {
+                    0xBA => (reg, u32::from(self.fetch8()?)),
+                    0xA3 => (4, self.get_reg(reg, osize)),
+                    0xAB => (5, self.get_reg(reg, osize)),
+                    0xB3 => (6, self.get_reg(reg, osize)),
+                    _ => (7, self.get_reg(reg, osize)),
+                };
+                let bits = osize * 8;
+                // a register offset can reach outside of a memory operand
+                let rm = match rm {
+                    Rm::Mem(addr) if op != 0xBA => {
+                        let words = sign_extend(offset, osize).div_euclid(bits as i32);
+                        Rm::Mem(addr.wrapping_add((words * osize as i32) as u32))
+                    },
+                    rm => rm,
+                };
+                let bit = offset % bits;
+                let value = self.read_rm(rm, osize)?;
+                self.set_flag(CF, (value >> bit) & 1 != 0);
+                let result = match kind {
+                    5 => value | (1 << bit),
+                    6 => value & !(1 << bit),
+                    7 => value ^ (1 << bit),
+                    _ => value,
+                };
+                if kind > 4 {
+                    self.write_rm(rm, osize, result)?;
+                }
+            },
+            0xA4 | 0xA5 | 0xAC | 0xAD if !prefixes.opsize => {
+                let (reg, rm) = self.modrm(prefixes)?;
+                let count = if op & 1 == 0 { u32::from(self.fetch8()?) } else { self.cpu.regs[ECX] } & 31;
+                if count != 0 {
+                    let (a, b) = (self.read_rm(rm, 4)?, self.get_reg(reg, 4));
+                    let (result, carry) = if op < 0xA8 {
+                        ((a << count) | (b >> (32 - count)), (a >> (32 - count)) & 1)
+                    } else {
+                        ((a >> count) | (b << (32 - count)), (a >> (count - 1)) & 1)
+                    };
+                    self.set_flag(CF, carry != 0);
+                    self.set_flag(OF, (result ^ a) & 0x8000_0000 != 0);
+                    self.set_result_flags(result, 4);
+                    self.write_rm(rm, 4, result)?;
+                }
+            },
+            0xAE => match self.modrm(prefixes)? {
+                (2, Rm::Mem(addr)) => self.cpu.mxcsr = self.memory.read_u32(addr)?,
+                (3, Rm::Mem(addr)) => self.memory.write_u32(addr, self.cpu.mxcsr)?,
+                (5..=7, Rm::Reg(_)) => (), // fences
+                _ => return Ok(false),
+            },
+            0xAF => {
+                let (reg, rm) = self.modrm(prefixes)?;
+                let result = self.imul(self.get_reg(reg, osize), self.read_rm(rm, osize)?, osize);
+                self.set_reg(reg, osize, result);
+            },
+            0xB0 | 0xB1 => {
+                let size = if op == 0xB0 { 1 } else { osize };
+                let (reg, rm) = self.modrm(prefixes)?;
+                let value = self.read_rm(rm, size)?;
+                self.alu(7, self.get_reg(EAX, size), value, size);
+                if self.flag(ZF) {
+                    self.write_rm(rm, size, self.get_reg(reg, size))?;
+                } else {
+                    self.set_reg(EAX, size, value);
+                }
+            },
+            0xB6 | 0xB7 | 0xBE | 0xBF => {
+                let size = if op & 1 == 0 { 1 } else { 2 };
+                let (reg, rm) = self.modrm(prefixes)?;
+                let value = self.read_rm(rm, size)?;
+                let value = if op >= 0xBE { sign_extend(value, size) as u32 } else { value };
+                self.set_reg(reg, osize, value);
+            },
+            0xBC | 0xBD if prefixes.rep == 0 => {
+                let (reg, rm) = self.modrm(prefixes)?;
+                let value = self.read_rm(rm, osize)?;
+                self.set_flag(ZF, value == 0);
+                if value != 0 {
+                    let bit = if op == 0xBC { value.trailing_zeros() } else { 31 - value.leading_zeros() };
+                    self.set_reg(reg, osize, bit);
+                }
+            },
+            0xC0 | 0xC1 => {
+                let size = if op == 0xC0 { 1 } else { osize };
+                let (reg, rm) = self.modrm(prefixes)?;
+                let (dest, source) = (self.read_rm(rm, size)?, self.get_reg(reg, size));
+                let sum = self.alu(0, dest, source, size);
+                self.set_reg(reg, size, dest);
+                self.write_rm(rm, size, sum)?;
+            },
+            0xC8..=0xCF => {
+                let reg = usize::from(op & 7);
+                self.cpu.regs[reg] = self.cpu.regs[reg].swap_bytes();
+            },
+            _ => return self.sse(op, prefixes),
+        }
+        Ok(true)
+    }
+
+    fn xmm_read(&self, rm: Rm, size: u32) -> Result<u128, String> {
+        match rm {
+            Rm::Reg(reg) => Ok(self.cpu.xmm[reg] & low_mask(size)),
+            Rm::Mem(addr) => Ok(match size {
+                4 => u128::from(self.memory.read_u32(addr)?),
+                8 => u128::from(self.memory.read_u64(addr)?),
+                _ => u128::from(self.memory.read_u64(addr)?) | u128::from(self.memory.read_u64(addr + 8)?) << 64,
+            }),
+        }
+    }
+
+    /// Writes the low bytes of a register or memory, leaving the rest of the register alone.
+    fn xmm_write(&mut self, rm: Rm, size: u32, value: u128) -> Result<(), String> {
+        match rm {
+            Rm::Reg(reg) => {
+                let mask = low_mask(size);
+                self.cpu.xmm[reg] = (self.cpu.xmm[reg] & !mask) | (value & mask);
+                Ok(())
+            },
+            Rm::Mem(addr) => match size {
+                4 => self.memory.write_u32(addr, value as u32),
+                8 => self.memory.write_u64(addr, value as u64),
+                _ => self.memory.write(addr, &value.to_le_bytes()),
+            },
+        }
+    }
+
+    /// The SSE instructions that get used for scalar maths and moving data around.
+    fn sse(&mut self, op: u8, prefixes: &Prefixes) -> Result<bool, String> {
+        // the mandatory prefix picks between the single, double, scalar and integer forms
+        let kind = match (prefixes.rep, prefixes.opsize) {
+            (0, false) => 0,
+            (0, true) => 0x66,
+            (rep, _) => rep,
+        };
+        let scalar_size = if kind == 0xF2 { 8 } else { 4 };
+        let (reg, rm) = match op {
+            0x10 | 0x11 | 0x28 | 0x29 | 0x2A | 0x2C..=0x2F | 0x51 | 0x54..=0x5A | 0x5C..=0x5F | 0x6E | 0x6F => {
+                self.modrm(prefixes)?
+            },
+            0x7E | 0x7F | 0xD6 | 0xDB | 0xEB | 0xEF => self.modrm(prefixes)?,
+            _ => return Ok(false),
+        };
+        let to_f64 = |bits: u128| {
+            if scalar_size == 8 { f64::from_bits(bits as u64) } else { f64::from(f32::from_bits(bits as u32)) }
+        };
+        let from_f64 = |value: f64| {
+            if scalar_size == 8 { u128::from(value.to_bits()) } else { u128::from((value as f32).to_bits()) }
+        };
+        match (kind, op) {
+            (0 | 0x66, 0x10 | 0x28) | (0x66 | 0xF3, 0x6F) => self.cpu.xmm[reg] = self.xmm_read(rm, 16)?,
+            (0 | 0x66, 0x11 | 0x29) | (0x66 | 0xF3, 0x7F) => self.xmm_write(rm, 16, self.cpu.xmm[reg])?,
+            (0xF2 | 0xF3, 0x10) => match rm {
+                Rm::Reg(_) => self.xmm_write(Rm::Reg(reg), scalar_size, self.xmm_read(rm, scalar_size)?)?,
+                Rm::Mem(_) => self.cpu.xmm[reg] = self.xmm_read(rm, scalar_size)?,
+            },
+            (0xF2 | 0xF3, 0x11) => self.xmm_write(rm, scalar_size, self.cpu.xmm[reg])?,
+            (0x66, 0x6E) => self.cpu.xmm[reg] = u128::from(self.read_rm(rm, 4)?),
+            (0x66, 0x7E) => self.write_rm(rm, 4, self.cpu.xmm[reg] as u32)?,
+            (0xF3, 0x7E) => self.cpu.xmm[reg] = self.xmm_read(rm, 8)?,
+            (0x66, 0xD6) => match rm {
+                Rm::Reg(dest) => self.cpu.xmm[dest] = self.cpu.xmm[reg] & low_mask(8),
+                Rm::Mem(_) => self.xmm_write(rm, 8, self.cpu.xmm[reg])?,
+            },
+            (0 | 0x66, 0x54..=0x57) | (0x66, 0xDB | 0xEB | 0xEF) => {
+                let (a, b) = (self.cpu.xmm[reg], self.xmm_read(rm, 16)?);
+                self.cpu.xmm[reg] = match op {
+                    0x54 | 0xDB => a & b,
+                    0x55 => !a & b,
+                    0x56 | 0xEB => a | b,
+                    _ => a ^ b,
+                };
+            },
+            (0xF2 | 0xF3, 0x51 | 0x58 | 0x59 | 0x5C..=0x5F) => {
+                let a = to_f64(self.cpu.xmm[reg]);
+                let b = to_f64(self.xmm_read(rm, scalar_size)?);
+                let result = match op {
+                    0x51 => b.sqrt(),
+                    0x58 => a + b,
+                    0x59 => a * b,
+                    0x5C => a - b,
+                    0x5D => if a < b { a } else { b },
+                    0x5E => a / b,
+                    _ => if a > b { a } else { b },
+                };
+                self.xmm_write(Rm::Reg(reg), scalar_size, from_f64(result))?;
+            },
+            (0xF2 | 0xF3, 0x2A) => {
+                let value = f64::from(self.read_rm(rm, 4)? as i32);
+                self.xmm_write(Rm::Reg(reg), scalar_size, from_f64(value))?;
+            },
+            (0xF2 | 0xF3, 0x2C | 0x2D) => {
+                let value = to_f64(self.xmm_read(rm, scalar_size)?);
+                let mode = if op == 0x2C { 3 } else { ((self.cpu.mxcsr >> 13) & 3) as u16 };
+                self.set_reg(reg, 4, to_i32(round(value, mode)));
+            },
+            (0xF2, 0x5A) => {
+                let value = f64::from_bits(self.xmm_read(rm, 8)? as u64) as f32;
+                self.xmm_write(Rm::Reg(reg), 4, u128::from(value.to_bits()))?;
+            },
+            (0xF3, 0x5A) => {
+                let value = f64::from(f32::from_bits(self.xmm_read(rm, 4)? as u32));
+                self.xmm_write(Rm::Reg(reg), 8, u128::from(value.to_bits()))?;
+            },
+            (0 | 0x66, 0x2E | 0x2F) => {
+                let size = if kind == 0x66 { 8 } else { 4 };
+                let convert = |bits: u128| {
+                    if size == 8 { f64::from_bits(bits as u64) } else { f64::from(f32::from_bits(bits as u32)) }
+                };
+                let (a, b) = (convert(self.cpu.xmm[reg]), convert(self.xmm_read(rm, size)?));
+                self.set_compare_flags(a, b);
+            },
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Does an arithmetic x87 operation in the order they're encoded: add, mul, com, comp, sub, subr, div, divr.
+    fn fpu_arith(&mut self, op: usize, dest: usize, source: f64) {
+        let fpu = &mut self.cpu.fpu;
+        let value = fpu.st(dest);
+        let result = match op {
+            0 => value + source,
+            1 => value * source,
+            2 | 3 => {
+                fpu.compare(value, source);
+                if op == 3 {
+                    fpu.pop();
+                }
+                return
+            },
+            4 => value - source,
+            5 => source - value,
+            6 => value / source,
+            _ => source / value,
+        };
+        fpu.set(dest, result);
+    }
+
+    /// Executes an x87 instruction, returning false if it isn't supported.
+    fn x87(&mut self, op: u8, prefixes: &Prefixes) -> Result<bool, String> {
+        let (reg, rm) = self.modrm(prefixes)?;
+        let addr = match rm {
+            Rm::Mem(addr) => addr,
+            Rm::Reg(i) => return Ok(self.x87_registers(op, reg, i)),
+        };
+        let st0 = self.cpu.fpu.st(0);
+        let rounded = self.cpu.fpu.round(st0);
+        // the store instructions which pop are all the ones with reg 1 (fisttp), reg 3 or reg 7
+        let pops = matches!((op, reg), (0xD9 | 0xDB | 0xDD | 0xDF, 1 | 3) | (0xDB | 0xDF, 7));
+        match (op, reg) {
+            (0xD8, _) => self.fpu_arith(reg, 0, f64::from(self.memory.read_f32(addr)?)),
+            (0xDA, _) => self.fpu_arith(reg, 0, f64::from(self.memory.read_u32(addr)? as i32)),
+            (0xDC, _) => self.fpu_arith(reg, 0, self.memory.read_f64(addr)?),
+            (0xDE, _) => self.fpu_arith(reg, 0, f64::from(self.memory.read_u16(addr)? as i16)),
+            (0xD9, 0) => self.cpu.fpu.push(f64::from(self.memory.read_f32(addr)?)),
+            (0xD9, 2 | 3) => self.memory.write_f32(addr, st0 as f32)?,
+            (0xD9, 5) => self.cpu.fpu.control = self.memory.read_u16(addr)?,
+            (0xD9, 7) => self.memory.write_u16(addr, self.cpu.fpu.control)?,
+            (0xDB, 0) => self.cpu.fpu.push(f64::from(self.memory.read_u32(addr)? as i32)),
+            (0xDB, 1) => self.memory.write_u32(addr, to_i32(st0.trunc()))?,
+            (0xDB, 2 | 3) => self.memory.write_u32(addr, to_i32(rounded))?,
+            (0xDB, 5) => self.cpu.fpu.push(from_f80(self.memory.read(addr)?)),
+            (0xDB, 7) => self.memory.write(addr, &to_f80(st0))?,
+            (0xDD, 0) => self.cpu.fpu.push(self.memory.read_f64(addr)?),
+            (0xDD, 1) => self.memory.write_u64(addr, to_i64(st0.trunc()))?,
+            (0xDD, 2 | 3) => self.memory.write_f64(addr, st0)?,
+            (0xDD, 7) => self.memory.write_u16(addr, self.cpu.fpu.status_word())?,
+            (0xDF, 0) => self.cpu.fpu.push(f64::from(self.memory.read_u16(addr)? as i16)),
+            (0xDF, 1) => self.memory.write_u16(addr, to_i16(st0.trunc()))?,
+            (0xDF, 2 | 3) => self.memory.write_u16(addr, to_i16(rounded))?,
+            (0xDF, 5) => self.cpu.fpu.push(self.memory.read_u64(addr)? as i64 as f64),
+            (0xDF, 7) => self.memory.write_u64(addr, to_i64(rounded))?,
+            _ => return Ok(false),
+        }
+        if pops {
+            self.cpu.fpu.pop();
+        }
+        Ok(true)
+    }
+
+    /// Executes an x87 instruction that only uses registers, returning false if it isn't supported.
+    fn x87_registers(&mut self, op: u8, reg: usize, i: usize) -> bool {
+        let st0 = self.cpu.fpu.st(0);
+        let sti = self.cpu.fpu.st(i);
+        match (op, reg) {
+            (0xD8, _) | (0xDC, 2 | 3) => self.fpu_arith(reg, 0, sti),
+            // with st(i) as the destination the sub and div forms are the other way around
+            (0xDC, _) => self.fpu_arith(if reg >= 4 { reg ^ 1 } else { reg }, i, st0),
+            (0xDE, 3) if i == 1 => {
+                self.cpu.fpu.compare(st0, sti);
+                self.cpu.fpu.pop();
+                self.cpu.fpu.pop();
+            },
+            (0xDE, 0 | 1 | 4..=7) => {
+                self.fpu_arith(if reg >= 4 { reg ^ 1 } else { reg }, i, st0);
+                self.cpu.fpu.pop();
+            },
+            (0xD9, 0) => self.cpu.fpu.push(sti),
+            (0xD9, 1) => {
+                self.cpu.fpu.set(0, sti);
+                self.cpu.fpu.set(i, st0);
+            },
+            (0xD9, 2) if i == 0 => (),
+            (0xD9, 4) => match i {
+                0 => self.cpu.fpu.set(0, -st0),
+                1 => self.cpu.fpu.set(0, st0.abs()),
+                4 => self.cpu.fpu.compare(st0, 0.0),
+                5 => {
+                    let class = if self.cpu.fpu.is_empty() {
+                        C3 | C0
+                    } else if st0.is_nan() {
+                        C0
+                    } else if st0.is_infinite() {
+                        C2 | C0
+                    } else if st0 == 0.0 {
+                        C3
+                    } else if st0.is_subnormal() {
+                        C3 | C2
+                    } else {
+                        C2
+                    };
+                    self.cpu.fpu.set_condition(class | if st0.is_sign_negative() { C1 } else { 0 });
+                },
+                _ => return false,
+            },
+            (0xD9, 5) => self.cpu.fpu.push(match i {
+                0 => 1.0,
+                1 => std::f64::consts::LOG2_10,
+                2 => std::f64::consts::LOG2_E,
+                3 => std::f64::consts::PI,
+                4 => std::f64::consts::LOG10_2,
+                5 => std::f64::consts::LN_2,
+                6 => 0.0,
+                _ => return false,
+            }),
+            (0xD9, 6) => match i {
+                0 => self.cpu.fpu.set(0, st0.exp2() - 1.0),
+                1 | 3 => {
+                    let st1 = self.cpu.fpu.st(1);
+                    self.cpu.fpu.set(1, if i == 1 { st1 * st0.log2() } else { st1.atan2(st0) });
+                    self.cpu.fpu.pop();
+                },
+                2 => {
+                    self.cpu.fpu.set(0, st0.tan());
+                    self.cpu.fpu.push(1.0);
+                    self.cpu.fpu.set_condition(0);
+                },
+                5 => {
+                    let st1 = self.cpu.fpu.st(1);
+                    self.cpu.fpu.set(0, st0 - st1 * round(st0 / st1, 0));
+                    self.cpu.fpu.set_condition(0);
+                },
+                6 => self.cpu.fpu.top = (self.cpu.fpu.top + 7) & 7,
+                7 => self.cpu.fpu.top = (self.cpu.fpu.top + 1) & 7,
+                _ => return false,
+            },
+            (0xD9, 7) => {
+                let st1 = self.cpu.fpu.st(1);
+                match i {
+                    0 => {
+                        self.cpu.fpu.set(0, st0 % st1);
+                        self.cpu.fpu.set_condition(0);
+                    },
+                    1 => {
+                        self.cpu.fpu.set(1, st1 * st0.ln_1p() / std::f64::consts::LN_2);
+                        self.cpu.fpu.pop();
+                    },
+                    2 => self.cpu.fpu.set(0, st0.sqrt()),
+                    3 => {
+                        self.cpu.fpu.set(0, st0.sin());
+                        self.cpu.fpu.push(st0.cos());
+                        self.cpu.fpu.set_condition(0);
+                    },
+                    4 => self.cpu.fpu.set(0, self.cpu.fpu.round(st0)),
+                    5 => self.cpu.fpu.set(0, st0 * 2f64.powf(st1.trunc())),
+                    6 => self.cpu.fpu.set(0, st0.sin()),
+                    _ => self.cpu.fpu.set(0, st0.cos()),
+                }
+            },
+            (0xDA | 0xDB, 0..=3) => {
+                let condition = match reg {
+                    0 => self.flag(CF),
+                    1 => self.flag(ZF),
+                    2 => self.flag(CF) || self.flag(ZF),
+                    _ => self.flag(PF),
+                };
+                if condition == (op == 0xDA) {
+                    self.cpu.fpu.set(0, sti);
+                }
+            },
+            (0xDA, 5) if i == 1 => {
+                self.cpu.fpu.compare(st0, sti);
+                self.cpu.fpu.pop();
+                self.cpu.fpu.pop();
+            },
+            (0xDB, 4) => match i {
+                2 => self.cpu.fpu.status &= !0x80FF,
+                3 => self.cpu.fpu.reset(),
+                _ => return false,
+            },
+            (0xDB, 5 | 6) | (0xDF, 5 | 6) => {
+                self.set_compare_flags(st0, sti);
+                if op == 0xDF {
+                    self.cpu.fpu.pop();
+                }
+            },
+            (0xDD, 0) => self.cpu.fpu.empty |= 1 << ((self.cpu.fpu.top + i) & 7),
+            (0xDD, 2 | 3) => {
+                self.cpu.fpu.set(i, st0);
+                if reg == 3 {
+                    self.cpu.fpu.pop();
+                }
+            },
+            (0xDD, 4 | 5) => {
+                self.cpu.fpu.compare(st0, sti);
+                if reg == 5 {
+                    self.cpu.fpu.pop();
+                }
+            },
+            (0xDF, 4) if i == 0 => self.set_reg(EAX, 2, self.cpu.fpu.status_word().into()),
+            _ => return false,
+        }
+        true
+    }
+}
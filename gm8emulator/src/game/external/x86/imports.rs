@@ -0,0 +1,1630 @@
+//! Stand-ins for the parts of kernel32, the C runtime and a few other system DLLs that helper DLLs use.
+//!
+//! Imports are bound to "thunk" addresses, which aren't mapped, and calling one runs the matching function here
+//! instead of an instruction. Anything involving the outside world (files, time, threads) is either faked in a
+//! deterministic way or left out, in which case calling it gives an error naming the import.
+
+use super::{cpu::*, memory::Memory, pe::ImportName, Machine, TEB};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, path::Path};
+
+pub const THUNK_BASE: u32 = 0x7FF0_0000;
+/// Where the handles of the system DLLs point, which is never mapped.
+const HOST_MODULE_BASE: u32 = 0x7FE0_0000;
+const HOST_MODULES: [&str; 5] = ["kernel32.dll", "msvcrt.dll", "user32.dll", "advapi32.dll", "oleaut32.dll"];
+
+pub const PROCESS_ID: u32 = 0x100;
+pub const THREAD_ID: u32 = 0x104;
+const HEAP_HANDLE: u32 = 0x0005_0000;
+const LAST_ERROR: u32 = TEB + 0x34;
+/// Where VirtualAlloc starts looking for room.
+const VIRTUAL_BASE: u32 = 0x0200_0000;
+
+// the clock only moves with the instruction count, so DLLs that look at the time do the same thing every run
+const STEPS_PER_SECOND: u64 = 100_000_000;
+const START_TIME: u64 = 1_230_768_000; // 2009-01-01
+const FILETIME_EPOCH: u64 = 11_644_473_600;
+
+const ERROR_MOD_NOT_FOUND: u32 = 126;
+const ERROR_PROC_NOT_FOUND: u32 = 127;
+const ERROR_INSUFFICIENT_BUFFER: u32 = 122;
+const ERROR_ENVVAR_NOT_FOUND: u32 = 203;
+const ERROR_FILE_NOT_FOUND: u32 = 2;
+
+type HostFn = fn(&mut Machine) -> Result<u32, String>;
+
+/// A function DLLs can import: its name, how many bytes of arguments it pops, and what it does.
+/// What it returns goes in eax.
+type HostEntry = (&'static str, u32, HostFn);
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HostState {
+    tls: Vec<Option<u32>>,
+    rand: u32,
+    strtok: u32,
+    data: Vec<(String, u32)>,
+}
+
+impl Default for HostState {
+    fn default() -> Self {
+        Self { tls: Vec::new(), rand: 1, strtok: 0, data: Vec::new() }
+    }
+}
+
+/// Which thunk an address is, if any.
+pub fn thunk_index(addr: u32, count: usize) -> Option<usize> {
+    addr.checked_sub(THUNK_BASE).map(|i| i as usize).filter(|&i| i < count)
+}
+
+/// Runs the host function behind a thunk, then returns from it like the real function would.
+pub fn call_host(m: &mut Machine, index: usize) -> Result<(), String> {
+    let (dll, name) = &m.thunks[index];
+    let (arg_bytes, function) = match find_function(dll, name) {
+        Some(function) => function,
+        None => return Err(format!("called {}!{}, which the interpreter doesn't support", dll, name)),
+    };
+    m.steps += 1;
+    let eax = function(m)?;
+    m.cpu.regs[EAX] = eax;
+    m.cpu.eip = m.pop()?;
+    m.cpu.regs[ESP] = m.cpu.regs[ESP].wrapping_add(arg_bytes);
+    Ok(())
+}
+
+/// Whether imports from a DLL are handled here rather than by loading it.
+pub fn is_host_dll(dll: &str) -> bool {
+    host_table(dll).is_some()
+}
+
+/// Finds the address to bind an import to, if it's something handled here.
+pub fn resolve(m: &mut Machine, dll: &str, name: &ImportName) -> Result<Option<u32>, String> {
+    if let (Some(table), ImportName::Name(name)) = (host_table(dll), name) {
+        match MSVCRT_DATA.iter().find(|(n, _)| n == name) {
+            Some((_, init)) if std::ptr::eq(table, MSVCRT) => return init(m).map(Some),
+            _ => (),
+        }
+    }
+    Ok(find_function(dll, name).map(|_| m.thunk(dll, name.clone())))
+}
+
+fn host_table(dll: &str) -> Option<&'static [HostEntry]> {
+    let dll = dll.to_ascii_lowercase();
+    let dll = dll.strip_suffix(".dll").unwrap_or(&dll);
+    match dll {
+        "kernel32" | "kernelbase" | "ntdll" => Some(KERNEL32),
+        "msvcrt" | "crtdll" | "ucrtbase" => Some(MSVCRT),
+        _ if dll.starts_with("msvcr") || dll.starts_with("vcruntime") || dll.starts_with("api-ms-win-crt-") => {
+            Some(MSVCRT)
+        },
+        "user32" => Some(USER32),
+        "advapi32" => Some(ADVAPI32),
+        "oleaut32" => Some(OLEAUT32),
+        _ => None,
+    }
+}
+
+fn find_function(dll: &str, name: &ImportName) -> Option<(u32, HostFn)> {
+    let table = host_table(dll)?;
+    let name = match name {
+        ImportName::Name(name) => name.as_str(),
+        // oleaut32 is usually imported by ordinal
+        ImportName::Ordinal(ordinal) if std::ptr::eq(table, OLEAUT32) => {
+            OLEAUT32_ORDINALS.iter().find(|(o, _)| o == ordinal)?.1
+        },
+        ImportName::Ordinal(_) => return None,
+    };
+    table.iter().find(|(n, ..)| *n == name).map(|&(_, arg_bytes, function)| (arg_bytes, function))
+}
+
+impl Machine {
+    /// Reads the `i`th dword of a host function's arguments.
+    fn arg(&self, i: u32) -> Result<u32, String> {
+        self.memory.read_u32(self.cpu.regs[ESP].wrapping_add(4 + i * 4))
+    }
+
+    /// Reads a double starting at the `i`th dword of a host function's arguments.
+    fn arg_f64(&self, i: u32) -> Result<f64, String> {
+        self.memory.read_f64(self.cpu.regs[ESP].wrapping_add(4 + i * 4))
+    }
+
+    fn set_last_error(&mut self, code: u32) -> Result<(), String> {
+        self.memory.write_u32(LAST_ERROR, code)
+    }
+
+    /// Returns a real the way C functions do, in st(0).
+    fn return_real(&mut self, value: f64) -> Result<u32, String> {
+        self.cpu.fpu.push(value);
+        Ok(0)
+    }
+
+    /// Allocates memory, giving a null pointer if there's no room.
+    fn malloc(&mut self, size: u32) -> u32 {
+        self.heap.alloc(&mut self.memory, size).unwrap_or(0)
+    }
+
+    fn calloc(&mut self, size: u32) -> Result<u32, String> {
+        let addr = self.malloc(size);
+        if addr != 0 {
+            self.memory.slice_mut(addr, size as usize)?.fill(0);
+        }
+        Ok(addr)
+    }
+
+    fn realloc(&mut self, addr: u32, size: u32) -> Result<u32, String> {
+        let old_size = match self.heap.size_of(addr) {
+            Some(old_size) => old_size,
+            None => return Ok(self.malloc(size)),
+        };
+        let new = self.malloc(size);
+        if new != 0 {
+            self.copy(new, addr, old_size.min(size))?;
+            self.heap.free(addr);
+        }
+        Ok(new)
+    }
+
+    fn copy(&mut self, dest: u32, source: u32, len: u32) -> Result<(), String> {
+        if len != 0 {
+            let bytes = self.memory.slice(source, len as usize)?.to_vec();
+            self.memory.write(dest, &bytes)?;
+        }
+        Ok(())
+    }
+
+    fn write_cstr(&mut self, addr: u32, string: &[u8]) -> Result<(), String> {
+        self.memory.write(addr, string)?;
+        self.memory.write_u8(addr + string.len() as u32, 0)
+    }
+
+    fn write_wstr(&mut self, addr: u32, string: &[u16]) -> Result<(), String> {
+        for (i, c) in string.iter().chain(&[0]).enumerate() {
+            self.memory.write_u16(addr + i as u32 * 2, *c)?;
+        }
+        Ok(())
+    }
+
+    /// Memory the stand-ins hand out which lives for as long as the process, made the first time it's asked for.
+    fn static_data(&mut self, key: &str, init: &[u8]) -> Result<u32, String> {
+        if let Some((_, addr)) = self.host.data.iter().find(|(k, _)| k == key) {
+            return Ok(*addr)
+        }
+        let addr = self.malloc(init.len() as u32);
+        if addr == 0 {
+            return Err("out of memory".into())
+        }
+        self.memory.write(addr, init)?;
+        self.host.data.push((key.into(), addr));
+        Ok(addr)
+    }
+
+    fn module_handle(&self, name: &[u8]) -> u32 {
+        let name = String::from_utf8_lossy(name);
+        let name = super::dll_file_name(&name);
+        if let Some(index) = HOST_MODULES.iter().position(|m| super::same_module(m, name)) {
+            HOST_MODULE_BASE + index as u32 * 0x10000
+        } else {
+            self.find_module(name).map(|i| self.modules[i].module.base).unwrap_or(0)
+        }
+    }
+
+    fn load_library(&mut self, name: &[u8]) -> Result<u32, String> {
+        let handle = self.module_handle(name);
+        if handle != 0 {
+            return Ok(handle)
+        }
+        // look next to the DLLs that are already loaded
+        let name = String::from_utf8_lossy(name).into_owned();
+        let dirs =
+            self.modules.iter().filter_map(|m| Path::new(&m.path).parent().map(Path::to_path_buf)).collect::<Vec<_>>();
+        for dir in dirs {
+            if let Some(path) = super::find_file(&dir, super::dll_file_name(&name)) {
+                if let Ok(index) = self.load(&path) {
+                    return Ok(self.modules[index].module.base)
+                }
+            }
+        }
+        self.set_last_error(ERROR_MOD_NOT_FOUND)?;
+        Ok(0)
+    }
+
+    fn get_proc_address(&mut self, handle: u32, name: u32) -> Result<u32, String> {
+        let name = if name < 0x10000 {
+            ImportName::Ordinal(name as u16)
+        } else {
+            ImportName::Name(String::from_utf8_lossy(&self.memory.read_cstr(name)?).into_owned())
+        };
+        let host_module = handle.checked_sub(HOST_MODULE_BASE).and_then(|i| HOST_MODULES.get(i as usize / 0x10000));
+        let addr = if let Some(dll) = host_module {
+            find_function(dll, &name).map(|_| self.thunk(dll, name))
+        } else {
+            self.modules.iter().find(|m| m.module.base == handle).and_then(|m| m.module.export(&name))
+        };
+        match addr {
+            Some(addr) => Ok(addr),
+            None => self.set_last_error(ERROR_PROC_NOT_FOUND).map(|_| 0),
+        }
+    }
+
+    fn module_file_name(&self, handle: u32) -> String {
+        match self.modules.iter().find(|m| m.module.base == handle) {
+            Some(module) => module.path.clone(),
+            None => "game.exe".into(),
+        }
+    }
+
+    fn unix_time(&self) -> u64 {
+        START_TIME + self.steps / STEPS_PER_SECOND
+    }
+
+    /// The time as a count of 100ns intervals since 1601, like a FILETIME.
+    fn file_time(&self) -> u64 {
+        (START_TIME + FILETIME_EPOCH) * 10_000_000 + self.steps / (STEPS_PER_SECOND / 10_000_000)
+    }
+
+    fn write_system_time(&mut self, addr: u32) -> Result<(), String> {
+        let seconds = self.unix_time();
+        let days = (seconds / 86400) as i64;
+        let (year, month, day) = civil_from_days(days);
+        let time = (seconds % 86400) as i64;
+        let millis = self.steps % STEPS_PER_SECOND * 1000 / STEPS_PER_SECOND;
+        let fields = [year, month, (days + 4).rem_euclid(7), day, time / 3600, time / 60 % 60, time % 60];
+        for (i, field) in fields.iter().chain(&[millis as i64]).enumerate() {
+            self.memory.write_u16(addr + i as u32 * 2, *field as u16)?;
+        }
+        Ok(())
+    }
+
+    fn tls_alloc(&mut self) -> u32 {
+        match self.host.tls.iter().position(Option::is_none) {
+            Some(index) => {
+                self.host.tls[index] = Some(0);
+                index as u32
+            },
+            None => {
+                self.host.tls.push(Some(0));
+                self.host.tls.len() as u32 - 1
+            },
+        }
+    }
+
+    fn tls_get(&mut self, index: u32) -> Result<u32, String> {
+        self.set_last_error(0)?;
+        Ok(self.host.tls.get(index as usize).copied().flatten().unwrap_or(0))
+    }
+
+    fn tls_set(&mut self, index: u32, value: u32) -> u32 {
+        match self.host.tls.get_mut(index as usize) {
+            Some(Some(slot)) => {
+                *slot = value;
+                1
+            },
+            _ => 0,
+        }
+    }
+
+    fn tls_free(&mut self, index: u32) -> u32 {
+        match self.host.tls.get_mut(index as usize) {
+            Some(slot @ Some(_)) => {
+                *slot = None;
+                1
+            },
+            _ => 0,
+        }
+    }
+
+    fn virtual_alloc(&mut self, addr: u32, size: u32) -> Result<u32, String> {
+        let size = size.checked_add(0xFFF).ok_or("VirtualAlloc size overflowed")? & !0xFFF;
+        if addr != 0 && self.memory.region_of(addr).is_some() {
+            // committing memory that was reserved earlier, which was all committed anyway
+            return Ok(addr)
+        }
+        let base = if addr != 0 { Some(addr & !0xFFFF) } else { self.memory.find_free(VIRTUAL_BASE, size) };
+        match base {
+            Some(base) if size != 0 && self.memory.map(base, size).is_ok() => Ok(base),
+            _ => Ok(0),
+        }
+    }
+
+    fn virtual_query(&mut self, addr: u32, info: u32) -> Result<u32, String> {
+        let page = addr & !0xFFF;
+        let fields = match self.memory.region_of(addr) {
+            Some((base, size)) => [page, base, 0x40, base + size - page, 0x1000, 0x40, 0x20000],
+            None => [page, 0, 0, 0x1000, 0x10000, 0x1, 0],
+        };
+        for (i, field) in fields.iter().enumerate() {
+            self.memory.write_u32(info + i as u32 * 4, *field)?;
+        }
+        Ok(28)
+    }
+
+    fn heap_alloc(&mut self, flags: u32, size: u32) -> Result<u32, String> {
+        if flags & 0x8 != 0 { self.calloc(size) } else { Ok(self.malloc(size)) }
+    }
+
+    /// Reads a string argument that might have an explicit length, or be null-terminated if the length is -1.
+    fn counted_str(&self, addr: u32, len: u32) -> Result<Vec<u8>, String> {
+        if len as i32 >= 0 { Ok(self.memory.slice(addr, len as usize)?.to_vec()) } else { self.memory.read_cstr(addr) }
+    }
+
+    fn counted_wstr(&self, addr: u32, len: u32) -> Result<Vec<u16>, String> {
+        if len as i32 >= 0 {
+            (0..len).map(|i| self.memory.read_u16(addr + i * 2)).collect()
+        } else {
+            self.memory.read_wstr(addr)
+        }
+    }
+
+    /// Writes the result of a Win32 string conversion, which reports the size needed when the buffer is empty.
+    fn conversion_result<T: Copy>(
+        &mut self,
+        result: &[T],
+        dest: u32,
+        dest_len: u32,
+        write: fn(&mut Memory, u32, T) -> Result<(), String>,
+        unit: u32,
+    ) -> Result<u32, String> {
+        if dest_len == 0 {
+            return Ok(result.len() as u32)
+        }
+        if result.len() > dest_len as usize {
+            self.set_last_error(ERROR_INSUFFICIENT_BUFFER)?;
+            return Ok(0)
+        }
+        for (i, c) in result.iter().enumerate() {
+            write(&mut self.memory, dest + i as u32 * unit, *c)?;
+        }
+        Ok(result.len() as u32)
+    }
+
+    fn multi_byte_to_wide_char(&mut self) -> Result<u32, String> {
+        let source = self.counted_str(self.arg(2)?, self.arg(3)?)?;
+        let mut wide = source.iter().map(|&b| u16::from(b)).collect::<Vec<_>>();
+        if (self.arg(3)? as i32) < 0 {
+            wide.push(0);
+        }
+        self.conversion_result(&wide, self.arg(4)?, self.arg(5)?, Memory::write_u16, 2)
+    }
+
+    fn wide_char_to_multi_byte(&mut self) -> Result<u32, String> {
+        let source = self.counted_wstr(self.arg(2)?, self.arg(3)?)?;
+        let mut bytes = source.iter().map(|&c| if c < 0x100 { c as u8 } else { b'?' }).collect::<Vec<_>>();
+        if (self.arg(3)? as i32) < 0 {
+            bytes.push(0);
+        }
+        self.conversion_result(&bytes, self.arg(4)?, self.arg(5)?, Memory::write_u8, 1)
+    }
+
+    fn lc_map_string(&mut self, wide: bool) -> Result<u32, String> {
+        let (flags, source, len) = (self.arg(1)?, self.arg(2)?, self.arg(3)?);
+        let map = |c: u16| match flags & 0x300 {
+            0x100 if c < 0x80 => u16::from((c as u8).to_ascii_lowercase()),
+            0x200 if c < 0x80 => u16::from((c as u8).to_ascii_uppercase()),
+            _ => c,
+        };
+        let mut result = if wide {
+            self.counted_wstr(source, len)?.into_iter().map(map).collect::<Vec<_>>()
+        } else {
+            self.counted_str(source, len)?.into_iter().map(|b| map(b.into())).collect()
+        };
+        if (len as i32) < 0 {
+            result.push(0);
+        }
+        if wide {
+            self.conversion_result(&result, self.arg(4)?, self.arg(5)?, Memory::write_u16, 2)
+        } else {
+            let result = result.into_iter().map(|c| c as u8).collect::<Vec<_>>();
+            self.conversion_result(&result, self.arg(4)?, self.arg(5)?, Memory::write_u8, 1)
+        }
+    }
+
+    fn get_string_type(&mut self, first: u32, wide: bool) -> Result<u32, String> {
+        let (kind, source) = (self.arg(first)?, self.arg(first + 1)?);
+        let (len, dest) = (self.arg(first + 2)?, self.arg(first + 3)?);
+        let chars = if wide {
+            self.counted_wstr(source, len)?
+        } else {
+            self.counted_str(source, len)?.into_iter().map(u16::from).collect()
+        };
+        for (i, c) in chars.into_iter().enumerate() {
+            let value = match kind {
+                1 if c < 0x100 => ctype(c as u8),
+                _ => 0,
+            };
+            self.memory.write_u16(dest + i as u32 * 2, value)?;
+        }
+        Ok(1)
+    }
+
+    fn compare_string(&mut self, wide: bool) -> Result<u32, String> {
+        let ignore_case = self.arg(1)? & 1 != 0;
+        let read = |m: &Machine, addr, len| -> Result<Vec<u16>, String> {
+            let chars = if wide {
+                m.counted_wstr(addr, len)?
+            } else {
+                m.counted_str(addr, len)?.into_iter().map(u16::from).collect()
+            };
+            let fold = |c: u16| if ignore_case && c < 0x80 { u16::from((c as u8).to_ascii_lowercase()) } else { c };
+            Ok(chars.into_iter().map(fold).collect())
+        };
+        let a = read(self, self.arg(2)?, self.arg(3)?)?;
+        let b = read(self, self.arg(4)?, self.arg(5)?)?;
+        Ok(match a.cmp(&b) {
+            Ordering::Less => 1,
+            Ordering::Equal => 2,
+            Ordering::Greater => 3,
+        })
+    }
+
+    fn initterm(&mut self, stop_on_error: bool) -> Result<u32, String> {
+        let (start, end) = (self.arg(0)?, self.arg(1)?);
+        for slot in (start..end).step_by(4) {
+            let function = self.memory.read_u32(slot)?;
+            if function != 0 {
+                let result = self.call(function, &[])?;
+                if stop_on_error && result != 0 {
+                    return Ok(result)
+                }
+            }
+        }
+        Ok(0)
+    }
+
+    fn qsort(&mut self) -> Result<u32, String> {
+        let (base, count, width, compare) = (self.arg(0)?, self.arg(1)?, self.arg(2)?, self.arg(3)?);
+        // an insertion sort, since the comparison function runs in the guest and that can fail
+        for i in 1..count {
+            let mut j = i;
+            while j > 0 {
+                let (a, b) = (base + (j - 1) * width, base + j * width);
+                if (self.call(compare, &[a, b])? as i32) <= 0 {
+                    break
+                }
+                let first = self.memory.slice(a, width as usize)?.to_vec();
+                self.copy(a, b, width)?;
+                self.memory.write(b, &first)?;
+                j -= 1;
+            }
+        }
+        Ok(0)
+    }
+
+    fn sprintf(&mut self, dest: u32, format: u32, args: VarArgs) -> Result<u32, String> {
+        let text = self.format(format, args)?;
+        self.write_cstr(dest, &text)?;
+        Ok(text.len() as u32)
+    }
+
+    /// Like sprintf, but with a size limit, and the terminator left off if the text only just fits.
+    fn snprintf(&mut self, dest: u32, count: u32, format: u32, args: VarArgs) -> Result<u32, String> {
+        let text = self.format(format, args)?;
+        let count = count as usize;
+        if text.len() < count {
+            self.write_cstr(dest, &text)?;
+            Ok(text.len() as u32)
+        } else {
+            self.memory.write(dest, &text[..count])?;
+            Ok(if text.len() == count { count as u32 } else { u32::MAX })
+        }
+    }
+
+    /// Formats a printf-style string like msvcrt does.
+    fn format(&self, format: u32, mut args: VarArgs) -> Result<Vec<u8>, String> {
+        let format = self.memory.read_cstr(format)?;
+        let mut out = Vec::new();
+        let mut i = 0;
+        let number = |i: &mut usize| {
+            let mut value = 0usize;
+            while let Some(digit) = format.get(*i).filter(|c| c.is_ascii_digit()) {
+                value = value.saturating_mul(10).saturating_add(usize::from(digit - b'0'));
+                *i += 1;
+            }
+            value
+        };
+        while let Some(&c) = format.get(i) {
+            i += 1;
+            if c != b'%' {
+                out.push(c);
+                continue
+            }
+            let (mut left, mut plus, mut space, mut alt, mut zero) = (false, false, false, false, false);
+            while let Some(&flag) = format.get(i) {
+                match flag {
+                    b'-' => left = true,
+                    b'+' => plus = true,
+                    b' ' => space = true,
+                    b'#' => alt = true,
+                    b'0' => zero = true,
+                    _ => break,
+                }
+                i += 1;
+            }
+            let width = if format.get(i) == Some(&b'*') {
+                i += 1;
+                let width = args.next(self)? as i32;
+                left |= width < 0;
+                width.unsigned_abs() as usize
+            } else {
+                number(&mut i)
+            };
+            let mut precision = None;
+            if format.get(i) == Some(&b'.') {
+                i += 1;
+                precision = if format.get(i) == Some(&b'*') {
+                    i += 1;
+                    Some(args.next(self)? as i32).filter(|&p| p >= 0).map(|p| p as usize)
+                } else {
+                    Some(number(&mut i))
+                };
+            }
+            let mut wide = false;
+            loop {
+                match format.get(i..).unwrap_or_default() {
+                    [b'l', b'l', ..] | [b'I', b'6', b'4', ..] => {
+                        wide = true;
+                        i += if format[i] == b'l' { 2 } else { 3 };
+                    },
+                    [b'I', b'3', b'2', ..] => i += 3,
+                    [b'h' | b'l' | b'L' | b'w' | b'I' | b'z' | b't' | b'j', ..] => i += 1,
+                    _ => break,
+                }
+            }
+            let conversion = match format.get(i) {
+                Some(&conversion) => conversion,
+                None => break,
+            };
+            i += 1;
+            let (prefix, body) = match conversion {
+                b'c' => (Vec::new(), vec![args.next(self)? as u8]),
+                b's' => {
+                    let addr = args.next(self)?;
+                    let mut string = if addr == 0 { b"(null)".to_vec() } else { self.memory.read_cstr(addr)? };
+                    if let Some(precision) = precision {
+                        string.truncate(precision);
+                    }
+                    (Vec::new(), string)
+                },
+                b'd' | b'i' | b'u' | b'x' | b'X' | b'o' => {
+                    let signed = matches!(conversion, b'd' | b'i');
+                    let raw = if wide { args.next_u64(self)? } else { u64::from(args.next(self)?) };
+                    let value = match (signed, wide) {
+                        (true, true) => raw as i64,
+                        (true, false) => i64::from(raw as u32 as i32),
+                        (false, _) => 0,
+                    };
+                    let magnitude = if signed { value.unsigned_abs() } else { raw };
+                    let mut digits = match conversion {
+                        b'x' => format!("{:x}", magnitude),
+                        b'X' => format!("{:X}", magnitude),
+                        b'o' => format!("{:o}", magnitude),
+                        _ => magnitude.to_string(),
+                    }
+                    .into_bytes();
+                    if let Some(precision) = precision {
+                        if precision == 0 && magnitude == 0 {
+                            digits.clear();
+                        }
+                        while digits.len() < precision {
+                            digits.insert(0, b'0');
+                        }
+                        zero = false;
+                    }
+                    let prefix: &[u8] = match conversion {
+                        _ if value < 0 => b"-",
+                        b'd' | b'i' if plus => b"+",
+                        b'd' | b'i' if space => b" ",
+                        b'x' if alt && magnitude != 0 => b"0x",
+                        b'X' if alt && magnitude != 0 => b"0X",
+                        b'o' if alt && digits.first() != Some(&b'0') => b"0",
+                        _ => b"",
+                    };
+                    (prefix.to_vec(), digits)
+                },
+                b'p' => (Vec::new(), format!("{:08X}", args.next(self)?).into_bytes()),
+                b'e' | b'E' | b'f' | b'F' | b'g' | b'G' => {
+                    let value = args.next_f64(self)?;
+                    let prefix: &[u8] = if value.is_sign_negative() {
+                        b"-"
+                    } else if plus {
+                        b"+"
+                    } else if space {
+                        b" "
+                    } else {
+                        b""
+                    };
+                    zero &= value.is_finite();
+                    (prefix.to_vec(), format_real(value, conversion, precision.unwrap_or(6), alt).into_bytes())
+                },
+                b'n' => {
+                    args.next(self)?;
+                    continue
+                },
+                other => (Vec::new(), vec![other]),
+            };
+            let padding = width.saturating_sub(prefix.len() + body.len());
+            if left {
+                out.extend(prefix.into_iter().chain(body).chain(vec![b' '; padding]));
+            } else if zero {
+                out.extend(prefix.into_iter().chain(vec![b'0'; padding]).chain(body));
+            } else {
+                out.extend(vec![b' '; padding].into_iter().chain(prefix).chain(body));
+            }
+        }
+        Ok(out)
+    }
+
+    fn strtod(&mut self) -> Result<u32, String> {
+        let (string, end) = (self.arg(0)?, self.arg(1)?);
+        let (value, len) = parse_real(&self.memory.read_cstr(string)?);
+        if end != 0 {
+            self.memory.write_u32(end, string + len as u32)?;
+        }
+        self.return_real(value)
+    }
+
+    fn strtol(&mut self, signed: bool) -> Result<u32, String> {
+        let (string, end, base) = (self.arg(0)?, self.arg(1)?, self.arg(2)?);
+        let (value, len) = parse_integer(&self.memory.read_cstr(string)?, base);
+        if end != 0 {
+            self.memory.write_u32(end, string + len as u32)?;
+        }
+        Ok(if signed {
+            value.clamp(i32::MIN.into(), i32::MAX.into()) as i32 as u32
+        } else if value.unsigned_abs() > u64::from(u32::MAX) {
+            u32::MAX
+        } else {
+            value as u32
+        })
+    }
+
+    fn strtok(&mut self) -> Result<u32, String> {
+        let (string, delimiters) = (self.arg(0)?, self.arg(1)?);
+        let delimiters = self.memory.read_cstr(delimiters)?;
+        let mut cursor = if string != 0 { string } else { self.host.strtok };
+        if cursor == 0 {
+            return Ok(0)
+        }
+        while delimiters.contains(&self.memory.read_u8(cursor)?) {
+            cursor += 1;
+        }
+        if self.memory.read_u8(cursor)? == 0 {
+            self.host.strtok = 0;
+            return Ok(0)
+        }
+        let start = cursor;
+        loop {
+            match self.memory.read_u8(cursor)? {
+                0 => {
+                    self.host.strtok = cursor;
+                    break
+                },
+                c if delimiters.contains(&c) => {
+                    self.memory.write_u8(cursor, 0)?;
+                    self.host.strtok = cursor + 1;
+                    break
+                },
+                _ => cursor += 1,
+            }
+        }
+        Ok(start)
+    }
+
+    fn itoa(&mut self, value: u64, negative: bool, dest: u32, radix: u32) -> Result<u32, String> {
+        let radix = u64::from(radix.clamp(2, 36));
+        let mut digits = Vec::new();
+        let mut value = value;
+        loop {
+            digits.push(b"0123456789abcdefghijklmnopqrstuvwxyz"[(value % radix) as usize]);
+            value /= radix;
+            if value == 0 {
+                break
+            }
+        }
+        if negative {
+            digits.push(b'-');
+        }
+        digits.reverse();
+        self.write_cstr(dest, &digits)?;
+        Ok(dest)
+    }
+
+    /// Searches a string argument, returning a pointer to where something was found or a null pointer.
+    fn find_in_str(&self, addr: u32, find: impl FnOnce(&[u8]) -> Option<usize>) -> Result<u32, String> {
+        let string = self.memory.read_cstr(addr)?;
+        Ok(find(&string).map(|i| addr + i as u32).unwrap_or(0))
+    }
+
+    fn compare_strs(&self, a: u32, b: u32, limit: Option<u32>, ignore_case: bool) -> Result<u32, String> {
+        let prepare = |addr| -> Result<Vec<u8>, String> {
+            let mut string = self.memory.read_cstr(addr)?;
+            if let Some(limit) = limit {
+                string.truncate(limit as usize);
+            }
+            if ignore_case {
+                string.make_ascii_lowercase();
+            }
+            Ok(string)
+        };
+        Ok(ordering_int(prepare(a)?.cmp(&prepare(b)?)))
+    }
+
+    fn sys_alloc_string(&mut self, source: u32, len: u32) -> Result<u32, String> {
+        let addr = self.malloc(len.saturating_mul(2).saturating_add(6));
+        if addr == 0 {
+            return Ok(0)
+        }
+        self.memory.write_u32(addr, len * 2)?;
+        if source != 0 {
+            self.copy(addr + 4, source, len * 2)?;
+        }
+        self.memory.write_u16(addr + 4 + len * 2, 0)?;
+        Ok(addr + 4)
+    }
+
+    fn sys_free_string(&mut self, string: u32) -> u32 {
+        if string != 0 {
+            self.heap.free(string - 4);
+        }
+        0
+    }
+
+    /// CharUpper and CharLower, which take either a pointer or a single character.
+    fn char_case(&mut self, upper: bool) -> Result<u32, String> {
+        let arg = self.arg(0)?;
+        let convert = |c: u8| if upper { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() };
+        if arg < 0x10000 {
+            return Ok(u32::from(convert(arg as u8)))
+        }
+        let mut string = self.memory.read_cstr(arg)?;
+        string.iter_mut().for_each(|c| *c = convert(*c));
+        self.memory.write(arg, &string)?;
+        Ok(arg)
+    }
+
+    fn char_case_buffer(&mut self, upper: bool) -> Result<u32, String> {
+        let (addr, len) = (self.arg(0)?, self.arg(1)?);
+        let string = self.memory.slice_mut(addr, len as usize)?;
+        string.iter_mut().for_each(|c| *c = if upper { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() });
+        Ok(len)
+    }
+}
+
+/// Where variadic arguments are read from: the stack for printf, or a va_list for vprintf.
+struct VarArgs(u32);
+
+impl VarArgs {
+    /// The variadic arguments of a host function, starting at the `i`th dword.
+    fn on_stack(m: &Machine, i: u32) -> Self {
+        Self(m.cpu.regs[ESP].wrapping_add(4 + i * 4))
+    }
+
+    fn next(&mut self, m: &Machine) -> Result<u32, String> {
+        let value = m.memory.read_u32(self.0)?;
+        self.0 = self.0.wrapping_add(4);
+        Ok(value)
+    }
+
+    fn next_u64(&mut self, m: &Machine) -> Result<u64, String> {
+        let value = m.memory.read_u64(self.0)?;
+        self.0 = self.0.wrapping_add(8);
+        Ok(value)
+    }
+
+    fn next_f64(&mut self, m: &Machine) -> Result<f64, String> {
+        self.next_u64(m).map(f64::from_bits)
+    }
+}
+
+/// Formats a real for printf, without its sign, including msvcrt's three digit exponents and odd infinities.
+pub fn format_real(value: f64, conversion: u8, precision: usize, alt: bool) -> String {
+    let upper = conversion.is_ascii_uppercase();
+    let value = value.abs();
+    if value.is_infinite() {
+        return "1.#INF".into()
+    } else if value.is_nan() {
+        return if value.is_sign_negative() { "1.#IND".into() } else { "1.#QNAN".into() }
+    }
+    match conversion.to_ascii_lowercase() {
+        b'f' => {
+            let mut text = format!("{:.*}", precision, value);
+            if alt && precision == 0 {
+                text.push('.');
+            }
+            text
+        },
+        b'e' => exponent_form(value, precision, alt, upper),
+        _ => {
+            let precision = precision.max(1);
+            let exponent = if value == 0.0 { 0 } else { exponent_of(&format!("{:.*e}", precision - 1, value)) };
+            let text = if exponent < -4 || exponent >= precision as i32 {
+                exponent_form(value, precision - 1, alt, upper)
+            } else {
+                format!("{:.*}", (precision as i32 - 1 - exponent) as usize, value)
+            };
+            if alt {
+                return text
+            }
+            let (mantissa, exponent) = text.split_at(text.find(['e', 'E']).unwrap_or(text.len()));
+            let mantissa =
+                if mantissa.contains('.') { mantissa.trim_end_matches('0').trim_end_matches('.') } else { mantissa };
+            format!("{}{}", mantissa, exponent)
+        },
+    }
+}
+
+fn exponent_of(text: &str) -> i32 {
+    text.split_once('e').and_then(|(_, e)| e.parse().ok()).unwrap_or(0)
+}
+
+fn exponent_form(value: f64, precision: usize, alt: bool, upper: bool) -> String {
+    let text = format!("{:.*e}", precision, value);
+    let exponent = exponent_of(&text);
+    let mut mantissa = text.split('e').next().unwrap_or_default().to_string();
+    if alt && precision == 0 {
+        mantissa.push('.');
+    }
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{}{}{}{:03}", mantissa, if upper { 'E' } else { 'e' }, sign, exponent.abs())
+}
+
+/// Parses the longest prefix of a string that's a real, like strtod, returning it and how many bytes it took.
+fn parse_real(string: &[u8]) -> (f64, usize) {
+    let mut i = string.iter().take_while(|c| c.is_ascii_whitespace()).count();
+    let start = i;
+    if matches!(string.get(i), Some(b'+' | b'-')) {
+        i += 1;
+    }
+    let digits = |i: &mut usize| {
+        let count = string[*i..].iter().take_while(|c| c.is_ascii_digit()).count();
+        *i += count;
+        count
+    };
+    let mut mantissa_digits = digits(&mut i);
+    if string.get(i) == Some(&b'.') {
+        i += 1;
+        mantissa_digits += digits(&mut i);
+    }
+    if mantissa_digits == 0 {
+        return (0.0, 0)
+    }
+    if matches!(string.get(i), Some(b'e' | b'E')) {
+        let mut j = i + 1;
+        if matches!(string.get(j), Some(b'+' | b'-')) {
+            j += 1;
+        }
+        if digits(&mut j) > 0 {
+            i = j;
+        }
+    }
+    let text = String::from_utf8_lossy(&string[start..i]);
+    let text = text.strip_suffix('.').unwrap_or(&text);
+    (text.replace("-.", "-0.").replace("+.", "0.").parse().unwrap_or(0.0), i)
+}
+
+/// Parses the longest prefix of a string that's an integer, like strtol, returning it and how many bytes it took.
+fn parse_integer(string: &[u8], base: u32) -> (i64, usize) {
+    let mut i = string.iter().take_while(|c| c.is_ascii_whitespace()).count();
+    let negative = string.get(i) == Some(&b'-');
+    if matches!(string.get(i), Some(b'+' | b'-')) {
+        i += 1;
+    }
+    let hex_prefix = string.get(i) == Some(&b'0') && matches!(string.get(i + 1), Some(b'x' | b'X'));
+    let base = match base {
+        0 if hex_prefix => 16,
+        0 if string.get(i) == Some(&b'0') => 8,
+        0 => 10,
+        base => base,
+    };
+    if base == 16 && hex_prefix && string.get(i + 2).and_then(|c| char::from(*c).to_digit(16)).is_some() {
+        i += 2;
+    }
+    let start = i;
+    let mut value = 0i64;
+    while let Some(digit) = string.get(i).and_then(|c| char::from(*c).to_digit(base)) {
+        value = value.saturating_mul(base.into()).saturating_add(digit.into());
+        i += 1;
+    }
+    if i == start {
+        return (0, 0)
+    }
+    (if negative { -value } else { value }, i)
+}
+
+/// The character type flags used by GetStringType and the C runtime's is* functions.
+fn ctype(c: u8) -> u16 {
+    let flags = [
+        (c.is_ascii_uppercase(), 0x1),
+        (c.is_ascii_lowercase(), 0x2),
+        (c.is_ascii_digit(), 0x4),
+        (c == b' ' || (9..=13).contains(&c), 0x8),
+        (c.is_ascii_punctuation(), 0x10),
+        (c.is_ascii_control(), 0x20),
+        (c == b' ' || c == b'\t', 0x40),
+        (c.is_ascii_hexdigit(), 0x80),
+        (c.is_ascii_alphabetic(), 0x100),
+    ];
+    flags.iter().filter(|(on, _)| *on).map(|(_, flag)| flag).sum()
+}
+
+fn is_ctype(m: &mut Machine, mask: u16) -> Result<u32, String> {
+    let c = m.arg(0)?;
+    Ok(if c < 0x100 { u32::from(ctype(c as u8) & mask) } else { 0 })
+}
+
+fn ordering_int(ordering: Ordering) -> u32 {
+    match ordering {
+        Ordering::Less => -1i32 as u32,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    }
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+fn real1(m: &mut Machine, f: fn(f64) -> f64) -> Result<u32, String> {
+    let value = f(m.arg_f64(0)?);
+    m.return_real(value)
+}
+
+fn real2(m: &mut Machine, f: fn(f64, f64) -> f64) -> Result<u32, String> {
+    let value = f(m.arg_f64(0)?, m.arg_f64(2)?);
+    m.return_real(value)
+}
+
+/// The _CI functions MSVC calls for maths on the x87 stack, taking their arguments from it too.
+fn x87_real1(m: &mut Machine, f: fn(f64) -> f64) -> Result<u32, String> {
+    let value = m.cpu.fpu.pop();
+    m.return_real(f(value))
+}
+
+fn x87_real2(m: &mut Machine, f: fn(f64, f64) -> f64) -> Result<u32, String> {
+    let y = m.cpu.fpu.pop();
+    let x = m.cpu.fpu.pop();
+    m.return_real(f(x, y))
+}
+
+/// _ftol and friends, which truncate st(0) into edx:eax.
+fn ftol(m: &mut Machine) -> Result<u32, String> {
+    let value = m.cpu.fpu.pop().trunc();
+    let value = if (-9223372036854775808.0..9223372036854775808.0).contains(&value) { value as i64 } else { i64::MIN };
+    m.cpu.regs[EDX] = (value >> 32) as u32;
+    Ok(value as u32)
+}
+
+fn frexp(m: &mut Machine) -> Result<u32, String> {
+    let value = m.arg_f64(0)?;
+    let (mut mantissa, mut exponent) = (value, 0);
+    if value != 0.0 && value.is_finite() {
+        exponent = value.abs().log2().floor() as i32 + 1;
+        mantissa = value / 2f64.powi(exponent);
+        while mantissa.abs() >= 1.0 {
+            mantissa /= 2.0;
+            exponent += 1;
+        }
+        while mantissa.abs() < 0.5 {
+            mantissa *= 2.0;
+            exponent -= 1;
+        }
+    }
+    m.memory.write_u32(m.arg(2)?, exponent as u32)?;
+    m.return_real(mantissa)
+}
+
+fn exit(m: &mut Machine) -> Result<u32, String> {
+    Err(format!("the DLL tried to exit the process with code {}", m.arg(0)? as i32))
+}
+
+fn exception(_: &mut Machine) -> Result<u32, String> {
+    Err("the DLL raised an exception, which the interpreter can't handle".into())
+}
+
+fn raise_exception(m: &mut Machine) -> Result<u32, String> {
+    Err(format!("the DLL raised exception {:#010x}, which the interpreter can't handle", m.arg(0)?))
+}
+
+// these are addresses in the guest, made the first time a DLL imports them
+static MSVCRT_DATA: &[(&str, HostFn)] = &[
+    ("_adjust_fdiv", |m| m.static_data("_adjust_fdiv", &[0; 4])),
+    ("__mb_cur_max", |m| m.static_data("__mb_cur_max", &1u32.to_le_bytes())),
+    ("_iob", |m| m.static_data("_iob", &[0; 32 * 3])),
+    ("_fmode", |m| m.static_data("_fmode", &[0; 4])),
+    ("_commode", |m| m.static_data("_commode", &[0; 4])),
+    ("_acmdln", |m| {
+        let command_line = m.static_data("GetCommandLineA", b"game.exe\0")?;
+        m.static_data("_acmdln", &command_line.to_le_bytes())
+    }),
+];
+
+static KERNEL32: &[HostEntry] = &[
+    ("GetLastError", 0, |m| m.memory.read_u32(LAST_ERROR)),
+    ("SetLastError", 4, |m| m.set_last_error(m.arg(0)?).map(|_| 0)),
+    ("GetCurrentProcess", 0, |_| Ok(0xFFFF_FFFF)),
+    ("GetCurrentThread", 0, |_| Ok(0xFFFF_FFFE)),
+    ("GetCurrentProcessId", 0, |_| Ok(PROCESS_ID)),
+    ("GetCurrentThreadId", 0, |_| Ok(THREAD_ID)),
+    ("GetTickCount", 0, |m| Ok((m.steps * 1000 / STEPS_PER_SECOND) as u32)),
+    ("GetTickCount64", 0, |m| {
+        let ticks = m.steps * 1000 / STEPS_PER_SECOND;
+        m.cpu.regs[EDX] = (ticks >> 32) as u32;
+        Ok(ticks as u32)
+    }),
+    ("QueryPerformanceCounter", 4, |m| m.memory.write_u64(m.arg(0)?, m.steps).map(|_| 1)),
+    ("QueryPerformanceFrequency", 4, |m| m.memory.write_u64(m.arg(0)?, STEPS_PER_SECOND).map(|_| 1)),
+    ("GetSystemTimeAsFileTime", 4, |m| m.memory.write_u64(m.arg(0)?, m.file_time()).map(|_| 0)),
+    ("GetSystemTime", 4, |m| m.write_system_time(m.arg(0)?).map(|_| 0)),
+    ("GetLocalTime", 4, |m| m.write_system_time(m.arg(0)?).map(|_| 0)),
+    ("GetTimeZoneInformation", 4, |m| m.memory.slice_mut(m.arg(0)?, 172).map(|s| s.fill(0)).map(|_| 0)),
+    ("Sleep", 4, |_| Ok(0)),
+    ("SleepEx", 8, |_| Ok(0)),
+    ("SwitchToThread", 0, |_| Ok(0)),
+    ("GetProcessHeap", 0, |_| Ok(HEAP_HANDLE)),
+    ("HeapCreate", 12, |_| Ok(HEAP_HANDLE)),
+    ("HeapDestroy", 4, |_| Ok(1)),
+    ("HeapAlloc", 12, |m| m.heap_alloc(m.arg(1)?, m.arg(2)?)),
+    ("HeapFree", 12, |m| Ok(m.heap.free(m.arg(2)?).into())),
+    ("HeapReAlloc", 16, |m| m.realloc(m.arg(2)?, m.arg(3)?)),
+    ("HeapSize", 12, |m| Ok(m.heap.size_of(m.arg(2)?).unwrap_or(u32::MAX))),
+    ("HeapValidate", 12, |_| Ok(1)),
+    ("HeapSetInformation", 16, |_| Ok(1)),
+    ("GlobalAlloc", 8, |m| m.heap_alloc(m.arg(0)? >> 3, m.arg(1)?)),
+    ("GlobalFree", 4, |m| Ok(if m.heap.free(m.arg(0)?) { 0 } else { m.arg(0)? })),
+    ("GlobalLock", 4, |m| m.arg(0)),
+    ("GlobalUnlock", 4, |_| Ok(1)),
+    ("LocalAlloc", 8, |m| m.heap_alloc(m.arg(0)? >> 3, m.arg(1)?)),
+    ("LocalFree", 4, |m| Ok(if m.heap.free(m.arg(0)?) { 0 } else { m.arg(0)? })),
+    ("VirtualAlloc", 16, |m| m.virtual_alloc(m.arg(0)?, m.arg(1)?)),
+    ("VirtualFree", 12, |m| Ok(if m.arg(2)? & 0x8000 != 0 { m.memory.unmap(m.arg(0)?).into() } else { 1 })),
+    ("VirtualQuery", 12, |m| m.virtual_query(m.arg(0)?, m.arg(1)?)),
+    ("VirtualProtect", 16, |m| m.memory.write_u32(m.arg(3)?, 0x40).map(|_| 1)),
+    ("TlsAlloc", 0, |m| Ok(m.tls_alloc())),
+    ("TlsFree", 4, |m| Ok(m.tls_free(m.arg(0)?))),
+    ("TlsGetValue", 4, |m| m.tls_get(m.arg(0)?)),
+    ("TlsSetValue", 8, |m| Ok(m.tls_set(m.arg(0)?, m.arg(1)?))),
+    ("FlsAlloc", 4, |m| Ok(m.tls_alloc())),
+    ("FlsFree", 4, |m| Ok(m.tls_free(m.arg(0)?))),
+    ("FlsGetValue", 4, |m| m.tls_get(m.arg(0)?)),
+    ("FlsSetValue", 8, |m| Ok(m.tls_set(m.arg(0)?, m.arg(1)?))),
+    // there's only ever one thread, so locks don't need to do anything
+    ("InitializeCriticalSection", 4, |_| Ok(0)),
+    ("InitializeCriticalSectionAndSpinCount", 8, |_| Ok(1)),
+    ("InitializeCriticalSectionEx", 12, |_| Ok(1)),
+    ("EnterCriticalSection", 4, |_| Ok(0)),
+    ("TryEnterCriticalSection", 4, |_| Ok(1)),
+    ("LeaveCriticalSection", 4, |_| Ok(0)),
+    ("DeleteCriticalSection", 4, |_| Ok(0)),
+    ("InterlockedIncrement", 4, |m| {
+        let value = m.memory.read_u32(m.arg(0)?)?.wrapping_add(1);
+        m.memory.write_u32(m.arg(0)?, value).map(|_| value)
+    }),
+    ("InterlockedDecrement", 4, |m| {
+        let value = m.memory.read_u32(m.arg(0)?)?.wrapping_sub(1);
+        m.memory.write_u32(m.arg(0)?, value).map(|_| value)
+    }),
+    ("InterlockedExchange", 8, |m| {
+        let old = m.memory.read_u32(m.arg(0)?)?;
+        m.memory.write_u32(m.arg(0)?, m.arg(1)?).map(|_| old)
+    }),
+    ("InterlockedExchangeAdd", 8, |m| {
+        let old = m.memory.read_u32(m.arg(0)?)?;
+        m.memory.write_u32(m.arg(0)?, old.wrapping_add(m.arg(1)?)).map(|_| old)
+    }),
+    ("InterlockedCompareExchange", 12, |m| {
+        let old = m.memory.read_u32(m.arg(0)?)?;
+        if old == m.arg(2)? {
+            m.memory.write_u32(m.arg(0)?, m.arg(1)?)?;
+        }
+        Ok(old)
+    }),
+    ("EncodePointer", 4, |m| m.arg(0)),
+    ("DecodePointer", 4, |m| m.arg(0)),
+    ("GetModuleHandleA", 4, |m| match m.arg(0)? {
+        0 => Ok(m.modules.first().map(|m| m.module.base).unwrap_or(0)),
+        name => Ok(m.module_handle(&m.memory.read_cstr(name)?)),
+    }),
+    ("GetModuleHandleW", 4, |m| match m.arg(0)? {
+        0 => Ok(m.modules.first().map(|m| m.module.base).unwrap_or(0)),
+        name => Ok(m.module_handle(String::from_utf16_lossy(&m.memory.read_wstr(name)?).as_bytes())),
+    }),
+    ("GetModuleFileNameA", 12, |m| {
+        let name = m.module_file_name(m.arg(0)?);
+        let len = name.len().min(m.arg(2)?.saturating_sub(1) as usize);
+        m.write_cstr(m.arg(1)?, &name.as_bytes()[..len]).map(|_| len as u32)
+    }),
+    ("GetModuleFileNameW", 12, |m| {
+        let name = m.module_file_name(m.arg(0)?).encode_utf16().collect::<Vec<_>>();
+        let len = name.len().min(m.arg(2)?.saturating_sub(1) as usize);
+        m.write_wstr(m.arg(1)?, &name[..len]).map(|_| len as u32)
+    }),
+    ("GetProcAddress", 8, |m| m.get_proc_address(m.arg(0)?, m.arg(1)?)),
+    ("LoadLibraryA", 4, |m| m.load_library(&m.memory.read_cstr(m.arg(0)?)?)),
+    ("LoadLibraryExA", 12, |m| m.load_library(&m.memory.read_cstr(m.arg(0)?)?)),
+    ("LoadLibraryW", 4, |m| m.load_library(String::from_utf16_lossy(&m.memory.read_wstr(m.arg(0)?)?).as_bytes())),
+    ("LoadLibraryExW", 12, |m| m.load_library(String::from_utf16_lossy(&m.memory.read_wstr(m.arg(0)?)?).as_bytes())),
+    ("FreeLibrary", 4, |_| Ok(1)),
+    ("DisableThreadLibraryCalls", 4, |_| Ok(1)),
+    ("GetCommandLineA", 0, |m| m.static_data("GetCommandLineA", b"game.exe\0")),
+    ("GetCommandLineW", 0, |m| m.static_data("GetCommandLineW", b"g\0a\0m\0e\0.\0e\0x\0e\0\0\0")),
+    ("GetEnvironmentStrings", 0, |m| m.static_data("GetEnvironmentStrings", &[0; 2])),
+    ("GetEnvironmentStringsA", 0, |m| m.static_data("GetEnvironmentStrings", &[0; 2])),
+    ("GetEnvironmentStringsW", 0, |m| m.static_data("GetEnvironmentStringsW", &[0; 4])),
+    ("FreeEnvironmentStringsA", 4, |_| Ok(1)),
+    ("FreeEnvironmentStringsW", 4, |_| Ok(1)),
+    ("GetEnvironmentVariableA", 12, |m| m.set_last_error(ERROR_ENVVAR_NOT_FOUND).map(|_| 0)),
+    ("GetEnvironmentVariableW", 12, |m| m.set_last_error(ERROR_ENVVAR_NOT_FOUND).map(|_| 0)),
+    ("GetStartupInfoA", 4, |m| {
+        m.memory.slice_mut(m.arg(0)?, 68)?.fill(0);
+        m.memory.write_u32(m.arg(0)?, 68).map(|_| 0)
+    }),
+    ("GetStartupInfoW", 4, |m| {
+        m.memory.slice_mut(m.arg(0)?, 68)?.fill(0);
+        m.memory.write_u32(m.arg(0)?, 68).map(|_| 0)
+    }),
+    // the standard handles are there, but anything written to them goes nowhere
+    ("GetStdHandle", 4, |m| m.arg(0)),
+    ("SetStdHandle", 8, |_| Ok(1)),
+    ("GetFileType", 4, |_| Ok(2)),
+    ("SetHandleCount", 4, |m| m.arg(0)),
+    ("WriteFile", 20, |m| {
+        if m.arg(3)? != 0 {
+            m.memory.write_u32(m.arg(3)?, m.arg(2)?)?;
+        }
+        Ok(1)
+    }),
+    ("FlushFileBuffers", 4, |_| Ok(1)),
+    ("CloseHandle", 4, |_| Ok(1)),
+    ("OutputDebugStringA", 4, |_| Ok(0)),
+    ("OutputDebugStringW", 4, |_| Ok(0)),
+    // Windows XP, which is what GM8 games expect to run on
+    ("GetVersion", 0, |_| Ok(0x0A28_0105)),
+    ("GetVersionExA", 4, |m| {
+        let info = m.arg(0)?;
+        for (i, field) in [5, 1, 2600, 2].iter().enumerate() {
+            m.memory.write_u32(info + 4 + i as u32 * 4, *field)?;
+        }
+        m.memory.write_u8(info + 20, 0).map(|_| 1)
+    }),
+    ("GetVersionExW", 4, |m| {
+        let info = m.arg(0)?;
+        for (i, field) in [5, 1, 2600, 2].iter().enumerate() {
+            m.memory.write_u32(info + 4 + i as u32 * 4, *field)?;
+        }
+        m.memory.write_u16(info + 20, 0).map(|_| 1)
+    }),
+    ("GetSystemInfo", 4, |m| {
+        let info = m.arg(0)?;
+        for (i, field) in [0, 0x1000, 0x1_0000, 0x7FFE_FFFF, 1, 1, 586, 0x1_0000, 6].iter().enumerate() {
+            m.memory.write_u32(info + i as u32 * 4, *field)?;
+        }
+        Ok(0)
+    }),
+    ("IsProcessorFeaturePresent", 4, |_| Ok(0)),
+    ("IsDebuggerPresent", 0, |_| Ok(0)),
+    ("SetUnhandledExceptionFilter", 4, |_| Ok(0)),
+    ("UnhandledExceptionFilter", 4, exception),
+    ("RaiseException", 16, raise_exception),
+    ("RtlUnwind", 16, exception),
+    ("ExitProcess", 4, exit),
+    ("ExitThread", 4, exit),
+    ("TerminateProcess", 8, |m| Err(format!("the DLL tried to exit the process with code {}", m.arg(1)? as i32))),
+    ("GetACP", 0, |_| Ok(1252)),
+    ("GetOEMCP", 0, |_| Ok(437)),
+    ("GetCPInfo", 8, |m| {
+        let info = m.arg(1)?;
+        m.memory.slice_mut(info, 20)?.fill(0);
+        m.memory.write_u32(info, 1)?;
+        m.memory.write_u8(info + 4, b'?').map(|_| 1)
+    }),
+    ("IsValidCodePage", 4, |_| Ok(1)),
+    ("IsDBCSLeadByte", 4, |_| Ok(0)),
+    ("GetUserDefaultLCID", 0, |_| Ok(0x409)),
+    ("GetSystemDefaultLCID", 0, |_| Ok(0x409)),
+    ("GetThreadLocale", 0, |_| Ok(0x409)),
+    ("GetLocaleInfoA", 16, |_| Ok(0)),
+    ("GetLocaleInfoW", 16, |_| Ok(0)),
+    ("MultiByteToWideChar", 24, Machine::multi_byte_to_wide_char),
+    ("WideCharToMultiByte", 32, Machine::wide_char_to_multi_byte),
+    ("LCMapStringA", 24, |m| m.lc_map_string(false)),
+    ("LCMapStringW", 24, |m| m.lc_map_string(true)),
+    ("GetStringTypeA", 20, |m| m.get_string_type(1, false)),
+    ("GetStringTypeExA", 20, |m| m.get_string_type(1, false)),
+    ("GetStringTypeW", 16, |m| m.get_string_type(0, true)),
+    ("CompareStringA", 24, |m| m.compare_string(false)),
+    ("CompareStringW", 24, |m| m.compare_string(true)),
+    ("lstrlenA", 4, |m| match m.arg(0)? {
+        0 => Ok(0),
+        string => m.memory.read_cstr(string).map(|s| s.len() as u32),
+    }),
+    ("lstrlenW", 4, |m| match m.arg(0)? {
+        0 => Ok(0),
+        string => m.memory.read_wstr(string).map(|s| s.len() as u32),
+    }),
+    ("lstrcpyA", 8, |m| {
+        let string = m.memory.read_cstr(m.arg(1)?)?;
+        m.write_cstr(m.arg(0)?, &string).and_then(|_| m.arg(0))
+    }),
+    ("lstrcatA", 8, |m| {
+        let (dest, source) = (m.arg(0)?, m.arg(1)?);
+        let (start, string) = (dest + m.memory.read_cstr(dest)?.len() as u32, m.memory.read_cstr(source)?);
+        m.write_cstr(start, &string).map(|_| dest)
+    }),
+    ("lstrcmpA", 8, |m| m.compare_strs(m.arg(0)?, m.arg(1)?, None, false)),
+    ("lstrcmpiA", 8, |m| m.compare_strs(m.arg(0)?, m.arg(1)?, None, true)),
+    ("MulDiv", 12, |m| {
+        let (a, b, c) = (i64::from(m.arg(0)? as i32), i64::from(m.arg(1)? as i32), i64::from(m.arg(2)? as i32));
+        if c == 0 {
+            return Ok(u32::MAX)
+        }
+        let product = a * b;
+        let rounded = if (product < 0) == (c < 0) { (product + c / 2) / c } else { (product - c / 2) / c };
+        Ok(i32::try_from(rounded).map(|r| r as u32).unwrap_or(u32::MAX))
+    }),
+    ("RtlZeroMemory", 8, |m| m.memory.slice_mut(m.arg(0)?, m.arg(1)? as usize).map(|s| s.fill(0)).map(|_| 0)),
+    ("RtlFillMemory", 12, |m| {
+        let value = m.arg(2)? as u8;
+        m.memory.slice_mut(m.arg(0)?, m.arg(1)? as usize).map(|s| s.fill(value)).map(|_| 0)
+    }),
+    ("RtlMoveMemory", 12, |m| m.copy(m.arg(0)?, m.arg(1)?, m.arg(2)?).map(|_| 0)),
+];
+
+static MSVCRT: &[HostEntry] = &[
+    ("malloc", 0, |m| Ok(m.malloc(m.arg(0)?))),
+    ("calloc", 0, |m| m.calloc(m.arg(0)?.saturating_mul(m.arg(1)?))),
+    ("realloc", 0, |m| match m.arg(1)? {
+        0 => Ok(m.heap.free(m.arg(0)?)).map(|_| 0),
+        size => m.realloc(m.arg(0)?, size),
+    }),
+    ("free", 0, |m| Ok(m.heap.free(m.arg(0)?)).map(|_| 0)),
+    ("_msize", 0, |m| Ok(m.heap.size_of(m.arg(0)?).unwrap_or(u32::MAX))),
+    ("??2@YAPAXI@Z", 0, |m| Ok(m.malloc(m.arg(0)?))),
+    ("??_U@YAPAXI@Z", 0, |m| Ok(m.malloc(m.arg(0)?))),
+    ("??3@YAXPAX@Z", 0, |m| Ok(m.heap.free(m.arg(0)?)).map(|_| 0)),
+    ("??_V@YAXPAX@Z", 0, |m| Ok(m.heap.free(m.arg(0)?)).map(|_| 0)),
+    ("memcpy", 0, |m| m.copy(m.arg(0)?, m.arg(1)?, m.arg(2)?).and_then(|_| m.arg(0))),
+    ("memmove", 0, |m| m.copy(m.arg(0)?, m.arg(1)?, m.arg(2)?).and_then(|_| m.arg(0))),
+    ("memset", 0, |m| {
+        let value = m.arg(1)? as u8;
+        if m.arg(2)? != 0 {
+            m.memory.slice_mut(m.arg(0)?, m.arg(2)? as usize)?.fill(value);
+        }
+        m.arg(0)
+    }),
+    ("memcmp", 0, |m| match m.arg(2)? as usize {
+        0 => Ok(0),
+        len => Ok(ordering_int(m.memory.slice(m.arg(0)?, len)?.cmp(m.memory.slice(m.arg(1)?, len)?))),
+    }),
+    ("memchr", 0, |m| match m.arg(2)? as usize {
+        0 => Ok(0),
+        len => {
+            let (addr, value) = (m.arg(0)?, m.arg(1)? as u8);
+            Ok(m.memory.slice(addr, len)?.iter().position(|&b| b == value).map(|i| addr + i as u32).unwrap_or(0))
+        },
+    }),
+    ("strlen", 0, |m| m.memory.read_cstr(m.arg(0)?).map(|s| s.len() as u32)),
+    ("wcslen", 0, |m| m.memory.read_wstr(m.arg(0)?).map(|s| s.len() as u32)),
+    ("strcpy", 0, |m| {
+        let string = m.memory.read_cstr(m.arg(1)?)?;
+        m.write_cstr(m.arg(0)?, &string).and_then(|_| m.arg(0))
+    }),
+    ("strncpy", 0, |m| {
+        let mut string = m.memory.read_cstr(m.arg(1)?)?;
+        string.resize(m.arg(2)? as usize, 0);
+        m.memory.write(m.arg(0)?, &string).and_then(|_| m.arg(0))
+    }),
+    ("strcat", 0, |m| {
+        let (dest, source) = (m.arg(0)?, m.arg(1)?);
+        let (start, string) = (dest + m.memory.read_cstr(dest)?.len() as u32, m.memory.read_cstr(source)?);
+        m.write_cstr(start, &string).map(|_| dest)
+    }),
+    ("strncat", 0, |m| {
+        let (dest, source) = (m.arg(0)?, m.arg(1)?);
+        let start = dest + m.memory.read_cstr(dest)?.len() as u32;
+        let mut string = m.memory.read_cstr(source)?;
+        string.truncate(m.arg(2)? as usize);
+        m.write_cstr(start, &string).map(|_| dest)
+    }),
+    ("strcmp", 0, |m| m.compare_strs(m.arg(0)?, m.arg(1)?, None, false)),
+    ("strncmp", 0, |m| m.compare_strs(m.arg(0)?, m.arg(1)?, Some(m.arg(2)?), false)),
+    ("_stricmp", 0, |m| m.compare_strs(m.arg(0)?, m.arg(1)?, None, true)),
+    ("_strcmpi", 0, |m| m.compare_strs(m.arg(0)?, m.arg(1)?, None, true)),
+    ("stricmp", 0, |m| m.compare_strs(m.arg(0)?, m.arg(1)?, None, true)),
+    ("_strnicmp", 0, |m| m.compare_strs(m.arg(0)?, m.arg(1)?, Some(m.arg(2)?), true)),
+    ("strchr", 0, |m| {
+        let c = m.arg(1)? as u8;
+        // the terminator counts as part of the string
+        m.find_in_str(m.arg(0)?, |s| if c == 0 { Some(s.len()) } else { s.iter().position(|&b| b == c) })
+    }),
+    ("strrchr", 0, |m| {
+        let c = m.arg(1)? as u8;
+        m.find_in_str(m.arg(0)?, |s| if c == 0 { Some(s.len()) } else { s.iter().rposition(|&b| b == c) })
+    }),
+    ("strstr", 0, |m| {
+        let needle = m.memory.read_cstr(m.arg(1)?)?;
+        m.find_in_str(m.arg(0)?, |s| (0..=s.len().saturating_sub(needle.len())).find(|&i| s[i..].starts_with(&needle)))
+    }),
+    ("strpbrk", 0, |m| {
+        let set = m.memory.read_cstr(m.arg(1)?)?;
+        m.find_in_str(m.arg(0)?, |s| s.iter().position(|b| set.contains(b)))
+    }),
+    ("strspn", 0, |m| {
+        let set = m.memory.read_cstr(m.arg(1)?)?;
+        Ok(m.memory.read_cstr(m.arg(0)?)?.iter().take_while(|b| set.contains(b)).count() as u32)
+    }),
+    ("strcspn", 0, |m| {
+        let set = m.memory.read_cstr(m.arg(1)?)?;
+        Ok(m.memory.read_cstr(m.arg(0)?)?.iter().take_while(|b| !set.contains(b)).count() as u32)
+    }),
+    ("strtok", 0, Machine::strtok),
+    ("_strdup", 0, |m| {
+        let string = m.memory.read_cstr(m.arg(0)?)?;
+        let addr = m.malloc(string.len() as u32 + 1);
+        if addr != 0 {
+            m.write_cstr(addr, &string)?;
+        }
+        Ok(addr)
+    }),
+    ("_strlwr", 0, |m| {
+        let mut string = m.memory.read_cstr(m.arg(0)?)?;
+        string.make_ascii_lowercase();
+        m.memory.write(m.arg(0)?, &string).and_then(|_| m.arg(0))
+    }),
+    ("_strupr", 0, |m| {
+        let mut string = m.memory.read_cstr(m.arg(0)?)?;
+        string.make_ascii_uppercase();
+        m.memory.write(m.arg(0)?, &string).and_then(|_| m.arg(0))
+    }),
+    ("toupper", 0, |m| m.arg(0).map(|c| if c < 0x80 { u32::from((c as u8).to_ascii_uppercase()) } else { c })),
+    ("tolower", 0, |m| m.arg(0).map(|c| if c < 0x80 { u32::from((c as u8).to_ascii_lowercase()) } else { c })),
+    ("isalpha", 0, |m| is_ctype(m, 0x103)),
+    ("isupper", 0, |m| is_ctype(m, 0x1)),
+    ("islower", 0, |m| is_ctype(m, 0x2)),
+    ("isdigit", 0, |m| is_ctype(m, 0x4)),
+    ("isxdigit", 0, |m| is_ctype(m, 0x80)),
+    ("isspace", 0, |m| is_ctype(m, 0x8)),
+    ("ispunct", 0, |m| is_ctype(m, 0x10)),
+    ("isalnum", 0, |m| is_ctype(m, 0x107)),
+    ("iscntrl", 0, |m| is_ctype(m, 0x20)),
+    ("isprint", 0, |m| Ok((0x20..0x7F).contains(&m.arg(0)?).into())),
+    ("isgraph", 0, |m| Ok((0x21..0x7F).contains(&m.arg(0)?).into())),
+    ("sprintf", 0, |m| m.sprintf(m.arg(0)?, m.arg(1)?, VarArgs::on_stack(m, 2))),
+    ("vsprintf", 0, |m| m.sprintf(m.arg(0)?, m.arg(1)?, VarArgs(m.arg(2)?))),
+    ("_snprintf", 0, |m| m.snprintf(m.arg(0)?, m.arg(1)?, m.arg(2)?, VarArgs::on_stack(m, 3))),
+    ("_vsnprintf", 0, |m| m.snprintf(m.arg(0)?, m.arg(1)?, m.arg(2)?, VarArgs(m.arg(3)?))),
+    // output goes nowhere
+    ("printf", 0, |m| m.format(m.arg(0)?, VarArgs::on_stack(m, 1)).map(|s| s.len() as u32)),
+    ("vprintf", 0, |m| m.format(m.arg(0)?, VarArgs(m.arg(1)?)).map(|s| s.len() as u32)),
+    ("fprintf", 0, |m| m.format(m.arg(1)?, VarArgs::on_stack(m, 2)).map(|s| s.len() as u32)),
+    ("vfprintf", 0, |m| m.format(m.arg(1)?, VarArgs(m.arg(2)?)).map(|s| s.len() as u32)),
+    ("puts", 0, |_| Ok(0)),
+    ("fputs", 0, |_| Ok(0)),
+    ("fwrite", 0, |m| m.arg(2)),
+    ("fflush", 0, |_| Ok(0)),
+    ("atoi", 0, |m| Ok(parse_integer(&m.memory.read_cstr(m.arg(0)?)?, 10).0 as u32)),
+    ("atol", 0, |m| Ok(parse_integer(&m.memory.read_cstr(m.arg(0)?)?, 10).0 as u32)),
+    ("_atoi64", 0, |m| {
+        let value = parse_integer(&m.memory.read_cstr(m.arg(0)?)?, 10).0;
+        m.cpu.regs[EDX] = (value >> 32) as u32;
+        Ok(value as u32)
+    }),
+    ("atof", 0, |m| {
+        let value = parse_real(&m.memory.read_cstr(m.arg(0)?)?).0;
+        m.return_real(value)
+    }),
+    ("strtod", 0, Machine::strtod),
+    ("strtol", 0, |m| m.strtol(true)),
+    ("strtoul", 0, |m| m.strtol(false)),
+    ("_itoa", 0, |m| {
+        let value = m.arg(0)? as i32;
+        let negative = value < 0 && m.arg(2)? == 10;
+        let magnitude = if negative { u64::from(value.unsigned_abs()) } else { u64::from(value as u32) };
+        m.itoa(magnitude, negative, m.arg(1)?, m.arg(2)?)
+    }),
+    ("_ltoa", 0, |m| {
+        let value = m.arg(0)? as i32;
+        let negative = value < 0 && m.arg(2)? == 10;
+        let magnitude = if negative { u64::from(value.unsigned_abs()) } else { u64::from(value as u32) };
+        m.itoa(magnitude, negative, m.arg(1)?, m.arg(2)?)
+    }),
+    ("_ultoa", 0, |m| m.itoa(m.arg(0)?.into(), false, m.arg(1)?, m.arg(2)?)),
+    ("_gcvt", 0, |m| {
+        let value = m.arg_f64(0)?;
+        let mut text = format_real(value, b'g', m.arg(2)? as usize, false);
+        if value.is_sign_negative() {
+            text.insert(0, '-');
+        }
+        m.write_cstr(m.arg(3)?, text.as_bytes()).and_then(|_| m.arg(3))
+    }),
+    ("abs", 0, |m| Ok((m.arg(0)? as i32).wrapping_abs() as u32)),
+    ("labs", 0, |m| Ok((m.arg(0)? as i32).wrapping_abs() as u32)),
+    ("sin", 0, |m| real1(m, f64::sin)),
+    ("cos", 0, |m| real1(m, f64::cos)),
+    ("tan", 0, |m| real1(m, f64::tan)),
+    ("asin", 0, |m| real1(m, f64::asin)),
+    ("acos", 0, |m| real1(m, f64::acos)),
+    ("atan", 0, |m| real1(m, f64::atan)),
+    ("sinh", 0, |m| real1(m, f64::sinh)),
+    ("cosh", 0, |m| real1(m, f64::cosh)),
+    ("tanh", 0, |m| real1(m, f64::tanh)),
+    ("exp", 0, |m| real1(m, f64::exp)),
+    ("log", 0, |m| real1(m, f64::ln)),
+    ("log10", 0, |m| real1(m, f64::log10)),
+    ("sqrt", 0, |m| real1(m, f64::sqrt)),
+    ("floor", 0, |m| real1(m, f64::floor)),
+    ("ceil", 0, |m| real1(m, f64::ceil)),
+    ("fabs", 0, |m| real1(m, f64::abs)),
+    ("atan2", 0, |m| real2(m, f64::atan2)),
+    ("pow", 0, |m| real2(m, f64::powf)),
+    ("fmod", 0, |m| real2(m, |a, b| a % b)),
+    ("_hypot", 0, |m| real2(m, f64::hypot)),
+    ("hypot", 0, |m| real2(m, f64::hypot)),
+    ("_copysign", 0, |m| real2(m, f64::copysign)),
+    ("_chgsign", 0, |m| real1(m, |x| -x)),
+    ("ldexp", 0, |m| {
+        let value = m.arg_f64(0)? * 2f64.powi(m.arg(2)? as i32);
+        m.return_real(value)
+    }),
+    ("frexp", 0, frexp),
+    ("modf", 0, |m| {
+        let value = m.arg_f64(0)?;
+        m.memory.write_f64(m.arg(2)?, value.trunc())?;
+        m.return_real(value - value.trunc())
+    }),
+    ("_isnan", 0, |m| Ok(m.arg_f64(0)?.is_nan().into())),
+    ("_finite", 0, |m| Ok(m.arg_f64(0)?.is_finite().into())),
+    ("_CIsin", 0, |m| x87_real1(m, f64::sin)),
+    ("_CIcos", 0, |m| x87_real1(m, f64::cos)),
+    ("_CItan", 0, |m| x87_real1(m, f64::tan)),
+    ("_CIasin", 0, |m| x87_real1(m, f64::asin)),
+    ("_CIacos", 0, |m| x87_real1(m, f64::acos)),
+    ("_CIatan", 0, |m| x87_real1(m, f64::atan)),
+    ("_CIsinh", 0, |m| x87_real1(m, f64::sinh)),
+    ("_CIcosh", 0, |m| x87_real1(m, f64::cosh)),
+    ("_CItanh", 0, |m| x87_real1(m, f64::tanh)),
+    ("_CIexp", 0, |m| x87_real1(m, f64::exp)),
+    ("_CIlog", 0, |m| x87_real1(m, f64::ln)),
+    ("_CIlog10", 0, |m| x87_real1(m, f64::log10)),
+    ("_CIsqrt", 0, |m| x87_real1(m, f64::sqrt)),
+    ("_CIatan2", 0, |m| x87_real2(m, f64::atan2)),
+    ("_CIpow", 0, |m| x87_real2(m, f64::powf)),
+    ("_CIfmod", 0, |m| x87_real2(m, |a, b| a % b)),
+    ("_ftol", 0, ftol),
+    ("_ftol2", 0, ftol),
+    ("_ftol2_sse", 0, ftol),
+    ("_controlfp", 0, |_| Ok(0x0009_001F)),
+    ("_control87", 0, |_| Ok(0x0009_001F)),
+    ("_controlfp_s", 0, |_| Ok(0)),
+    ("_clearfp", 0, |_| Ok(0)),
+    ("_statusfp", 0, |_| Ok(0)),
+    ("_fpreset", 0, |m| {
+        m.cpu.fpu.reset();
+        Ok(0)
+    }),
+    // the same generator as msvcrt, so seeded sequences match
+    ("rand", 0, |m| {
+        m.host.rand = m.host.rand.wrapping_mul(214013).wrapping_add(2531011);
+        Ok((m.host.rand >> 16) & 0x7FFF)
+    }),
+    ("srand", 0, |m| {
+        m.host.rand = m.arg(0)?;
+        Ok(0)
+    }),
+    ("time", 0, |m| {
+        let time = m.unix_time() as u32;
+        if m.arg(0)? != 0 {
+            m.memory.write_u32(m.arg(0)?, time)?;
+        }
+        Ok(time)
+    }),
+    ("_time64", 0, |m| {
+        let time = m.unix_time();
+        if m.arg(0)? != 0 {
+            m.memory.write_u64(m.arg(0)?, time)?;
+        }
+        m.cpu.regs[EDX] = (time >> 32) as u32;
+        Ok(time as u32)
+    }),
+    ("clock", 0, |m| Ok((m.steps * 1000 / STEPS_PER_SECOND) as u32)),
+    ("qsort", 0, Machine::qsort),
+    ("_initterm", 0, |m| m.initterm(false)),
+    ("_initterm_e", 0, |m| m.initterm(true)),
+    // the process never exits normally, so there's no need to remember exit handlers
+    ("atexit", 0, |_| Ok(0)),
+    ("_onexit", 0, |m| m.arg(0)),
+    ("__dllonexit", 0, |m| m.arg(0)),
+    ("_lock", 0, |_| Ok(0)),
+    ("_unlock", 0, |_| Ok(0)),
+    ("__set_app_type", 0, |_| Ok(0)),
+    ("__setusermatherr", 0, |_| Ok(0)),
+    ("_set_error_mode", 0, |_| Ok(0)),
+    ("signal", 0, |_| Ok(0)),
+    ("_cexit", 0, |_| Ok(0)),
+    ("_errno", 0, |m| m.static_data("errno", &[0; 4])),
+    ("__doserrno", 0, |m| m.static_data("_doserrno", &[0; 4])),
+    ("__p__fmode", 0, |m| m.static_data("_fmode", &[0; 4])),
+    ("__p__commode", 0, |m| m.static_data("_commode", &[0; 4])),
+    ("__iob_func", 0, |m| m.static_data("_iob", &[0; 32 * 3])),
+    ("abort", 0, |_| Err("the DLL called abort".into())),
+    ("exit", 0, exit),
+    ("_exit", 0, exit),
+    ("_amsg_exit", 0, |m| Err(format!("the C runtime failed with error {}", m.arg(0)?))),
+    ("_purecall", 0, |_| Err("the DLL called a pure virtual function".into())),
+    ("_CxxThrowException", 0, |_| Err("the DLL threw a C++ exception, which the interpreter can't handle".into())),
+    ("__CxxFrameHandler", 0, exception),
+    ("__CxxFrameHandler3", 0, exception),
+    ("_except_handler3", 0, exception),
+    ("_except_handler4_common", 0, exception),
+    ("_XcptFilter", 0, exception),
+    ("raise", 0, exception),
+];
+
+static USER32: &[HostEntry] = &[
+    ("MessageBoxA", 16, |_| Ok(1)),
+    ("MessageBoxW", 16, |_| Ok(1)),
+    ("wsprintfA", 0, |m| m.sprintf(m.arg(0)?, m.arg(1)?, VarArgs::on_stack(m, 2))),
+    ("wvsprintfA", 12, |m| m.sprintf(m.arg(0)?, m.arg(1)?, VarArgs(m.arg(2)?))),
+    ("CharUpperA", 4, |m| m.char_case(true)),
+    ("CharLowerA", 4, |m| m.char_case(false)),
+    ("CharUpperBuffA", 8, |m| m.char_case_buffer(true)),
+    ("CharLowerBuffA", 8, |m| m.char_case_buffer(false)),
+    ("CharNextA", 4, |m| Ok(if m.memory.read_u8(m.arg(0)?)? == 0 { m.arg(0)? } else { m.arg(0)? + 1 })),
+    ("CharPrevA", 8, |m| Ok(if m.arg(1)? > m.arg(0)? { m.arg(1)? - 1 } else { m.arg(0)? })),
+    ("GetSystemMetrics", 4, |_| Ok(0)),
+    ("GetKeyboardType", 4, |m| Ok(match m.arg(0)? {
+        0 => 4,
+        2 => 12,
+        _ => 0,
+    })),
+    ("LoadStringA", 16, |_| Ok(0)),
+    ("LoadStringW", 16, |_| Ok(0)),
+];
+
+// the registry is empty
+static ADVAPI32: &[HostEntry] = &[
+    ("RegOpenKeyExA", 20, |_| Ok(ERROR_FILE_NOT_FOUND)),
+    ("RegOpenKeyExW", 20, |_| Ok(ERROR_FILE_NOT_FOUND)),
+    ("RegQueryValueExA", 24, |_| Ok(ERROR_FILE_NOT_FOUND)),
+    ("RegQueryValueExW", 24, |_| Ok(ERROR_FILE_NOT_FOUND)),
+    ("RegCloseKey", 4, |_| Ok(0)),
+];
+
+static OLEAUT32: &[HostEntry] = &[
+    ("SysAllocString", 4, |m| match m.arg(0)? {
+        0 => Ok(0),
+        source => {
+            let len = m.memory.read_wstr(source)?.len() as u32;
+            m.sys_alloc_string(source, len)
+        },
+    }),
+    ("SysAllocStringLen", 8, |m| m.sys_alloc_string(m.arg(0)?, m.arg(1)?)),
+    ("SysReAllocStringLen", 12, |m| {
+        let new = m.sys_alloc_string(m.arg(1)?, m.arg(2)?)?;
+        let old = m.memory.read_u32(m.arg(0)?)?;
+        m.sys_free_string(old);
+        m.memory.write_u32(m.arg(0)?, new).map(|_| u32::from(new != 0))
+    }),
+    ("SysFreeString", 4, |m| Ok(m.sys_free_string(m.arg(0)?))),
+    ("SysStringLen", 4, |m| match m.arg(0)? {
+        0 => Ok(0),
+        string => m.memory.read_u32(string - 4).map(|len| len / 2),
+    }),
+    ("VariantInit", 4, |m| m.memory.write_u16(m.arg(0)?, 0).map(|_| 0)),
+    ("VariantClear", 4, |m| m.memory.write_u16(m.arg(0)?, 0).map(|_| 0)),
+];
+
+static OLEAUT32_ORDINALS: &[(u16, &str)] = &[
+    (2, "SysAllocString"),
+    (4, "SysAllocStringLen"),
+    (5, "SysReAllocStringLen"),
+    (6, "SysFreeString"),
+    (7, "SysStringLen"),
+    (8, "VariantInit"),
+    (9, "VariantClear"),
+];
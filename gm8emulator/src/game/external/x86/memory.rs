@@ -0,0 +1,234 @@
+//! The address space interpreted DLLs live in, and the heap they allocate from.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Region {
+    base: u32,
+    data: Vec<u8>,
+}
+
+impl Region {
+    fn end(&self) -> u64 {
+        u64::from(self.base) + self.data.len() as u64
+    }
+}
+
+/// A sparse 32-bit address space made of separately mapped regions.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Memory {
+    regions: Vec<Region>,
+}
+
+fn fault(addr: u32) -> String {
+    format!("access violation at {:#010x}", addr)
+}
+
+impl Memory {
+    /// Maps zeroed memory at the given address, as long as it doesn't overlap anything already mapped.
+    pub fn map(&mut self, base: u32, size: u32) -> Result<(), String> {
+        if size == 0 || u64::from(base) + u64::from(size) > 1 << 32 || !self.is_free(base, size) {
+            return Err(format!("couldn't map {:#x} bytes at {:#010x}", size, base))
+        }
+        self.regions.push(Region { base, data: vec![0; size as usize] });
+        Ok(())
+    }
+
+    /// Unmaps the region starting at the given address, returning whether there was one.
+    pub fn unmap(&mut self, base: u32) -> bool {
+        let count = self.regions.len();
+        self.regions.retain(|r| r.base != base);
+        self.regions.len() != count
+    }
+
+    /// Extends the region starting at the given address, as long as there's room after it.
+    pub fn grow(&mut self, base: u32, size: u32) -> bool {
+        let (index, old_size) = match self.regions.iter().position(|r| r.base == base) {
+            Some(index) => (index, self.regions[index].data.len() as u32),
+            None => return false,
+        };
+        if size <= old_size {
+            return true
+        }
+        if u64::from(base) + u64::from(size) > 1 << 32 || !self.is_free(base + old_size, size - old_size) {
+            return false
+        }
+        self.regions[index].data.resize(size as usize, 0);
+        true
+    }
+
+    pub fn is_free(&self, base: u32, size: u32) -> bool {
+        let end = u64::from(base) + u64::from(size);
+        self.regions.iter().all(|r| end <= u64::from(r.base) || u64::from(base) >= r.end())
+    }
+
+    /// Finds a 64K-aligned address at or after `from` where the given amount of memory could be mapped.
+    pub fn find_free(&self, from: u32, size: u32) -> Option<u32> {
+        let mut base = u64::from(from);
+        while base + u64::from(size) <= 1 << 32 {
+            let end = base + u64::from(size);
+            match self.regions.iter().find(|r| end > u64::from(r.base) && base < r.end()) {
+                Some(region) => base = (region.end() + 0xFFFF) & !0xFFFF,
+                None => return Some(base as u32),
+            }
+        }
+        None
+    }
+
+    /// The base and size of the region an address is in.
+    pub fn region_of(&self, addr: u32) -> Option<(u32, u32)> {
+        self.regions
+            .iter()
+            .find(|r| addr >= r.base && u64::from(addr) < r.end())
+            .map(|r| (r.base, r.data.len() as u32))
+    }
+
+    fn locate(&self, addr: u32, len: usize) -> Option<(usize, usize)> {
+        self.regions.iter().enumerate().find_map(|(i, r)| {
+            let offset = addr.checked_sub(r.base)? as usize;
+            Some((i, offset)).filter(|_| offset + len <= r.data.len())
+        })
+    }
+
+    pub fn slice(&self, addr: u32, len: usize) -> Result<&[u8], String> {
+        let (i, offset) = self.locate(addr, len).ok_or_else(|| fault(addr))?;
+        Ok(&self.regions[i].data[offset..offset + len])
+    }
+
+    pub fn slice_mut(&mut self, addr: u32, len: usize) -> Result<&mut [u8], String> {
+        let (i, offset) = self.locate(addr, len).ok_or_else(|| fault(addr))?;
+        Ok(&mut self.regions[i].data[offset..offset + len])
+    }
+
+    pub fn read<const N: usize>(&self, addr: u32) -> Result<[u8; N], String> {
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(self.slice(addr, N)?);
+        Ok(bytes)
+    }
+
+    pub fn write(&mut self, addr: u32, bytes: &[u8]) -> Result<(), String> {
+        self.slice_mut(addr, bytes.len())?.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    pub fn read_u8(&self, addr: u32) -> Result<u8, String> {
+        Ok(self.read::<1>(addr)?[0])
+    }
+
+    pub fn read_u16(&self, addr: u32) -> Result<u16, String> {
+        self.read(addr).map(u16::from_le_bytes)
+    }
+
+    pub fn read_u32(&self, addr: u32) -> Result<u32, String> {
+        self.read(addr).map(u32::from_le_bytes)
+    }
+
+    pub fn read_u64(&self, addr: u32) -> Result<u64, String> {
+        self.read(addr).map(u64::from_le_bytes)
+    }
+
+    pub fn read_f32(&self, addr: u32) -> Result<f32, String> {
+        self.read(addr).map(f32::from_le_bytes)
+    }
+
+    pub fn read_f64(&self, addr: u32) -> Result<f64, String> {
+        self.read(addr).map(f64::from_le_bytes)
+    }
+
+    pub fn write_u8(&mut self, addr: u32, value: u8) -> Result<(), String> {
+        self.write(addr, &[value])
+    }
+
+    pub fn write_u16(&mut self, addr: u32, value: u16) -> Result<(), String> {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    pub fn write_u32(&mut self, addr: u32, value: u32) -> Result<(), String> {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    pub fn write_u64(&mut self, addr: u32, value: u64) -> Result<(), String> {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    pub fn write_f32(&mut self, addr: u32, value: f32) -> Result<(), String> {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    pub fn write_f64(&mut self, addr: u32, value: f64) -> Result<(), String> {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    /// Reads a null-terminated string, not including the terminator.
+    pub fn read_cstr(&self, addr: u32) -> Result<Vec<u8>, String> {
+        let (i, offset) = self.locate(addr, 1).ok_or_else(|| fault(addr))?;
+        let data = &self.regions[i].data[offset..];
+        match data.iter().position(|&b| b == 0) {
+            Some(len) => Ok(data[..len].to_vec()),
+            None => Err(fault(addr.wrapping_add(data.len() as u32))),
+        }
+    }
+
+    /// Reads a null-terminated UTF-16 string, not including the terminator.
+    pub fn read_wstr(&self, addr: u32) -> Result<Vec<u16>, String> {
+        let mut string = Vec::new();
+        loop {
+            match self.read_u16(addr.wrapping_add(string.len() as u32 * 2))? {
+                0 => break Ok(string),
+                c => string.push(c),
+            }
+        }
+    }
+}
+
+/// A first-fit allocator handing out 16-byte aligned blocks of one mapped region.
+/// The region starts small and grows when it needs to, up to a limit, so savestates don't get bloated.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Heap {
+    base: u32,
+    limit: u32,
+    blocks: BTreeMap<u32, u32>,
+}
+
+impl Heap {
+    pub const INITIAL_SIZE: u32 = 0x10_0000;
+
+    /// Makes a heap out of a region which must already be mapped with the initial size.
+    pub fn new(base: u32, limit: u32) -> Self {
+        Self { base, limit, blocks: BTreeMap::new() }
+    }
+
+    pub fn alloc(&mut self, memory: &mut Memory, size: u32) -> Option<u32> {
+        let needed = u64::from(size.max(1));
+        let mut cursor = u64::from(self.base);
+        for (&addr, &len) in &self.blocks {
+            if u64::from(addr).saturating_sub(cursor) >= needed {
+                break
+            }
+            cursor = (u64::from(addr) + u64::from(len.max(1)) + 15) & !15;
+        }
+        let end = cursor + needed - u64::from(self.base);
+        if end > u64::from(self.limit) {
+            return None
+        }
+        let (_, mapped) = memory.region_of(self.base)?;
+        if end > u64::from(mapped) {
+            let new_size = end.max(u64::from(mapped) * 2).min(u64::from(self.limit));
+            if !memory.grow(self.base, new_size as u32) {
+                return None
+            }
+        }
+        self.blocks.insert(cursor as u32, size);
+        Some(cursor as u32)
+    }
+
+    /// Frees a block, returning whether it was actually allocated.
+    pub fn free(&mut self, addr: u32) -> bool {
+        self.blocks.remove(&addr).is_some()
+    }
+
+    pub fn size_of(&self, addr: u32) -> Option<u32> {
+        self.blocks.get(&addr).copied()
+    }
+}
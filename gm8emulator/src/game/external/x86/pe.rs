@@ -0,0 +1,204 @@
+//! Maps 32-bit PE images into an interpreter's address space.
+
+use super::memory::Memory;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
+
+const DIRECTORY_EXPORT: u32 = 0;
+const DIRECTORY_IMPORT: u32 = 1;
+const DIRECTORY_RELOCATION: u32 = 5;
+
+/// Where images go if their preferred base is taken or they don't have one.
+const FALLBACK_BASE: u32 = 0x1000_0000;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ImportName {
+    Name(String),
+    Ordinal(u16),
+}
+
+impl fmt::Display for ImportName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Name(name) => write!(f, "{}", name),
+            Self::Ordinal(ordinal) => write!(f, "#{}", ordinal),
+        }
+    }
+}
+
+/// An entry in an image's import address table, which needs to be filled in with the address of what it imports.
+pub struct Import {
+    pub dll: String,
+    pub name: ImportName,
+    pub slot: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Module {
+    pub name: String,
+    pub base: u32,
+    pub entry: Option<u32>,
+    exports: HashMap<String, u32>,
+    ordinals: HashMap<u16, u32>,
+}
+
+impl Module {
+    pub fn export(&self, name: &ImportName) -> Option<u32> {
+        match name {
+            ImportName::Name(name) => self.exports.get(name).copied(),
+            ImportName::Ordinal(ordinal) => self.ordinals.get(ordinal).copied(),
+        }
+    }
+}
+
+struct Image<'a>(&'a [u8]);
+
+impl Image<'_> {
+    fn bytes(&self, offset: u32, len: u32) -> Result<&[u8], String> {
+        let start = offset as usize;
+        self.0.get(start..start + len as usize).ok_or_else(|| "image is truncated".to_string())
+    }
+
+    fn u16(&self, offset: u32) -> Result<u16, String> {
+        self.bytes(offset, 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&self, offset: u32) -> Result<u32, String> {
+        self.bytes(offset, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+/// Maps an image, relocating it if it can't go at its preferred base, and returns its import table for the caller
+/// to resolve. The entry point isn't run.
+pub fn load(memory: &mut Memory, name: &str, data: &[u8]) -> Result<(Module, Vec<Import>), String> {
+    let image = Image(data);
+    if image.bytes(0, 2)? != b"MZ" {
+        return Err("not an executable".into())
+    }
+    let pe = image.u32(0x3C)?;
+    if image.bytes(pe, 4)? != b"PE\0\0" {
+        return Err("not a PE executable".into())
+    }
+    let coff = pe + 4;
+    if image.u16(coff)? != 0x14C {
+        return Err("not a 32-bit x86 executable".into())
+    }
+    let section_count = u32::from(image.u16(coff + 2)?);
+    let optional = coff + 20;
+    let sections = optional + u32::from(image.u16(coff + 16)?);
+    if image.u16(optional)? != 0x10B {
+        return Err("not a PE32 executable".into())
+    }
+    let entry = image.u32(optional + 16)?;
+    let preferred_base = image.u32(optional + 28)?;
+    let image_size = image.u32(optional + 56)?;
+    let headers_size = image.u32(optional + 60)?;
+    let directory_count = image.u32(optional + 92)?;
+    let directory = |index: u32| -> Result<Option<(u32, u32)>, String> {
+        if index >= directory_count {
+            return Ok(None)
+        }
+        let rva = image.u32(optional + 96 + index * 8)?;
+        let size = image.u32(optional + 100 + index * 8)?;
+        Ok(if rva != 0 && size != 0 { Some((rva, size)) } else { None })
+    };
+    let relocations = directory(DIRECTORY_RELOCATION)?;
+
+    let base = if preferred_base != 0 && memory.is_free(preferred_base, image_size) {
+        preferred_base
+    } else if relocations.is_some() {
+        memory.find_free(FALLBACK_BASE, image_size).ok_or("no room to load image")?
+    } else {
+        return Err(format!("image can't be relocated from {:#010x}, and that address is taken", preferred_base))
+    };
+    memory.map(base, image_size)?;
+    memory.write(base, image.bytes(0, headers_size.min(image_size).min(data.len() as u32))?)?;
+    for section in (0..section_count).map(|i| sections + i * 40) {
+        let address = image.u32(section + 12)?;
+        let raw_size = image.u32(section + 16)?;
+        let raw_offset = image.u32(section + 20)?;
+        let len = raw_size.min(image_size.saturating_sub(address)).min((data.len() as u32).saturating_sub(raw_offset));
+        if len > 0 {
+            memory.write(base + address, image.bytes(raw_offset, len)?)?;
+        }
+    }
+
+    if let Some((rva, size)) = relocations.filter(|_| base != preferred_base) {
+        let delta = base.wrapping_sub(preferred_base);
+        let mut block = base + rva;
+        while block < base + rva + size {
+            let page = memory.read_u32(block)?;
+            let block_size = memory.read_u32(block + 4)?;
+            if block_size < 8 {
+                break
+            }
+            for entry in (block + 8..block + block_size).step_by(2) {
+                let entry = memory.read_u16(entry)?;
+                let addr = base + page + u32::from(entry & 0xFFF);
+                match entry >> 12 {
+                    0 => (),
+                    3 => memory.write_u32(addr, memory.read_u32(addr)?.wrapping_add(delta))?,
+                    kind => return Err(format!("unsupported relocation type {}", kind)),
+                }
+            }
+            block += block_size;
+        }
+    }
+
+    let mut exports = HashMap::new();
+    let mut ordinals = HashMap::new();
+    if let Some((rva, size)) = directory(DIRECTORY_EXPORT)? {
+        let table = base + rva;
+        let ordinal_base = memory.read_u32(table + 16)?;
+        let function_count = memory.read_u32(table + 20)?;
+        let name_count = memory.read_u32(table + 24)?;
+        let functions = base + memory.read_u32(table + 28)?;
+        let names = base + memory.read_u32(table + 32)?;
+        let name_ordinals = base + memory.read_u32(table + 36)?;
+        // anything pointing back into the export table is forwarded to another DLL, which isn't supported
+        let is_code = |function: u32| function != 0 && !(rva..rva + size).contains(&function);
+        for i in 0..function_count {
+            let function = memory.read_u32(functions + i * 4)?;
+            if is_code(function) {
+                ordinals.insert((ordinal_base + i) as u16, base + function);
+            }
+        }
+        for i in 0..name_count {
+            let name = memory.read_cstr(base + memory.read_u32(names + i * 4)?)?;
+            let index = u32::from(memory.read_u16(name_ordinals + i * 2)?);
+            let function = memory.read_u32(functions + index * 4)?;
+            if is_code(function) {
+                exports.insert(String::from_utf8_lossy(&name).into_owned(), base + function);
+            }
+        }
+    }
+
+    let mut imports = Vec::new();
+    if let Some((rva, _)) = directory(DIRECTORY_IMPORT)? {
+        for descriptor in (base + rva..).step_by(20) {
+            let lookup = memory.read_u32(descriptor)?;
+            let dll = memory.read_u32(descriptor + 12)?;
+            let slots = memory.read_u32(descriptor + 16)?;
+            if dll == 0 || slots == 0 {
+                break
+            }
+            let dll = String::from_utf8_lossy(&memory.read_cstr(base + dll)?).into_owned();
+            let lookup = base + if lookup != 0 { lookup } else { slots };
+            for i in 0.. {
+                let entry = memory.read_u32(lookup + i * 4)?;
+                if entry == 0 {
+                    break
+                }
+                let name = if entry & 0x8000_0000 != 0 {
+                    ImportName::Ordinal(entry as u16)
+                } else {
+                    ImportName::Name(String::from_utf8_lossy(&memory.read_cstr(base + entry + 2)?).into_owned())
+                };
+                imports.push(Import { dll: dll.clone(), name, slot: base + slots + i * 4 });
+            }
+        }
+    }
+
+    let entry = if entry != 0 { Some(base + entry) } else { None };
+    Ok((Module { name: name.into(), base, entry, exports, ordinals }, imports))
+}
@@ -0,0 +1,280 @@
+use crate::{
+    game::{
+        dialog::{self, DialogInput, MessageFont, Modal, Rect},
+        draw, replay, Game, GetAsset,
+    },
+    gml::{self, Value},
+    input::Button,
+    math::Real,
+    types::ID,
+};
+use serde::{Deserialize, Serialize};
+
+/// The number of places in the highscore table.
+pub const PLACES: usize = 10;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HighscoreEntry {
+    /// The player's name. Empty places have an empty name, and are shown with the "nobody" string instead.
+    pub name: gml::String,
+    pub score: i32,
+}
+
+/// How the highscore table looks when it's shown, which can be changed with the highscore_set_* functions.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HighscoreSettings {
+    pub background: ID,
+    pub border: bool,
+    pub font: MessageFont,
+    pub back_colour: i32,
+    pub new_colour: i32,
+    pub other_colour: i32,
+    pub caption: gml::String,
+    pub nobody: gml::String,
+    pub escape: gml::String,
+}
+
+/// The game's highscore table. It's part of the game state, so it's included in savestates.
+/// It isn't saved to disk, since GM8's highscore file format isn't implemented, so it starts empty every time.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Highscores {
+    entries: Vec<HighscoreEntry>,
+    pub settings: HighscoreSettings,
+}
+
+impl Default for HighscoreSettings {
+    fn default() -> Self {
+        Self {
+            background: -1,
+            border: true,
+            font: MessageFont { name: "Times New Roman".into(), size: 10, colour: 0, style: 0, charset: 1 },
+            back_colour: 0xFFFFFF,
+            new_colour: 0x0000FF,
+            other_colour: 0x000000,
+            caption: "Top Ten Players".into(),
+            nobody: "<nobody>".into(),
+            escape: "press <Escape> to close".into(),
+        }
+    }
+}
+
+impl Highscores {
+    pub fn new() -> Self {
+        let mut highscores = Self { entries: Vec::with_capacity(PLACES), settings: Default::default() };
+        highscores.clear();
+        highscores
+    }
+
+    /// Empties every place in the table. The settings aren't changed.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.entries.resize(PLACES, HighscoreEntry { name: "".into(), score: 0 });
+    }
+
+    /// Gets the entry in the given place, counting from 1.
+    pub fn get(&self, place: i32) -> Option<&HighscoreEntry> {
+        usize::try_from(place).ok().and_then(|p| p.checked_sub(1)).and_then(|p| self.entries.get(p))
+    }
+
+    /// Finds the index a score would be placed at, or None if it isn't high enough to get in the table.
+    pub fn place_for(&self, score: i32) -> Option<usize> {
+        self.entries.iter().position(|e| score > e.score)
+    }
+
+    /// Adds an entry to the table if the score is high enough, returning the index it was placed at.
+    pub fn add(&mut self, name: gml::String, score: i32) -> Option<usize> {
+        let index = self.place_for(score)?;
+        self.entries.insert(index, HighscoreEntry { name, score });
+        self.entries.truncate(PLACES);
+        Some(index)
+    }
+
+    /// Gets an entry's name, or the "nobody" string if the place is empty.
+    fn display_name(&self, entry: &HighscoreEntry) -> gml::String {
+        if entry.name.as_ref().is_empty() { self.settings.nobody.clone() } else { entry.name.clone() }
+    }
+}
+
+/// The highscore table as shown by highscore_show, optionally with a new entry that the player types a name into.
+/// The result is the name that was typed in, or an empty string if there was no new entry.
+struct HighscoreTable {
+    entries: Vec<(gml::String, i32)>,
+    new_entry: Option<usize>,
+    name: String,
+    entering: bool,
+}
+
+impl HighscoreTable {
+    fn layout(&self, game: &mut Game) -> (Rect, i32) {
+        let settings = &game.highscores.settings;
+        let (font, caption, escape) = (settings.font.clone(), settings.caption.clone(), settings.escape.clone());
+        let (screen_w, screen_h) = (game.unscaled_width as i32, game.unscaled_height as i32);
+        let line_h = game.dialog_text_size(&font, &"|".into(), None).1 + 4;
+        let rows_w = self
+            .entries
+            .iter()
+            .map(|(name, score)| {
+                let score_w = game.dialog_text_size(&font, &score.to_string().into(), None).0;
+                game.dialog_text_size(&font, name, None).0 + score_w
+            })
+            .chain([game.dialog_text_size(&font, &caption, None).0, game.dialog_text_size(&font, &escape, None).0])
+            .max()
+            .unwrap_or(0);
+        let width = (rows_w + dialog::PADDING * 6).max(dialog::MIN_WIDTH).min(screen_w);
+        let height = (line_h * (self.entries.len() as i32 + 3) + dialog::PADDING * 2).min(screen_h);
+        (((screen_w - width) / 2, (screen_h - height) / 2, width, height), line_h)
+    }
+}
+
+impl Modal for HighscoreTable {
+    type Output = String;
+
+    fn handle(&mut self, _game: &mut Game, input: DialogInput) -> Option<String> {
+        match input {
+            DialogInput::Key(vk) if vk == Button::Escape as u8 => return Some(std::mem::take(&mut self.name)),
+            DialogInput::Key(vk) if vk == Button::Return as u8 => {
+                if !self.entering {
+                    return Some(std::mem::take(&mut self.name))
+                }
+                self.entering = false;
+            },
+            DialogInput::Key(vk) if vk == Button::Backspace as u8 && self.entering => {
+                self.name.pop();
+            },
+            DialogInput::Char(c) if self.entering && !c.is_control() => self.name.push(c),
+            DialogInput::MouseUp(..) if !self.entering => return Some(std::mem::take(&mut self.name)),
+            DialogInput::Close => return Some(std::mem::take(&mut self.name)),
+            _ => (),
+        }
+        None
+    }
+
+    fn draw(&self, game: &mut Game) {
+        let settings = game.highscores.settings.clone();
+        let ((x, y, w, h), line_h) = self.layout(game);
+
+        let background = game
+            .assets
+            .backgrounds
+            .get_asset(settings.background)
+            .and_then(|b| b.atlas_ref.map(|a| (a, b.width.max(1), b.height.max(1))));
+        match background {
+            Some((atlas_ref, bg_w, bg_h)) => {
+                let (xscale, yscale) = (f64::from(w) / f64::from(bg_w), f64::from(h) / f64::from(bg_h));
+                game.renderer.draw_sprite(atlas_ref, x.into(), y.into(), xscale, yscale, 0.0, 0xFFFFFF, 1.0);
+            },
+            None => dialog::draw_box(game, (x, y, w, h), settings.back_colour, settings.back_colour, 1.0),
+        }
+        if settings.border {
+            let (x1, y1, x2, y2) = (f64::from(x), f64::from(y), f64::from(x + w - 1), f64::from(y + h - 1));
+            game.renderer.draw_rectangle_outline(x1, y1, x2, y2, dialog::COLOUR_BORDER, 1.0);
+        }
+
+        let centred = |game: &mut Game, font: &MessageFont, text: &gml::String, row_y: i32| {
+            let text_w = game.dialog_text_size(font, text, None).0;
+            game.dialog_draw_text(font, x + (w - text_w) / 2, row_y, text, None, 1.0);
+        };
+        let other_font = MessageFont { colour: settings.other_colour, ..settings.font.clone() };
+        let new_font = MessageFont { colour: settings.new_colour, ..settings.font.clone() };
+        centred(game, &other_font, &settings.caption, y + dialog::PADDING);
+
+        let (left, right) = (x + dialog::PADDING * 3, x + w - dialog::PADDING * 3);
+        for (i, (name, score)) in self.entries.iter().enumerate() {
+            let row_y = y + dialog::PADDING + line_h * (i as i32 + 2);
+            let font = if self.new_entry == Some(i) { &new_font } else { &other_font };
+            let name = match self.new_entry {
+                Some(new) if new == i && self.entering => game.dialog_string(&format!("{}|", self.name)),
+                Some(new) if new == i => game.dialog_string(&self.name),
+                _ => name.clone(),
+            };
+            let score = gml::String::from(score.to_string().as_str());
+            let score_w = game.dialog_text_size(font, &score, None).0;
+            game.dialog_draw_text(font, left, row_y, &name, None, 1.0);
+            game.dialog_draw_text(font, right - score_w, row_y, &score, None, 1.0);
+        }
+
+        if !self.entering {
+            centred(game, &other_font, &settings.escape, y + h - dialog::PADDING - line_h);
+        }
+    }
+}
+
+impl Game {
+    /// Gets the name in a place in the table, counting from 1. Empty places give the "nobody" string.
+    pub fn highscore_name_at(&self, place: i32) -> Value {
+        match self.highscores.get(place) {
+            Some(entry) => self.highscores.display_name(entry).into(),
+            None => "".into(),
+        }
+    }
+
+    /// Shows the highscore table over the game window. If the score is high enough to get in,
+    /// the player types their name into the table first. The name typed in is stored in the replay.
+    pub fn highscore_show_table(&mut self, score: i32) -> gml::Result<()> {
+        let new_entry = self.highscores.place_for(score);
+        let name = self.dialog_result(
+            "highscore_show",
+            |game| {
+                let mut entries = game
+                    .highscores
+                    .entries
+                    .iter()
+                    .map(|e| (game.highscores.display_name(e), e.score))
+                    .collect::<Vec<_>>();
+                if let Some(index) = new_entry {
+                    entries.insert(index, ("".into(), score));
+                    entries.truncate(PLACES);
+                }
+                let mut table =
+                    HighscoreTable { entries, new_entry, name: String::new(), entering: new_entry.is_some() };
+                let name = game.run_modal(&mut table);
+                game.dialog_string(&name).into()
+            },
+            replay::Event::HighscoreName,
+            |ev| if let replay::Event::HighscoreName(v) = ev { Some(v) } else { None },
+        )?;
+        if new_entry.is_some() {
+            self.highscores.add(name.repr(), score);
+        }
+        Ok(())
+    }
+
+    /// Adds the current score to the table, asking the player for their name if it's high enough to get in.
+    pub fn highscore_add_score(&mut self) -> gml::Result<()> {
+        let score = self.score;
+        if self.highscores.place_for(score).is_some() {
+            let name = self.dialog_result(
+                "highscore_add_current",
+                |game| {
+                    let caption = game.dialog_caption();
+                    match game.dialog_input(caption, "Please enter your name.".into(), String::new()) {
+                        Some(name) => game.dialog_string(&name).into(),
+                        None => "".into(),
+                    }
+                },
+                replay::Event::HighscoreName,
+                |ev| if let replay::Event::HighscoreName(v) = ev { Some(v) } else { None },
+            )?;
+            self.highscores.add(name.repr(), score);
+        }
+        Ok(())
+    }
+
+    /// Draws the highscore table in a region with the current font and colour,
+    /// with the names on the left and the scores on the right.
+    pub fn draw_highscore_table(&mut self, x1: Real, y1: Real, x2: Real, y2: Real) {
+        let row_height = (y2 - y1) / Real::from(PLACES as f64);
+        let old_halign = self.draw_halign;
+        let rows =
+            self.highscores.entries.iter().map(|e| (self.highscores.display_name(e), e.score)).collect::<Vec<_>>();
+        for (i, (name, score)) in rows.into_iter().enumerate() {
+            let y = y1 + row_height * Real::from(i as f64);
+            let alpha = self.draw_alpha;
+            self.draw_halign = draw::Halign::Left;
+            self.draw_string(x1, y, name, None, None, 1.into(), 1.into(), 0.into(), None, alpha);
+            self.draw_halign = draw::Halign::Right;
+            self.draw_string(x2, y, score.to_string().into(), None, None, 1.into(), 1.into(), 0.into(), None, alpha);
+        }
+        self.draw_halign = old_halign;
+    }
+}
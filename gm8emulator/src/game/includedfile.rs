@@ -1,3 +1,4 @@
+use crate::{game::Game, gml::vfs::FileSystem};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
@@ -20,25 +21,34 @@ pub enum ExportSetting {
 }
 
 impl IncludedFile {
-    pub fn export(&mut self, temp_directory: PathBuf, program_directory: PathBuf) -> std::io::Result<()> {
+    pub fn export(
+        &mut self,
+        vfs: &FileSystem,
+        temp_directory: PathBuf,
+        program_directory: PathBuf,
+    ) -> std::io::Result<()> {
         if self.data.is_some() {
-            if let Some(mut export_path) = match self.export_settings.clone() {
+            // The temp folder is made by the emulator and deleted afterwards, and games often export DLLs there
+            // which have to be loaded from the host's disk, so it's written to directly even when sandboxed.
+            let host = FileSystem::new(temp_directory.clone());
+            if let Some((vfs, mut export_path)) = match self.export_settings.clone() {
                 ExportSetting::NoExport => None,
-                ExportSetting::TempFolder => Some(temp_directory),
-                ExportSetting::GameFolder => Some(program_directory),
-                ExportSetting::CustomFolder(dir) => Some(dir.clone().into()),
+                ExportSetting::TempFolder => Some((&host, temp_directory)),
+                ExportSetting::GameFolder => Some((vfs, program_directory)),
+                ExportSetting::CustomFolder(dir) => Some((vfs, dir.clone().into())),
             } {
                 export_path.push(&self.name);
-                self.export_to(&export_path)?;
+                self.export_to(vfs, &export_path)?;
             }
         }
         Ok(())
     }
 
-    pub fn export_to(&mut self, path: &Path) -> std::io::Result<()> {
+    pub fn export_to(&mut self, vfs: &FileSystem, path: &Path) -> std::io::Result<()> {
         if let Some(data) = self.data.as_ref() {
-            if self.overwrite || !path.exists() {
-                std::fs::write(path, &data)?;
+            let path = path.to_string_lossy();
+            if self.overwrite || !vfs.file_exists(&path) {
+                vfs.write(&path, data)?;
             }
             if self.free_after_export {
                 self.data = None;
@@ -47,3 +57,16 @@ impl IncludedFile {
         Ok(())
     }
 }
+
+impl Game {
+    /// Exports the included files which are set to be exported when the game starts. This should be done once the
+    /// filesystem sandbox has been set up, so that they're written into it.
+    pub fn export_included_files(&mut self) -> std::io::Result<()> {
+        let temp_directory = PathBuf::from(self.decode_str(self.temp_directory.as_ref()).into_owned());
+        let program_directory = PathBuf::from(self.decode_str(self.program_directory.as_ref()).into_owned());
+        for file in self.included_files.iter_mut() {
+            file.export(&self.vfs, temp_directory.clone(), program_directory.clone())?;
+        }
+        Ok(())
+    }
+}
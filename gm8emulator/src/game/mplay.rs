@@ -0,0 +1,672 @@
+use crate::{
+    game::{external::ResultMode, Game},
+    gml::{self, Value},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::{self, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The port sessions are hosted and looked for on, unless the address given to mplay_init_tcpip has one.
+/// It's the one DirectPlay used for finding sessions.
+pub const DEFAULT_PORT: u16 = 47624;
+
+/// The highest index mplay_data_write and mplay_data_read can use.
+pub const DATA_LIMIT: u32 = 10000;
+
+/// What results from the network are stored under in replays, where a real DLL's name would go.
+const REPLAY_NAME: &str = "mplay";
+
+const FIND_TIMEOUT: Duration = Duration::from_millis(500);
+const JOIN_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_PACKET_SIZE: usize = 1 << 24;
+
+/// What goes between instances. Sessions are found over UDP, and everything else goes over a TCP connection to the
+/// host, which passes things on between the other players.
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    FindSessions,
+    Session { name: Vec<u8>, players: u32, max_players: u32 },
+    Join { name: Vec<u8> },
+    Welcome { id: u32, players: Vec<(u32, Vec<u8>)>, data: Vec<(u32, Value)> },
+    Refused,
+    PlayerJoined { id: u32, name: Vec<u8> },
+    PlayerLeft { id: u32 },
+    /// A message from one player to another. `to` is 0 for everyone but the sender.
+    Message { from: u32, to: u32, id: i32, value: Value },
+    Data { index: u32, value: Value },
+}
+
+/// A TCP connection carrying length-prefixed packets, which is read from without blocking.
+struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self { stream, incoming: Vec::new() })
+    }
+
+    fn send(&mut self, packet: &Packet) -> io::Result<()> {
+        let body = bincode::serialize(packet).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut bytes = (body.len() as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(&body);
+        let mut written = 0;
+        while written < bytes.len() {
+            match self.stream.write(&bytes[written..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(count) => written += count,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(1)),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads whatever packets have arrived, and whether the connection is still open.
+    fn receive(&mut self) -> (Vec<Packet>, bool) {
+        let mut open = true;
+        let mut buf = [0u8; 4096];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    open = false;
+                    break
+                },
+                Ok(count) => self.incoming.extend_from_slice(&buf[..count]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(_) => {
+                    open = false;
+                    break
+                },
+            }
+        }
+        let mut packets = Vec::new();
+        while self.incoming.len() >= 4 {
+            let len = u32::from_le_bytes([self.incoming[0], self.incoming[1], self.incoming[2], self.incoming[3]]);
+            let len = len as usize;
+            if len > MAX_PACKET_SIZE {
+                return (packets, false)
+            }
+            if self.incoming.len() < len + 4 {
+                break
+            }
+            match bincode::deserialize(&self.incoming[4..len + 4]) {
+                Ok(packet) => packets.push(packet),
+                Err(_) => return (packets, false),
+            }
+            self.incoming.drain(..len + 4);
+        }
+        (packets, open)
+    }
+}
+
+struct Host {
+    listener: TcpListener,
+    /// Answers searches for sessions. Without it, the session can still be joined, but not found.
+    discovery: Option<UdpSocket>,
+    name: Vec<u8>,
+    max_players: u32,
+    next_id: u32,
+    /// Connections which haven't asked to join yet.
+    pending: Vec<Connection>,
+    clients: Vec<(u32, Connection)>,
+}
+
+enum Session {
+    None,
+    Host(Host),
+    Client(Connection),
+}
+
+struct Message {
+    from: u32,
+    name: Vec<u8>,
+    id: i32,
+    value: Value,
+}
+
+/// What the game can see of multiplayer, which goes into savestates.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MultiplayerState {
+    /// Whether mplay_init_tcpip has been called. The other connection types aren't supported.
+    pub initialised: bool,
+    /// The names of the sessions found by mplay_session_find.
+    pub session_names: Vec<Value>,
+    /// The IDs and names of the players found by mplay_player_find.
+    pub players: Vec<(Value, Value)>,
+    /// The ID, value, sender ID and sender name of the last message received.
+    pub message: [Value; 4],
+}
+
+impl Default for MultiplayerState {
+    fn default() -> Self {
+        Self {
+            initialised: false,
+            session_names: Vec::new(),
+            players: Vec::new(),
+            message: [Value::from(0), Value::from(0), Value::from(0), Value::from("")],
+        }
+    }
+}
+
+/// GM8's multiplayer functions, which used DirectPlay. This does the same sort of thing over TCP/IP, so sessions
+/// can be hosted and joined by other instances of the emulator, but not by the real runner.
+///
+/// Everything the game gets from the network goes through `Game::mplay_network`, so it's stored in replays like the
+/// results of real DLLs, and on playback that's used instead of the network. Only `state` goes into savestates, since connections can't,
+/// so they stay open across savestate loads.
+pub struct Multiplayer {
+    pub state: MultiplayerState,
+
+    // the live network, which isn't used on playback
+    address: String,
+    session: Session,
+    local_id: u32,
+    roster: Vec<(u32, Vec<u8>)>,
+    data: BTreeMap<u32, Value>,
+    messages: VecDeque<Message>,
+    found: Vec<SocketAddr>,
+}
+
+impl Default for Multiplayer {
+    fn default() -> Self {
+        Self {
+            state: Default::default(),
+            address: String::new(),
+            session: Session::None,
+            local_id: 0,
+            roster: Vec::new(),
+            data: BTreeMap::new(),
+            messages: VecDeque::new(),
+            found: Vec::new(),
+        }
+    }
+}
+
+impl Multiplayer {
+    pub fn init(&mut self, address: String) {
+        self.end();
+        self.address = address;
+        self.state.initialised = true;
+    }
+
+    pub fn end(&mut self) {
+        self.end_session();
+        self.state.initialised = false;
+        self.found.clear();
+    }
+
+    /// The host part of the address given to mplay_init_tcpip, and the port from it or the default one.
+    fn host_and_port(&self) -> (&str, u16) {
+        match self.address.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().unwrap_or(DEFAULT_PORT)),
+            None => (&self.address, DEFAULT_PORT),
+        }
+    }
+
+    pub fn create_session(&mut self, name: &[u8], max_players: i32, player_name: &[u8]) -> bool {
+        if !self.state.initialised {
+            return false
+        }
+        self.end_session();
+        let port = self.host_and_port().1;
+        let listener = match TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)) {
+            Ok(listener) if listener.set_nonblocking(true).is_ok() => listener,
+            _ => return false,
+        };
+        // if the port was picked by the OS, the session is found on the same one
+        let port = listener.local_addr().map(|a| a.port()).unwrap_or(port);
+        let discovery = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))
+            .and_then(|s| s.set_nonblocking(true).map(|_| s))
+            .ok();
+        self.session = Session::Host(Host {
+            listener,
+            discovery,
+            name: name.to_vec(),
+            max_players: max_players.max(0) as u32,
+            next_id: 2,
+            pending: Vec::new(),
+            clients: Vec::new(),
+        });
+        self.local_id = 1;
+        self.roster = vec![(1, player_name.to_vec())];
+        true
+    }
+
+    /// Looks for sessions at the address given to mplay_init_tcpip, or on the local network if there wasn't one,
+    /// returning their names.
+    pub fn find_sessions(&mut self) -> Vec<Value> {
+        self.found.clear();
+        if !self.state.initialised {
+            return Vec::new()
+        }
+        let socket = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)) {
+            Ok(socket) => socket,
+            Err(_) => return Vec::new(),
+        };
+        let _ = socket.set_broadcast(true);
+        let (host, port) = self.host_and_port();
+        let targets: Vec<SocketAddr> = if host.is_empty() {
+            vec![(Ipv4Addr::BROADCAST, port).into(), (Ipv4Addr::LOCALHOST, port).into()]
+        } else {
+            (host, port).to_socket_addrs().map(|a| a.collect()).unwrap_or_default()
+        };
+        let request = match bincode::serialize(&Packet::FindSessions) {
+            Ok(request) => request,
+            Err(_) => return Vec::new(),
+        };
+        for target in targets {
+            let _ = socket.send_to(&request, target);
+        }
+        let mut names = Vec::new();
+        let mut buf = [0u8; 4096];
+        let deadline = Instant::now() + FIND_TIMEOUT;
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()).filter(|d| !d.is_zero()) {
+            if socket.set_read_timeout(Some(remaining)).is_err() {
+                break
+            }
+            let (len, from) = match socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            // the same session can answer both the broadcast and localhost
+            if let Ok(Packet::Session { name, .. }) = bincode::deserialize(&buf[..len]) {
+                let duplicate = self
+                    .found
+                    .iter()
+                    .any(|a| a.port() == from.port() && (a.ip() == from.ip() || from.ip().is_loopback()));
+                if !duplicate {
+                    self.found.push(from);
+                    names.push(Value::from(name));
+                }
+            }
+        }
+        names
+    }
+
+    pub fn join_session(&mut self, index: usize, player_name: &[u8]) -> bool {
+        match self.found.get(index).copied() {
+            Some(address) => self.connect(address, player_name).is_ok(),
+            None => false,
+        }
+    }
+
+    fn connect(&mut self, address: SocketAddr, player_name: &[u8]) -> io::Result<()> {
+        self.end_session();
+        let mut connection = Connection::new(TcpStream::connect_timeout(&address, JOIN_TIMEOUT)?)?;
+        connection.send(&Packet::Join { name: player_name.to_vec() })?;
+        let deadline = Instant::now() + JOIN_TIMEOUT;
+        loop {
+            let (packets, open) = connection.receive();
+            let mut packets = packets.into_iter();
+            match packets.next() {
+                Some(Packet::Welcome { id, players, data }) => {
+                    self.local_id = id;
+                    self.roster = players;
+                    self.data = data.into_iter().collect();
+                    self.session = Session::Client(connection);
+                    // anything that came in straight after being let in
+                    self.handle_from_host(packets.collect());
+                    return Ok(())
+                },
+                Some(_) => return Err(io::ErrorKind::ConnectionRefused.into()),
+                None if !open => return Err(io::ErrorKind::ConnectionAborted.into()),
+                None if Instant::now() >= deadline => return Err(io::ErrorKind::TimedOut.into()),
+                None => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+    }
+
+    pub fn session_status(&self) -> i32 {
+        match self.session {
+            Session::None => 0,
+            Session::Host(_) => 1,
+            Session::Client(_) => 2,
+        }
+    }
+
+    pub fn end_session(&mut self) {
+        self.session = Session::None;
+        self.local_id = 0;
+        self.roster.clear();
+        self.data.clear();
+        self.messages.clear();
+    }
+
+    /// The IDs and names of everyone in the session, flattened.
+    pub fn find_players(&mut self) -> Vec<Value> {
+        self.poll();
+        self.roster.iter().flat_map(|(id, name)| [Value::from(*id), Value::from(name.clone())]).collect()
+    }
+
+    /// Which player an argument refers to, by ID or name. 0 means everyone.
+    fn player_id(&self, player: &Value) -> Option<u32> {
+        match player {
+            Value::Str(name) => self.roster.iter().find(|(_, n)| n.as_slice() == name.as_ref()).map(|(id, _)| *id),
+            Value::Real(_) => Some(i32::from(player.clone()).max(0) as u32),
+        }
+    }
+
+    pub fn send_message(&mut self, player: &Value, id: i32, value: Value) {
+        let to = match self.player_id(player) {
+            Some(to) if to != self.local_id => to,
+            _ => return,
+        };
+        let packet = Packet::Message { from: self.local_id, to, id, value };
+        match &mut self.session {
+            Session::Host(_) => self.route(packet, 1),
+            Session::Client(connection) => {
+                let _ = connection.send(&packet);
+            },
+            Session::None => (),
+        }
+    }
+
+    pub fn write_data(&mut self, index: u32, value: Value) {
+        if index > DATA_LIMIT {
+            return
+        }
+        self.data.insert(index, value.clone());
+        let packet = Packet::Data { index, value };
+        match &mut self.session {
+            Session::Host(host) => {
+                for (_, connection) in &mut host.clients {
+                    let _ = connection.send(&packet);
+                }
+            },
+            Session::Client(connection) => {
+                let _ = connection.send(&packet);
+            },
+            Session::None => (),
+        }
+    }
+
+    pub fn read_data(&mut self, index: u32) -> Value {
+        self.poll();
+        self.data.get(&index).cloned().unwrap_or_else(|| Value::from(0))
+    }
+
+    fn matches(&self, message: &Message, player: &Value) -> bool {
+        match self.player_id(player) {
+            Some(0) => true,
+            Some(id) => message.from == id,
+            None => false,
+        }
+    }
+
+    /// Takes the next message from the given player, giving its ID, value, sender ID and sender name.
+    pub fn receive_message(&mut self, player: &Value) -> Vec<Value> {
+        self.poll();
+        match self.messages.iter().position(|m| self.matches(m, player)).and_then(|i| self.messages.remove(i)) {
+            Some(Message { from, name, id, value }) => vec![id.into(), value, from.into(), name.into()],
+            None => Vec::new(),
+        }
+    }
+
+    pub fn count_messages(&mut self, player: &Value) -> usize {
+        self.poll();
+        self.messages.iter().filter(|m| self.matches(m, player)).count()
+    }
+
+    pub fn clear_messages(&mut self, player: &Value) {
+        self.poll();
+        let messages = std::mem::take(&mut self.messages);
+        self.messages = messages.into_iter().filter(|m| !self.matches(m, player)).collect();
+    }
+
+    fn player_name(&self, id: u32) -> Vec<u8> {
+        self.roster.iter().find(|(i, _)| *i == id).map(|(_, name)| name.clone()).unwrap_or_default()
+    }
+
+    /// Deals with whatever's come in over the network. This happens every frame, and before anything that reads
+    /// what's come in, so the host keeps passing things on even if the game doesn't look at them.
+    pub fn poll(&mut self) {
+        match &mut self.session {
+            Session::None => (),
+            Session::Client(connection) => {
+                let (packets, open) = connection.receive();
+                self.handle_from_host(packets);
+                if !open {
+                    // the host left, which ends the session for everyone
+                    self.end_session();
+                }
+            },
+            Session::Host(host) => {
+                let mut buf = [0u8; 4096];
+                if let Some(discovery) = &host.discovery {
+                    while let Ok((len, from)) = discovery.recv_from(&mut buf) {
+                        let players = host.clients.len() as u32 + 1;
+                        let full = host.max_players != 0 && players >= host.max_players;
+                        if let (Ok(Packet::FindSessions), false) = (bincode::deserialize(&buf[..len]), full) {
+                            let reply =
+                                Packet::Session { name: host.name.clone(), players, max_players: host.max_players };
+                            if let Ok(reply) = bincode::serialize(&reply) {
+                                let _ = discovery.send_to(&reply, from);
+                            }
+                        }
+                    }
+                }
+                while let Ok((stream, _)) = host.listener.accept() {
+                    if let Ok(connection) = Connection::new(stream) {
+                        host.pending.push(connection);
+                    }
+                }
+                let mut joining = Vec::new();
+                let mut i = 0;
+                while i < host.pending.len() {
+                    let (packets, open) = host.pending[i].receive();
+                    match packets.into_iter().next() {
+                        Some(Packet::Join { name }) => joining.push((name, host.pending.swap_remove(i))),
+                        Some(_) => drop(host.pending.swap_remove(i)),
+                        None if !open => drop(host.pending.swap_remove(i)),
+                        None => i += 1,
+                    }
+                }
+                for (name, connection) in joining {
+                    self.admit(name, connection);
+                }
+                let mut received = Vec::new();
+                let mut left = Vec::new();
+                if let Session::Host(host) = &mut self.session {
+                    for (id, connection) in &mut host.clients {
+                        let (packets, open) = connection.receive();
+                        received.extend(packets.into_iter().map(|p| (*id, p)));
+                        if !open {
+                            left.push(*id);
+                        }
+                    }
+                }
+                for (from, packet) in received {
+                    match packet {
+                        Packet::Message { to, id, value, .. } => {
+                            self.route(Packet::Message { from, to, id, value }, from)
+                        },
+                        Packet::Data { index, value } if index <= DATA_LIMIT => {
+                            self.data.insert(index, value.clone());
+                            self.broadcast(&Packet::Data { index, value }, from);
+                        },
+                        _ => (),
+                    }
+                }
+                for id in left {
+                    if let Session::Host(host) = &mut self.session {
+                        host.clients.retain(|(i, _)| *i != id);
+                    }
+                    self.roster.retain(|(i, _)| *i != id);
+                    self.broadcast(&Packet::PlayerLeft { id }, id);
+                }
+            },
+        }
+    }
+
+    /// Lets a player into the hosted session, unless it's full.
+    fn admit(&mut self, name: Vec<u8>, mut connection: Connection) {
+        let host = match &mut self.session {
+            Session::Host(host) => host,
+            _ => return,
+        };
+        if host.max_players != 0 && host.clients.len() as u32 + 1 >= host.max_players {
+            let _ = connection.send(&Packet::Refused);
+            return
+        }
+        let id = host.next_id;
+        host.next_id += 1;
+        self.roster.push((id, name.clone()));
+        let welcome = Packet::Welcome {
+            id,
+            players: self.roster.clone(),
+            data: self.data.iter().map(|(i, v)| (*i, v.clone())).collect(),
+        };
+        if connection.send(&welcome).is_err() {
+            self.roster.pop();
+            return
+        }
+        self.broadcast(&Packet::PlayerJoined { id, name }, 0);
+        if let Session::Host(host) = &mut self.session {
+            host.clients.push((id, connection));
+        }
+    }
+
+    /// Sends a packet to every other player, except the one it came from.
+    fn broadcast(&mut self, packet: &Packet, except: u32) {
+        if let Session::Host(host) = &mut self.session {
+            for (_, connection) in host.clients.iter_mut().filter(|(id, _)| *id != except) {
+                let _ = connection.send(packet);
+            }
+        }
+    }
+
+    /// Delivers a message as the host, which might be for the host, another player or everyone.
+    fn route(&mut self, packet: Packet, from: u32) {
+        let to = match &packet {
+            Packet::Message { to, .. } => *to,
+            _ => return,
+        };
+        if let Packet::Message { from, id, value, .. } = &packet {
+            if (to == 0 && *from != self.local_id) || to == self.local_id {
+                let name = self.player_name(*from);
+                self.messages.push_back(Message { from: *from, name, id: *id, value: value.clone() });
+            }
+        }
+        if to == 0 {
+            self.broadcast(&packet, from);
+        } else if let Session::Host(host) = &mut self.session {
+            if let Some((_, connection)) = host.clients.iter_mut().find(|(id, _)| *id == to) {
+                let _ = connection.send(&packet);
+            }
+        }
+    }
+
+    fn handle_from_host(&mut self, packets: Vec<Packet>) {
+        for packet in packets {
+            match packet {
+                Packet::PlayerJoined { id, name } => self.roster.push((id, name)),
+                Packet::PlayerLeft { id } => self.roster.retain(|(i, _)| *i != id),
+                Packet::Message { from, id, value, .. } => {
+                    let name = self.player_name(from);
+                    self.messages.push_back(Message { from, name, id, value });
+                },
+                Packet::Data { index, value } => {
+                    self.data.insert(index, value);
+                },
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Game {
+    /// Gets something that depends on what's come in over the network. Like a real DLL's result, it's stored in the
+    /// replay when recording, and on playback it's taken from there, so sessions can be replayed without the other
+    /// players.
+    pub fn mplay_network(
+        &mut self,
+        function: &str,
+        args: &[Value],
+        live: impl FnOnce(&mut Multiplayer) -> Value,
+    ) -> gml::Result<Value> {
+        self.host_result(REPLAY_NAME, function, args, |game| Ok(live(&mut game.mplay)))
+    }
+
+    /// Does something on the network, unless results are being replayed, as there's no network then.
+    pub fn mplay_live(&mut self, action: impl FnOnce(&mut Multiplayer)) {
+        if self.externals.result_mode() != ResultMode::Replay {
+            action(&mut self.mplay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn host_and_join() {
+        let (port_sender, port_receiver) = mpsc::channel();
+        let (done_sender, done_receiver) = mpsc::channel::<()>();
+        // the host has to keep polling while the other player joins, so it runs on its own thread
+        let host = thread::spawn(move || {
+            let mut host = Multiplayer::default();
+            host.init("127.0.0.1:0".into());
+            assert!(host.create_session(b"test session", 0, b"host"));
+            let port = match &host.session {
+                Session::Host(h) => h.listener.local_addr().unwrap().port(),
+                _ => unreachable!(),
+            };
+            host.write_data(3, Value::from(7));
+            port_sender.send(port).unwrap();
+            let mut received = None;
+            while done_receiver.try_recv().is_err() {
+                host.poll();
+                if received.is_none() {
+                    let message = host.receive_message(&Value::from(0));
+                    if !message.is_empty() {
+                        let name = gml::String::from(message[3].clone());
+                        let name = String::from_utf8_lossy(name.as_ref()).into_owned();
+                        received = Some((i32::from(message[0].clone()), name));
+                        host.send_message(&Value::from("client"), 5, Value::from("pong"));
+                    }
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+            received
+        });
+
+        let port = port_receiver.recv().unwrap();
+        let mut client = Multiplayer::default();
+        client.init(format!("127.0.0.1:{}", port));
+        let names = client.find_sessions();
+        assert_eq!(names.len(), 1);
+        assert_eq!(names[0], Value::from("test session"));
+        assert!(client.join_session(0, b"client"));
+        assert_eq!(client.session_status(), 2);
+        assert_eq!(client.find_players().len(), 4);
+        assert_eq!(client.read_data(3), Value::from(7));
+
+        client.send_message(&Value::from(0), 42, Value::from(1.5));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let reply = loop {
+            let message = client.receive_message(&Value::from("host"));
+            if !message.is_empty() || Instant::now() > deadline {
+                break message
+            }
+            thread::sleep(Duration::from_millis(5));
+        };
+        assert_eq!(reply, vec![Value::from(5), Value::from("pong"), Value::from(1), Value::from("host")]);
+
+        done_sender.send(()).unwrap();
+        assert_eq!(host.join().unwrap(), Some((42, "client".into())));
+    }
+}
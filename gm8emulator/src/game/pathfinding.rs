@@ -4,6 +4,7 @@ use crate::{
     math::Real,
 };
 use serde::{Deserialize, Serialize};
+use std::{cell::Cell, collections::VecDeque, ops::RangeInclusive};
 
 #[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct PotentialStepSettings {
@@ -63,6 +64,122 @@ impl MpGrid {
     pub fn set(&mut self, x: usize, y: usize, val: i32) {
         self.mpgrid[x][y] = val;
     }
+
+    /// The columns and rows of the cells overlapping a rectangle, whose edges are inclusive like a bounding box's.
+    /// Gives None if none of the grid is covered, which is always the case for cells that have no size.
+    pub fn cells_in_rectangle(
+        &self,
+        left: i32,
+        top: i32,
+        right: i32,
+        bottom: i32,
+    ) -> Option<(RangeInclusive<usize>, RangeInclusive<usize>)> {
+        let span = |low: i32, high: i32, start: i32, size: i32, count: usize| {
+            if size <= 0 || count == 0 || high < low {
+                return None
+            }
+            let first = (i64::from(low) - i64::from(start)).div_euclid(i64::from(size));
+            let last = (i64::from(high) - i64::from(start)).div_euclid(i64::from(size));
+            if last < 0 || first >= count as i64 {
+                return None
+            }
+            Some(first.max(0) as usize..=last.min(count as i64 - 1) as usize)
+        };
+        let columns = span(left, right, self.left, self.cellwidth, self.hcells)?;
+        let rows = span(top, bottom, self.top, self.cellheight, self.vcells)?;
+        Some((columns, rows))
+    }
+
+    /// The cell containing the given position, if it's inside the grid.
+    fn cell_at(&self, x: Real, y: Real) -> Option<(usize, usize)> {
+        let cx = ((x - self.left.into()) / self.cellwidth.into()).floor().into_inner();
+        let cy = ((y - self.top.into()) / self.cellheight.into()).floor().into_inner();
+        if cx >= 0.0 && cy >= 0.0 && cx < self.hcells as f64 && cy < self.vcells as f64 {
+            Some((cx as usize, cy as usize))
+        } else {
+            None
+        }
+    }
+
+    /// The cells next to the given one, in the order GM8 checks them in when walking back along a path:
+    /// diagonals first, if they're allowed, then left, right, up and down. Diagonal moves can't cut corners,
+    /// so they're only included when both cells beside them are free.
+    fn neighbours(&self, x: usize, y: usize, allow_diag: bool) -> Vec<(usize, usize)> {
+        let free = |x: usize, y: usize, dx: isize, dy: isize| {
+            let (x, y) = (x as isize + dx, y as isize + dy);
+            if x >= 0 && y >= 0 && (x as usize) < self.hcells && (y as usize) < self.vcells {
+                Some((x as usize, y as usize)).filter(|&(x, y)| self.get(x, y) >= 0)
+            } else {
+                None
+            }
+        };
+        let mut cells = Vec::with_capacity(8);
+        if allow_diag {
+            cells.extend([(-1, -1), (1, -1), (-1, 1), (1, 1)].iter().filter_map(|&(dx, dy)| {
+                free(x, y, dx, 0).and(free(x, y, 0, dy)).and_then(|_| free(x, y, dx, dy))
+            }));
+        }
+        cells.extend([(-1, 0), (1, 0), (0, -1), (0, 1)].iter().filter_map(|&(dx, dy)| free(x, y, dx, dy)));
+        cells
+    }
+
+    /// Finds a path from one position to another through the free cells, like mp_grid_path.
+    /// Every step between cells costs the same, diagonal or not, as in GM8. The path goes through the centres of
+    /// the cells, except at the ends, which are exactly the given positions. Returns None if there's no way through.
+    pub fn find_path(
+        &self,
+        xstart: Real,
+        ystart: Real,
+        xgoal: Real,
+        ygoal: Real,
+        allow_diag: bool,
+    ) -> Option<Vec<(Real, Real)>> {
+        let (xs, ys) = self.cell_at(xstart, ystart)?;
+        let (xg, yg) = self.cell_at(xgoal, ygoal)?;
+        if self.get(xs, ys) < 0 || self.get(xg, yg) < 0 {
+            return None
+        }
+
+        // breadth-first search, numbering cells by how many steps they are from the start
+        // the start is 1 so 0 can still mean a free cell that hasn't been reached
+        let mut steps = vec![vec![0; self.vcells]; self.hcells];
+        steps[xs][ys] = 1;
+        let mut queue = VecDeque::new();
+        queue.push_back((xs, ys));
+        while let Some((x, y)) = queue.pop_front() {
+            if (x, y) == (xg, yg) {
+                break
+            }
+            for (nx, ny) in self.neighbours(x, y, allow_diag) {
+                if steps[nx][ny] == 0 {
+                    steps[nx][ny] = steps[x][y] + 1;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+        if steps[xg][yg] == 0 {
+            return None
+        }
+
+        // then walk back from the goal, always to a cell one step closer to the start
+        let mut points = vec![(xgoal, ygoal)];
+        let (mut x, mut y) = (xg, yg);
+        while steps[x][y] > 1 {
+            let closer = steps[x][y] - 1;
+            let (nx, ny) = self.neighbours(x, y, allow_diag).into_iter().find(|&(nx, ny)| steps[nx][ny] == closer)?;
+            x = nx;
+            y = ny;
+            if steps[x][y] > 1 {
+                points.push((
+                    Real::from(self.left + x as i32 * self.cellwidth) + Real::from(self.cellwidth) / 2.into(),
+                    Real::from(self.top + y as i32 * self.cellheight) + Real::from(self.cellheight) / 2.into(),
+                ));
+            }
+        }
+        points.push((xstart, ystart));
+        points.reverse();
+        Some(points)
+    }
 }
 
 /// Performs a step straight towards the given destination, stopping when a wall is reached.
@@ -194,3 +311,193 @@ pub fn make_path(inst: &Instance, path: &mut Path, func: impl Fn(&Instance) -> P
     inst.bbox_is_stale.set(true);
     result == PathGenResult::Done
 }
+
+/// Makes a path out of linear steps, like mp_linear_path. Returns whether it reaches the goal.
+pub fn linear_path(
+    inst: &Instance,
+    path: &mut Path,
+    x: Real,
+    y: Real,
+    step_size: Real,
+    coll: impl Fn() -> bool,
+) -> bool {
+    make_path(inst, path, |inst| {
+        let (old_x, old_y) = (inst.x.get(), inst.y.get());
+        if linear_step(x, y, step_size, inst, &coll) {
+            PathGenResult::Done
+        } else if inst.x.get() == old_x && inst.y.get() == old_y {
+            PathGenResult::Failed
+        } else {
+            PathGenResult::NotDone
+        }
+    })
+}
+
+/// Makes a path out of potential steps, like mp_potential_path. Returns whether it reaches the goal.
+/// Steps that don't go anywhere still count, so it gives up once the steps add up to more than `factor` times
+/// the straight distance to the goal.
+#[allow(clippy::too_many_arguments)] // It's mp_potential_path's arguments plus what they apply to.
+pub fn potential_path(
+    inst: &Instance,
+    path: &mut Path,
+    x: Real,
+    y: Real,
+    step_size: Real,
+    factor: Real,
+    settings: &PotentialStepSettings,
+    coll: impl Fn() -> bool,
+) -> bool {
+    if step_size <= 0.into() {
+        path.points.clear();
+        path.update();
+        return false
+    }
+    let max_length = factor * (inst.x.get() - x).into_inner().hypot((inst.y.get() - y).into()).into();
+    let steps = Cell::new(0i32);
+    make_path(inst, path, |inst| {
+        steps.set(steps.get() + 1);
+        if potential_step(x, y, step_size, settings, inst, &coll) {
+            PathGenResult::Done
+        } else if step_size * Real::from(steps.get()) > max_length {
+            PathGenResult::Failed
+        } else {
+            PathGenResult::NotDone
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    // The expected grid paths are worked through by hand from GM8's rules: a flood fill from the start where every
+    // step costs 1, then a walk back from the goal taking the first neighbour one step closer. They aren't captured
+    // from the real runner, so a difference from it should be fixed here as well as in the code.
+
+    use super::*;
+
+    fn points(points: &[(f64, f64)]) -> Option<Vec<(Real, Real)>> {
+        Some(points.iter().map(|&(x, y)| (x.into(), y.into())).collect())
+    }
+
+    fn grid_path(grid: &MpGrid, start: (f64, f64), goal: (f64, f64), allow_diag: bool) -> Option<Vec<(Real, Real)>> {
+        grid.find_path(start.0.into(), start.1.into(), goal.0.into(), goal.1.into(), allow_diag)
+    }
+
+    #[test]
+    fn cells_in_rectangle() {
+        let grid = MpGrid::new(-20, 0, 4, 3, 10, 10);
+        assert_eq!(grid.cells_in_rectangle(-20, 0, -11, 9), Some((0..=0, 0..=0)));
+        assert_eq!(grid.cells_in_rectangle(-15, 5, -10, 10), Some((0..=1, 0..=1)));
+        assert_eq!(grid.cells_in_rectangle(-100, -100, 100, 100), Some((0..=3, 0..=2)));
+        // partly off the grid
+        assert_eq!(grid.cells_in_rectangle(15, 25, 30, 40), Some((3..=3, 2..=2)));
+        // off the grid entirely
+        assert_eq!(grid.cells_in_rectangle(-30, 0, -21, 9), None);
+        assert_eq!(grid.cells_in_rectangle(20, 0, 30, 9), None);
+        assert_eq!(grid.cells_in_rectangle(0, 30, 10, 40), None);
+        assert_eq!(MpGrid::new(0, 0, 4, 4, 0, 10).cells_in_rectangle(0, 0, 10, 10), None);
+    }
+
+    #[test]
+    fn grid_path_straight() {
+        let grid = MpGrid::new(0, 0, 5, 5, 10, 10);
+        assert_eq!(
+            grid_path(&grid, (2.0, 4.0), (47.0, 6.0), false),
+            points(&[(2.0, 4.0), (15.0, 5.0), (25.0, 5.0), (35.0, 5.0), (47.0, 6.0)]),
+        );
+        assert_eq!(grid_path(&grid, (2.0, 4.0), (8.0, 1.0), false), points(&[(2.0, 4.0), (8.0, 1.0)]));
+    }
+
+    #[test]
+    fn grid_path_around_wall() {
+        let mut grid = MpGrid::new(0, 0, 5, 5, 10, 10);
+        for y in 0..4 {
+            grid.set(2, y, -1);
+        }
+        assert_eq!(
+            grid_path(&grid, (5.0, 5.0), (45.0, 5.0), false),
+            points(&[
+                (5.0, 5.0),
+                (5.0, 15.0),
+                (5.0, 25.0),
+                (5.0, 35.0),
+                (5.0, 45.0),
+                (15.0, 45.0),
+                (25.0, 45.0),
+                (35.0, 45.0),
+                (35.0, 35.0),
+                (35.0, 25.0),
+                (35.0, 15.0),
+                (35.0, 5.0),
+                (45.0, 5.0),
+            ]),
+        );
+        assert_eq!(
+            grid_path(&grid, (5.0, 5.0), (45.0, 5.0), true),
+            points(&[
+                (5.0, 5.0),
+                (5.0, 15.0),
+                (15.0, 25.0),
+                (5.0, 35.0),
+                (15.0, 45.0),
+                (25.0, 45.0),
+                (35.0, 45.0),
+                (35.0, 35.0),
+                (45.0, 25.0),
+                (35.0, 15.0),
+                (45.0, 5.0),
+            ]),
+        );
+    }
+
+    #[test]
+    fn grid_path_diagonals() {
+        let mut grid = MpGrid::new(10, 20, 3, 3, 16, 8);
+        assert_eq!(
+            grid_path(&grid, (18.0, 24.0), (50.0, 40.0), true),
+            points(&[(18.0, 24.0), (34.0, 32.0), (50.0, 40.0)]),
+        );
+        // diagonal steps can't cut the corner of an occupied cell
+        grid.set(1, 0, -1);
+        assert_eq!(
+            grid_path(&grid, (18.0, 24.0), (34.0, 32.0), true),
+            points(&[(18.0, 24.0), (18.0, 32.0), (34.0, 32.0)]),
+        );
+    }
+
+    #[test]
+    fn grid_path_tie_breaks() {
+        // walking back from the goal, diagonals are tried before straight moves, so paths zigzag where
+        // there's more than one shortest way
+        let grid = MpGrid::new(0, 0, 4, 4, 10, 10);
+        assert_eq!(
+            grid_path(&grid, (5.0, 5.0), (35.0, 25.0), true),
+            points(&[(5.0, 5.0), (15.0, 5.0), (25.0, 15.0), (35.0, 25.0)]),
+        );
+        assert_eq!(
+            grid_path(&grid, (35.0, 25.0), (5.0, 5.0), true),
+            points(&[(35.0, 25.0), (25.0, 25.0), (15.0, 15.0), (5.0, 5.0)]),
+        );
+        // without them it tries left, right, up and down, in that order
+        assert_eq!(
+            grid_path(&grid, (5.0, 5.0), (25.0, 25.0), false),
+            points(&[(5.0, 5.0), (5.0, 15.0), (5.0, 25.0), (15.0, 25.0), (25.0, 25.0)]),
+        );
+    }
+
+    #[test]
+    fn grid_path_impossible() {
+        let mut grid = MpGrid::new(0, 0, 4, 4, 10, 10);
+        // outside the grid
+        assert_eq!(grid_path(&grid, (-1.0, 5.0), (35.0, 5.0), true), None);
+        assert_eq!(grid_path(&grid, (5.0, 5.0), (40.0, 5.0), true), None);
+        // goal is occupied
+        grid.set(3, 0, -1);
+        assert_eq!(grid_path(&grid, (5.0, 5.0), (35.0, 5.0), true), None);
+        // goal is walled off
+        grid.set(3, 0, 0);
+        grid.set(2, 0, -1);
+        grid.set(2, 1, -1);
+        grid.set(3, 1, -1);
+        assert_eq!(grid_path(&grid, (5.0, 5.0), (35.0, 5.0), true), None);
+    }
+}
@@ -1,4 +1,25 @@
+mod linux;
 mod windows;
 
 #[cfg(windows)]
 pub use windows::{disk_free, disk_size, display_colour_depth, display_frequency, display_height, display_width};
+
+#[cfg(target_os = "linux")]
+pub use linux::Joysticks;
+#[cfg(windows)]
+pub use windows::Joysticks;
+
+/// Joysticks aren't supported on this platform, so none are ever connected.
+#[cfg(not(any(windows, target_os = "linux")))]
+pub struct Joysticks;
+
+#[cfg(not(any(windows, target_os = "linux")))]
+impl Joysticks {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn poll(&mut self, _id: usize) -> crate::input::Joystick {
+        crate::input::Joystick::new()
+    }
+}
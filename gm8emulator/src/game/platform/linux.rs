@@ -0,0 +1,128 @@
+#![cfg(target_os = "linux")]
+
+use crate::input::{Joystick, JOYSTICK_AXES, JOYSTICK_BUTTONS, JOYSTICK_COUNT, POV_CENTRED};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read},
+    os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+};
+
+// ioctls and event types from linux/joystick.h
+const JSIOCGAXES: u32 = 0x80016a11;
+const JSIOCGBUTTONS: u32 = 0x80016a12;
+const JSIOCGNAME_128: u32 = 0x80806a13;
+const JS_EVENT_BUTTON: u8 = 0x01;
+const JS_EVENT_AXIS: u8 = 0x02;
+
+/// Hats show up as a pair of axes after the sticks, which is where the first one usually is on a gamepad.
+const HAT_AXES: (u8, u8) = (6, 7);
+
+struct Device {
+    file: File,
+    joystick: Joystick,
+    hat: (i16, i16),
+}
+
+/// Reads joysticks through the kernel's joystick API, keeping their state up to date from the events it sends.
+pub struct Joysticks {
+    devices: [Option<Device>; JOYSTICK_COUNT],
+}
+
+impl Joysticks {
+    pub fn new() -> Self {
+        Self { devices: [None, None] }
+    }
+
+    pub fn poll(&mut self, id: usize) -> Joystick {
+        let slot = match self.devices.get_mut(id) {
+            Some(slot) => slot,
+            None => return Joystick::new(),
+        };
+        if slot.is_none() {
+            *slot = Device::open(id).ok();
+        }
+        match slot.as_mut().map(Device::read_events) {
+            Some(Ok(())) => slot.as_ref().unwrap().joystick.clone(),
+            Some(Err(_)) => {
+                // unplugged, so try to open it again next time
+                *slot = None;
+                Joystick::new()
+            },
+            None => Joystick::new(),
+        }
+    }
+}
+
+impl Device {
+    fn open(id: usize) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(format!("/dev/input/js{}", id))?;
+        let fd = file.as_raw_fd();
+        let (mut axes, mut buttons, mut name) = (0u8, 0u8, [0u8; 128]);
+        unsafe {
+            libc::ioctl(fd, JSIOCGAXES as _, &mut axes);
+            libc::ioctl(fd, JSIOCGBUTTONS as _, &mut buttons);
+            libc::ioctl(fd, JSIOCGNAME_128 as _, name.as_mut_ptr());
+        }
+        let name_len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        let joystick = Joystick {
+            connected: true,
+            name: String::from_utf8_lossy(&name[..name_len]).into_owned(),
+            axis_count: axes.min(JOYSTICK_AXES as u8),
+            button_count: buttons.min(JOYSTICK_BUTTONS),
+            has_pov: axes > HAT_AXES.1,
+            ..Joystick::new()
+        };
+        Ok(Self { file, joystick, hat: (0, 0) })
+    }
+
+    /// Applies all the events that have arrived since the last poll.
+    fn read_events(&mut self) -> io::Result<()> {
+        let mut event = [0u8; 8];
+        loop {
+            match self.file.read_exact(&mut event) {
+                Ok(()) => (),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(e) => break Err(e),
+            }
+            let value = i16::from_ne_bytes([event[4], event[5]]);
+            let number = event[7];
+            // the init flag (0x80) marks the events describing the initial state, which get applied all the same
+            match event[6] & !0x80 {
+                JS_EVENT_BUTTON if number < JOYSTICK_BUTTONS => {
+                    if value != 0 {
+                        self.joystick.buttons |= 1 << number;
+                    } else {
+                        self.joystick.buttons &= !(1 << number);
+                    }
+                },
+                JS_EVENT_AXIS if number == HAT_AXES.0 => self.update_hat((value, self.hat.1)),
+                JS_EVENT_AXIS if number == HAT_AXES.1 => self.update_hat((self.hat.0, value)),
+                JS_EVENT_AXIS => {
+                    if let Some(axis) = self.joystick.axes.get_mut(usize::from(number)) {
+                        *axis = (i32::from(value) + 32768) as u16;
+                    }
+                },
+                _ => (),
+            }
+        }
+    }
+
+    /// Turns the hat's X and Y axes into a POV direction like winmm's, in hundredths of a degree.
+    fn update_hat(&mut self, hat: (i16, i16)) {
+        self.hat = hat;
+        self.joystick.pov = match (hat.0.signum(), hat.1.signum()) {
+            (0, -1) => 0,
+            (1, -1) => 4500,
+            (1, 0) => 9000,
+            (1, 1) => 13500,
+            (0, 1) => 18000,
+            (-1, 1) => 22500,
+            (-1, 0) => 27000,
+            (-1, -1) => 31500,
+            _ => POV_CENTRED,
+        };
+    }
+}
@@ -1,5 +1,6 @@
 #![cfg(windows)]
 
+use crate::input::{Joystick, AXIS_CENTRE, JOYSTICK_AXES, JOYSTICK_BUTTONS};
 use std::{ffi::OsStr, mem, os::windows::ffi::OsStrExt, ptr};
 
 #[allow(non_snake_case)]
@@ -47,6 +48,114 @@ extern "system" {
     ) -> i32;
 }
 
+#[allow(non_snake_case)]
+#[repr(C)]
+struct JOYINFOEX {
+    dwSize: u32,
+    dwFlags: u32,
+    dwXpos: u32,
+    dwYpos: u32,
+    dwZpos: u32,
+    dwRpos: u32,
+    dwUpos: u32,
+    dwVpos: u32,
+    dwButtons: u32,
+    dwButtonNumber: u32,
+    dwPOV: u32,
+    dwReserved1: u32,
+    dwReserved2: u32,
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct JOYCAPSW {
+    wMid: u16,
+    wPid: u16,
+    szPname: [u16; 32],
+    wXmin: u32,
+    wXmax: u32,
+    wYmin: u32,
+    wYmax: u32,
+    wZmin: u32,
+    wZmax: u32,
+    wNumButtons: u32,
+    wPeriodMin: u32,
+    wPeriodMax: u32,
+    wRmin: u32,
+    wRmax: u32,
+    wUmin: u32,
+    wUmax: u32,
+    wVmin: u32,
+    wVmax: u32,
+    wCaps: u32,
+    wMaxAxes: u32,
+    wNumAxes: u32,
+    wMaxButtons: u32,
+    szRegKey: [u16; 32],
+    szOEMVxD: [u16; 260],
+}
+
+const JOY_RETURNALL: u32 = 0xFF;
+const JOYCAPS_HASPOV: u32 = 0x10;
+const JOYERR_NOERROR: u32 = 0;
+
+#[link(name = "winmm")]
+extern "system" {
+    fn joyGetPosEx(uJoyID: u32, pji: *mut JOYINFOEX) -> u32;
+    fn joyGetDevCapsW(uJoyID: usize, pjc: *mut JOYCAPSW, cbjc: u32) -> u32;
+}
+
+/// Reads joysticks through winmm, the same way GM8 does.
+pub struct Joysticks;
+
+impl Joysticks {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn poll(&mut self, id: usize) -> Joystick {
+        unsafe {
+            let mut caps: JOYCAPSW = mem::zeroed();
+            if joyGetDevCapsW(id, &mut caps, mem::size_of::<JOYCAPSW>() as _) != JOYERR_NOERROR {
+                return Joystick::new()
+            }
+            let mut info: JOYINFOEX = mem::zeroed();
+            info.dwSize = mem::size_of::<JOYINFOEX>() as _;
+            info.dwFlags = JOY_RETURNALL;
+            if joyGetPosEx(id as u32, &mut info) != JOYERR_NOERROR {
+                return Joystick::new()
+            }
+            let name_len = caps.szPname.iter().position(|&c| c == 0).unwrap_or(caps.szPname.len());
+            let positions = [info.dwXpos, info.dwYpos, info.dwZpos, info.dwRpos, info.dwUpos, info.dwVpos];
+            let ranges = [
+                (caps.wXmin, caps.wXmax),
+                (caps.wYmin, caps.wYmax),
+                (caps.wZmin, caps.wZmax),
+                (caps.wRmin, caps.wRmax),
+                (caps.wUmin, caps.wUmax),
+                (caps.wVmin, caps.wVmax),
+            ];
+            let mut axes = [AXIS_CENTRE; JOYSTICK_AXES];
+            for ((axis, position), (min, max)) in axes.iter_mut().zip(positions).zip(ranges) {
+                if max > min {
+                    let offset = u64::from(position.clamp(min, max) - min);
+                    *axis = (offset * 65535 / u64::from(max - min)) as u16;
+                }
+            }
+            Joystick {
+                connected: true,
+                name: String::from_utf16_lossy(&caps.szPname[..name_len]),
+                axis_count: caps.wNumAxes.min(JOYSTICK_AXES as u32) as u8,
+                button_count: caps.wNumButtons.min(JOYSTICK_BUTTONS.into()) as u8,
+                has_pov: caps.wCaps & JOYCAPS_HASPOV != 0,
+                axes,
+                buttons: info.dwButtons,
+                pov: info.dwPOV as u16,
+            }
+        }
+    }
+}
+
 fn get_display_settings() -> Option<DEVMODEW> {
     unsafe {
         let mut device = DEVMODEW { dmSize: mem::size_of::<DEVMODEW>() as _, ..mem::zeroed() };
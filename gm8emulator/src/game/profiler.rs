@@ -0,0 +1,219 @@
+use crate::{
+    game::{Game, GetAsset},
+    gml::mappings,
+    types::ID,
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// Something which can be timed by the profiler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProfileKey {
+    /// An object event. `object` is the object which owns the event code, which may be a parent of the instance.
+    Event { object: ID, event_type: usize, event_number: u32 },
+    Script(usize),
+    Function(usize),
+}
+
+/// Accumulated timing information for one ProfileKey.
+#[derive(Clone, Copy, Default)]
+pub struct ProfileEntry {
+    pub calls: u64,
+    /// Time spent inside this key, including everything it called. Recursive calls are only counted once.
+    pub total_time: Duration,
+    /// Time spent inside this key, excluding anything else profiled which it called.
+    pub self_time: Duration,
+}
+
+/// Which column the profiler report should be sorted by.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    Name,
+    Calls,
+    TotalTime,
+    SelfTime,
+}
+
+struct StackFrame {
+    key: ProfileKey,
+    start: Instant,
+    child_time: Duration,
+}
+
+/// Collects call counts and timings of events, scripts and kernel functions while the game runs.
+/// Does nothing unless it's enabled, so it can be left in place in all play modes.
+pub struct Profiler {
+    enabled: bool,
+    frames: u64,
+    stack: Vec<StackFrame>,
+    entries: HashMap<ProfileKey, ProfileEntry>,
+    folded: HashMap<Box<[ProfileKey]>, Duration>,
+}
+
+impl ProfileKey {
+    /// Gets a human-readable name for this key, such as "obj_player: Begin Step" or "script_move".
+    pub fn name(&self, game: &Game) -> String {
+        match *self {
+            Self::Event { object, event_type, event_number } => format!(
+                "{}: {}",
+                game.assets
+                    .objects
+                    .get_asset(object)
+                    .map(|x| game.decode_str(x.name.as_ref()).into_owned())
+                    .unwrap_or_else(|| format!("<object {}>", object)),
+                game.event_name(event_type, event_number),
+            ),
+            Self::Script(id) => game
+                .assets
+                .scripts
+                .get_asset(id as ID)
+                .map(|x| game.decode_str(x.name.as_ref()).into_owned())
+                .unwrap_or_else(|| format!("<script {}>", id)),
+            Self::Function(id) => match mappings::FUNCTIONS.index(id) {
+                Some((name, _)) => format!("{}()", name),
+                None => format!("<function {}>", id),
+            },
+        }
+    }
+}
+
+impl Profiler {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, frames: 0, stack: Vec::new(), entries: HashMap::new(), folded: HashMap::new() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turns profiling on or off. Anything which is currently being timed gets discarded.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.stack.clear();
+    }
+
+    /// Clears all the data collected so far.
+    pub fn reset(&mut self) {
+        self.frames = 0;
+        self.stack.clear();
+        self.entries.clear();
+        self.folded.clear();
+    }
+
+    /// Number of frames which have been run while profiling was enabled.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Should be called once at the start of every `Game::frame`.
+    pub fn frame_begin(&mut self) {
+        if self.enabled {
+            self.frames += 1;
+        }
+    }
+
+    /// Starts timing a key. Every call to this must be followed by a call to `exit`, even if execution failed.
+    #[inline]
+    pub fn enter(&mut self, key: ProfileKey) {
+        if self.enabled {
+            self.stack.push(StackFrame { key, start: Instant::now(), child_time: Duration::ZERO });
+        }
+    }
+
+    /// Stops timing the key which was most recently entered.
+    #[inline]
+    pub fn exit(&mut self) {
+        if self.enabled {
+            if let Some(frame) = self.stack.pop() {
+                let elapsed = frame.start.elapsed();
+                let self_time = elapsed.saturating_sub(frame.child_time);
+                let recursive = self.stack.iter().any(|x| x.key == frame.key);
+
+                let entry = self.entries.entry(frame.key).or_default();
+                entry.calls += 1;
+                entry.self_time += self_time;
+                if !recursive {
+                    entry.total_time += elapsed;
+                }
+
+                let path = self.stack.iter().map(|x| x.key).chain(std::iter::once(frame.key)).collect::<Box<[_]>>();
+                *self.folded.entry(path).or_default() += self_time;
+
+                if let Some(parent) = self.stack.last_mut() {
+                    parent.child_time += elapsed;
+                }
+            }
+        }
+    }
+
+    /// Gets all the profiled keys and their timings, sorted by the given column, with the biggest values first.
+    pub fn sorted_entries(&self, game: &Game, sort_by: SortBy) -> Vec<(String, ProfileEntry)> {
+        let mut entries = self.entries.iter().map(|(k, v)| (k.name(game), *v)).collect::<Vec<_>>();
+        match sort_by {
+            SortBy::Name => entries.sort_by(|(a, _), (b, _)| a.cmp(b)),
+            SortBy::Calls => entries.sort_by(|(_, a), (_, b)| b.calls.cmp(&a.calls)),
+            SortBy::TotalTime => entries.sort_by(|(_, a), (_, b)| b.total_time.cmp(&a.total_time)),
+            SortBy::SelfTime => entries.sort_by(|(_, a), (_, b)| b.self_time.cmp(&a.self_time)),
+        }
+        entries
+    }
+
+    /// Writes a tab-separated table of every profiled key to a file, sorted by self time.
+    pub fn write_report(&self, game: &Game, path: &Path) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "# {} frames profiled", self.frames)?;
+        writeln!(file, "name\tcalls\ttotal_us\tself_us\tself_us_per_frame")?;
+        for (name, entry) in self.sorted_entries(game, SortBy::SelfTime) {
+            writeln!(
+                file,
+                "{}\t{}\t{}\t{}\t{:.3}",
+                name,
+                entry.calls,
+                entry.total_time.as_micros(),
+                entry.self_time.as_micros(),
+                entry.self_time.as_secs_f64() * 1_000_000.0 / self.frames.max(1) as f64,
+            )?;
+        }
+        file.flush()
+    }
+
+    /// Writes the collected call stacks in the "folded" format used by flamegraph tools,
+    /// one stack per line, with the self time in microseconds as the sample count.
+    pub fn write_folded(&self, game: &Game, path: &Path) -> io::Result<()> {
+        let mut names: HashMap<ProfileKey, String> = HashMap::new();
+        let mut lines = self
+            .folded
+            .iter()
+            .map(|(stack, time)| {
+                let stack = stack
+                    .iter()
+                    .map(|key| names.entry(*key).or_insert_with(|| key.name(game).replace(';', ":")).clone())
+                    .collect::<Vec<_>>()
+                    .join(";");
+                (stack, time.as_micros())
+            })
+            .collect::<Vec<_>>();
+        lines.sort();
+
+        let mut file = BufWriter::new(File::create(path)?);
+        for (stack, micros) in lines.into_iter().filter(|(_, micros)| *micros > 0) {
+            writeln!(file, "{} {}", stack, micros)?;
+        }
+        file.flush()
+    }
+}
+
+impl Game {
+    /// Writes the profiler's report to a file, and its folded call stacks to the same path with ".folded" appended.
+    pub fn save_profile(&self, path: &Path) -> io::Result<()> {
+        self.profiler.write_report(self, path)?;
+        let mut folded_path = path.as_os_str().to_owned();
+        folded_path.push(".folded");
+        self.profiler.write_folded(self, Path::new(&folded_path))
+    }
+}
@@ -9,6 +9,8 @@ mod console;
 mod menu_bar;
 mod input_edit;
 mod macro_window;
+mod profiler_window;
+mod joystick_window;
 mod set_mouse_dialog;
 mod popup_dialog;
 
@@ -23,9 +25,11 @@ use crate::{
                 Openable,
             },
         },
+        registry::Registry,
         replay::{self, Replay},
-        Game, SceneChange,
+        Game, PlayType, SceneChange,
     },
+    gml::vfs,
     render::{atlas::AtlasRef, PrimitiveType, RendererState},
     types::Colour,
     imgui, input,
@@ -98,6 +102,9 @@ struct UIState<'g> {
     /// What the game thinks is the current state of the mouse buttons we care about
     mouse_state: [KeyState; 3],
 
+    /// How the user wants the joysticks to be from the next frame on, which starts out as the game's current state
+    joystick_state: [input::Joystick; input::JOYSTICK_COUNT],
+
     /// Mouse position set by the user to be taken into use next time they advance a frame
     new_mouse_pos: Option<(i32, i32)>,
 
@@ -424,6 +431,8 @@ pub enum WindowKind {
     Keybindings,
     Macro(usize),
     Console(usize),
+    Profiler,
+    Joystick,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -489,7 +498,14 @@ impl ProjectConfig {
 }
 
 impl Game {
-    pub fn record(&mut self, project_path: PathBuf, pause: bool, start_save_path: Option<&PathBuf>) {
+    /// Records a replay. `starting_files` is what was in the sandbox before the included files were exported.
+    pub fn record(
+        &mut self,
+        project_path: PathBuf,
+        pause: bool,
+        start_save_path: Option<&PathBuf>,
+        starting_files: Option<vfs::Sandbox>,
+    ) {
         let mut save_buffer = savestate::Buffer::new();
         let mut startup_successful = true;
 
@@ -501,6 +517,8 @@ impl Game {
         let mut config = ProjectConfig::from_file_or_default(&config_path);
 
         let mut replay = Replay::new(self.spoofed_time_nanos.unwrap_or(0), self.rand.seed());
+        replay.registry = self.registry.to_text();
+        replay.sandbox = starting_files;
 
         let mut context = imgui::Context::new();
         context.make_current();
@@ -586,6 +604,9 @@ impl Game {
         let mut game_running = true; // false indicates the game closed or crashed, and so advancing is not allowed
         let mut err_string: Option<String> = None;
 
+        // stubbed externals are part of the project, and get stored in its replays so they're used on playback too
+        replay.external_stubs = self.externals.stubs().to_vec();
+
         let savestate;
         let mut renderer_state;
 
@@ -602,6 +623,22 @@ impl Game {
                                 self.spoofed_time_nanos = Some(backup_replay.start_time);
                                 replay.start_seed = backup_replay.start_seed;
                                 replay.start_time = backup_replay.start_time;
+                                match Registry::from_text(&backup_replay.registry) {
+                                    Ok(registry) => {
+                                        self.registry = registry;
+                                        replay.registry = backup_replay.registry.clone();
+                                    },
+                                    Err(e) => {
+                                        err_string = Some(format!("Warning: Failed to load backup registry: {}", e))
+                                    },
+                                }
+                                if let Some(sandbox) = &backup_replay.sandbox {
+                                    self.vfs.load_state(Some(sandbox.clone()));
+                                    if let Err(e) = self.export_included_files() {
+                                        err_string = Some(format!("Warning: Failed to export included files: {}", e));
+                                    }
+                                }
+                                replay.sandbox = backup_replay.sandbox.clone();
                             }
 
                             if backup_replay.contains_part(&replay) {
@@ -621,6 +658,7 @@ impl Game {
             load_backup_recording!();
         }
 
+        self.externals.set_result_mode(replay.external_result_mode(PlayType::Record));
         if !save_paths[config.quicksave_slot].exists() || (pause && start_save_path.is_none()) {
             if let Err(e) = match self.init() {
                 Ok(()) => match self.scene_change {
@@ -746,12 +784,16 @@ impl Game {
                 },
             }
         }
+        // the replay may have come from a savestate or backup made before externals.ini was changed
+        replay.external_stubs = self.externals.stubs().to_vec();
+        self.externals.set_result_mode(replay.external_result_mode(PlayType::Record));
 
         if config.ui_maximised {
             self.window.set_maximised(true);
         } else {
             self.window.set_size((config.ui_width, config.ui_height));
         }
+        self.ui_window_size = (config.ui_width.into(), config.ui_height.into());
 
         for (i, state) in keyboard_state.iter_mut().enumerate() {
             if self.input.keyboard_check_direct(i as u8) {
@@ -763,6 +805,7 @@ impl Game {
                 *state = KeyState::Held;
             }
         }
+        let joystick_state = self.input.joysticks().clone();
 
         let mut keybind_path = project_path.clone();
         keybind_path.push("keybindings.cfg");
@@ -784,6 +827,8 @@ impl Game {
                 WindowKind::Keybindings => windows.push((Box::new(keybinds::KeybindWindow::open(0)), false)),
                 WindowKind::Macro(id) => windows.push((Box::new(macro_window::MacroWindow::open(*id)), false)),
                 WindowKind::Console(id) => windows.push((Box::new(console::ConsoleWindow::open(*id)), false)),
+                WindowKind::Profiler => windows.push((Box::new(profiler_window::ProfilerWindow::open(0)), false)),
+                WindowKind::Joystick => windows.push((Box::new(joystick_window::JoystickWindow::open(0)), false)),
                 WindowKind::Control 
                  | WindowKind::Game
                  | WindowKind::InstanceReports
@@ -808,6 +853,7 @@ impl Game {
             err_string,
             keyboard_state,
             mouse_state,
+            joystick_state,
             new_mouse_pos: None,
             setting_mouse_pos: false,
             ui_renderer_state,
@@ -1027,6 +1073,7 @@ impl UIState<'_> {
                 Event::Resize((width, height)) => {
                     self.config.ui_width = u16::try_from(width).unwrap_or(u16::MAX);
                     self.config.ui_height = u16::try_from(height).unwrap_or(u16::MAX);
+                    self.game.ui_window_size = (width as _, height as _);
                     io.set_display_size(imgui::Vec2(width as f32, height as f32));
                     self.game.renderer.resize_framebuffer(width as _, height as _, false);
                     self.clear_context_menu = true;
@@ -1070,6 +1117,7 @@ impl UIState<'_> {
 
             keyboard_state: &mut self.keyboard_state,
             mouse_state: &mut self.mouse_state,
+            joystick_state: &mut self.joystick_state,
             savestate: &mut self.cached_savestate,
             renderer_state: &mut self.game_renderer_state,
             save_buffer: &mut self.lz4_buffer,
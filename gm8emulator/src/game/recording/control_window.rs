@@ -9,6 +9,7 @@ use crate::{
         Game, SceneChange,
     },
     imgui,
+    input::{Joystick, JOYSTICK_BUTTONS, JOYSTICK_COUNT},
     types::Colour,
 };
 use std::time::Duration;
@@ -283,6 +284,7 @@ impl ControlWindow {
 
             self.update_keyboard_state(info.keyboard_state, new_frame);
             self.update_mouse_state(info.mouse_state, new_frame);
+            self.update_joystick_state(info.joystick_state, info.game.input.joysticks(), new_frame);
 
             if let Some((x, y)) = *info.new_mouse_pos {
                 new_frame.mouse_x = x;
@@ -317,6 +319,7 @@ impl ControlWindow {
         for (i, state) in info.mouse_state.iter_mut().enumerate() {
             state.reset_to(info.game.input.mouse_check_button(i as i8 + 1));
         }
+        *info.joystick_state = info.game.input.joysticks().clone();
 
         // Fake frame limiter stuff (don't actually frame-limit in record mode)
         if let Some(t) = info.game.spoofed_time_nanos.as_mut() {
@@ -364,6 +367,56 @@ impl ControlWindow {
         }
     }
 
+    /// Records whatever the user changed about the joysticks since the last frame.
+    fn update_joystick_state(
+        &self,
+        joystick_state: &[Joystick; JOYSTICK_COUNT],
+        current_state: &[Joystick; JOYSTICK_COUNT],
+        frame: &mut Frame,
+    ) {
+        for (id, (wanted, current)) in joystick_state.iter().zip(current_state.iter()).enumerate() {
+            let id = id as u8;
+            if !wanted.connected {
+                if current.connected {
+                    frame.inputs.push(replay::Input::JoystickDisconnect(id));
+                }
+                continue
+            }
+            // connecting a joystick starts it off centred with nothing pressed
+            let current = if current.connected { current.clone() } else { Joystick::new() };
+            if !current.connected
+                || wanted.name != current.name
+                || wanted.axis_count != current.axis_count
+                || wanted.button_count != current.button_count
+                || wanted.has_pov != current.has_pov
+            {
+                frame.inputs.push(replay::Input::JoystickConnect {
+                    id,
+                    name: wanted.name.clone(),
+                    axes: wanted.axis_count,
+                    buttons: wanted.button_count,
+                    has_pov: wanted.has_pov,
+                });
+            }
+            for (axis, (&position, &current_position)) in wanted.axes.iter().zip(current.axes.iter()).enumerate() {
+                if position != current_position {
+                    frame.inputs.push(replay::Input::JoystickAxis(id, axis as u8, position));
+                }
+            }
+            for button in 0..JOYSTICK_BUTTONS {
+                let mask = 1 << button;
+                match (wanted.buttons & mask != 0, current.buttons & mask != 0) {
+                    (true, false) => frame.inputs.push(replay::Input::JoystickButtonPress(id, button)),
+                    (false, true) => frame.inputs.push(replay::Input::JoystickButtonRelease(id, button)),
+                    _ => (),
+                }
+            }
+            if wanted.pov != current.pov {
+                frame.inputs.push(replay::Input::JoystickPov(id, wanted.pov));
+            }
+        }
+    }
+
     fn update_mouse_state(&self, mouse_state: &mut [KeyState; 3], frame: &mut Frame) {
         for (i, state) in mouse_state.iter().enumerate() {
             let i = i as i8 + 1;
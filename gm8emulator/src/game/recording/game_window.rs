@@ -2,8 +2,10 @@ use crate::{
     imgui,
     game::{
         Renderer,
+        screenshot,
         recording::{
             instance_report::InstanceReport,
+            keybinds::Binding,
             set_mouse_dialog::{SetMouseDialog, MouseDialogResult},
             window::{Window, DisplayInformation},
         },
@@ -151,10 +153,30 @@ impl GameWindow {
                 self.set_context_menu_instances(info);
             }
         }
+
+        if info.keybind_pressed(Binding::Screenshot) {
+            self.save_screenshot(info);
+        }
         
         info.frame.end();
     }
 
+    /// Saves the frame that was last played into the project's screenshots folder, named like replay screenshots.
+    fn save_screenshot(&self, info: &mut DisplayInformation) {
+        let frame = match info.config.current_frame.checked_sub(1) {
+            Some(frame) => frame,
+            None => return,
+        };
+        let dir = info.project_path.join("screenshots");
+        let image = info.game.stored_screenshot();
+        let result = std::fs::create_dir_all(&dir)
+            .map_err(|e| e.to_string())
+            .and_then(|()| image.save(dir.join(screenshot::file_name(frame))).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            *info.err_string = Some(format!("Failed to save screenshot: {}", e));
+        }
+    }
+
     fn display_context_menu(&mut self, info: &mut DisplayInformation) -> bool {
         for (label, id) in self.context_menu_options.as_ref().unwrap() {
             if info.frame.menu_item(&label) {
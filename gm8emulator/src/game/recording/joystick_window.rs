@@ -0,0 +1,112 @@
+use crate::{
+    game::recording::window::{DisplayInformation, Openable, Window},
+    imgui,
+    input::{Joystick, AXIS_CENTRE, JOYSTICK_AXES, JOYSTICK_BUTTONS, POV_CENTRED},
+};
+
+const AXIS_NAMES: [&str; JOYSTICK_AXES] = ["X", "Y", "Z", "R", "U", "V"];
+const BUTTONS_PER_ROW: u8 = 8;
+
+pub struct JoystickWindow {
+    is_open: bool,
+}
+
+impl Openable<Self> for JoystickWindow {
+    fn window_name() -> &'static str {
+        "Joysticks"
+    }
+
+    fn open(_id: usize) -> Self {
+        Self::new()
+    }
+}
+
+impl Window for JoystickWindow {
+    fn stored_kind(&self) -> Option<super::WindowKind> {
+        Some(super::WindowKind::Joystick)
+    }
+
+    fn name(&self) -> String {
+        "Joysticks".to_owned()
+    }
+
+    fn show_window(&mut self, info: &mut DisplayInformation) {
+        let DisplayInformation { frame, joystick_state, .. } = info;
+
+        frame.setup_next_window(imgui::Vec2(100.0, 100.0), Some(imgui::Vec2(320.0, 400.0)), None);
+        if frame.begin_window(Self::window_name(), None, true, false, Some(&mut self.is_open)) {
+            frame.text("Changes are applied on the next frame advance");
+            for (id, joystick) in joystick_state.iter_mut().enumerate() {
+                if frame.begin_tree_node(&format!("Joystick {}", id + 1)) {
+                    Self::show_joystick(frame, id, joystick);
+                    frame.pop_tree_node();
+                }
+            }
+        }
+        frame.end();
+    }
+
+    fn is_open(&self) -> bool {
+        self.is_open
+    }
+}
+
+impl JoystickWindow {
+    pub fn new() -> Self {
+        Self { is_open: true }
+    }
+
+    fn show_joystick(frame: &mut imgui::Frame, id: usize, joystick: &mut Joystick) {
+        if frame.checkbox(&format!("Connected##connected{}", id), &mut joystick.connected) {
+            if joystick.connected {
+                // a fully featured joystick, so that every function has something to read
+                *joystick = Joystick {
+                    connected: true,
+                    name: "Joystick".into(),
+                    axis_count: JOYSTICK_AXES as u8,
+                    button_count: JOYSTICK_BUTTONS,
+                    has_pov: true,
+                    ..Joystick::new()
+                };
+            } else {
+                *joystick = Joystick::new();
+            }
+        }
+        if !joystick.connected {
+            return
+        }
+
+        for (axis, name) in AXIS_NAMES.iter().enumerate().take(joystick.axis_count.into()) {
+            let mut value = (f64::from(joystick.axes[axis]) / 32767.5 - 1.0) as f32;
+            if frame.slider_float(&format!("{}##axis{}_{}", name, id, axis), &mut value, -1.0, 1.0) {
+                joystick.axes[axis] = ((f64::from(value) + 1.0) * 32767.5).round() as u16;
+            }
+        }
+        if frame.button(&format!("Centre##centre{}", id), imgui::Vec2(80.0, 20.0), None) {
+            joystick.axes = [AXIS_CENTRE; JOYSTICK_AXES];
+        }
+
+        for button in 0..joystick.button_count {
+            if button % BUTTONS_PER_ROW != 0 {
+                frame.same_line(0.0, -1.0);
+            }
+            let mut held = joystick.buttons & (1 << button) != 0;
+            if frame.checkbox(&format!("{}##button{}_{}", button + 1, id, button), &mut held) {
+                joystick.buttons ^= 1 << button;
+            }
+        }
+
+        if joystick.has_pov {
+            let mut pressed = joystick.pov != POV_CENTRED;
+            if frame.checkbox(&format!("POV##pov_pressed{}", id), &mut pressed) {
+                joystick.pov = if pressed { 0 } else { POV_CENTRED };
+            }
+            if pressed {
+                let mut degrees = f32::from(joystick.pov) / 100.0;
+                if frame.slider_float(&format!("Direction##pov{}", id), &mut degrees, 0.0, 359.99) {
+                    joystick.pov = (degrees * 100.0).round() as u16;
+                }
+            }
+        }
+    }
+}
@@ -29,6 +29,7 @@ pub enum Binding {
     ExportGmtas,
     ToggleMacros,
     SetMouse,
+    Screenshot,
 }
 impl Display for Binding {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
@@ -45,6 +46,7 @@ impl Display for Binding {
             Self::ExportGmtas => write!(f, "Export .gmtas"),
             Self::ToggleMacros => write!(f, "Toggle \"Run Macro\""),
             Self::SetMouse => write!(f, "Set Mouse"),
+            Self::Screenshot => write!(f, "Save screenshot"),
             //_ => write!(f, "{:?}", self),
         }
     }
@@ -64,6 +66,7 @@ impl Binding {
             Self::ExportGmtas => Some(KeyCombination::from(vec![Button::Control, Button::Shift, Button::E])),
             Self::ToggleMacros => Some(KeyCombination::from(vec![Button::Control, Button::Alpha1])),
             Self::SetMouse => Some(KeyCombination::from(vec![Button::Control, Button::M])),
+            Self::Screenshot => Some(KeyCombination::from(vec![Button::Control, Button::P])),
             //_ => None,
         }
     }
@@ -135,6 +138,7 @@ impl Keybindings {
         insert!(Binding::ExportGmtas);
         insert!(Binding::ToggleMacros);
         insert!(Binding::SetMouse);
+        insert!(Binding::Screenshot);
     }
 
     pub fn keybind_pressed(&self, bind: Binding, frame: &imgui::Frame) -> bool {
@@ -6,6 +6,8 @@ use crate::{
         input_edit::InputEditWindow,
         console::ConsoleWindow,
         macro_window::MacroWindow,
+        profiler_window::ProfilerWindow,
+        joystick_window::JoystickWindow,
         window::{
             Openable,
         },
@@ -79,6 +81,8 @@ impl UIState<'_> {
                     openable! {
                         single KeybindWindow,
                         single InputEditWindow,
+                        single ProfilerWindow,
+                        single JoystickWindow,
                         multi ConsoleWindow,
                         multi MacroWindow,
                     }
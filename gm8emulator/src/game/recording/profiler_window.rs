@@ -0,0 +1,122 @@
+use crate::{
+    game::{
+        profiler::SortBy,
+        recording::window::{Window, Openable, DisplayInformation},
+    },
+    imgui,
+};
+
+pub struct ProfilerWindow {
+    is_open: bool,
+}
+
+impl Openable<Self> for ProfilerWindow {
+    fn window_name() -> &'static str {
+        "Profiler"
+    }
+
+    fn open(_id: usize) -> Self {
+        Self::new()
+    }
+}
+impl Window for ProfilerWindow {
+    fn stored_kind(&self) -> Option<super::WindowKind> {
+        Some(super::WindowKind::Profiler)
+    }
+
+    fn name(&self) -> String {
+        "Profiler".to_owned()
+    }
+
+    fn show_window(&mut self, info: &mut DisplayInformation) {
+        let DisplayInformation {
+            frame,
+            game,
+            project_path,
+            err_string,
+            ..
+        } = info;
+
+        frame.setup_next_window(imgui::Vec2(100.0, 100.0), Some(imgui::Vec2(600.0, 400.0)), None);
+        if frame.begin_window(Self::window_name(), None, true, false, Some(&mut self.is_open)) {
+            let mut enabled = game.profiler.is_enabled();
+            if frame.checkbox("Enabled", &mut enabled) {
+                game.profiler.set_enabled(enabled);
+            }
+            frame.same_line(0.0, -1.0);
+            if frame.button("Reset", imgui::Vec2(60.0, 20.0), None) {
+                game.profiler.reset();
+            }
+            frame.same_line(0.0, -1.0);
+            if frame.button("Save", imgui::Vec2(60.0, 20.0), None) {
+                let path = project_path.join("profile.txt");
+                if let Err(e) = game.save_profile(&path) {
+                    **err_string = Some(format!("Couldn't save profile to {}: {}", path.to_string_lossy(), e));
+                }
+            }
+            frame.same_line(0.0, -1.0);
+            let frames = game.profiler.frames();
+            frame.text(&format!("{} frames profiled", frames));
+
+            let table_size = frame.window_size() - imgui::Vec2(0.0, 60.0);
+            if frame.begin_table(
+                "Profile",
+                5,
+                (cimgui_sys::ImGuiTableFlags__ImGuiTableFlags_RowBg
+                    | cimgui_sys::ImGuiTableFlags__ImGuiTableFlags_Borders
+                    | cimgui_sys::ImGuiTableFlags__ImGuiTableFlags_Sortable
+                    | cimgui_sys::ImGuiTableFlags__ImGuiTableFlags_ScrollY) as i32,
+                table_size,
+                0.0
+            ) {
+                frame.table_setup_column("Name", 0, 0.0);
+                frame.table_setup_column("Calls", cimgui_sys::ImGuiTableColumnFlags__ImGuiTableColumnFlags_WidthFixed as i32, 70.0);
+                frame.table_setup_column("Total (ms)", cimgui_sys::ImGuiTableColumnFlags__ImGuiTableColumnFlags_WidthFixed as i32, 80.0);
+                frame.table_setup_column("Self (ms)", cimgui_sys::ImGuiTableColumnFlags__ImGuiTableColumnFlags_WidthFixed as i32, 80.0);
+                frame.table_setup_column("Self/frame (us)", cimgui_sys::ImGuiTableColumnFlags__ImGuiTableColumnFlags_WidthFixed as i32, 100.0);
+                frame.table_setup_scroll_freeze(0, 1); // freeze header row
+                frame.table_headers_row();
+
+                let (sort_by, ascending) = match frame.table_sort_column() {
+                    Some((0, ascending)) => (SortBy::Name, ascending),
+                    Some((1, ascending)) => (SortBy::Calls, ascending),
+                    Some((2, ascending)) => (SortBy::TotalTime, ascending),
+                    Some((_, ascending)) => (SortBy::SelfTime, ascending),
+                    None => (SortBy::SelfTime, false),
+                };
+                let mut entries = game.profiler.sorted_entries(game, sort_by);
+                // names are sorted A-Z, everything else is sorted biggest first
+                if ascending != (sort_by == SortBy::Name) {
+                    entries.reverse();
+                }
+
+                for (name, entry) in entries.iter() {
+                    frame.table_next_row(0, 0.0);
+                    frame.table_next_column();
+                    frame.text(name);
+                    frame.table_next_column();
+                    frame.text(&entry.calls.to_string());
+                    frame.table_next_column();
+                    frame.text(&format!("{:.3}", entry.total_time.as_secs_f64() * 1000.0));
+                    frame.table_next_column();
+                    frame.text(&format!("{:.3}", entry.self_time.as_secs_f64() * 1000.0));
+                    frame.table_next_column();
+                    frame.text(&format!("{:.1}", entry.self_time.as_secs_f64() * 1_000_000.0 / frames.max(1) as f64));
+                }
+
+                frame.end_table();
+            }
+        }
+        frame.end();
+    }
+
+    fn is_open(&self) -> bool {
+        self.is_open
+    }
+}
+
+impl ProfilerWindow {
+    pub fn new() -> Self {
+        Self { is_open: true }
+    }
+}
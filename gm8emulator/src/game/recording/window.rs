@@ -1,7 +1,7 @@
 use crate::{
-    imgui,
+    imgui, input,
     game::{
-        Game,
+        Game, PlayType,
         recording::{WindowKind, KeyState, ProjectConfig, instance_report::InstanceReport, keybinds::{Keybindings, Binding}, popup_dialog::Dialog},
         replay::{Replay, FrameRng},
         savestate::{self, SaveState},
@@ -23,6 +23,7 @@ pub struct DisplayInformation<'a, 'f> {
 
     pub keyboard_state: &'a mut [KeyState; 256],
     pub mouse_state: &'a mut [KeyState; 3],
+    pub joystick_state: &'a mut [input::Joystick; input::JOYSTICK_COUNT],
     pub savestate: &'a mut SaveState,
     pub renderer_state: &'a mut RendererState,
     pub save_buffer: &'a mut savestate::Buffer,
@@ -216,6 +217,7 @@ impl DisplayInformation<'_, '_> {
             *state =
                 if self.game.input.mouse_check_button(i as i8 + 1) { KeyState::Held } else { KeyState::Neutral };
         }
+        *self.joystick_state = self.game.input.joysticks().clone();
 
         self.clear_context_menu();
         *self.new_rand = None;
@@ -232,6 +234,8 @@ impl DisplayInformation<'_, '_> {
             }
         } else {
             *self.replay = new_replay;
+            self.replay.external_stubs = self.game.externals.stubs().to_vec();
+            self.game.externals.set_result_mode(self.replay.external_result_mode(PlayType::Record));
         }
         self.config.rerecords += 1;
         self.config.save();
@@ -0,0 +1,235 @@
+use crate::{
+    game::Game,
+    gml::{self, Value},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Registry roots which can be selected with `registry_set_root`, in order.
+pub const ROOTS: [&str; 4] = ["HKEY_CURRENT_USER", "HKEY_LOCAL_MACHINE", "HKEY_CLASSES_ROOT", "HKEY_USERS"];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RegistryValue {
+    String(String),
+    Real(f64),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct RegistryKey {
+    /// Full path of this key including its root, as it was first written.
+    path: String,
+    /// Maps lowercase value names to the original name and the value.
+    values: BTreeMap<String, (String, RegistryValue)>,
+}
+
+/// An emulated Windows registry. Registry keys and value names are case-insensitive, same as on Windows.
+/// It's part of the game state, so it's included in savestates, and can be stored in a human-readable file.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Registry {
+    root: usize,
+    /// Maps lowercase key paths to keys.
+    keys: BTreeMap<String, RegistryKey>,
+}
+
+/// Normalises a key path, removing any leading, trailing or duplicate backslashes.
+fn key_path(root: usize, key: &str) -> String {
+    let mut path = String::from(ROOTS[root]);
+    for part in key.split('\\').filter(|x| !x.is_empty()) {
+        path.push('\\');
+        path.push_str(part);
+    }
+    path
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Parses a quoted string from the start of `s`, returning it and whatever came after the closing quote.
+fn unescape(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut out = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((out, &s[i + 2..])),
+            '\\' => match chars.next()?.1 {
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                c => out.push(c),
+            },
+            c => out.push(c),
+        }
+    }
+    None
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the root used by the `_ext` registry functions, as an index into `ROOTS`.
+    pub fn root(&self) -> usize {
+        self.root
+    }
+
+    /// Sets the root used by the `_ext` registry functions. Invalid roots are ignored, as in GM8.
+    pub fn set_root(&mut self, root: i32) {
+        if (0..ROOTS.len() as i32).contains(&root) {
+            self.root = root as usize;
+        }
+    }
+
+    pub fn get(&self, root: usize, key: &str, name: &str) -> Option<&RegistryValue> {
+        self.keys
+            .get(&key_path(root, key).to_lowercase())
+            .and_then(|k| k.values.get(&name.to_lowercase()))
+            .map(|(_, value)| value)
+    }
+
+    pub fn set(&mut self, root: usize, key: &str, name: &str, value: RegistryValue) {
+        let path = key_path(root, key);
+        let key = self
+            .keys
+            .entry(path.to_lowercase())
+            .or_insert_with(|| RegistryKey { path, values: BTreeMap::new() });
+        key.values
+            .entry(name.to_lowercase())
+            .and_modify(|(_, v)| *v = value.clone())
+            .or_insert_with(|| (name.to_string(), value));
+    }
+
+    /// Writes the registry as text, in a format similar to a .reg file:
+    /// a line with the full path of each key in square brackets, followed by one line for each of its values.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for key in self.keys.values() {
+            let _ = writeln!(text, "[{}]", key.path);
+            for (name, value) in key.values.values() {
+                let _ = match value {
+                    RegistryValue::String(s) => writeln!(text, "{}={}", escape(name), escape(s)),
+                    RegistryValue::Real(r) => writeln!(text, "{}=real:{}", escape(name), r),
+                };
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Reads a registry in the format written by `to_text`. Lines starting with ';' are ignored.
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut registry = Self::new();
+        let mut current_key: Option<(usize, String)> = None;
+        for (line_number, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+            if line.is_empty() || line.starts_with(';') {
+                continue
+            }
+            if let Some(path) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+                let (root, key) = path.split_once('\\').unwrap_or((path, ""));
+                match ROOTS.iter().position(|r| r.eq_ignore_ascii_case(root)) {
+                    Some(root) => current_key = Some((root, key.to_string())),
+                    None => return Err(format!("line {}: unknown registry root {}", line_number, root)),
+                }
+            } else {
+                let (root, key) = current_key
+                    .as_ref()
+                    .ok_or_else(|| format!("line {}: value outside of a registry key", line_number))?;
+                let (name, rest) =
+                    unescape(line).ok_or_else(|| format!("line {}: expected a quoted value name", line_number))?;
+                let value = match rest.trim_start().strip_prefix('=').map(str::trim_start) {
+                    Some(v) if v.starts_with('"') => match unescape(v) {
+                        Some((s, _)) => RegistryValue::String(s),
+                        None => return Err(format!("line {}: unterminated string", line_number)),
+                    },
+                    Some(v) => match v.strip_prefix("real:").and_then(|x| x.trim().parse::<f64>().ok()) {
+                        Some(r) => RegistryValue::Real(r),
+                        None => return Err(format!("line {}: invalid value {}", line_number, v)),
+                    },
+                    None => return Err(format!("line {}: expected '=' after value name", line_number)),
+                };
+                registry.set(*root, key, &name, value);
+            }
+        }
+        Ok(registry)
+    }
+}
+
+impl Game {
+    /// Loads the registry from a file, if it exists. If `write_back` is set, any changes the game makes to the
+    /// registry will be written to that file straight away. Otherwise they only exist in memory and in savestates.
+    pub fn load_registry(&mut self, path: &Path, write_back: bool) -> Result<(), String> {
+        match fs::read_to_string(path) {
+            Ok(text) => self.registry = Registry::from_text(&text)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => self.registry = Registry::new(),
+            Err(e) => return Err(e.to_string()),
+        }
+        self.registry_file = if write_back { Some(PathBuf::from(path)) } else { None };
+        Ok(())
+    }
+
+    /// Gets the key the registry functions without `_ext` use. This key is unique to each game.
+    fn registry_game_key(&self) -> String {
+        format!("Software\\Game Maker\\Games\\{}", self.game_id)
+    }
+
+    /// Reads a registry value. If `key` is None, the value is read from the game's own key.
+    pub fn registry_read(&self, key: Option<&[u8]>, name: &[u8]) -> Option<&RegistryValue> {
+        let name = self.decode_str(name);
+        match key {
+            Some(key) => self.registry.get(self.registry.root(), &self.decode_str(key), &name),
+            None => self.registry.get(0, &self.registry_game_key(), &name),
+        }
+    }
+
+    /// Writes a registry value, and saves the registry file if there is one.
+    /// If `key` is None, the value is written to the game's own key.
+    pub fn registry_write(&mut self, key: Option<&[u8]>, name: &[u8], value: RegistryValue) {
+        let name = self.decode_str(name).into_owned();
+        let (root, key) = match key {
+            Some(key) => (self.registry.root(), self.decode_str(key).into_owned()),
+            None => (0, self.registry_game_key()),
+        };
+        self.registry.set(root, &key, &name, value);
+        if let Some(path) = &self.registry_file {
+            if let Err(e) = fs::write(path, self.registry.to_text()) {
+                eprintln!("Couldn't save registry to {}: {}", path.to_string_lossy(), e);
+            }
+        }
+    }
+
+    /// Converts a registry value into a GML string, returning "" if it doesn't exist or isn't a string.
+    pub fn registry_value_to_string(&self, value: Option<&RegistryValue>) -> Value {
+        match value {
+            Some(RegistryValue::String(s)) => match self.encode_str_maybe(s) {
+                Some(bytes) => gml::String::from(bytes.as_ref()).into(),
+                None => s.as_str().into(),
+            },
+            _ => "".into(),
+        }
+    }
+
+    /// Converts a registry value into a GML real, returning 0 if it doesn't exist or isn't a real.
+    pub fn registry_value_to_real(value: Option<&RegistryValue>) -> Value {
+        match value {
+            Some(RegistryValue::Real(r)) => (*r).into(),
+            _ => Default::default(),
+        }
+    }
+}
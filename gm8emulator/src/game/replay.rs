@@ -1,4 +1,10 @@
-use crate::gml::Value;
+use crate::{
+    game::{
+        external::{stub::Stub, ResultMode},
+        PlayType,
+    },
+    gml::{vfs::Sandbox, Value},
+};
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
 use lzzzz::lz4;
 use serde::{Deserialize, Serialize};
@@ -23,6 +29,44 @@ pub struct Replay {
 
     // List of frames in this replay.
     frames: Vec<Frame>,
+
+    // User-supplied stand-ins for external functions, which must be the same for the replay to sync.
+    pub external_stubs: Vec<Stub>,
+
+    // Whether the results of calls to real DLLs are stored as events, rather than the DLLs being called on playback.
+    pub external_results: bool,
+
+    // Contents of the registry when the game started, in the format of a registry file.
+    pub registry: String,
+
+    // Contents of the filesystem sandbox when the game started, such as files from --sandbox-seed. Included files
+    // aren't part of it, since they get exported again on playback.
+    pub sandbox: Option<Sandbox>,
+}
+
+// Replays as they were in version 1 files, before external calls, the registry and the sandbox were recorded
+#[derive(Deserialize)]
+struct ReplayV1 {
+    start_time: u128,
+    start_seed: i32,
+    startup_events: Vec<Event>,
+    frames: Vec<Frame>,
+}
+
+impl From<ReplayV1> for Replay {
+    fn from(replay: ReplayV1) -> Self {
+        let ReplayV1 { start_time, start_seed, startup_events, frames } = replay;
+        Self {
+            start_time,
+            start_seed,
+            startup_events,
+            frames,
+            external_stubs: Vec::new(),
+            external_results: false,
+            registry: String::new(),
+            sandbox: None,
+        }
+    }
 }
 
 #[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
@@ -69,6 +113,15 @@ pub enum Event {
     ShowMenu(Value),     // value returned from show_menu()
     ShowMessage,         // acknowledges that a show_message() does not need to be shown during replay
     ShowQuestion(Value), // value returned from show_question()
+
+    // (new events go at the end so that existing replay files can still be read)
+    ShowMessageExt(Value), // value returned from show_message_ext()
+    GetColour(Value),      // value returned from get_color()
+    GetPath(Value),        // value returned from get_open_filename(), get_save_filename() or get_directory()
+    HighscoreName(Value),  // name entered by highscore_show() or highscore_add_current()
+    SplashClosed,          // acknowledges that a splash screen or the game information was closed
+    // a call to a real DLL and what it returned, which is given back on playback instead of calling the DLL again
+    External { dll: String, symbol: String, args: Vec<Value>, result: Value },
 }
 
 // An input event which takes place during a frame
@@ -80,6 +133,12 @@ pub enum Input {
     MouseRelease(i8),
     MouseWheelUp,
     MouseWheelDown,
+    JoystickConnect { id: u8, name: String, axes: u8, buttons: u8, has_pov: bool },
+    JoystickDisconnect(u8),
+    JoystickButtonPress(u8, u8),   // joystick, button from 0
+    JoystickButtonRelease(u8, u8), // joystick, button from 0
+    JoystickAxis(u8, u8, u16),     // joystick, axis, raw position
+    JoystickPov(u8, u16),          // joystick, raw direction
 }
 
 #[derive(Debug)]
@@ -98,8 +157,26 @@ pub enum WriteError {
 }
 
 impl Replay {
+    /// How results of external calls should be handled when playing this replay in the given way.
+    pub fn external_result_mode(&self, play_type: PlayType) -> ResultMode {
+        match play_type {
+            PlayType::Record if self.external_results => ResultMode::Record,
+            PlayType::Replay if self.external_results => ResultMode::Replay,
+            _ => ResultMode::Live,
+        }
+    }
+
     pub fn new(start_time: u128, start_seed: i32) -> Self {
-        Self { start_time, start_seed, startup_events: Vec::new(), frames: Vec::new() }
+        Self {
+            start_time,
+            start_seed,
+            startup_events: Vec::new(),
+            frames: Vec::new(),
+            external_stubs: Vec::new(),
+            external_results: true,
+            registry: String::new(),
+            sandbox: None,
+        }
     }
 
     // Loads a Replay from a gmtas-format file (doesn't check the file extension)
@@ -109,7 +186,7 @@ impl Replay {
         let mut file = File::open(path).map_err(ReadError::IOErr)?;
 
         match file.read_u32::<LE>() {
-            Ok(1) => {
+            Ok(version @ 1..=2) => {
                 let init_size = file.metadata().map(|m| m.len() as usize + 1).unwrap_or(0);
                 lz4_buf.reserve(init_size);
                 match file.read_to_end(&mut lz4_buf) {
@@ -120,8 +197,11 @@ impl Replay {
                             match lz4::decompress(block, bin_buf.as_mut_slice()) {
                                 Ok(len) => {
                                     unsafe { bin_buf.set_len(len) };
-                                    bincode::deserialize::<'_, Self>(bin_buf.as_slice())
-                                        .map_err(ReadError::DeserializeErr)
+                                    match version {
+                                        1 => bincode::deserialize::<'_, ReplayV1>(bin_buf.as_slice()).map(Self::from),
+                                        _ => bincode::deserialize::<'_, Self>(bin_buf.as_slice()),
+                                    }
+                                    .map_err(ReadError::DeserializeErr)
                                 },
                                 Err(err) => Err(ReadError::DecompressErr(err)),
                             }
@@ -145,7 +225,7 @@ impl Replay {
             Ok(()) => match lz4::compress_to_vec(bin_buf.as_slice(), lz4_buf.as_mut(), lz4::ACC_LEVEL_DEFAULT) {
                 Ok(_length) => {
                     match OpenOptions::new().create(true).write(true).truncate(true).open(path).and_then(|mut f| {
-                        f.write_u32::<LE>(1).and_then(|_| {
+                        f.write_u32::<LE>(2).and_then(|_| {
                             f.write_u64::<LE>(bin_buf.len() as u64).and_then(|_| f.write_all(lz4_buf.as_slice()))
                         })
                     }) {
@@ -1,10 +1,11 @@
 use crate::{
     game::{
-        audio::AudioState, draw, external, includedfile::IncludedFile, model::Model, particle,
-        pathfinding::PotentialStepSettings, surface::Surface, transition::UserTransition, Assets, Game, Replay,
-        RoomState, Version,
+        audio::AudioState, dialog::MessageSettings, draw, external, highscore::Highscores, includedfile::IncludedFile,
+        model::Model, mplay::MultiplayerState, particle, pathfinding::PotentialStepSettings, registry::Registry,
+        splash::{GameInfo, SplashOverlay, SplashSettings}, surface::Surface, transition::UserTransition, Assets, Game,
+        Replay, RoomState, Version,
     },
-    gml::{self, ds, rand::Random, Compiler},
+    gml::{self, ds, rand::Random, vfs, Compiler},
     handleman::HandleList,
     input::Input,
     instance::DummyFieldHolder,
@@ -70,6 +71,10 @@ pub struct SaveState {
     pub draw_alpha: Real,
     pub draw_halign: draw::Halign,
     pub draw_valign: draw::Valign,
+    pub message_settings: MessageSettings,
+    pub splash_settings: SplashSettings,
+    pub splash_overlay: Option<SplashOverlay>,
+    pub game_info: GameInfo,
     pub surfaces: Vec<Option<Surface>>,
     pub surface_target: Option<i32>,
     pub models: Vec<Option<Model>>,
@@ -103,6 +108,10 @@ pub struct SaveState {
 
     pub game_id: i32,
     pub program_directory: gml::String,
+    pub registry: Registry,
+    pub highscores: Highscores,
+    pub mplay: MultiplayerState,
+    pub vfs: Option<vfs::Sandbox>,
     pub included_files: Vec<IncludedFile>,
     pub gm_version: Version,
     pub spoofed_time_nanos: Option<u128>,
@@ -163,6 +172,10 @@ impl SaveState {
             draw_alpha: game.draw_alpha.clone(),
             draw_halign: game.draw_halign.clone(),
             draw_valign: game.draw_valign.clone(),
+            message_settings: game.message_settings.clone(),
+            splash_settings: game.splash_settings.clone(),
+            splash_overlay: game.splash_overlay.clone(),
+            game_info: game.game_info.clone(),
             surfaces: game.surfaces.clone(),
             surface_target: game.surface_target,
             models: game.models.clone(),
@@ -192,6 +205,10 @@ impl SaveState {
             error_last: game.error_last.clone(),
             game_id: game.game_id.clone(),
             program_directory: game.program_directory.clone(),
+            registry: game.registry.clone(),
+            highscores: game.highscores.clone(),
+            mplay: game.mplay.state.clone(),
+            vfs: game.vfs.state(),
             included_files: game.included_files.clone(),
             gm_version: game.gm_version.clone(),
             spoofed_time_nanos: game.spoofed_time_nanos,
@@ -221,11 +238,12 @@ impl SaveState {
             game.renderer.reset_target();
         }
 
-        game.externals.load_state(self.externals);
+        // GML stubs get compiled as the externals are remade, so the compiler has to be loaded first
+        game.compiler = self.compiler;
+        game.externals.load_state(self.externals, &mut game.compiler);
 
         game.surface_fix = self.surface_fix;
 
-        game.compiler = self.compiler;
         game.rand = self.rand;
         game.input = self.input;
         game.assets = self.assets;
@@ -255,6 +273,10 @@ impl SaveState {
         game.draw_alpha = self.draw_alpha;
         game.draw_halign = self.draw_halign;
         game.draw_valign = self.draw_valign;
+        game.message_settings = self.message_settings;
+        game.splash_settings = self.splash_settings;
+        game.splash_overlay = self.splash_overlay;
+        game.game_info = self.game_info;
         game.surfaces = surfaces;
         game.surface_target = self.surface_target;
         game.models = self.models;
@@ -283,6 +305,10 @@ impl SaveState {
         game.error_last = self.error_last;
         game.game_id = self.game_id;
         game.program_directory = self.program_directory;
+        game.registry = self.registry;
+        game.highscores = self.highscores;
+        game.mplay.state = self.mplay;
+        game.vfs.load_state(self.vfs);
         game.included_files = self.included_files;
         game.gm_version = self.gm_version;
         game.spoofed_time_nanos = self.spoofed_time_nanos;
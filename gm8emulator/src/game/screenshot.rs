@@ -0,0 +1,140 @@
+//! Saving the game's framebuffer as PNGs, and comparing it against reference images for visual regression tests.
+
+use crate::game::Game;
+use image::RgbaImage;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Which frames of a replay to capture, and optionally which reference images to compare them against.
+pub struct ScreenshotSettings {
+    pub dir: PathBuf,
+    pub first_frame: usize,
+    pub last_frame: Option<usize>,
+    pub reference_dir: Option<PathBuf>,
+    pub tolerance: u8,
+}
+
+impl Default for ScreenshotSettings {
+    fn default() -> Self {
+        Self { dir: "screenshots".into(), first_frame: 0, last_frame: None, reference_dir: None, tolerance: 0 }
+    }
+}
+
+impl ScreenshotSettings {
+    fn in_range(&self, frame: usize) -> bool {
+        frame >= self.first_frame && !matches!(self.last_frame, Some(last) if frame > last)
+    }
+}
+
+/// The result of comparing a frame to a reference image.
+pub struct Comparison {
+    pub differing_pixels: usize,
+    pub max_difference: u8,
+}
+
+impl Comparison {
+    pub fn matches(&self) -> bool {
+        self.differing_pixels == 0
+    }
+}
+
+/// The filename a frame's screenshot gets saved under, which is also where its reference image is looked for.
+pub fn file_name(frame: usize) -> String {
+    format!("frame_{:06}.png", frame)
+}
+
+/// Turns pixels from the renderer into an image. The window's alpha channel is meaningless, so it's made opaque.
+fn to_image(pixels: Box<[u8]>, width: u32, height: u32) -> RgbaImage {
+    let mut image = RgbaImage::from_raw(width, height, pixels.into_vec()).expect("Framebuffer had the wrong size");
+    image.pixels_mut().for_each(|p| p[3] = 255);
+    image
+}
+
+/// Compares an image to a reference, ignoring alpha. A pixel only counts as different if one of its
+/// channels is more than `tolerance` away from the reference, to allow for rounding differences in blending.
+pub fn compare(image: &RgbaImage, reference: &RgbaImage, tolerance: u8) -> Result<Comparison, String> {
+    if image.dimensions() != reference.dimensions() {
+        let (w, h) = image.dimensions();
+        let (ref_w, ref_h) = reference.dimensions();
+        return Err(format!("frame is {}x{} but the reference image is {}x{}", w, h, ref_w, ref_h))
+    }
+    let mut comparison = Comparison { differing_pixels: 0, max_difference: 0 };
+    for (pixel, ref_pixel) in image.pixels().zip(reference.pixels()) {
+        let difference = (0..3).map(|i| pixel[i].max(ref_pixel[i]) - pixel[i].min(ref_pixel[i])).max().unwrap_or(0);
+        comparison.max_difference = comparison.max_difference.max(difference);
+        if difference > tolerance {
+            comparison.differing_pixels += 1;
+        }
+    }
+    Ok(comparison)
+}
+
+/// Compares an image to a reference image file, which can be in any format the image crate can read.
+pub fn compare_to_file(image: &RgbaImage, path: &Path, tolerance: u8) -> Result<Comparison, String> {
+    let reference = image::open(path).map_err(|e| format!("couldn't open {}: {}", path.to_string_lossy(), e))?;
+    compare(image, &reference.to_rgba8(), tolerance)
+}
+
+/// Tracks the screenshots being taken over the course of a replay.
+pub struct Screenshotter {
+    settings: ScreenshotSettings,
+    mismatches: usize,
+}
+
+impl Screenshotter {
+    pub fn new(settings: ScreenshotSettings) -> io::Result<Self> {
+        fs::create_dir_all(&settings.dir)?;
+        Ok(Self { settings, mismatches: 0 })
+    }
+
+    /// How many frames haven't matched their reference images so far.
+    pub fn mismatches(&self) -> usize {
+        self.mismatches
+    }
+}
+
+impl Game {
+    /// Captures the frame that was just drawn, at the size of the window.
+    pub fn screenshot(&self) -> RgbaImage {
+        let (width, height) = self.window_inner_size;
+        to_image(self.renderer.get_pixels(0, 0, width as i32, height as i32), width, height)
+    }
+
+    /// Captures the game's last frame while the TAS UI is drawn over it, which is when it's kept stored away.
+    pub fn stored_screenshot(&self) -> RgbaImage {
+        let (width, height) = self.renderer.stored_size();
+        to_image(self.renderer.stored_pixels(), width, height)
+    }
+
+    /// Saves and compares the frame that was just drawn, if it's in the range the replay should capture.
+    pub fn replay_screenshot(&mut self, frame: usize) {
+        let image = match &self.screenshotter {
+            Some(screenshotter) if screenshotter.settings.in_range(frame) => self.screenshot(),
+            _ => return,
+        };
+        let screenshotter = self.screenshotter.as_mut().unwrap();
+        let settings = &screenshotter.settings;
+        let name = file_name(frame);
+        if let Err(e) = image.save(settings.dir.join(&name)) {
+            eprintln!("Failed to save screenshot of frame {}: {}", frame, e);
+        }
+        if let Some(reference_dir) = &settings.reference_dir {
+            match compare_to_file(&image, &reference_dir.join(&name), settings.tolerance) {
+                Ok(comparison) if comparison.matches() => (),
+                Ok(comparison) => {
+                    eprintln!(
+                        "Frame {} doesn't match its reference: {} pixels differ, by up to {}",
+                        frame, comparison.differing_pixels, comparison.max_difference,
+                    );
+                    screenshotter.mismatches += 1;
+                },
+                Err(e) => {
+                    eprintln!("Frame {} couldn't be compared: {}", frame, e);
+                    screenshotter.mismatches += 1;
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,712 @@
+use crate::{
+    game::{
+        dialog::{self, DialogInput, MessageFont, Modal, Rect},
+        replay, Game,
+    },
+    gml::{self, file},
+    input::Button,
+    render::atlas::AtlasRef,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// The font used for text splash screens and the game information. Its size and style change with the text.
+const TEXT_FONT: &str = "Arial";
+const CLOSE_BUTTON_SIZE: i32 = dialog::CAPTION_HEIGHT - 4;
+const SCROLL_STEP: i32 = 16;
+
+/// Settings for splash screens, which can be changed with the splash_set_* functions.
+/// The ones which only make sense for a separate window (such as staying on top) are kept but have no effect.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SplashSettings {
+    pub caption: gml::String,
+    pub fullscreen: bool,
+    pub border: bool,
+    pub size: (i32, i32),
+    pub position: (i32, i32),
+    pub adapt: bool,
+    pub top: bool,
+    pub colour: i32,
+    pub main: bool,
+    pub scale: f64,
+    pub cursor: bool,
+    pub interrupt: bool,
+    pub stop_key: bool,
+    pub close_button: bool,
+    pub stop_mouse: bool,
+}
+
+impl Default for SplashSettings {
+    fn default() -> Self {
+        Self {
+            caption: "".into(),
+            fullscreen: false,
+            border: true,
+            size: (640, 480),
+            position: (-1, -1),
+            adapt: true,
+            top: true,
+            colour: 0,
+            main: true,
+            scale: 0.0,
+            cursor: true,
+            interrupt: true,
+            stop_key: true,
+            close_button: true,
+            stop_mouse: true,
+        }
+    }
+}
+
+/// The game information from Global Game Settings, shown by show_info. The text can be replaced with load_info.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameInfo {
+    pub caption: gml::String,
+    pub colour: i32,
+    pub new_window: bool,
+    pub size: (i32, i32),
+    pub border: bool,
+    pub freeze: bool,
+    pub text: gml::String,
+}
+
+/// A piece of text where the formatting doesn't change.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TextRun {
+    pub text: Vec<u8>,
+    pub bold: bool,
+    pub italic: bool,
+    pub colour: i32,
+    pub size: i32,
+}
+
+/// Where the text in an RTF group goes.
+#[derive(Clone, Copy, PartialEq)]
+enum Destination {
+    Text,
+    ColourTable,
+    Skip,
+}
+
+#[derive(Clone, Copy)]
+struct RtfState {
+    destination: Destination,
+    bold: bool,
+    italic: bool,
+    colour: usize,
+    size: i32,
+}
+
+impl RtfState {
+    fn plain(destination: Destination) -> Self {
+        Self { destination, bold: false, italic: false, colour: 0, size: 12 }
+    }
+}
+
+/// Converts a document into lines of formatted text. Documents starting with "{\rtf" are read as RTF, keeping
+/// the bold, italic, colour and size of the text and dropping everything else. Anything else is read as plain text.
+pub fn parse_document(data: &[u8], default_colour: i32) -> Vec<Vec<TextRun>> {
+    let mut lines: Vec<Vec<TextRun>> = vec![Vec::new()];
+    let push = |lines: &mut Vec<Vec<TextRun>>, state: &RtfState, colour: i32, c: u8| {
+        let line = lines.last_mut().unwrap();
+        match line.last_mut() {
+            Some(run)
+                if run.bold == state.bold
+                    && run.italic == state.italic
+                    && run.colour == colour
+                    && run.size == state.size =>
+            {
+                run.text.push(c)
+            },
+            _ => line.push(TextRun { text: vec![c], bold: state.bold, italic: state.italic, colour, size: state.size }),
+        }
+    };
+
+    if !data.starts_with(b"{\\rtf") {
+        let state = RtfState::plain(Destination::Text);
+        for c in data.iter().copied().filter(|c| *c != b'\r') {
+            if c == b'\n' {
+                lines.push(Vec::new());
+            } else {
+                push(&mut lines, &state, default_colour, c);
+            }
+        }
+        return lines
+    }
+
+    let mut colours: Vec<Option<i32>> = Vec::new();
+    let mut colour_entry: Option<i32> = None;
+    let mut stack = Vec::new();
+    let mut state = RtfState::plain(Destination::Text);
+    let mut i = 0;
+    while i < data.len() {
+        let colour = colours.get(state.colour).copied().flatten().unwrap_or(default_colour);
+        match data[i] {
+            b'{' => stack.push(state),
+            b'}' => state = stack.pop().unwrap_or(state),
+            b'\r' | b'\n' => (),
+            b'\\' => {
+                i += 1;
+                let symbol = match data.get(i).copied() {
+                    Some(c @ (b'\\' | b'{' | b'}')) => Some(c),
+                    Some(b'\'') => {
+                        let hex = data.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+                        i += 2;
+                        hex.and_then(|h| u8::from_str_radix(h, 16).ok())
+                    },
+                    Some(b'~') => Some(b' '),
+                    Some(b'_') => Some(b'-'),
+                    Some(b'*') => {
+                        state.destination = Destination::Skip;
+                        None
+                    },
+                    Some(c) if c.is_ascii_alphabetic() => {
+                        let start = i;
+                        while matches!(data.get(i), Some(c) if c.is_ascii_alphabetic()) {
+                            i += 1;
+                        }
+                        let word = &data[start..i];
+                        let param_start = i;
+                        if data.get(i) == Some(&b'-') {
+                            i += 1;
+                        }
+                        while matches!(data.get(i), Some(c) if c.is_ascii_digit()) {
+                            i += 1;
+                        }
+                        let param = std::str::from_utf8(&data[param_start..i]).ok().and_then(|p| p.parse::<i32>().ok());
+                        // A space after a control word is part of it, anything else is the next character
+                        if data.get(i) != Some(&b' ') {
+                            i -= 1;
+                        }
+                        match (state.destination, word) {
+                            (Destination::Skip, _) => None,
+                            (Destination::ColourTable, b"red") => {
+                                colour_entry = Some(colour_entry.unwrap_or(0) | param.unwrap_or(0) & 0xFF);
+                                None
+                            },
+                            (Destination::ColourTable, b"green") => {
+                                colour_entry = Some(colour_entry.unwrap_or(0) | (param.unwrap_or(0) & 0xFF) << 8);
+                                None
+                            },
+                            (Destination::ColourTable, b"blue") => {
+                                colour_entry = Some(colour_entry.unwrap_or(0) | (param.unwrap_or(0) & 0xFF) << 16);
+                                None
+                            },
+                            (_, b"colortbl") => {
+                                state.destination = Destination::ColourTable;
+                                None
+                            },
+                            (
+                                _,
+                                b"fonttbl" | b"stylesheet" | b"info" | b"pict" | b"header" | b"footer" | b"object"
+                                | b"listtable" | b"listoverridetable" | b"rsidtbl" | b"generator",
+                            ) => {
+                                state.destination = Destination::Skip;
+                                None
+                            },
+                            (Destination::ColourTable, _) => None,
+                            (Destination::Text, b"par" | b"line") => {
+                                lines.push(Vec::new());
+                                None
+                            },
+                            (Destination::Text, b"plain") => {
+                                state = RtfState::plain(Destination::Text);
+                                None
+                            },
+                            (Destination::Text, b"b") => {
+                                state.bold = param != Some(0);
+                                None
+                            },
+                            (Destination::Text, b"i") => {
+                                state.italic = param != Some(0);
+                                None
+                            },
+                            (Destination::Text, b"cf") => {
+                                state.colour = param.unwrap_or(0).max(0) as usize;
+                                None
+                            },
+                            (Destination::Text, b"fs") => {
+                                state.size = param.unwrap_or(24) / 2;
+                                None
+                            },
+                            (Destination::Text, b"tab") => {
+                                for _ in 0..4 {
+                                    push(&mut lines, &state, colour, b' ');
+                                }
+                                None
+                            },
+                            (Destination::Text, b"emdash" | b"endash") => Some(b'-'),
+                            (Destination::Text, b"bullet") => Some(b'*'),
+                            (Destination::Text, b"lquote" | b"rquote") => Some(b'\''),
+                            (Destination::Text, b"ldblquote" | b"rdblquote") => Some(b'"'),
+                            // \u is followed by a fallback character for readers that don't understand it, like this
+                            (Destination::Text, _) => None,
+                        }
+                    },
+                    _ => None,
+                };
+                if let (Some(c), Destination::Text) = (symbol, state.destination) {
+                    let colour = colours.get(state.colour).copied().flatten().unwrap_or(default_colour);
+                    push(&mut lines, &state, colour, c);
+                }
+            },
+            b';' if state.destination == Destination::ColourTable => colours.push(colour_entry.take()),
+            c if state.destination == Destination::Text => push(&mut lines, &state, colour, c),
+            _ => (),
+        }
+        i += 1;
+    }
+    lines
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum SplashContent {
+    Text(Vec<Vec<TextRun>>),
+    Image {
+        atlas_ref: AtlasRef,
+        width: u32,
+        height: u32,
+    },
+    /// Something that can't be shown in the game window, like a video or a web page, so a message is shown instead.
+    Message(gml::String),
+}
+
+/// A splash screen, or the game information. These are drawn over the game window: in the middle of it if they
+/// would have been in their own window, or covering it if they would have been in the game window.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Splash {
+    content: SplashContent,
+    colour: i32,
+    /// The text in the caption bar, if there is a border.
+    caption: Option<gml::String>,
+    close_button: bool,
+    /// The area of the game window covered, or None to cover all of it.
+    bounds: Option<Rect>,
+    /// Scale to draw images at, or 0 to fit them in the splash screen.
+    scale: f64,
+    stop_key: bool,
+    stop_mouse: bool,
+    /// Whether the arrow keys scroll the text, as they do in the game information window.
+    scrollable: bool,
+    scroll: i32,
+    /// How long to show the splash screen for, in milliseconds, or None to show it until it's closed.
+    delay: Option<u32>,
+}
+
+/// A splash screen shown while the game keeps running, because it was set to not interrupt the game.
+/// It's closed by the game's own input, so it doesn't need anything stored in replays.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SplashOverlay {
+    splash: Splash,
+    frames_left: Option<u32>,
+}
+
+impl Splash {
+    fn new(content: SplashContent, colour: i32) -> Self {
+        Self {
+            content,
+            colour,
+            caption: None,
+            close_button: false,
+            bounds: None,
+            scale: 0.0,
+            stop_key: true,
+            stop_mouse: true,
+            scrollable: false,
+            scroll: 0,
+            delay: None,
+        }
+    }
+
+    /// Releases the image texture, if there is one.
+    fn free(&self, game: &mut Game) {
+        if let SplashContent::Image { atlas_ref, .. } = self.content {
+            game.renderer.delete_sprite(atlas_ref);
+        }
+    }
+
+    fn rect(&self, game: &Game) -> Rect {
+        self.bounds.unwrap_or((0, 0, game.unscaled_width as i32, game.unscaled_height as i32))
+    }
+
+    fn caption_height(&self) -> i32 {
+        if self.caption.is_some() { dialog::CAPTION_HEIGHT } else { 0 }
+    }
+
+    fn close_button_rect(&self, game: &Game) -> Option<Rect> {
+        let (x, y, w, _) = self.rect(game);
+        (self.caption.is_some() && self.close_button)
+            .then(|| (x + w - CLOSE_BUTTON_SIZE - 2, y + 2, CLOSE_BUTTON_SIZE, CLOSE_BUTTON_SIZE))
+    }
+
+    /// Handles input, returning true if the splash screen should close.
+    fn handle(&mut self, game: &Game, input: DialogInput) -> bool {
+        match input {
+            DialogInput::Key(_) if self.stop_key => true,
+            DialogInput::Key(vk) if vk == Button::Escape as u8 => self.scrollable,
+            DialogInput::Key(vk) if self.scrollable => {
+                let (_, _, _, h) = self.rect(game);
+                let page = (h - self.caption_height() - dialog::PADDING * 2).max(SCROLL_STEP);
+                self.scroll = match vk {
+                    vk if vk == Button::UpArrow as u8 => self.scroll - SCROLL_STEP,
+                    vk if vk == Button::DownArrow as u8 => self.scroll + SCROLL_STEP,
+                    vk if vk == Button::PageUp as u8 => self.scroll - page,
+                    vk if vk == Button::PageDown as u8 => self.scroll + page,
+                    vk if vk == Button::Home as u8 => 0,
+                    _ => self.scroll,
+                }
+                .max(0);
+                false
+            },
+            DialogInput::MouseDown(x, y) => {
+                self.stop_mouse || matches!(self.close_button_rect(game), Some(r) if dialog::contains(r, (x, y)))
+            },
+            DialogInput::Close => true,
+            _ => false,
+        }
+    }
+
+    /// Draws formatted text in an area, wrapping it at spaces.
+    fn draw_text(&self, game: &mut Game, lines: &[Vec<TextRun>], width: i32) {
+        let font_for = |run: &TextRun| MessageFont {
+            name: TEXT_FONT.into(),
+            size: run.size,
+            colour: run.colour,
+            style: i32::from(run.bold) | i32::from(run.italic) << 1,
+            charset: 1,
+        };
+        let default_height = game.dialog_text_size(&MessageFont::new(0), &"|".into(), None).1;
+        let mut y = 0;
+        for line in lines {
+            let line_h = line
+                .iter()
+                .map(|run| game.dialog_text_size(&font_for(run), &"|".into(), None).1)
+                .max()
+                .unwrap_or(default_height);
+            let mut x = 0;
+            for run in line {
+                let font = font_for(run);
+                for word in run.text.split_inclusive(|c| *c == b' ') {
+                    let word = gml::String::from(word);
+                    let word_w = game.dialog_text_size(&font, &word, None).0;
+                    if x > 0 && x + word_w > width {
+                        x = 0;
+                        y += line_h;
+                    }
+                    game.dialog_draw_text(&font, x, y, &word, None, 1.0);
+                    x += word_w;
+                }
+            }
+            y += line_h;
+        }
+    }
+
+    fn draw(&self, game: &mut Game) {
+        let (x, y, w, h) = self.rect(game);
+        let (screen_w, screen_h) = (game.unscaled_width as i32, game.unscaled_height as i32);
+        dialog::draw_box(game, (x, y, w, h), self.colour, self.colour, 1.0);
+
+        if let Some(caption) = &self.caption {
+            let caption_rect = (x, y, w, dialog::CAPTION_HEIGHT);
+            dialog::draw_box(game, caption_rect, dialog::COLOUR_CAPTION, dialog::COLOUR_CAPTION, 1.0);
+            let font = MessageFont::new(0xFFFFFF);
+            let caption_h = game.dialog_text_size(&font, caption, None).1;
+            game.dialog_draw_text(&font, x + 6, y + (dialog::CAPTION_HEIGHT - caption_h) / 2, caption, None, 1.0);
+            if let Some((bx, by, bw, bh)) = self.close_button_rect(game) {
+                dialog::draw_box(game, (bx, by, bw, bh), 0x2311E8, 0x2311E8, 1.0);
+                let (x1, y1, x2, y2) =
+                    (f64::from(bx + 4), f64::from(by + 4), f64::from(bx + bw - 5), f64::from(by + bh - 5));
+                game.renderer.draw_line(x1, y1, x2, y2, None, 0xFFFFFF, 0xFFFFFF, 1.0);
+                game.renderer.draw_line(x1, y2, x2, y1, None, 0xFFFFFF, 0xFFFFFF, 1.0);
+            }
+            let (x1, y1, x2, y2) = (f64::from(x), f64::from(y), f64::from(x + w - 1), f64::from(y + h - 1));
+            game.renderer.draw_rectangle_outline(x1, y1, x2, y2, dialog::COLOUR_BORDER, 1.0);
+        }
+
+        // Draw the contents through a viewport, so that they get clipped to the splash screen
+        let caption_h = self.caption_height();
+        let (content_x, content_y, content_w, content_h) = (x, y + caption_h, w, h - caption_h);
+        match &self.content {
+            SplashContent::Text(lines) => {
+                let (text_w, text_h) = (content_w - dialog::PADDING * 2, content_h - dialog::PADDING * 2);
+                let (text_x, text_y) = (content_x + dialog::PADDING, content_y + dialog::PADDING);
+                game.renderer.set_view(0, self.scroll, text_w, text_h, 0.0, text_x, text_y, text_w, text_h);
+                self.draw_text(game, lines, text_w);
+            },
+            SplashContent::Image { atlas_ref, width, height } => {
+                let (width, height) = (f64::from(*width), f64::from(*height));
+                let scale = if self.scale > 0.0 {
+                    self.scale
+                } else {
+                    (f64::from(content_w) / width.max(1.0)).min(f64::from(content_h) / height.max(1.0))
+                };
+                let image_x = (f64::from(content_w) - width * scale) / 2.0;
+                let image_y = (f64::from(content_h) - height * scale) / 2.0;
+                game.renderer.set_view(0, 0, content_w, content_h, 0.0, content_x, content_y, content_w, content_h);
+                game.renderer.draw_sprite(*atlas_ref, image_x, image_y, scale, scale, 0.0, 0xFFFFFF, 1.0);
+            },
+            SplashContent::Message(text) => {
+                game.renderer.set_view(0, 0, content_w, content_h, 0.0, content_x, content_y, content_w, content_h);
+                let font = MessageFont::new(0xFFFFFF ^ (self.colour & 0xFFFFFF));
+                let (text_w, text_h) = game.dialog_text_size(&font, text, Some(content_w - dialog::PADDING * 2));
+                let (text_x, text_y) = ((content_w - text_w) / 2, (content_h - text_h) / 2);
+                game.dialog_draw_text(&font, text_x, text_y, text, Some(content_w - dialog::PADDING * 2), 1.0);
+            },
+        }
+        game.renderer.set_view(0, 0, screen_w, screen_h, 0.0, 0, 0, screen_w, screen_h);
+    }
+}
+
+/// A splash screen shown modally, blocking the game until it's closed or its delay runs out.
+struct SplashScreen {
+    splash: Splash,
+    deadline: Option<Instant>,
+}
+
+impl Modal for SplashScreen {
+    type Output = ();
+
+    fn handle(&mut self, game: &mut Game, input: DialogInput) -> Option<()> {
+        self.splash.handle(game, input).then_some(())
+    }
+
+    fn tick(&mut self) -> Option<()> {
+        self.deadline.filter(|d| Instant::now() >= *d).map(|_| ())
+    }
+
+    fn draw(&self, game: &mut Game) {
+        self.splash.draw(game)
+    }
+}
+
+impl Game {
+    /// Loads a document for a text splash screen. Missing files show as empty documents, like in GM8.
+    fn splash_load_text(&self, fname: &str) -> Vec<Vec<TextRun>> {
+        match self.vfs.read(file::to_path(fname).as_ref()) {
+            Ok(data) => parse_document(&data, 0),
+            Err(e) => {
+                eprintln!("Warning: couldn't load splash text from {}: {}", fname, e);
+                Vec::new()
+            },
+        }
+    }
+
+    /// Loads an image for an image splash screen.
+    fn splash_load_image(&mut self, fname: &str) -> Option<SplashContent> {
+        match file::load_image(&self.vfs, file::to_path(fname).as_ref()) {
+            Ok(image) => {
+                let (width, height) = (image.width(), image.height());
+                let pixels = image.into_raw().into_boxed_slice();
+                let atlas_ref = self.renderer.upload_sprite(pixels, width as _, height as _, 0, 0).ok()?;
+                Some(SplashContent::Image { atlas_ref, width, height })
+            },
+            Err(e) => {
+                eprintln!("Warning: couldn't load splash image from {}: {}", fname, e);
+                None
+            },
+        }
+    }
+
+    /// Makes a splash screen using the settings from the splash_set_* functions.
+    /// `size` is the size of the image or video, which the splash screen is fitted to if splash_set_adapt is on.
+    fn splash_from_settings(&self, content: SplashContent, size: Option<(i32, i32)>, delay: i32) -> Splash {
+        let settings = &self.splash_settings;
+        let mut splash = Splash::new(content, settings.colour);
+        splash.stop_key = settings.stop_key;
+        splash.stop_mouse = settings.stop_mouse;
+        splash.scale = settings.scale;
+        splash.delay = u32::try_from(delay).ok().filter(|d| *d > 0);
+        if !settings.main && !settings.fullscreen {
+            if settings.border {
+                splash.caption = Some(if settings.caption.as_ref().is_empty() {
+                    self.dialog_string(&self.get_window_title())
+                } else {
+                    settings.caption.clone()
+                });
+                splash.close_button = settings.close_button;
+            }
+            let caption_h = splash.caption_height();
+            let (w, h) = match size {
+                Some((w, h)) if settings.adapt => {
+                    let scale = if settings.scale > 0.0 { settings.scale } else { 1.0 };
+                    ((f64::from(w) * scale) as i32, (f64::from(h) * scale) as i32 + caption_h)
+                },
+                _ => settings.size,
+            };
+            splash.bounds = Some(self.splash_bounds(settings.position, (w, h)));
+        }
+        splash
+    }
+
+    /// Works out where a splash screen goes on the game window. Negative positions mean it's centred.
+    fn splash_bounds(&self, (x, y): (i32, i32), (w, h): (i32, i32)) -> Rect {
+        let (screen_w, screen_h) = (self.unscaled_width as i32, self.unscaled_height as i32);
+        let (w, h) = (w.min(screen_w).max(1), h.min(screen_h).max(1));
+        let x = if x < 0 { (screen_w - w) / 2 } else { x.min(screen_w - w) };
+        let y = if y < 0 { (screen_h - h) / 2 } else { y.min(screen_h - h) };
+        (x, y, w, h)
+    }
+
+    /// Shows a splash screen. If it interrupts the game, this blocks until it's closed, and in record mode the fact
+    /// that it was closed gets stored in the replay. Otherwise it's drawn over the game until it's closed.
+    fn show_splash(&mut self, function: &str, splash: Splash, interrupt: bool) -> gml::Result<()> {
+        if !interrupt {
+            let frames_left = splash.delay.map(|d| ((u64::from(d) * u64::from(self.room.speed) + 999) / 1000) as u32);
+            if let Some(old) = self.splash_overlay.replace(SplashOverlay { splash, frames_left }) {
+                old.splash.free(self);
+            }
+            return Ok(())
+        }
+        let free_splash = splash.clone();
+        self.dialog_result(
+            function,
+            |game| {
+                let deadline = splash.delay.map(|d| Instant::now() + Duration::from_millis(d.into()));
+                game.run_modal(&mut SplashScreen { splash, deadline });
+                Default::default()
+            },
+            |_| replay::Event::SplashClosed,
+            |ev| matches!(ev, replay::Event::SplashClosed).then(Default::default),
+        )?;
+        free_splash.free(self);
+        Ok(())
+    }
+
+    /// Shows a splash screen using the settings from the splash_set_* functions.
+    fn show_splash_with_settings(
+        &mut self,
+        function: &str,
+        content: SplashContent,
+        size: Option<(i32, i32)>,
+        delay: i32,
+    ) -> gml::Result<()> {
+        let splash = self.splash_from_settings(content, size, delay);
+        let interrupt = self.splash_settings.main || self.splash_settings.interrupt;
+        self.show_splash(function, splash, interrupt)
+    }
+
+    pub fn splash_text(&mut self, function: &str, fname: &str, delay: i32) -> gml::Result<()> {
+        let content = SplashContent::Text(self.splash_load_text(fname));
+        self.show_splash_with_settings(function, content, None, delay)
+    }
+
+    pub fn splash_image(&mut self, function: &str, fname: &str, delay: i32) -> gml::Result<()> {
+        match self.splash_load_image(fname) {
+            Some(content) => {
+                let size = match content {
+                    SplashContent::Image { width, height, .. } => Some((width as i32, height as i32)),
+                    _ => None,
+                };
+                self.show_splash_with_settings(function, content, size, delay)
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Videos can't be played, so the file name is shown instead until the splash screen is closed.
+    pub fn splash_video(&mut self, function: &str, fname: &str) -> gml::Result<()> {
+        let content = SplashContent::Message(self.dialog_string(&format!("Video: {}", fname)));
+        self.show_splash_with_settings(function, content, None, 0)
+    }
+
+    /// Web pages can't be shown, so the URL is shown instead.
+    pub fn splash_web(&mut self, function: &str, url: &str, delay: i32) -> gml::Result<()> {
+        let content = SplashContent::Message(self.dialog_string(&format!("Web page: {}", url)));
+        self.show_splash_with_settings(function, content, None, delay)
+    }
+
+    /// Shows a splash screen in the style of the old show_* functions, which don't use the splash settings.
+    /// If `full` is false, it's shown in the middle of the game window like a separate window would be.
+    pub fn splash_legacy(
+        &mut self,
+        function: &str,
+        content: SplashContent,
+        full: bool,
+        colour: i32,
+        delay: i32,
+    ) -> gml::Result<()> {
+        let size = match content {
+            SplashContent::Image { width, height, .. } => (width as i32, height as i32),
+            _ => SplashSettings::default().size,
+        };
+        let mut splash = Splash::new(content, colour);
+        splash.delay = u32::try_from(delay).ok().filter(|d| *d > 0);
+        if !full {
+            splash.bounds = Some(self.splash_bounds((-1, -1), size));
+        }
+        self.show_splash(function, splash, true)
+    }
+
+    pub fn splash_legacy_text(&mut self, fname: &str, full: bool, colour: i32, delay: i32) -> gml::Result<()> {
+        let content = SplashContent::Text(self.splash_load_text(fname));
+        self.splash_legacy("show_text", content, full, colour, delay)
+    }
+
+    pub fn splash_legacy_image(&mut self, fname: &str, full: bool, delay: i32) -> gml::Result<()> {
+        match self.splash_load_image(fname) {
+            Some(content) => self.splash_legacy("show_image", content, full, 0, delay),
+            None => Ok(()),
+        }
+    }
+
+    pub fn splash_legacy_video(&mut self, fname: &str, full: bool) -> gml::Result<()> {
+        let content = SplashContent::Message(self.dialog_string(&format!("Video: {}", fname)));
+        self.splash_legacy("show_video", content, full, 0, 0)
+    }
+
+    /// Shows the game information. If it's set to freeze the game, or to show in the game window, the game waits
+    /// until it's closed with Escape. Otherwise it stays over the game until Escape is pressed.
+    pub fn show_game_info(&mut self) -> gml::Result<()> {
+        let info = self.game_info.clone();
+        let mut splash = Splash::new(SplashContent::Text(parse_document(info.text.as_ref(), 0)), info.colour);
+        splash.stop_key = false;
+        splash.stop_mouse = false;
+        splash.scrollable = true;
+        if info.new_window {
+            if info.border {
+                splash.caption = Some(info.caption.clone());
+                splash.close_button = true;
+            }
+            splash.bounds = Some(self.splash_bounds((-1, -1), info.size));
+        }
+        self.show_splash("show_info", splash, info.freeze || !info.new_window)
+    }
+
+    /// Replaces the game information with the contents of an RTF file.
+    pub fn load_game_info(&mut self, fname: &str) -> gml::Result<()> {
+        match self.vfs.read(file::to_path(fname).as_ref()) {
+            Ok(data) => self.game_info.text = data.into(),
+            Err(e) => eprintln!("Warning: load_info on {} failed: {}", fname, e),
+        }
+        Ok(())
+    }
+
+    /// Updates the splash screen being shown over the game, if there is one, closing it when the player presses
+    /// a key or clicks (if it's set to close that way) or when its delay runs out. Called at the start of each frame.
+    pub fn update_splash_overlay(&mut self) {
+        if let Some(mut overlay) = self.splash_overlay.take() {
+            let mut close = overlay.frames_left == Some(0);
+            overlay.frames_left = overlay.frames_left.map(|f| f.saturating_sub(1));
+            let mouse = (self.input.mouse_x(), self.input.mouse_y());
+            for vk in (0..=255u8).filter(|vk| self.input.keyboard_check_pressed(*vk)) {
+                close |= overlay.splash.handle(self, DialogInput::Key(vk));
+            }
+            if self.input.mouse_check_button_pressed_any() {
+                close |= overlay.splash.handle(self, DialogInput::MouseDown(mouse.0, mouse.1));
+            }
+            if close {
+                overlay.splash.free(self);
+            } else {
+                self.splash_overlay = Some(overlay);
+            }
+        }
+    }
+
+    /// Draws the splash screen being shown over the game, if there is one.
+    pub fn draw_splash_overlay(&mut self) {
+        if let Some(overlay) = self.splash_overlay.take() {
+            overlay.splash.draw(self);
+            self.splash_overlay = Some(overlay);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{
+    game::{Game, GetAsset},
+    gml,
+    types::ID,
+};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Writes a line-based log of everything the engine dispatches - events, instance creation and destruction,
+/// room changes - so that two runs of the same game can be compared with a regular diff tool.
+/// Does nothing unless a trace file has been opened.
+pub struct Tracer {
+    file: Option<BufWriter<File>>,
+    frame: u64,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Self { file: None, frame: 0 }
+    }
+
+    /// Starts writing a trace to the given file, replacing any trace which was already being written.
+    pub fn start(&mut self, path: &Path) -> io::Result<()> {
+        self.stop()?;
+        self.file = Some(BufWriter::new(File::create(path)?));
+        self.frame = 0;
+        Ok(())
+    }
+
+    /// Stops tracing and flushes the trace file, if there is one.
+    pub fn stop(&mut self) -> io::Result<()> {
+        match self.file.take() {
+            Some(mut file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Should be called once at the start of every `Game::frame`.
+    pub fn frame_begin(&mut self) {
+        if self.is_enabled() {
+            self.frame += 1;
+            let frame = self.frame;
+            self.write(format_args!("frame {}", frame));
+        }
+    }
+
+    fn write(&mut self, line: std::fmt::Arguments) {
+        if let Some(file) = self.file.as_mut() {
+            // A trace that can't be written isn't worth crashing the game over, so stop tracing instead.
+            if let Err(e) = file.write_fmt(line).and_then(|_| file.write_all(b"\n")) {
+                eprintln!("Couldn't write to trace file, tracing has been stopped: {}", e);
+                self.file = None;
+            }
+        }
+    }
+}
+
+impl Game {
+    /// Describes an instance by its ID and object name, such as "100005 obj_player".
+    fn trace_instance_name(&self, handle: usize) -> String {
+        let instance = self.room.instance_list.get(handle);
+        let object_id = instance.object_index.get();
+        match self.assets.objects.get_asset(object_id) {
+            Some(object) => format!("{} {}", instance.id.get(), self.decode_str(object.name.as_ref())),
+            None => format!("{} <object {}>", instance.id.get(), object_id),
+        }
+    }
+
+    /// Logs an event being run. `other` is only logged if it's a different instance, as it is for collisions.
+    pub fn trace_event(&mut self, event_id: usize, event_sub: u32, instance: usize, other: usize) {
+        if self.tracer.is_enabled() {
+            let name = self.trace_instance_name(instance);
+            let event = self.event_name(event_id, event_sub);
+            if other != instance && event_id == gml::ev::COLLISION {
+                let other = self.room.instance_list.get(other).id.get();
+                self.tracer.write(format_args!("event {}: {} (other {})", name, event, other));
+            } else {
+                self.tracer.write(format_args!("event {}: {}", name, event));
+            }
+        }
+    }
+
+    /// Logs a timeline moment being run for an instance.
+    pub fn trace_timeline_moment(&mut self, instance: usize, timeline: ID, moment: i32) {
+        if self.tracer.is_enabled() {
+            let name = self.trace_instance_name(instance);
+            let timeline = match self.assets.timelines.get_asset(timeline) {
+                Some(t) => self.decode_str(t.name.as_ref()).into_owned(),
+                None => format!("<timeline {}>", timeline),
+            };
+            self.tracer.write(format_args!("event {}: Timeline {} moment {}", name, timeline, moment));
+        }
+    }
+
+    pub fn trace_instance_created(&mut self, instance: usize) {
+        if self.tracer.is_enabled() {
+            let name = self.trace_instance_name(instance);
+            self.tracer.write(format_args!("create {}", name));
+        }
+    }
+
+    pub fn trace_instance_destroyed(&mut self, instance: usize) {
+        if self.tracer.is_enabled() {
+            let name = self.trace_instance_name(instance);
+            self.tracer.write(format_args!("destroy {}", name));
+        }
+    }
+
+    pub fn trace_instance_changed(&mut self, old_instance: usize, new_instance: usize) {
+        if self.tracer.is_enabled() {
+            let old_name = self.trace_instance_name(old_instance);
+            let new_name = self.trace_instance_name(new_instance);
+            self.tracer.write(format_args!("change {} -> {}", old_name, new_name));
+        }
+    }
+
+    pub fn trace_room_change(&mut self, room_id: ID) {
+        if self.tracer.is_enabled() {
+            let room = match self.assets.rooms.get_asset(room_id) {
+                Some(r) => self.decode_str(r.name.as_ref()).into_owned(),
+                None => format!("<room {}>", room_id),
+            };
+            self.tracer.write(format_args!("room {} {}", room_id, room));
+        }
+    }
+}
@@ -10,6 +10,7 @@ pub mod rand;
 pub mod runtime;
 pub mod string;
 pub mod value;
+pub mod vfs;
 
 pub use compiler::Compiler;
 pub use context::Context;
@@ -1,6 +1,6 @@
 use crate::{gml::Value, math::Real};
 use serde::{Deserialize, Serialize};
-use std::{cmp::Ordering, collections};
+use std::{cmp::Ordering, collections, io::Read};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -239,6 +239,23 @@ impl Grid {
         })
     }
 
+    /// The values in a region, paired with where they'd be if the region's top left was moved to (xpos, ypos).
+    /// That can be outside the grid, which `get_mut` will catch.
+    pub fn region_moved(&self, x1: i32, y1: i32, x2: i32, y2: i32, xpos: i32, ypos: i32) -> Vec<((i32, i32), Value)> {
+        let (left, top) = (x1.min(x2), y1.min(y2));
+        self.region_positioned(x1, y1, x2, y2)
+            .map(|((x, y), val)| ((x as i32 - left + xpos, y as i32 - top + ypos), val.clone()))
+            .collect()
+    }
+
+    /// Swaps two cells, by their index when going through each column.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        let (ax, ay) = (a / self.height, a % self.height);
+        let (bx, by) = (b / self.height, b % self.height);
+        let value = std::mem::take(&mut self.grid[ax][ay]);
+        self.grid[ax][ay] = std::mem::replace(&mut self.grid[bx][by], value);
+    }
+
     /// Goes through each column
     pub fn all(&self) -> impl Iterator<Item = &Value> {
         self.grid.iter().flatten()
@@ -258,6 +275,38 @@ impl Grid {
     }
 }
 
+/// Writes a queue in the format of ds_queue_write: a header, the number of values, how many of those were already
+/// dequeued, then the values. Queues written by GM8 can still hold dequeued values, but they're dropped here,
+/// so there are never any.
+pub fn write_queue(queue: &Queue) -> String {
+    let mut output = "C9000000".to_string();
+    output.push_str(&hex::encode_upper((queue.len() as u32).to_le_bytes()));
+    output.push_str("00000000");
+    output.extend(queue.iter().map(|v| hex::encode_upper(v.as_bytes())));
+    output
+}
+
+/// Reads a queue written by ds_queue_write, leaving out any values that were already dequeued.
+pub fn read_queue(mut reader: &[u8]) -> Option<Queue> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf).ok()?;
+    if u32::from_le_bytes(buf) != 0xc9 {
+        return None
+    }
+    reader.read_exact(&mut buf).ok()?;
+    let size = u32::from_le_bytes(buf) as usize;
+    reader.read_exact(&mut buf).ok()?;
+    let head = u32::from_le_bytes(buf) as usize;
+    let mut queue = Queue::with_capacity(size.saturating_sub(head));
+    for i in 0..size {
+        let value = Value::from_reader(&mut reader)?;
+        if i >= head {
+            queue.push_back(value);
+        }
+    }
+    Some(queue)
+}
+
 pub fn eq(v1: &Value, v2: &Value, precision: Real) -> bool {
     match (v1, v2) {
         (Value::Real(x), Value::Real(y)) => (*x - *y).abs() <= precision,
@@ -280,3 +329,51 @@ pub fn cmp(v1: &Value, v2: &Value, precision: Real) -> Ordering {
         (Value::Str(_), Value::Real(_)) => Ordering::Greater,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a queue holding 1 and "ab"
+    const QUEUE: &str = concat!(
+        "C9000000",
+        "02000000",
+        "00000000",
+        "00000000000000000000F03F00000000",
+        "01000000000000000000000002000000",
+        "6162",
+    );
+
+    // the same queue, after 0 was enqueued before them and then dequeued
+    const DEQUEUED: &str = concat!(
+        "C9000000",
+        "03000000",
+        "01000000",
+        "00000000000000000000000000000000",
+        "00000000000000000000F03F00000000",
+        "01000000000000000000000002000000",
+        "6162",
+    );
+
+    fn queue() -> Queue {
+        [Value::from(1.0), Value::from("ab")].into_iter().collect()
+    }
+
+    #[test]
+    fn queue_write() {
+        assert_eq!(write_queue(&queue()), QUEUE);
+        assert_eq!(write_queue(&Queue::new()), "C90000000000000000000000");
+    }
+
+    #[test]
+    fn queue_read() {
+        assert_eq!(read_queue(&hex::decode(QUEUE).unwrap()), Some(queue()));
+        assert_eq!(read_queue(&hex::decode(DEQUEUED).unwrap()), Some(queue()));
+        // everything was dequeued
+        let empty = concat!("C9000000", "01000000", "01000000", "00000000000000000000000000000000");
+        assert_eq!(read_queue(&hex::decode(empty).unwrap()), Some(Queue::new()));
+        // a stack, and a queue that's cut off
+        assert_eq!(read_queue(&hex::decode("650000000000000000000000").unwrap()), None);
+        assert_eq!(read_queue(&hex::decode(&QUEUE[..QUEUE.len() - 2]).unwrap()), None);
+    }
+}
@@ -1,7 +1,8 @@
-use image::{codecs::gif::GifDecoder, AnimationDecoder, ImageError, ImageFormat, Pixel, RgbaImage};
+use crate::gml::vfs::{FileSystem, OpenOptions, Stream};
+use byteorder::{ReadBytesExt, LE};
+use image::{codecs::gif::GifDecoder, AnimationDecoder, DynamicImage, ImageError, ImageFormat, Pixel, RgbaImage};
 use std::{
-    fs::{File, OpenOptions},
-    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write},
     path::Path,
 };
 
@@ -9,14 +10,14 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum TextHandle {
-    Read(BufReader<File>),
-    Write(BufWriter<File>),
+    Read(BufReader<Stream>),
+    Write(BufWriter<Stream>),
 }
 #[derive(Debug)]
 pub enum BinaryHandle {
-    Read(BufReader<File>),
-    Write(BufWriter<File>),
-    ReadWrite(File),
+    Read(BufReader<Stream>),
+    Write(BufWriter<Stream>),
+    ReadWrite(Stream),
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -97,7 +98,7 @@ pub fn to_path(path: &str) -> std::borrow::Cow<str> {
 }
 
 impl TextHandle {
-    pub fn open(path: &str, mode: AccessMode) -> io::Result<Self> {
+    pub fn open(fs: &FileSystem, path: &str, mode: AccessMode) -> io::Result<Self> {
         #[rustfmt::skip]
         let (read, write, append) = match mode {
             AccessMode::Read    => (true,  false, false),
@@ -111,7 +112,7 @@ impl TextHandle {
             .write(write)
             .append(append)
             .truncate(write && !append)
-            .open(path)?;
+            .open(fs, path)?;
 
         Ok(match mode {
             AccessMode::Read => TextHandle::Read(BufReader::new(file)),
@@ -119,14 +120,14 @@ impl TextHandle {
         })
     }
 
-    fn get_reader(&mut self) -> Result<&mut BufReader<File>> {
+    fn get_reader(&mut self) -> Result<&mut BufReader<Stream>> {
         match self {
             Self::Read(f) => Ok(f),
             _ => Err(Error::CantRead),
         }
     }
 
-    fn get_writer(&mut self) -> Result<&mut BufWriter<File>> {
+    fn get_writer(&mut self) -> Result<&mut BufWriter<Stream>> {
         match self {
             Self::Write(f) => Ok(f),
             _ => Err(Error::CantWrite),
@@ -200,8 +201,8 @@ impl TextHandle {
 }
 
 impl BinaryHandle {
-    pub fn open(path: &str, mode: AccessMode) -> io::Result<Self> {
-        let file = Self::_open(path, mode)?;
+    pub fn open(fs: &FileSystem, path: &str, mode: AccessMode) -> io::Result<Self> {
+        let file = Self::_open(fs, path, mode)?;
         match mode {
             AccessMode::Read => Ok(Self::Read(BufReader::new(file))),
             AccessMode::Write => Ok(Self::Write(BufWriter::new(file))),
@@ -218,7 +219,7 @@ impl BinaryHandle {
     // same name between testing and opening, and also would require an additional
     // function call every time. Instead, when a read-only or write-only mode was
     // requested for a file, we try first to create it and fail if it's exists.
-    fn _open(path: &str, mode: AccessMode) -> io::Result<File> {
+    fn _open(fs: &FileSystem, path: &str, mode: AccessMode) -> io::Result<Stream> {
         let mut opts = OpenOptions::new();
 
         #[rustfmt::skip]
@@ -231,7 +232,7 @@ impl BinaryHandle {
         if !(read && write) {
             // We don't return on other errors (that is, not AlreadyExists) here
             // because the second call to .open() may give us a more exact one.
-            if let r @ Ok(_) = opts.create_new(true).read(true).write(true).open(path) {
+            if let r @ Ok(_) = opts.create_new(true).read(true).write(true).open(fs, path) {
                 return r
             };
 
@@ -244,7 +245,7 @@ impl BinaryHandle {
         opts.create(write) // not .create(true), read the initial comment why!
             .read(read)
             .write(write)
-            .open(path)
+            .open(fs, path)
     }
 
     fn get_reader(&mut self) -> Result<&mut dyn Read> {
@@ -380,50 +381,19 @@ pub fn skip_line(f: impl Read) -> io::Result<()> {
     Ok(())
 }
 
-pub fn file_exists(path: &str) -> bool {
-    Path::new(path).is_file()
+pub fn load_image(fs: &FileSystem, path: &str) -> Result<RgbaImage> {
+    let data = fs.read(path)?;
+    Ok(image::io::Reader::new(Cursor::new(data)).with_guessed_format()?.decode()?.into_rgba8())
 }
 
-pub fn rename(from: &str, to: &str) -> Result<()> {
-    if !Path::new(to).exists() {
-        std::fs::rename(from, to)?;
-    }
-    Ok(())
-}
-
-pub fn copy(from: &str, to: &str) -> Result<()> {
-    std::fs::copy(from, to)?;
-    Ok(())
-}
-
-pub fn dir_exists(path: &str) -> bool {
-    Path::new(path).is_dir()
-}
-
-pub fn dir_create(path: &str) -> Result<()> {
-    std::fs::create_dir_all(path)?;
-    Ok(())
-}
-
-pub fn delete(path: &str) -> Result<()> {
-    if Path::new(path).exists() {
-        std::fs::remove_file(path)?;
-    }
-    Ok(())
-}
-
-pub fn load_image(path: &str) -> Result<RgbaImage> {
-    Ok(image::open(path)?.into_rgba8())
-}
-
-pub fn load_animation(path: &str, imgnumb: usize) -> Result<Vec<RgbaImage>> {
+pub fn load_animation(fs: &FileSystem, path: &str, imgnumb: usize) -> Result<Vec<RgbaImage>> {
     if ImageFormat::from_path(path)? == ImageFormat::Gif {
-        GifDecoder::new(BufReader::new(File::open(path)?))?
+        GifDecoder::new(Cursor::new(fs.read(path)?))?
             .into_frames()
             .map(|r| r.map(|f| f.into_buffer()).map_err(Error::from))
             .collect()
     } else {
-        let image = load_image(path.as_ref())?;
+        let image = load_image(fs, path)?;
         let sprite_width = image.width() as usize / imgnumb;
         let sprite_height = image.height() as usize;
         // get pixel data for each frame
@@ -445,12 +415,164 @@ pub fn load_animation(path: &str, imgnumb: usize) -> Result<Vec<RgbaImage>> {
     }
 }
 
-pub fn save_image<P: AsRef<Path>>(path: P, image: RgbaImage) -> Result<()> {
+pub fn save_image(fs: &FileSystem, path: &str, image: RgbaImage) -> Result<()> {
     // save to png if the filename is .png otherwise bmp regardless of filename
-    if path.as_ref().extension().and_then(|s| s.to_str()).map(|s| s.eq_ignore_ascii_case("png")).unwrap_or(false) {
-        image.save_with_format(path, ImageFormat::Png)?;
-    } else {
-        image.save_with_format(path, ImageFormat::Bmp)?;
-    }
+    let is_png =
+        Path::new(path).extension().and_then(|s| s.to_str()).map(|s| s.eq_ignore_ascii_case("png")).unwrap_or(false);
+    let format = if is_png { ImageFormat::Png } else { ImageFormat::Bmp };
+    let mut data = Vec::new();
+    DynamicImage::ImageRgba8(image).write_to(&mut data, format)?;
+    fs.write(path, &data)?;
     Ok(())
 }
+
+/// The contents of a sprite file saved from GM8's sprite editor.
+pub struct SpriteFile {
+    pub origin_x: i32,
+    pub origin_y: i32,
+    pub frames: Vec<RgbaImage>,
+    pub tolerance: u8,
+    pub sepmasks: bool,
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::IOError(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+// Reads a version number, width and height, and BGRA pixels as laid out in GM8's sprite and background files
+fn read_bitmap(mut f: impl Read) -> Result<RgbaImage> {
+    if f.read_u32::<LE>()? != 800 {
+        return Err(invalid_data("unsupported image version"))
+    }
+    let width = f.read_u32::<LE>()?;
+    let height = f.read_u32::<LE>()?;
+    let size = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|n| n.checked_mul(4))
+        .ok_or_else(|| invalid_data("image is too big"))?;
+    let mut data = Vec::new();
+    if size != 0 {
+        let len = f.read_u32::<LE>()? as usize;
+        if len != size {
+            return Err(invalid_data("image data has the wrong length"))
+        }
+        // the length is checked against what's actually there as it's read, so a bad file can't make this huge
+        f.take(len as u64).read_to_end(&mut data)?;
+        if data.len() != len {
+            return Err(invalid_data("image data is cut off"))
+        }
+        for px in data.chunks_exact_mut(4) {
+            px.swap(0, 2);
+        }
+    }
+    Ok(RgbaImage::from_vec(width, height, data).unwrap())
+}
+
+pub fn load_sprite_file(fs: &FileSystem, path: &str) -> Result<SpriteFile> {
+    let mut f = Cursor::new(fs.read(path)?);
+    if f.read_u32::<LE>()? != 800 {
+        return Err(invalid_data("unsupported sprite file version"))
+    }
+    let origin_x = f.read_i32::<LE>()?;
+    let origin_y = f.read_i32::<LE>()?;
+    let frame_count = f.read_u32::<LE>()?;
+    let frames = (0..frame_count).map(|_| read_bitmap(&mut f)).collect::<Result<Vec<_>>>()?;
+    if frames.is_empty() || frames.iter().any(|i| i.dimensions() != frames[0].dimensions()) {
+        return Err(invalid_data("sprite has no frames or frames of different sizes"))
+    }
+    let _shape = f.read_u32::<LE>()?;
+    let tolerance = f.read_u32::<LE>()?.min(255) as u8;
+    let sepmasks = f.read_u32::<LE>()? != 0;
+    // the bounding box settings that follow aren't used, the mask is always made precise
+    Ok(SpriteFile { origin_x, origin_y, frames, tolerance, sepmasks })
+}
+
+pub fn load_background_file(fs: &FileSystem, path: &str) -> Result<RgbaImage> {
+    let mut f = Cursor::new(fs.read(path)?);
+    if f.read_u32::<LE>()? != 710 {
+        return Err(invalid_data("unsupported background file version"))
+    }
+    // tileset settings
+    f.seek(SeekFrom::Current(7 * 4))?;
+    read_bitmap(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A filesystem that only exists in memory, holding one file.
+    fn filesystem(path: &str, data: &[u8]) -> FileSystem {
+        let mut fs = FileSystem::new(PathBuf::from("gm8emulator-test"));
+        fs.enable_sandbox();
+        fs.write(path, data).unwrap();
+        fs
+    }
+
+    fn push_u32s(data: &mut Vec<u8>, values: &[u32]) {
+        data.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+    }
+
+    /// An image as laid out in sprite and background files, with the given BGRA pixels.
+    fn bitmap(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        push_u32s(&mut data, &[800, width, height]);
+        if width * height != 0 {
+            push_u32s(&mut data, &[pixels.len() as u32]);
+            data.extend_from_slice(pixels);
+        }
+        data
+    }
+
+    #[test]
+    fn sprite_file() {
+        let mut data = Vec::new();
+        // version, origin and two 2x1 frames
+        push_u32s(&mut data, &[800, 1, 0xFFFF_FFFF, 2]);
+        data.extend(bitmap(2, 1, &[0x30, 0x20, 0x10, 0xFF, 0, 0, 0, 0]));
+        data.extend(bitmap(2, 1, &[1, 2, 3, 4, 5, 6, 7, 8]));
+        // shape, tolerance, separate masks, then the bounding box settings
+        push_u32s(&mut data, &[0, 300, 1, 0, 0, 0, 0, 0]);
+        let sprite = load_sprite_file(&filesystem("test.gmspr", &data), "test.gmspr").unwrap();
+        assert_eq!((sprite.origin_x, sprite.origin_y), (1, -1));
+        assert_eq!(sprite.frames.len(), 2);
+        assert_eq!(sprite.frames[0].dimensions(), (2, 1));
+        assert_eq!(sprite.frames[0].as_raw(), &[0x10, 0x20, 0x30, 0xFF, 0, 0, 0, 0]);
+        assert_eq!(sprite.frames[1].as_raw(), &[3, 2, 1, 4, 7, 6, 5, 8]);
+        assert_eq!(sprite.tolerance, 255);
+        assert!(sprite.sepmasks);
+    }
+
+    #[test]
+    fn background_file() {
+        let mut data = Vec::new();
+        // version, then the tileset settings
+        push_u32s(&mut data, &[710, 0, 16, 16, 0, 0, 0, 0]);
+        data.extend(bitmap(1, 2, &[0, 0, 0xFF, 0xFF, 0xFF, 0, 0, 0x80]));
+        let background = load_background_file(&filesystem("test.gmbck", &data), "test.gmbck").unwrap();
+        assert_eq!(background.dimensions(), (1, 2));
+        assert_eq!(background.as_raw(), &[0xFF, 0, 0, 0xFF, 0, 0, 0xFF, 0x80]);
+        // an empty background has no pixel data at all
+        data.truncate(8 * 4);
+        data.extend(bitmap(0, 0, &[]));
+        let background = load_background_file(&filesystem("empty.gmbck", &data), "empty.gmbck").unwrap();
+        assert_eq!(background.dimensions(), (0, 0));
+    }
+
+    #[test]
+    fn bad_bitmaps() {
+        // the length doesn't match the size
+        let mut data = Vec::new();
+        push_u32s(&mut data, &[800, 1, 1, 8]);
+        assert!(read_bitmap(data.as_slice()).is_err());
+        // a huge image whose data isn't there, which mustn't be allocated up front
+        let mut data = Vec::new();
+        push_u32s(&mut data, &[800, 0x4000, 0x4000, 0x4000_0000, 0]);
+        assert!(read_bitmap(data.as_slice()).is_err());
+        // too big to even work out the length of
+        let mut data = Vec::new();
+        push_u32s(&mut data, &[800, u32::MAX, u32::MAX]);
+        assert!(read_bitmap(data.as_slice()).is_err());
+    }
+}
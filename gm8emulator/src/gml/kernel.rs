@@ -5,18 +5,19 @@
 use crate::{
     action, asset,
     game::{
-        draw, external, gm_save::GMSave, model, particle, pathfinding, platform, replay, surface::Surface,
-        transition::UserTransition, view::View, Game, GetAsset, PlayType, SceneChange, Version,
+        audio, draw, external, gm_save::GMSave, model, particle, pathfinding, platform, profiler::ProfileKey,
+        registry::RegistryValue, replay, surface::Surface, transition::UserTransition, view::View, Game, GetAsset,
+        PlayType, SceneChange, Version,
     },
     gml::{
         self,
         datetime::{self, DateTime},
         ds, file,
         mappings::{self, constants as gml_consts},
-        network, Context, Value,
+        network, vfs, Context, Value,
     },
     handleman::HandleManager,
-    input::MouseButton,
+    input::{self, MouseButton},
     instance::{Field, Instance, InstanceState},
     math::Real,
     render::{BlendType, Fog, Light, Renderer, Scaling},
@@ -546,7 +547,7 @@ impl Game {
         let rgba = self.renderer.get_pixels(0, 0, width as _, height as _);
         let mut image = RgbaImage::from_vec(width, height, rgba.into()).unwrap();
         asset::sprite::process_image(&mut image, false, false, true);
-        match file::save_image(file::to_path(&fname).as_ref(), image) {
+        match file::save_image(&self.vfs, file::to_path(&fname).as_ref(), image) {
             Ok(()) => Ok(Default::default()),
             Err(e) => Err(gml::Error::FunctionError("screen_save".into(), e.to_string())),
         }
@@ -562,7 +563,7 @@ impl Game {
         let rgba = self.renderer.get_pixels(x, y, w, h);
         let mut image = RgbaImage::from_vec(w as _, h as _, rgba.into()).unwrap();
         asset::sprite::process_image(&mut image, false, false, true);
-        match file::save_image(file::to_path(&fname).as_ref(), image) {
+        match file::save_image(&self.vfs, file::to_path(&fname).as_ref(), image) {
             Ok(()) => Ok(Default::default()),
             Err(e) => Err(gml::Error::FunctionError("screen_save_part".into(), e.to_string())),
         }
@@ -2458,7 +2459,7 @@ impl Game {
             let mut image =
                 RgbaImage::from_vec(surf.width, surf.height, self.renderer.dump_sprite(surf.atlas_ref).into()).unwrap();
             asset::sprite::process_image(&mut image, false, false, true);
-            match file::save_image(file::to_path(&fname).as_ref(), image) {
+            match file::save_image(&self.vfs, file::to_path(&fname).as_ref(), image) {
                 Ok(()) => Ok(Default::default()),
                 Err(e) => Err(gml::Error::FunctionError("surface_save".into(), e.to_string())),
             }
@@ -2481,7 +2482,7 @@ impl Game {
                 RgbaImage::from_vec(w as _, h as _, self.renderer.dump_sprite_part(surf.atlas_ref, x, y, w, h).into())
                     .unwrap();
             asset::sprite::process_image(&mut image, false, false, true);
-            match file::save_image(file::to_path(&fname).as_ref(), image) {
+            match file::save_image(&self.vfs, file::to_path(&fname).as_ref(), image) {
                 Ok(()) => Ok(Default::default()),
                 Err(e) => Err(gml::Error::FunctionError("surface_save_part".into(), e.to_string())),
             }
@@ -2837,6 +2838,7 @@ impl Game {
                 object_id,
                 object,
             ));
+            self.trace_instance_created(instance);
             self.run_instance_event(gml::ev::CREATE, 0, instance, instance, None)?;
             Ok(Default::default())
         } else {
@@ -2861,6 +2863,7 @@ impl Game {
                 object_id,
                 object,
             ));
+            self.trace_instance_created(instance);
             self.room.instance_list.get(instance).set_speed_direction(speed, direction);
             self.run_instance_event(gml::ev::CREATE, 0, instance, instance, None)?;
             Ok(Default::default())
@@ -2891,6 +2894,7 @@ impl Game {
             self.last_instance_id += 1;
             let id = self.last_instance_id;
             let instance = self.room.instance_list.insert(Instance::new(id, x, y, object_id, object));
+            self.trace_instance_created(instance);
             self.run_instance_event(gml::ev::CREATE, 0, instance, instance, None)?;
         }
         Ok(Default::default())
@@ -3076,46 +3080,68 @@ impl Game {
         }])
     }
 
-    pub fn action_splash_text(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function action_splash_text");
+    pub fn action_splash_text(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let fname = expect_args!(args, [string])?;
+        self.splash_text("action_splash_text", &fname, 0)?;
         Ok(Default::default())
     }
 
-    pub fn action_splash_image(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function action_splash_image");
+    pub fn action_splash_image(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let fname = expect_args!(args, [string])?;
+        self.splash_image("action_splash_image", &fname, 0)?;
         Ok(Default::default())
     }
 
-    pub fn action_splash_web(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function action_splash_web");
+    pub fn action_splash_web(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (url, in_browser) = expect_args!(args, [string, bool])?;
+        if in_browser {
+            // Opening the player's web browser would be a side effect outside of the game, so don't
+            eprintln!("Ignoring action_splash_web opening {} in a browser", url);
+        } else {
+            self.splash_web("action_splash_web", &url, 0)?;
+        }
         Ok(Default::default())
     }
 
-    pub fn action_splash_settings(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        println!("Called unimplemented kernel function action_splash_settings");
+    pub fn action_splash_settings(&mut self, args: &[Value]) -> gml::Result<Value> {
+        // Arguments: where to show (game window, separate window or fullscreen), caption, close button,
+        // whether a key stops it, and whether a click stops it
+        let (kind, caption, close_button, stop_key, stop_mouse) = expect_args!(args, [int, bytes, bool, bool, bool])?;
+        let settings = &mut self.splash_settings;
+        settings.main = kind == 0;
+        settings.fullscreen = kind == 2;
+        settings.caption = caption;
+        settings.close_button = close_button;
+        settings.stop_key = stop_key;
+        settings.stop_mouse = stop_mouse;
         Ok(Default::default())
     }
 
-    pub fn action_replace_sprite(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function action_replace_sprite");
-        Ok(Default::default())
+    pub fn action_replace_sprite(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sprite_id, fname, imgnumb) = expect_args!(args, [int, any, int])?;
+        // the sprite keeps its origin
+        let (origin_x, origin_y) =
+            self.assets.sprites.get_asset(sprite_id).map(|s| (s.origin_x, s.origin_y)).unwrap_or((0, 0));
+        self.sprite_replace(&[
+            sprite_id.into(),
+            fname,
+            imgnumb.into(),
+            false.into(),
+            false.into(),
+            origin_x.into(),
+            origin_y.into(),
+        ])
     }
 
-    pub fn action_replace_sound(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function action_replace_sound");
-        Ok(Default::default())
+    pub fn action_replace_sound(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, fname) = expect_args!(args, [int, any])?;
+        let kind = self.assets.sounds.get_asset(sound_id).map(|s| Value::from(s.gml_kind)).unwrap_or_default();
+        self.sound_replace(&[sound_id.into(), fname, kind, true.into()])
     }
 
-    pub fn action_replace_background(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function action_replace_background");
-        Ok(Default::default())
+    pub fn action_replace_background(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (background_id, fname) = expect_args!(args, [int, any])?;
+        self.background_replace(&[background_id.into(), fname, false.into(), false.into()])
     }
 
     pub fn action_if_empty(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
@@ -3221,7 +3247,10 @@ impl Game {
                 ],
                 5,
             );
-            self.execute(&instructions, &mut new_context)?;
+            self.profiler.enter(ProfileKey::Script(script_id as usize));
+            let result = self.execute(&instructions, &mut new_context);
+            self.profiler.exit();
+            result?;
             Ok(new_context.return_value)
         } else {
             Err(gml::Error::NonexistentAsset(asset::Type::Script, script_id))
@@ -3297,9 +3326,26 @@ impl Game {
         self.draw_text(&[x.into(), y.into(), format!("{}{}", caption, self.score).into()])
     }
 
-    pub fn action_highscore_show(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        println!("Called unimplemented kernel function action_highscore_show");
+    pub fn action_highscore_show(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (background, border, new_colour, other_colour, font) = expect_args!(args, [int, bool, int, int, bytes])?;
+        // The font is a font string like "Times New Roman,10,0,0,0,0,0": name, size, colour, bold, italic, ...
+        let mut parts = font.as_ref().split(|c| *c == b',');
+        let settings = &mut self.highscores.settings;
+        settings.background = background;
+        settings.border = border;
+        settings.new_colour = new_colour;
+        settings.other_colour = other_colour;
+        if let Some(name) = parts.next().filter(|x| !x.is_empty()) {
+            settings.font.name = name.into();
+        }
+        let mut numbers = parts.map(|x| std::str::from_utf8(x).ok().and_then(|x| x.trim().parse::<i32>().ok()));
+        if let Some(Some(size)) = numbers.next() {
+            settings.font.size = size;
+        }
+        let mut flags = numbers.skip(1).map(|x| x.unwrap_or(0) != 0);
+        let (bold, italic) = (flags.next().unwrap_or(false), flags.next().unwrap_or(false));
+        settings.font.style = i32::from(bold) | (i32::from(italic) << 1);
+        self.highscore_show_table(self.score)?;
         Ok(Default::default())
     }
 
@@ -4859,25 +4905,15 @@ impl Game {
             usize::try_from(path_id).ok().and_then(|id| self.assets.paths.get_mut(id)).and_then(Option::take)
         {
             let inst = self.room.instance_list.get(context.this);
-            let coll = || {
+            let found = pathfinding::linear_path(inst, &mut path, xg, yg, step_size, || {
                 if checkall {
                     self.check_collision_any(context.this).is_some()
                 } else {
                     self.check_collision_solid(context.this).is_some()
                 }
-            };
-            pathfinding::make_path(inst, &mut path, |inst| {
-                let (old_x, old_y) = (inst.x.get(), inst.y.get());
-                if pathfinding::linear_step(xg, yg, step_size, inst, coll) {
-                    pathfinding::PathGenResult::Done
-                } else if inst.x.get() == old_x && inst.y.get() == old_y {
-                    pathfinding::PathGenResult::Failed
-                } else {
-                    pathfinding::PathGenResult::NotDone
-                }
             });
             self.assets.paths[path_id as usize] = Some(path);
-            Ok(Default::default())
+            Ok(found.into())
         } else {
             Err(gml::Error::NonexistentAsset(asset::Type::Path, path_id))
         }
@@ -4893,10 +4929,22 @@ impl Game {
         .into())
     }
 
-    pub fn mp_linear_path_object(&mut self, _context: &mut Context, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        println!("Called unimplemented kernel function mp_linear_path_object");
-        Ok(Default::default())
+    pub fn mp_linear_path_object(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, xg, yg, step_size, obj) = expect_args!(args, [int, real, real, real, int])?;
+        if let Some(mut path) =
+            usize::try_from(path_id).ok().and_then(|id| self.assets.paths.get_mut(id)).and_then(Option::take)
+        {
+            let inst = self.room.instance_list.get(context.this);
+            let found = pathfinding::linear_path(inst, &mut path, xg, yg, step_size, || match obj {
+                gml::SELF => false,
+                gml::OTHER => self.check_collision(context.this, context.other),
+                obj => self.find_instance_with(obj, |handle| self.check_collision(context.this, handle)).is_some(),
+            });
+            self.assets.paths[path_id as usize] = Some(path);
+            Ok(found.into())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Path, path_id))
+        }
     }
 
     pub fn mp_potential_settings(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -4929,10 +4977,25 @@ impl Game {
         .into())
     }
 
-    pub fn mp_potential_path(&mut self, _context: &mut Context, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        println!("Called unimplemented kernel function mp_potential_path");
-        Ok(Default::default())
+    pub fn mp_potential_path(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, xg, yg, step_size, factor, checkall) = expect_args!(args, [int, real, real, real, real, bool])?;
+        if let Some(mut path) =
+            usize::try_from(path_id).ok().and_then(|id| self.assets.paths.get_mut(id)).and_then(Option::take)
+        {
+            let inst = self.room.instance_list.get(context.this);
+            let settings = &self.potential_step_settings;
+            let found = pathfinding::potential_path(inst, &mut path, xg, yg, step_size, factor, settings, || {
+                if checkall {
+                    self.check_collision_any(context.this).is_some()
+                } else {
+                    self.check_collision_solid(context.this).is_some()
+                }
+            });
+            self.assets.paths[path_id as usize] = Some(path);
+            Ok(found.into())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Path, path_id))
+        }
     }
 
     pub fn mp_potential_step_object(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
@@ -4952,10 +5015,23 @@ impl Game {
         .into())
     }
 
-    pub fn mp_potential_path_object(&mut self, _context: &mut Context, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        println!("Called unimplemented kernel function mp_potential_path_object");
-        Ok(Default::default())
+    pub fn mp_potential_path_object(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, xg, yg, step_size, factor, obj) = expect_args!(args, [int, real, real, real, real, int])?;
+        if let Some(mut path) =
+            usize::try_from(path_id).ok().and_then(|id| self.assets.paths.get_mut(id)).and_then(Option::take)
+        {
+            let inst = self.room.instance_list.get(context.this);
+            let settings = &self.potential_step_settings;
+            let found = pathfinding::potential_path(inst, &mut path, xg, yg, step_size, factor, settings, || match obj {
+                gml::SELF => false,
+                gml::OTHER => self.check_collision(context.this, context.other),
+                obj => self.find_instance_with(obj, |handle| self.check_collision(context.this, handle)).is_some(),
+            });
+            self.assets.paths[path_id as usize] = Some(path);
+            Ok(found.into())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Path, path_id))
+        }
     }
 
     pub fn mp_grid_create(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -5088,16 +5164,106 @@ impl Game {
         }
     }
 
-    pub fn mp_grid_add_instances(&mut self, _context: &mut Context, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function mp_grid_add_instances");
+    pub fn mp_grid_add_instances(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        let (id, obj, precise) = expect_args!(args, [int, int, bool])?;
+        let mpgrid = match self.mpgrids.get(id) {
+            Some(mpgrid) => mpgrid,
+            None => {
+                return Err(gml::Error::FunctionError(
+                    "mp_grid_add_instances".into(),
+                    pathfinding::Error::NonexistentStructure(id).into(),
+                ))
+            },
+        };
+        let mut instances = Vec::new();
+        match obj {
+            gml::SELF => instances.push(context.this),
+            gml::OTHER => instances.push(context.other),
+            gml::ALL => {
+                let mut iter = self.room.instance_list.iter_by_drawing();
+                while let Some(handle) = iter.next(&self.room.instance_list) {
+                    instances.push(handle);
+                }
+            },
+            _ if obj < 0 => (),
+            obj if obj < 100000 => {
+                let mut iter = self.room.instance_list.iter_by_identity(obj);
+                while let Some(handle) = iter.next(&self.room.instance_list) {
+                    instances.push(handle);
+                }
+            },
+            inst_id => {
+                if let Some(handle) = self.room.instance_list.get_by_instid(inst_id) {
+                    if self.room.instance_list.get(handle).is_active() {
+                        instances.push(handle);
+                    }
+                }
+            },
+        }
+        // a cell gets marked if any of the instances touch it, like with collision_rectangle,
+        // and only the cells under an instance's bounding box can be touched by it
+        let mut occupied_cells = Vec::new();
+        for handle in instances {
+            let instance = self.room.instance_list.get(handle);
+            instance.update_bbox(self.get_instance_mask_sprite(handle));
+            let (columns, rows) = match mpgrid.cells_in_rectangle(
+                instance.bbox_left.get(),
+                instance.bbox_top.get(),
+                instance.bbox_right.get(),
+                instance.bbox_bottom.get(),
+            ) {
+                Some(cells) => cells,
+                None => continue,
+            };
+            for x in columns {
+                for y in rows.clone() {
+                    if mpgrid.get(x, y) == -1 {
+                        continue
+                    }
+                    let x1 = mpgrid.left + x as i32 * mpgrid.cellwidth;
+                    let y1 = mpgrid.top + y as i32 * mpgrid.cellheight;
+                    let x2 = x1 + mpgrid.cellwidth - 1;
+                    let y2 = y1 + mpgrid.cellheight - 1;
+                    if !precise || self.check_collision_rectangle(handle, x1, y1, x2, y2, true) {
+                        occupied_cells.push((x, y));
+                    }
+                }
+            }
+        }
+        if let Some(mpgrid) = self.mpgrids.get_mut(id) {
+            for (x, y) in occupied_cells {
+                mpgrid.set(x, y, -1);
+            }
+        }
         Ok(Default::default())
     }
 
-    pub fn mp_grid_path(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        println!("Called unimplemented kernel function mp_grid_path");
-        Ok(Default::default())
+    pub fn mp_grid_path(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, path_id, xstart, ystart, xgoal, ygoal, allow_diag) =
+            expect_args!(args, [int, int, real, real, real, real, bool])?;
+        let points = match self.mpgrids.get(id) {
+            Some(mpgrid) => mpgrid.find_path(xstart, ystart, xgoal, ygoal, allow_diag),
+            None => {
+                return Err(gml::Error::FunctionError(
+                    "mp_grid_path".into(),
+                    pathfinding::Error::NonexistentStructure(id).into(),
+                ))
+            },
+        };
+        match self.assets.paths.get_asset_mut(path_id) {
+            Some(path) => {
+                path.curve = false;
+                path.closed = false;
+                path.points.clear();
+                let found = points.is_some();
+                for (x, y) in points.into_iter().flatten() {
+                    path.points.push(asset::path::Point { x, y, speed: 100.into() });
+                }
+                path.update();
+                Ok(found.into())
+            },
+            None => Err(gml::Error::NonexistentAsset(asset::Type::Path, path_id)),
+        }
     }
 
     pub fn mp_grid_draw(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -5416,6 +5582,7 @@ impl Game {
         self.last_instance_id += 1;
         let id = self.last_instance_id;
         let instance = self.room.instance_list.insert(Instance::new(id, x, y, object_id, object));
+        self.trace_instance_created(instance);
         self.run_instance_event(gml::ev::CREATE, 0, instance, instance, None)?;
         Ok(id.into())
     }
@@ -5427,6 +5594,7 @@ impl Game {
         let id = self.last_instance_id;
         new_instance.id.set(id);
         let handle = self.room.instance_list.insert(new_instance);
+        self.trace_instance_created(handle);
         if run_event {
             self.run_instance_event(gml::ev::CREATE, 0, handle, handle, None)?;
         }
@@ -5468,6 +5636,7 @@ impl Game {
 
         self.room.instance_list.mark_deleted(context.this);
         let handle = self.room.instance_list.insert(new_instance);
+        self.trace_instance_changed(context.this, handle);
 
         if run_events {
             self.run_instance_event(gml::ev::CREATE, 0, handle, handle, None)?;
@@ -5479,6 +5648,7 @@ impl Game {
     pub fn instance_destroy(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
         self.run_instance_event(gml::ev::DESTROY, 0, context.this, context.this, None)?;
+        self.trace_instance_destroyed(context.this);
         self.room.instance_list.mark_deleted(context.this);
         Ok(Default::default())
     }
@@ -5510,6 +5680,7 @@ impl Game {
         while let Some(handle) = iter.next(&self.room.instance_list) {
             if self.check_collision_point(handle, x, y, true) {
                 self.run_instance_event(gml::ev::DESTROY, 0, handle, handle, None)?;
+                self.trace_instance_destroyed(handle);
                 self.room.instance_list.mark_deleted(handle);
             }
         }
@@ -5768,7 +5939,11 @@ impl Game {
     pub fn game_save(&mut self, args: &[Value]) -> gml::Result<Value> {
         let fname = expect_args!(args, [string])?;
         let save = GMSave::from_game(self);
-        let mut file = std::fs::File::create(file::to_path(&fname).as_ref())
+        let mut file = vfs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&self.vfs, file::to_path(&fname).as_ref())
             .map(std::io::BufWriter::new)
             .map_err(|e| gml::Error::FunctionError("game_save".into(), format!("{}", e)))?;
         // write magic number (0x21c in GM8)
@@ -5891,7 +6066,7 @@ impl Game {
             1 => file::AccessMode::Write,
             2 | _ => file::AccessMode::Special,
         };
-        match self.binary_files.add_from(|| Ok(file::BinaryHandle::open(file::to_path(&filename).as_ref(), mode)?)) {
+        match self.binary_files.add_from(|| Ok(file::BinaryHandle::open(&self.vfs, file::to_path(&filename).as_ref(), mode)?)) {
             Ok(i) => Ok((i + 1).into()),
             Err(e) => Err(gml::Error::FunctionError("file_bin_open".into(), e.to_string())),
         }
@@ -5969,7 +6144,7 @@ impl Game {
         let filename = expect_args!(args, [string])?;
         use std::error::Error as _; // for .source() trait method
 
-        match self.text_files.add_from(|| Ok(file::TextHandle::open(file::to_path(&filename).as_ref(), file::AccessMode::Read)?)) {
+        match self.text_files.add_from(|| Ok(file::TextHandle::open(&self.vfs, file::to_path(&filename).as_ref(), file::AccessMode::Read)?)) {
             Ok(i) => Ok((i + 1).into()),
             Err(e)
                 if e.source()
@@ -5984,7 +6159,7 @@ impl Game {
 
     pub fn file_text_open_write(&mut self, args: &[Value]) -> gml::Result<Value> {
         let filename = expect_args!(args, [string])?;
-        match self.text_files.add_from(|| Ok(file::TextHandle::open(file::to_path(&filename).as_ref(), file::AccessMode::Write)?)) {
+        match self.text_files.add_from(|| Ok(file::TextHandle::open(&self.vfs, file::to_path(&filename).as_ref(), file::AccessMode::Write)?)) {
             Ok(i) => Ok((i + 1).into()),
             Err(e) => Err(gml::Error::FunctionError("file_text_open_write".into(), e.to_string())),
         }
@@ -5992,7 +6167,7 @@ impl Game {
 
     pub fn file_text_open_append(&mut self, args: &[Value]) -> gml::Result<Value> {
         let filename = expect_args!(args, [string])?;
-        match self.text_files.add_from(|| Ok(file::TextHandle::open(file::to_path(&filename).as_ref(), file::AccessMode::Special)?)) {
+        match self.text_files.add_from(|| Ok(file::TextHandle::open(&self.vfs, file::to_path(&filename).as_ref(), file::AccessMode::Special)?)) {
             Ok(i) => Ok((i + 1).into()),
             Err(e) => Err(gml::Error::FunctionError("file_text_open_append".into(), e.to_string())),
         }
@@ -6090,7 +6265,7 @@ impl Game {
 
     pub fn file_open_read(&mut self, args: &[Value]) -> gml::Result<Value> {
         let filename = expect_args!(args, [string])?;
-        match file::TextHandle::open(file::to_path(&filename).as_ref(), file::AccessMode::Read) {
+        match file::TextHandle::open(&self.vfs, file::to_path(&filename).as_ref(), file::AccessMode::Read) {
             Ok(f) => {
                 self.open_file.replace(f);
             },
@@ -6106,7 +6281,7 @@ impl Game {
 
     pub fn file_open_write(&mut self, args: &[Value]) -> gml::Result<Value> {
         let filename = expect_args!(args, [string])?;
-        match file::TextHandle::open(file::to_path(&filename).as_ref(), file::AccessMode::Write) {
+        match file::TextHandle::open(&self.vfs, file::to_path(&filename).as_ref(), file::AccessMode::Write) {
             Ok(f) => {
                 self.open_file.replace(f);
                 Ok(Default::default())
@@ -6120,7 +6295,7 @@ impl Game {
 
     pub fn file_open_append(&mut self, args: &[Value]) -> gml::Result<Value> {
         let filename = expect_args!(args, [string])?;
-        match file::TextHandle::open(file::to_path(&filename).as_ref(), file::AccessMode::Special) {
+        match file::TextHandle::open(&self.vfs, file::to_path(&filename).as_ref(), file::AccessMode::Special) {
             Ok(f) => {
                 self.open_file.replace(f);
                 Ok(Default::default())
@@ -6209,14 +6384,14 @@ impl Game {
 
     pub fn file_exists(&self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [any]).map(|x| match x {
-            Value::Str(s) => file::file_exists(file::to_path(&self.decode_str(s.as_ref())).as_ref()).into(),
+            Value::Str(s) => self.vfs.file_exists(file::to_path(&self.decode_str(s.as_ref())).as_ref()).into(),
             Value::Real(_) => gml::FALSE.into(),
         })
     }
 
     pub fn file_delete(&self, args: &[Value]) -> gml::Result<Value> {
         let filename = expect_args!(args, [string])?;
-        match file::delete(file::to_path(&filename).as_ref()) {
+        match self.vfs.delete(file::to_path(&filename).as_ref()) {
             Ok(()) => Ok(Default::default()),
             Err(e) => Err(gml::Error::FunctionError("file_delete".into(), e.to_string())),
         }
@@ -6224,7 +6399,7 @@ impl Game {
 
     pub fn file_rename(&self, args: &[Value]) -> gml::Result<Value> {
         let (from, to) = expect_args!(args, [string, string])?;
-        if file::rename(file::to_path(&from).as_ref(), file::to_path(&to).as_ref()).is_err() {
+        if self.vfs.rename(file::to_path(&from).as_ref(), file::to_path(&to).as_ref()).is_err() {
             // Fail silently
             eprintln!("Warning (file_rename): could not rename {} to {}", from, to);
         }
@@ -6233,7 +6408,7 @@ impl Game {
 
     pub fn file_copy(&self, args: &[Value]) -> gml::Result<Value> {
         let (from, to) = expect_args!(args, [string, string])?;
-        if file::copy(file::to_path(&from).as_ref(), file::to_path(&to).as_ref()).is_err() {
+        if self.vfs.copy(file::to_path(&from).as_ref(), file::to_path(&to).as_ref()).is_err() {
             // Fail silently
             eprintln!("Warning (file_copy): could not copy {} to {}", from, to);
         }
@@ -6242,14 +6417,14 @@ impl Game {
 
     pub fn directory_exists(&self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [any]).map(|x| match x {
-            Value::Str(s) => file::dir_exists(file::to_path(&self.decode_str(s.as_ref())).as_ref()).into(),
+            Value::Str(s) => self.vfs.dir_exists(file::to_path(&self.decode_str(s.as_ref())).as_ref()).into(),
             Value::Real(_) => gml::FALSE.into(),
         })
     }
 
     pub fn directory_create(&self, args: &[Value]) -> gml::Result<Value> {
         let path = expect_args!(args, [string])?;
-        match file::dir_create(file::to_path(&path).as_ref()) {
+        match self.vfs.dir_create(file::to_path(&path).as_ref()) {
             Ok(()) => Ok(Default::default()),
             Err(e) => Err(gml::Error::FunctionError("directory_create".into(), e.to_string())),
         }
@@ -6265,43 +6440,19 @@ impl Game {
         // unwrap arguments
         let path: &str = path.as_ref();
         let include_read_only = (attribs & 1) != 0;
-        let include_hidden = (attribs & 2) != 0;
-        let include_sys_file = (attribs & 4) != 0;
-        let include_volume_id = (attribs & 8) != 0;
         let include_directory = (attribs & 16) != 0;
-        let include_archive = (attribs & 32) != 0;
-        match glob::glob_with(path, glob::MatchOptions { case_sensitive: false, ..Default::default() }) {
-            Ok(paths) => {
+        // hidden, system, volume id and archive attributes aren't checked yet
+        match self.vfs.find(path, include_directory, include_read_only) {
+            Ok(names) => {
                 // add . and .. to start if necessary
-                let path: &std::path::Path = path.as_ref();
-                let preceding: Vec<std::path::PathBuf> = match path.file_name().and_then(|p| p.to_str()) {
-                    Some("*") | Some(".*") | Some("*.") => vec![".".into(), "..".into()],
-                    Some(".") => vec![".".into()],
-                    Some("..") => vec!["..".into()],
-                    _ => vec![],
-                };
-                self.file_finder = Some(Box::new(
-                    preceding.into_iter().chain(
-                        paths
-                            .filter_map(Result::ok)
-                            .filter(move |p| {
-                                let md = match p.metadata() {
-                                    Ok(m) => m,
-                                    Err(_) => return false,
-                                };
-                                let is_dir = md.is_dir();
-                                // false means the check isn't in yet
-                                // also note: apparently directories are read only?
-                                (include_read_only || is_dir || !md.permissions().readonly())
-                                    && (include_hidden || !false)
-                                    && (include_sys_file || !false)
-                                    && (include_volume_id || !false)
-                                    && (include_directory || !is_dir)
-                                    && (include_archive || !false)
-                            })
-                            .map(|p| p.file_name().map(|p| p.into()).unwrap_or(p)),
-                    ),
-                ));
+                let preceding: Vec<std::path::PathBuf> =
+                    match std::path::Path::new(path).file_name().and_then(|p| p.to_str()) {
+                        Some("*") | Some(".*") | Some("*.") => vec![".".into(), "..".into()],
+                        Some(".") => vec![".".into()],
+                        Some("..") => vec!["..".into()],
+                        _ => vec![],
+                    };
+                self.file_finder = Some(Box::new(preceding.into_iter().chain(names.into_iter().map(Into::into))));
                 self.file_find_next(&[])
             },
             Err(e) => Err(gml::Error::FunctionError("file_find_first".into(), e.to_string())),
@@ -6386,7 +6537,7 @@ impl Game {
         let program_directory = self.decode_str(self.program_directory.as_ref()).into_owned().into();
         if let Some(file) = self.included_files.iter_mut().filter(|i| name.eq_ignore_ascii_case(i.name.as_ref())).next()
         {
-            match file.export(temp_directory, program_directory) {
+            match file.export(&self.vfs, temp_directory, program_directory) {
                 Ok(()) => Ok(Default::default()),
                 Err(e) => Err(gml::Error::FunctionError("export_include_file".into(), e.to_string())),
             }
@@ -6400,7 +6551,7 @@ impl Game {
         if let Some(file) = self.included_files.iter_mut().filter(|i| name.eq_ignore_ascii_case(i.name.as_ref())).next()
         {
             let path_ref: &str = path.as_ref();
-            match file.export_to(path_ref.as_ref()) {
+            match file.export_to(&self.vfs, path_ref.as_ref()) {
                 Ok(()) => Ok(Default::default()),
                 Err(e) => Err(gml::Error::FunctionError("export_include_file_location".into(), e.to_string())),
             }
@@ -6514,77 +6665,79 @@ impl Game {
         Ok(env.as_ref().into())
     }
 
-    pub fn registry_write_string(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function registry_write_string");
+    pub fn registry_write_string(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, value) = expect_args!(args, [bytes, bytes])?;
+        let value = RegistryValue::String(self.decode_str(value.as_ref()).into_owned());
+        self.registry_write(None, name.as_ref(), value);
         Ok(Default::default())
     }
 
-    pub fn registry_write_real(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function registry_write_real");
+    pub fn registry_write_real(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, value) = expect_args!(args, [bytes, real])?;
+        self.registry_write(None, name.as_ref(), RegistryValue::Real(value.into()));
         Ok(Default::default())
     }
 
-    pub fn registry_read_string(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function registry_read_string");
-        Ok(Default::default())
+    pub fn registry_read_string(&self, args: &[Value]) -> gml::Result<Value> {
+        let name = expect_args!(args, [bytes])?;
+        Ok(self.registry_value_to_string(self.registry_read(None, name.as_ref())))
     }
 
-    pub fn registry_read_real(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function registry_read_real");
-        Ok(Default::default())
+    pub fn registry_read_real(&self, args: &[Value]) -> gml::Result<Value> {
+        let name = expect_args!(args, [bytes])?;
+        Ok(Game::registry_value_to_real(self.registry_read(None, name.as_ref())))
     }
 
-    pub fn registry_exists(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function registry_exists");
-        Ok(Default::default())
+    pub fn registry_exists(&self, args: &[Value]) -> gml::Result<Value> {
+        let name = expect_args!(args, [bytes])?;
+        Ok(self.registry_read(None, name.as_ref()).is_some().into())
     }
 
-    pub fn registry_write_string_ext(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function registry_write_string_ext");
+    pub fn registry_write_string_ext(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (key, name, value) = expect_args!(args, [bytes, bytes, bytes])?;
+        let value = RegistryValue::String(self.decode_str(value.as_ref()).into_owned());
+        self.registry_write(Some(key.as_ref()), name.as_ref(), value);
         Ok(Default::default())
     }
 
-    pub fn registry_write_real_ext(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function registry_write_real_ext");
+    pub fn registry_write_real_ext(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (key, name, value) = expect_args!(args, [bytes, bytes, real])?;
+        self.registry_write(Some(key.as_ref()), name.as_ref(), RegistryValue::Real(value.into()));
         Ok(Default::default())
     }
 
-    pub fn registry_read_string_ext(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function registry_read_string_ext");
-        Ok(Default::default())
+    pub fn registry_read_string_ext(&self, args: &[Value]) -> gml::Result<Value> {
+        let (key, name) = expect_args!(args, [bytes, bytes])?;
+        Ok(self.registry_value_to_string(self.registry_read(Some(key.as_ref()), name.as_ref())))
     }
 
-    pub fn registry_read_real_ext(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function registry_read_real_ext");
-        Ok(Default::default())
+    pub fn registry_read_real_ext(&self, args: &[Value]) -> gml::Result<Value> {
+        let (key, name) = expect_args!(args, [bytes, bytes])?;
+        Ok(Game::registry_value_to_real(self.registry_read(Some(key.as_ref()), name.as_ref())))
     }
 
-    pub fn registry_exists_ext(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function registry_exists_ext");
-        Ok(Default::default())
+    pub fn registry_exists_ext(&self, args: &[Value]) -> gml::Result<Value> {
+        let (key, name) = expect_args!(args, [bytes, bytes])?;
+        Ok(self.registry_read(Some(key.as_ref()), name.as_ref()).is_some().into())
     }
 
-    pub fn registry_set_root(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function registry_set_root");
+    pub fn registry_set_root(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let root = expect_args!(args, [int])?;
+        self.registry.set_root(root);
         Ok(Default::default())
     }
 
     pub fn ini_open(&mut self, args: &[Value]) -> gml::Result<Value> {
         let name = expect_args!(args, [bytes])?;
         let name_str = self.decode_str(name.as_ref());
-        if file::file_exists(&file::to_path(&name_str)) {
-            match ini::Ini::load_from_file(name_str.as_ref()) {
+        if self.vfs.file_exists(&file::to_path(&name_str)) {
+            let data = self
+                .vfs
+                .read(name_str.as_ref())
+                .map_err(|e| gml::Error::FunctionError("ini_open".into(), format!("{}", e)))?;
+            // skip the UTF-8 BOM if there is one, same as Ini::load_from_file
+            let mut data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&data[..]);
+            match ini::Ini::read_from(&mut data) {
                 Ok(ini) => {
                     self.open_ini = Some((ini, name));
                     Ok(Default::default())
@@ -6600,12 +6753,16 @@ impl Game {
     pub fn ini_close(&mut self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
         match self.open_ini.as_ref() {
-            Some((ini, path)) => match ini.write_to_file(self.decode_str(path.as_ref()).as_ref()) {
-                Ok(()) => {
-                    self.open_ini = None;
-                    Ok(Default::default())
-                },
-                Err(e) => Err(gml::Error::FunctionError("ini_close".into(), format!("{}", e))),
+            Some((ini, path)) => {
+                let mut data = Vec::new();
+                let path = self.decode_str(path.as_ref());
+                match ini.write_to(&mut data).and_then(|_| self.vfs.write(path.as_ref(), &data)) {
+                    Ok(()) => {
+                        self.open_ini = None;
+                        Ok(Default::default())
+                    },
+                    Err(e) => Err(gml::Error::FunctionError("ini_close".into(), format!("{}", e))),
+                }
             },
             None => Ok(Default::default()),
         }
@@ -6751,148 +6908,146 @@ impl Game {
         Ok((0x1_00000_00000u64 as f64).into())
     }
 
-    pub fn splash_set_caption(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function splash_set_caption");
+    pub fn splash_set_caption(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let caption = expect_args!(args, [bytes])?;
+        self.splash_settings.caption = caption;
         Ok(Default::default())
     }
 
-    pub fn splash_set_fullscreen(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function splash_set_fullscreen");
+    pub fn splash_set_fullscreen(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let full = expect_args!(args, [bool])?;
+        self.splash_settings.fullscreen = full;
         Ok(Default::default())
     }
 
-    pub fn splash_set_border(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function splash_set_border");
+    pub fn splash_set_border(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let border = expect_args!(args, [bool])?;
+        self.splash_settings.border = border;
         Ok(Default::default())
     }
 
-    pub fn splash_set_size(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function splash_set_size");
+    pub fn splash_set_size(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (width, height) = expect_args!(args, [int, int])?;
+        self.splash_settings.size = (width, height);
         Ok(Default::default())
     }
 
-    pub fn splash_set_position(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function splash_set_position");
+    pub fn splash_set_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y) = expect_args!(args, [int, int])?;
+        self.splash_settings.position = (x, y);
         Ok(Default::default())
     }
 
-    pub fn splash_set_adapt(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function splash_set_adapt");
+    pub fn splash_set_adapt(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let adapt = expect_args!(args, [bool])?;
+        self.splash_settings.adapt = adapt;
         Ok(Default::default())
     }
 
-    pub fn splash_set_top(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function splash_set_top");
+    pub fn splash_set_top(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let top = expect_args!(args, [bool])?;
+        self.splash_settings.top = top;
         Ok(Default::default())
     }
 
-    pub fn splash_set_color(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function splash_set_color");
+    pub fn splash_set_color(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let colour = expect_args!(args, [int])?;
+        self.splash_settings.colour = colour;
         Ok(Default::default())
     }
 
-    pub fn splash_set_main(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function splash_set_main");
+    pub fn splash_set_main(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let main = expect_args!(args, [bool])?;
+        self.splash_settings.main = main;
         Ok(Default::default())
     }
 
-    pub fn splash_set_scale(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function splash_set_scale");
+    pub fn splash_set_scale(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let scale = expect_args!(args, [real])?;
+        self.splash_settings.scale = scale.into();
         Ok(Default::default())
     }
 
-    pub fn splash_set_cursor(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function splash_set_cursor");
+    pub fn splash_set_cursor(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let cursor = expect_args!(args, [bool])?;
+        self.splash_settings.cursor = cursor;
         Ok(Default::default())
     }
 
-    pub fn splash_set_interrupt(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function splash_set_interrupt");
+    pub fn splash_set_interrupt(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let interrupt = expect_args!(args, [bool])?;
+        self.splash_settings.interrupt = interrupt;
         Ok(Default::default())
     }
 
-    pub fn splash_set_stop_key(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function splash_set_stop_key");
+    pub fn splash_set_stop_key(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let stop = expect_args!(args, [bool])?;
+        self.splash_settings.stop_key = stop;
         Ok(Default::default())
     }
 
-    pub fn splash_set_close_button(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function splash_set_close_button");
+    pub fn splash_set_close_button(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let show = expect_args!(args, [bool])?;
+        self.splash_settings.close_button = show;
         Ok(Default::default())
     }
 
-    pub fn splash_set_stop_mouse(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function splash_set_stop_mouse");
+    pub fn splash_set_stop_mouse(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let stop = expect_args!(args, [bool])?;
+        self.splash_settings.stop_mouse = stop;
         Ok(Default::default())
     }
 
-    pub fn splash_show_video(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function splash_show_video");
+    pub fn splash_show_video(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (fname, _loop) = expect_args!(args, [string, bool])?;
+        self.splash_video("splash_show_video", &fname)?;
         Ok(Default::default())
     }
 
-    pub fn splash_show_image(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function splash_show_image");
+    pub fn splash_show_image(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (fname, delay) = expect_args!(args, [string, int])?;
+        self.splash_image("splash_show_image", &fname, delay)?;
         Ok(Default::default())
     }
 
-    pub fn splash_show_text(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function splash_show_text");
+    pub fn splash_show_text(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (fname, delay) = expect_args!(args, [string, int])?;
+        self.splash_text("splash_show_text", &fname, delay)?;
         Ok(Default::default())
     }
 
-    pub fn splash_show_web(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function splash_show_web");
+    pub fn splash_show_web(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (url, delay) = expect_args!(args, [string, int])?;
+        self.splash_web("splash_show_web", &url, delay)?;
         Ok(Default::default())
     }
 
-    pub fn show_image(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function show_image");
+    pub fn show_image(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (fname, full, delay) = expect_args!(args, [string, bool, int])?;
+        self.splash_legacy_image(&fname, full, delay)?;
         Ok(Default::default())
     }
 
-    pub fn show_video(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function show_video");
+    pub fn show_video(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (fname, full, _loop) = expect_args!(args, [string, bool, bool])?;
+        self.splash_legacy_video(&fname, full)?;
         Ok(Default::default())
     }
 
-    pub fn show_text(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        println!("Called unimplemented kernel function show_text");
+    pub fn show_text(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (fname, full, colour, delay) = expect_args!(args, [string, bool, int, int])?;
+        self.splash_legacy_text(&fname, full, colour, delay)?;
         Ok(Default::default())
     }
 
-    pub fn show_message(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function show_message");
-        Ok(Default::default())
+    pub fn show_message(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let message = expect_args!(args, [any])?;
+        self.dialog_show_message(message.repr())
     }
 
-    pub fn show_question(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function show_question");
-        Ok(Default::default())
+    pub fn show_question(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let message = expect_args!(args, [any])?;
+        self.dialog_show_question(message.repr())
     }
 
     pub fn show_error(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -6900,238 +7055,251 @@ impl Game {
         Err(gml::Error::FunctionError("show_error".into(), text.into()))
     }
 
-    pub fn show_info(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function show_info");
+    pub fn show_info(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.show_game_info()?;
         Ok(Default::default())
     }
 
-    pub fn load_info(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function load_info");
+    pub fn load_info(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let fname = expect_args!(args, [string])?;
+        self.load_game_info(&fname)?;
         Ok(Default::default())
     }
 
-    pub fn highscore_show(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function highscore_show");
+    pub fn highscore_show(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let score = expect_args!(args, [int])?;
+        self.highscore_show_table(score)?;
         Ok(Default::default())
     }
 
-    pub fn highscore_set_background(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function highscore_set_background");
+    pub fn highscore_set_background(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let background = expect_args!(args, [int])?;
+        self.highscores.settings.background = background;
         Ok(Default::default())
     }
 
-    pub fn highscore_set_border(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function highscore_set_border");
+    pub fn highscore_set_border(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let border = expect_args!(args, [bool])?;
+        self.highscores.settings.border = border;
         Ok(Default::default())
     }
 
-    pub fn highscore_set_font(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function highscore_set_font");
+    pub fn highscore_set_font(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, size, style) = expect_args!(args, [bytes, int, int])?;
+        let font = &mut self.highscores.settings.font;
+        font.name = name;
+        font.size = size;
+        font.style = style;
         Ok(Default::default())
     }
 
-    pub fn highscore_set_strings(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function highscore_set_strings");
+    pub fn highscore_set_strings(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (caption, nobody, escape) = expect_args!(args, [bytes, bytes, bytes])?;
+        let settings = &mut self.highscores.settings;
+        settings.caption = caption;
+        settings.nobody = nobody;
+        settings.escape = escape;
         Ok(Default::default())
     }
 
-    pub fn highscore_set_colors(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function highscore_set_colors");
+    pub fn highscore_set_colors(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (back, new, other) = expect_args!(args, [int, int, int])?;
+        let settings = &mut self.highscores.settings;
+        settings.back_colour = back;
+        settings.new_colour = new;
+        settings.other_colour = other;
         Ok(Default::default())
     }
 
-    pub fn highscore_show_ext(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        println!("Called unimplemented kernel function highscore_show_ext");
+    pub fn highscore_show_ext(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (score, background, border, new_colour, other_colour, name, size) =
+            expect_args!(args, [int, int, bool, int, int, bytes, int])?;
+        let settings = &mut self.highscores.settings;
+        settings.background = background;
+        settings.border = border;
+        settings.new_colour = new_colour;
+        settings.other_colour = other_colour;
+        settings.font.name = name;
+        settings.font.size = size;
+        self.highscore_show_table(score)?;
         Ok(Default::default())
     }
 
-    pub fn highscore_clear(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function highscore_clear");
+    pub fn highscore_clear(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.highscores.clear();
         Ok(Default::default())
     }
 
-    pub fn highscore_add(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function highscore_add");
+    pub fn highscore_add(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, score) = expect_args!(args, [bytes, int])?;
+        self.highscores.add(name, score);
         Ok(Default::default())
     }
 
-    pub fn highscore_add_current(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function highscore_add_current");
+    pub fn highscore_add_current(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.highscore_add_score()?;
         Ok(Default::default())
     }
 
-    pub fn highscore_value(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function highscore_value");
-        Ok(Default::default())
+    pub fn highscore_value(&self, args: &[Value]) -> gml::Result<Value> {
+        let place = expect_args!(args, [int])?;
+        Ok(self.highscores.get(place).map(|e| e.score).unwrap_or(0).into())
     }
 
-    pub fn highscore_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function highscore_name");
-        Ok(Default::default())
+    pub fn highscore_name(&self, args: &[Value]) -> gml::Result<Value> {
+        let place = expect_args!(args, [int])?;
+        Ok(self.highscore_name_at(place))
     }
 
-    pub fn draw_highscore(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        println!("Called unimplemented kernel function draw_highscore");
+    pub fn draw_highscore(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x1, y1, x2, y2) = expect_args!(args, [real, real, real, real])?;
+        self.draw_highscore_table(x1, y1, x2, y2);
         Ok(Default::default())
     }
 
-    pub fn show_message_ext(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        println!("Called unimplemented kernel function show_message_ext");
-        Ok(Default::default())
+    pub fn show_message_ext(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (message, but1, but2, but3) = expect_args!(args, [any, bytes, bytes, bytes])?;
+        self.dialog_show_message_ext(message.repr(), [but1, but2, but3])
     }
 
-    pub fn message_background(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function message_background")
-        // TODO
+    pub fn message_background(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let back = expect_args!(args, [int])?;
+        self.message_settings.background = back;
         Ok(Default::default())
     }
 
-    pub fn message_button(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function message_button")
-        // TODO
+    pub fn message_button(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let sprite = expect_args!(args, [int])?;
+        self.message_settings.button = sprite;
         Ok(Default::default())
     }
 
-    pub fn message_alpha(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function message_alpha")
-        // TODO
+    pub fn message_alpha(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let alpha = expect_args!(args, [real])?;
+        self.message_settings.alpha = alpha;
         Ok(Default::default())
     }
 
-    pub fn message_text_font(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        //unimplemented!("Called unimplemented kernel function message_text_font")
-        // TODO
+    pub fn message_text_font(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, size, colour, style) = expect_args!(args, [bytes, int, int, int])?;
+        let font = &mut self.message_settings.text_font;
+        font.name = name;
+        font.size = size;
+        font.colour = colour;
+        font.style = style;
         Ok(Default::default())
     }
 
-    pub fn message_button_font(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        //unimplemented!("Called unimplemented kernel function message_button_font")
-        // TODO
+    pub fn message_button_font(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, size, colour, style) = expect_args!(args, [bytes, int, int, int])?;
+        let font = &mut self.message_settings.button_font;
+        font.name = name;
+        font.size = size;
+        font.colour = colour;
+        font.style = style;
         Ok(Default::default())
     }
 
-    pub fn message_input_font(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        //unimplemented!("Called unimplemented kernel function message_input_font")
-        // TODO
+    pub fn message_input_font(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, size, colour, style) = expect_args!(args, [bytes, int, int, int])?;
+        let font = &mut self.message_settings.input_font;
+        font.name = name;
+        font.size = size;
+        font.colour = colour;
+        font.style = style;
         Ok(Default::default())
     }
 
-    pub fn message_text_charset(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        //unimplemented!("Called unimplemented kernel function message_text_charset")
-        // TODO
+    pub fn message_text_charset(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (kind, charset) = expect_args!(args, [int, int])?;
+        match kind {
+            0 => self.message_settings.text_font.charset = charset,
+            1 => self.message_settings.button_font.charset = charset,
+            2 => self.message_settings.input_font.charset = charset,
+            _ => (),
+        }
         Ok(Default::default())
     }
 
-    pub fn message_mouse_color(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function message_mouse_color")
-        // TODO
+    pub fn message_mouse_color(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let colour = expect_args!(args, [int])?;
+        self.message_settings.mouse_colour = colour;
         Ok(Default::default())
     }
 
-    pub fn message_input_color(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function message_input_color")
-        // TODO
+    pub fn message_input_color(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let colour = expect_args!(args, [int])?;
+        self.message_settings.input_colour = colour;
         Ok(Default::default())
     }
 
-    pub fn message_position(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        //unimplemented!("Called unimplemented kernel function message_position")
-        // TODO
+    pub fn message_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y) = expect_args!(args, [int, int])?;
+        self.message_settings.position = (x, y);
         Ok(Default::default())
     }
 
-    pub fn message_size(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        //unimplemented!("Called unimplemented kernel function message_size")
-        // TODO
+    pub fn message_size(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (w, h) = expect_args!(args, [int, int])?;
+        self.message_settings.size = (w, h);
         Ok(Default::default())
     }
 
-    pub fn message_caption(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        //unimplemented!("Called unimplemented kernel function message_caption")
-        // TODO
+    pub fn message_caption(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (show, caption) = expect_args!(args, [bool, bytes])?;
+        self.message_settings.show_caption = show;
+        self.message_settings.caption = caption;
         Ok(Default::default())
     }
 
-    pub fn show_menu(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function show_menu");
-        Ok(Default::default())
+    pub fn show_menu(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (items, default) = expect_args!(args, [bytes, any])?;
+        let (x, y) = (self.input.mouse_x(), self.input.mouse_y());
+        self.dialog_show_menu("show_menu", x, y, items, default)
     }
 
-    pub fn show_menu_pos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        println!("Called unimplemented kernel function show_menu_pos");
-        Ok(Default::default())
+    pub fn show_menu_pos(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y, items, default) = expect_args!(args, [int, int, bytes, any])?;
+        let (x, y) = (x - self.window_offset_spoof.0, y - self.window_offset_spoof.1);
+        self.dialog_show_menu("show_menu_pos", x, y, items, default)
     }
 
-    pub fn get_integer(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function get_integer");
-        Ok(Default::default())
+    pub fn get_integer(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (message, default) = expect_args!(args, [any, any])?;
+        self.dialog_get_integer(message.repr(), default)
     }
 
-    pub fn get_string(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function get_string");
-        Ok(Default::default())
+    pub fn get_string(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (message, default) = expect_args!(args, [any, any])?;
+        self.dialog_get_string(message.repr(), default)
     }
 
-    pub fn get_color(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function get_color");
-        Ok(Default::default())
+    pub fn get_color(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let default = expect_args!(args, [int])?;
+        self.dialog_get_colour(default)
     }
 
-    pub fn get_open_filename(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function get_open_filename");
-        Ok(Default::default())
+    pub fn get_open_filename(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (_filter, fname) = expect_args!(args, [any, bytes])?;
+        self.dialog_get_path("get_open_filename", "Open", "File name:".into(), fname)
     }
 
-    pub fn get_save_filename(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function get_save_filename");
-        Ok(Default::default())
+    pub fn get_save_filename(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (_filter, fname) = expect_args!(args, [any, bytes])?;
+        self.dialog_get_path("get_save_filename", "Save As", "File name:".into(), fname)
     }
 
-    pub fn get_directory(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function get_directory");
-        Ok(Default::default())
+    pub fn get_directory(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let dname = expect_args!(args, [bytes])?;
+        self.dialog_get_path("get_directory", "Select Directory", "Directory:".into(), dname)
     }
 
-    pub fn get_directory_alt(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function get_directory_alt");
-        Ok(Default::default())
+    pub fn get_directory_alt(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (caption, root) = expect_args!(args, [bytes, bytes])?;
+        self.dialog_get_path("get_directory_alt", "Browse for Folder", caption, root)
     }
 
     // NB: This function is constant because numlock state is tracked.
@@ -7253,102 +7421,87 @@ impl Game {
         Ok(self.input.mouse_wheel_down().into())
     }
 
-    pub fn joystick_exists(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_exists")
-        // TODO
-        Ok(gml::FALSE.into())
+    pub fn joystick_exists(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.joystick(id).connected.into())
     }
 
-    pub fn joystick_direction(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_direction")
-        // TODO
-        Ok(101.into())
+    pub fn joystick_direction(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.joystick(id).direction().into())
     }
 
-    pub fn joystick_name(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_name")
-        // TODO
-        Ok("".into())
+    pub fn joystick_name(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.joystick(id).name.as_str().into())
     }
 
-    pub fn joystick_axes(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_axes")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_axes(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.joystick(id).axis_count.into())
     }
 
-    pub fn joystick_buttons(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_buttons")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_buttons(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.joystick(id).button_count.into())
     }
 
-    pub fn joystick_has_pov(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_has_pov")
-        // TODO
-        Ok(gml::FALSE.into())
+    pub fn joystick_has_pov(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.joystick(id).has_pov.into())
     }
 
-    pub fn joystick_check_button(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        //unimplemented!("Called unimplemented kernel function joystick_check_button")
-        // TODO
-        Ok(gml::FALSE.into())
+    pub fn joystick_check_button(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, button) = expect_args!(args, [int, int])?;
+        let pressed = match button.checked_sub(1).and_then(|b| u8::try_from(b).ok()) {
+            Some(button) => self.joystick(id).button(button),
+            None => false,
+        };
+        Ok(pressed.into())
     }
 
-    pub fn joystick_xpos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_xpos")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_xpos(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.joystick(id).axis(0).into())
     }
 
-    pub fn joystick_ypos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_ypos")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_ypos(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.joystick(id).axis(1).into())
     }
 
-    pub fn joystick_zpos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_zpos")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_zpos(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.joystick(id).axis(2).into())
     }
 
-    pub fn joystick_rpos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_rpos")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_rpos(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.joystick(id).axis(3).into())
     }
 
-    pub fn joystick_upos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_upos")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_upos(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.joystick(id).axis(4).into())
     }
 
-    pub fn joystick_vpos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_vpos")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_vpos(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.joystick(id).axis(5).into())
     }
 
-    pub fn joystick_pov(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_pov")
-        // TODO
-        Ok((-1).into())
+    pub fn joystick_pov(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.joystick(id).pov_degrees().into())
+    }
+
+    /// Gets a joystick by its GML id, which starts from 1. Ones that GM8 doesn't support are never connected.
+    fn joystick(&self, id: i32) -> &input::Joystick {
+        static DISCONNECTED: input::Joystick = input::Joystick::new();
+        id.checked_sub(1)
+            .and_then(|id| usize::try_from(id).ok())
+            .and_then(|id| self.input.joystick(id))
+            .unwrap_or(&DISCONNECTED)
     }
 
     pub fn keyboard_clear(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -7402,171 +7555,215 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn mplay_init_ipx(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function mplay_init_ipx");
-        Ok(Default::default())
+    pub fn mplay_init_ipx(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        // only TCP/IP is supported
+        Ok(gml::FALSE.into())
     }
 
-    pub fn mplay_init_tcpip(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function mplay_init_tcpip");
-        Ok(Default::default())
+    pub fn mplay_init_tcpip(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let address = expect_args!(args, [string])?;
+        self.mplay.init(address.trim().to_string());
+        Ok(gml::TRUE.into())
     }
 
-    pub fn mplay_init_modem(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function mplay_init_modem");
-        Ok(Default::default())
+    pub fn mplay_init_modem(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [string, string])?;
+        Ok(gml::FALSE.into())
     }
 
-    pub fn mplay_init_serial(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        println!("Called unimplemented kernel function mplay_init_serial");
-        Ok(Default::default())
+    pub fn mplay_init_serial(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [int, int, int, int, int])?;
+        Ok(gml::FALSE.into())
     }
 
-    pub fn mplay_connect_status(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function mplay_connect_status");
-        Ok(Default::default())
+    pub fn mplay_connect_status(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        // 2 is TCP/IP
+        Ok(if self.mplay.state.initialised { 2.into() } else { 0.into() })
     }
 
-    pub fn mplay_end(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function mplay_end");
+    pub fn mplay_end(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.mplay_live(|mplay| mplay.end());
+        self.mplay.state.initialised = false;
         Ok(Default::default())
     }
 
-    pub fn mplay_session_mode(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function mplay_session_mode");
+    pub fn mplay_session_mode(&mut self, args: &[Value]) -> gml::Result<Value> {
+        // the host always moves to the next player if it leaves, so there's nothing to do here
+        expect_args!(args, [any])?;
         Ok(Default::default())
     }
 
-    pub fn mplay_session_create(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function mplay_session_create");
-        Ok(Default::default())
+    pub fn mplay_session_create(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (name, max_players, player_name) = expect_args!(args, [bytes, int, bytes])?;
+        self.mplay_network("mplay_session_create", args, |mplay| {
+            mplay.create_session(name.as_ref(), max_players, player_name.as_ref()).into()
+        })
     }
 
-    pub fn mplay_session_find(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function mplay_session_find");
-        Ok(Default::default())
+    pub fn mplay_session_find(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        let count = self.mplay_network("mplay_session_find", args, |mplay| {
+            mplay.state.session_names = mplay.find_sessions();
+            mplay.state.session_names.len().into()
+        })?;
+        // each name is stored on its own, so they can be given back on playback
+        let mut names = Vec::new();
+        for i in 0..count.round().max(0) {
+            names.push(self.mplay_network("mplay_session_name", &[Value::from(i)], |mplay| {
+                mplay.state.session_names.get(i as usize).cloned().unwrap_or_default()
+            })?);
+        }
+        self.mplay.state.session_names = names;
+        Ok(count)
     }
 
-    pub fn mplay_session_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function mplay_session_name");
-        Ok(Default::default())
+    pub fn mplay_session_name(&self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        Ok(usize::try_from(index).ok().and_then(|i| self.mplay.state.session_names.get(i).cloned()).unwrap_or_default())
     }
 
-    pub fn mplay_session_join(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function mplay_session_join");
-        Ok(Default::default())
+    pub fn mplay_session_join(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (index, player_name) = expect_args!(args, [int, bytes])?;
+        self.mplay_network("mplay_session_join", args, |mplay| match usize::try_from(index) {
+            Ok(index) => mplay.join_session(index, player_name.as_ref()).into(),
+            Err(_) => gml::FALSE.into(),
+        })
     }
 
-    pub fn mplay_session_status(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function mplay_session_status");
-        Ok(Default::default())
+    pub fn mplay_session_status(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.mplay_network("mplay_session_status", args, |mplay| {
+            mplay.poll();
+            mplay.session_status().into()
+        })
     }
 
-    pub fn mplay_session_end(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function mplay_session_end");
+    pub fn mplay_session_end(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.mplay_live(|mplay| mplay.end_session());
         Ok(Default::default())
     }
 
-    pub fn mplay_player_find(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function mplay_player_find");
-        Ok(Default::default())
+    pub fn mplay_player_find(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        let count = self.mplay_network("mplay_player_find", args, |mplay| {
+            let players = mplay.find_players();
+            mplay.state.players = players.chunks_exact(2).map(|p| (p[0].clone(), p[1].clone())).collect();
+            mplay.state.players.len().into()
+        })?;
+        // each ID and name is stored on its own, so they can be given back on playback
+        let mut players = Vec::new();
+        for i in 0..count.round().max(0) {
+            let id = self.mplay_network("mplay_player_id", &[Value::from(i)], |mplay| {
+                mplay.state.players.get(i as usize).map(|p| p.0.clone()).unwrap_or_default()
+            })?;
+            let name = self.mplay_network("mplay_player_name", &[Value::from(i)], |mplay| {
+                mplay.state.players.get(i as usize).map(|p| p.1.clone()).unwrap_or_default()
+            })?;
+            players.push((id, name));
+        }
+        self.mplay.state.players = players;
+        Ok(count)
     }
 
-    pub fn mplay_player_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function mplay_player_name");
-        Ok(Default::default())
+    pub fn mplay_player_name(&self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        let player = usize::try_from(index).ok().and_then(|i| self.mplay.state.players.get(i));
+        Ok(player.map(|p| p.1.clone()).unwrap_or_default())
     }
 
-    pub fn mplay_player_id(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function mplay_player_id");
-        Ok(Default::default())
+    pub fn mplay_player_id(&self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        let player = usize::try_from(index).ok().and_then(|i| self.mplay.state.players.get(i));
+        Ok(player.map(|p| p.0.clone()).unwrap_or_default())
     }
 
-    pub fn mplay_data_write(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function mplay_data_write");
+    pub fn mplay_data_write(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (index, value) = expect_args!(args, [int, any])?;
+        if let Ok(index) = u32::try_from(index) {
+            self.mplay_live(|mplay| mplay.write_data(index, value));
+        }
         Ok(Default::default())
     }
 
-    pub fn mplay_data_read(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function mplay_data_read");
-        Ok(Default::default())
+    pub fn mplay_data_read(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        self.mplay_network("mplay_data_read", args, |mplay| match u32::try_from(index) {
+            Ok(index) => mplay.read_data(index),
+            Err(_) => Default::default(),
+        })
     }
 
-    pub fn mplay_data_mode(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function mplay_data_mode");
+    pub fn mplay_data_mode(&mut self, args: &[Value]) -> gml::Result<Value> {
+        // everything goes over TCP, so it's always guaranteed
+        expect_args!(args, [any])?;
         Ok(Default::default())
     }
 
-    pub fn mplay_message_send(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function mplay_message_send");
+    pub fn mplay_message_send(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (player, id, value) = expect_args!(args, [any, int, any])?;
+        self.mplay_live(|mplay| mplay.send_message(&player, id, value));
         Ok(Default::default())
     }
 
-    pub fn mplay_message_send_guaranteed(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function mplay_message_send_guaranteed");
-        Ok(Default::default())
+    pub fn mplay_message_send_guaranteed(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.mplay_message_send(args)
     }
 
-    pub fn mplay_message_receive(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function mplay_message_receive");
-        Ok(Default::default())
+    pub fn mplay_message_receive(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let player = expect_args!(args, [any])?;
+        let received = self.mplay_network("mplay_message_receive", args, |mplay| {
+            match <[Value; 4]>::try_from(mplay.receive_message(&player)) {
+                Ok(message) => {
+                    mplay.state.message = message;
+                    gml::TRUE.into()
+                },
+                Err(_) => gml::FALSE.into(),
+            }
+        })?;
+        if received.is_truthy() {
+            // each part of the message is stored on its own, so it can be given back on playback
+            let mut message = self.mplay.state.message.clone();
+            let functions = ["mplay_message_id", "mplay_message_value", "mplay_message_player", "mplay_message_name"];
+            for (i, (part, function)) in message.iter_mut().zip(functions).enumerate() {
+                *part = self.mplay_network(function, &[], |mplay| mplay.state.message[i].clone())?;
+            }
+            self.mplay.state.message = message;
+        }
+        Ok(received)
     }
 
-    pub fn mplay_message_id(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function mplay_message_id");
-        Ok(Default::default())
+    pub fn mplay_message_id(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.state.message[0].clone())
     }
 
-    pub fn mplay_message_value(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function mplay_message_value");
-        Ok(Default::default())
+    pub fn mplay_message_value(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.state.message[1].clone())
     }
 
-    pub fn mplay_message_player(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function mplay_message_player");
-        Ok(Default::default())
+    pub fn mplay_message_player(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.state.message[2].clone())
     }
 
-    pub fn mplay_message_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        println!("Called unimplemented kernel function mplay_message_name");
-        Ok(Default::default())
+    pub fn mplay_message_name(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.mplay.state.message[3].clone())
     }
 
-    pub fn mplay_message_count(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function mplay_message_count");
-        Ok(Default::default())
+    pub fn mplay_message_count(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let player = expect_args!(args, [any])?;
+        self.mplay_network("mplay_message_count", args, |mplay| mplay.count_messages(&player).into())
     }
 
-    pub fn mplay_message_clear(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function mplay_message_clear");
+    pub fn mplay_message_clear(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let player = expect_args!(args, [any])?;
+        self.mplay_live(|mplay| mplay.clear_messages(&player));
         Ok(Default::default())
     }
 
@@ -7649,14 +7846,15 @@ impl Game {
                 })
                 .collect::<Vec<_>>();
 
+            let signature = external::dll::ExternalSignature {
+                dll: dll.into_owned(),
+                symbol: function.into_owned(),
+                call_conv,
+                type_args: arg_types,
+                type_return: res_type,
+            };
             self.externals
-                .define(external::dll::ExternalSignature {
-                    dll: dll.into_owned(),
-                    symbol: function.into_owned(),
-                    call_conv,
-                    type_args: arg_types,
-                    type_return: res_type,
-                })
+                .define(signature, &mut self.compiler)
                 .map(Value::from)
                 .map_err(|e| gml::Error::FunctionError("external_define".into(), e))
         } else {
@@ -7828,7 +8026,7 @@ impl Game {
             for (src, dest) in args.iter().zip(new_args.iter_mut()) {
                 *dest = src.clone();
             }
-            match std::fs::read(self.decode_str(path.as_ref()).as_ref()) {
+            match self.vfs.read(self.decode_str(path.as_ref()).as_ref()) {
                 Ok(code) => {
                     new_args[0] = code.into();
                     self.execute_string(context, &new_args)
@@ -8651,7 +8849,7 @@ impl Game {
         let (fname, imgnumb, removeback, smooth, origin_x, origin_y) =
             expect_args!(args, [string, int, bool, bool, int, int])?;
         let imgnumb = imgnumb.max(1) as usize;
-        let mut images = match file::load_animation(file::to_path(&fname).as_ref(), imgnumb) {
+        let mut images = match file::load_animation(&self.vfs, file::to_path(&fname).as_ref(), imgnumb) {
             Ok(frames) => frames,
             Err(e) => {
                 eprintln!("Warning: sprite_add on {} failed: {}", fname, e);
@@ -8705,7 +8903,7 @@ impl Game {
                 self.renderer.delete_sprite(frame.atlas_ref);
             }
             let imgnumb = imgnumb.max(1) as usize;
-            let mut images = match file::load_animation(file::to_path(&fname).as_ref(), imgnumb) {
+            let mut images = match file::load_animation(&self.vfs, file::to_path(&fname).as_ref(), imgnumb) {
                 Ok(frames) => frames,
                 Err(e) => {
                     eprintln!("Warning: sprite_replace on {} failed: {}", fname, e);
@@ -8752,16 +8950,88 @@ impl Game {
         }
     }
 
-    pub fn sprite_add_sprite(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function sprite_add_sprite");
-        Ok(Default::default())
+    /// Uploads the frames of a sprite file, giving it a precise collision mask.
+    fn sprite_from_file(
+        &mut self,
+        function: &str,
+        name: gml::String,
+        mut sprite_file: file::SpriteFile,
+    ) -> gml::Result<asset::Sprite> {
+        let (width, height) = sprite_file.frames[0].dimensions();
+        let (origin_x, origin_y) = (sprite_file.origin_x, sprite_file.origin_y);
+        let colliders =
+            asset::sprite::make_colliders_precise(&sprite_file.frames, sprite_file.tolerance, sprite_file.sepmasks);
+        let renderer = &mut self.renderer;
+        let frames = sprite_file
+            .frames
+            .drain(..)
+            .map(|i| {
+                Ok(asset::sprite::Frame {
+                    width,
+                    height,
+                    atlas_ref: renderer
+                        .upload_sprite(i.into_raw().into_boxed_slice(), width as _, height as _, origin_x, origin_y)
+                        .map_err(|e| gml::Error::FunctionError(function.into(), e.into()))?,
+                })
+            })
+            .collect::<gml::Result<_>>()?;
+        Ok(asset::Sprite {
+            name,
+            frames,
+            bbox_left: colliders.iter().map(|c| c.bbox_left).min().unwrap(),
+            bbox_right: colliders.iter().map(|c| c.bbox_right).max().unwrap(),
+            bbox_top: colliders.iter().map(|c| c.bbox_top).min().unwrap(),
+            bbox_bottom: colliders.iter().map(|c| c.bbox_bottom).max().unwrap(),
+            colliders,
+            width,
+            height,
+            origin_x,
+            origin_y,
+            per_frame_colliders: sprite_file.sepmasks,
+        })
     }
 
-    pub fn sprite_replace_sprite(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function sprite_replace_sprite");
-        Ok(Default::default())
+    pub fn sprite_add_sprite(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let fname = expect_args!(args, [string])?;
+        let sprite_file = match file::load_sprite_file(&self.vfs, file::to_path(&fname).as_ref()) {
+            Ok(sprite_file) => sprite_file,
+            Err(e) => {
+                eprintln!("Warning: sprite_add_sprite on {} failed: {}", fname, e);
+                return Ok((-1).into())
+            },
+        };
+        let sprite_id = self.assets.sprites.len();
+        let name = format!("__newsprite{}", sprite_id).into();
+        let sprite = self.sprite_from_file("sprite_add_sprite", name, sprite_file)?;
+        self.assets.sprites.push(Some(Box::new(sprite)));
+        Ok(sprite_id.into())
+    }
+
+    pub fn sprite_replace_sprite(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sprite_id, fname) = expect_args!(args, [int, string])?;
+        if let Some(sprite) = self.assets.sprites.get_asset(sprite_id) {
+            let name = sprite.name.clone();
+            let sprite_file = match file::load_sprite_file(&self.vfs, file::to_path(&fname).as_ref()) {
+                Ok(sprite_file) => sprite_file,
+                Err(e) => {
+                    eprintln!("Warning: sprite_replace_sprite on {} failed: {}", fname, e);
+                    return Ok((-1).into())
+                },
+            };
+            // the old frames are only deleted once the new ones are uploaded, so a failure leaves the sprite intact
+            let sprite = self.sprite_from_file("sprite_replace_sprite", name, sprite_file)?;
+            if let Some(old_sprite) = self.assets.sprites[sprite_id as usize].replace(Box::new(sprite)) {
+                for frame in &old_sprite.frames {
+                    self.renderer.delete_sprite(frame.atlas_ref);
+                }
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::FunctionError(
+                "sprite_replace_sprite".into(),
+                "Trying to replace non-existing sprite.".into(),
+            ))
+        }
     }
 
     pub fn sprite_delete(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -8777,10 +9047,43 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn sprite_duplicate(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function sprite_duplicate");
-        Ok(Default::default())
+    pub fn sprite_duplicate(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let src_id = expect_args!(args, [int])?;
+        if let Some(src) = self.assets.sprites.get_asset(src_id) {
+            let frames = src
+                .frames
+                .iter()
+                .map(|f| {
+                    Ok(asset::sprite::Frame {
+                        atlas_ref: self
+                            .renderer
+                            .duplicate_sprite(f.atlas_ref)
+                            .map_err(|e| gml::Error::FunctionError("sprite_duplicate".into(), e.into()))?,
+                        width: f.width,
+                        height: f.height,
+                    })
+                })
+                .collect::<gml::Result<_>>()?;
+            let dst_id = self.assets.sprites.len();
+            let sprite = asset::Sprite {
+                name: format!("__newsprite{}", dst_id).into(),
+                frames,
+                colliders: src.colliders.clone(),
+                width: src.width,
+                height: src.height,
+                origin_x: src.origin_x,
+                origin_y: src.origin_y,
+                per_frame_colliders: src.per_frame_colliders,
+                bbox_left: src.bbox_left,
+                bbox_right: src.bbox_right,
+                bbox_top: src.bbox_top,
+                bbox_bottom: src.bbox_bottom,
+            };
+            self.assets.sprites.push(Some(Box::new(sprite)));
+            Ok(dst_id.into())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Sprite, src_id))
+        }
     }
 
     pub fn sprite_assign(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -8829,10 +9132,54 @@ impl Game {
         }
     }
 
-    pub fn sprite_merge(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function sprite_merge");
-        Ok(Default::default())
+    pub fn sprite_merge(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (dst_id, src_id) = expect_args!(args, [int, int])?;
+        let src_frames = match self.assets.sprites.get_asset(src_id) {
+            Some(src) => src.frames.clone(),
+            None => return Err(gml::Error::NonexistentAsset(asset::Type::Sprite, src_id)),
+        };
+        if let Some(sprite) = self.assets.sprites.get_asset(dst_id) {
+            // the new frames are stretched to fit the sprite they're added to
+            let (width, height) = match (sprite.frames.first(), src_frames.first()) {
+                (Some(_), _) => (sprite.width, sprite.height),
+                (None, Some(f)) => (f.width, f.height),
+                (None, None) => return Ok(Default::default()),
+            };
+            let mut images = Vec::with_capacity(sprite.frames.len() + src_frames.len());
+            for f in sprite.frames.iter().chain(src_frames.iter()) {
+                let mut image =
+                    RgbaImage::from_vec(f.width, f.height, self.renderer.dump_sprite(f.atlas_ref).into_vec()).unwrap();
+                asset::sprite::scale(&mut image, width, height);
+                images.push(image);
+            }
+            let sprite = self.assets.sprites.get_asset_mut(dst_id).unwrap();
+            sprite.width = width;
+            sprite.height = height;
+            sprite.colliders = asset::sprite::make_colliders_precise(&images, 0, sprite.per_frame_colliders);
+            sprite.bbox_left = sprite.colliders.iter().map(|c| c.bbox_left).min().unwrap();
+            sprite.bbox_top = sprite.colliders.iter().map(|c| c.bbox_top).min().unwrap();
+            sprite.bbox_right = sprite.colliders.iter().map(|c| c.bbox_right).max().unwrap();
+            sprite.bbox_bottom = sprite.colliders.iter().map(|c| c.bbox_bottom).max().unwrap();
+            for image in images.drain(sprite.frames.len()..) {
+                sprite.frames.push(asset::sprite::Frame {
+                    width,
+                    height,
+                    atlas_ref: self
+                        .renderer
+                        .upload_sprite(
+                            image.into_raw().into_boxed_slice(),
+                            width as _,
+                            height as _,
+                            sprite.origin_x,
+                            sprite.origin_y,
+                        )
+                        .map_err(|e| gml::Error::FunctionError("sprite_merge".into(), e.into()))?,
+                });
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Sprite, dst_id))
+        }
     }
 
     pub fn sprite_save(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -8842,6 +9189,7 @@ impl Game {
             if let Some(frame) = sprite.get_frame(image_index) {
                 // get RGBA
                 if let Err(e) = file::save_image(
+                    &self.vfs,
                     file::to_path(&fname).as_ref(),
                     RgbaImage::from_vec(frame.width, frame.height, self.renderer.dump_sprite(frame.atlas_ref).into())
                         .unwrap(),
@@ -8853,9 +9201,20 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn sprite_save_strip(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function sprite_save_strip");
+    pub fn sprite_save_strip(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sprite_id, fname) = expect_args!(args, [int, string])?;
+        if let Some(sprite) = self.assets.sprites.get_asset(sprite_id) {
+            let mut strip = RgbaImage::new(sprite.width * sprite.frames.len() as u32, sprite.height);
+            for (i, frame) in sprite.frames.iter().enumerate() {
+                let image =
+                    RgbaImage::from_vec(frame.width, frame.height, self.renderer.dump_sprite(frame.atlas_ref).into())
+                        .unwrap();
+                image::imageops::replace(&mut strip, &image, (i as u32 * sprite.width) as _, 0);
+            }
+            if let Err(e) = file::save_image(&self.vfs, file::to_path(&fname).as_ref(), strip) {
+                return Err(gml::Error::FunctionError("sprite_save_strip".into(), e.to_string()))
+            }
+        }
         Ok(Default::default())
     }
 
@@ -9072,15 +9431,29 @@ impl Game {
         Ok(background_id.into())
     }
 
-    pub fn background_create_gradient(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        println!("Called unimplemented kernel function background_create_gradient");
-        Ok(Default::default())
+    pub fn background_create_gradient(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (w, h, col1, col2, kind) = expect_args!(args, [int, int, int, int, int])?;
+        let (w, h) = (w.max(1), h.max(1));
+        let data = asset::background::make_gradient(w, h, col1, col2, kind).ok_or_else(|| {
+            gml::Error::FunctionError("background_create_gradient".into(), "the background is too big".into())
+        })?;
+        let atlas_ref = self
+            .renderer
+            .upload_sprite(data.into_boxed_slice(), w, h, 0, 0)
+            .map_err(|e| gml::Error::FunctionError("background_create_gradient".into(), e))?;
+        let background_id = self.assets.backgrounds.len();
+        self.assets.backgrounds.push(Some(Box::new(asset::Background {
+            name: format!("__newbackground{}", background_id).into(),
+            width: w as _,
+            height: h as _,
+            atlas_ref: Some(atlas_ref),
+        })));
+        Ok(background_id.into())
     }
 
     pub fn background_add(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (fname, removeback, smooth) = expect_args!(args, [string, bool, bool])?;
-        let mut image = match file::load_image(file::to_path(&fname).as_ref()) {
+        let mut image = match file::load_image(&self.vfs, file::to_path(&fname).as_ref()) {
             Ok(im) => im,
             Err(e) => {
                 eprintln!("Warning: background_add on {} failed: {}", fname, e);
@@ -9110,7 +9483,7 @@ impl Game {
             if let Some(atlas_ref) = background.atlas_ref {
                 self.renderer.delete_sprite(atlas_ref);
             }
-            let mut image = match file::load_image(file::to_path(&fname).as_ref()) {
+            let mut image = match file::load_image(&self.vfs, file::to_path(&fname).as_ref()) {
                 Ok(im) => im,
                 Err(e) => {
                     eprintln!("Warning: background_replace on {} failed: {}", fname, e);
@@ -9136,16 +9509,70 @@ impl Game {
         }
     }
 
-    pub fn background_add_background(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function background_add_background");
-        Ok(Default::default())
+    pub fn background_add_background(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let fname = expect_args!(args, [string])?;
+        let image = match file::load_background_file(&self.vfs, file::to_path(&fname).as_ref()) {
+            Ok(im) => im,
+            Err(e) => {
+                eprintln!("Warning: background_add_background on {} failed: {}", fname, e);
+                return Ok((-1).into())
+            },
+        };
+        let (width, height) = image.dimensions();
+        let atlas_ref = if width != 0 && height != 0 {
+            Some(
+                self.renderer
+                    .upload_sprite(image.into_raw().into_boxed_slice(), width as _, height as _, 0, 0)
+                    .map_err(|e| gml::Error::FunctionError("background_add_background".into(), e.into()))?,
+            )
+        } else {
+            None
+        };
+        let background_id = self.assets.backgrounds.len();
+        self.assets.backgrounds.push(Some(Box::new(asset::Background {
+            name: format!("__newbackground{}", background_id).into(),
+            width,
+            height,
+            atlas_ref,
+        })));
+        Ok(background_id.into())
     }
 
-    pub fn background_replace_background(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function background_replace_background");
-        Ok(Default::default())
+    pub fn background_replace_background(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (background_id, fname) = expect_args!(args, [int, string])?;
+        if self.assets.backgrounds.get_asset(background_id).is_some() {
+            let image = match file::load_background_file(&self.vfs, file::to_path(&fname).as_ref()) {
+                Ok(im) => im,
+                Err(e) => {
+                    eprintln!("Warning: background_replace_background on {} failed: {}", fname, e);
+                    return Ok((-1).into())
+                },
+            };
+            let (width, height) = image.dimensions();
+            let atlas_ref = if width != 0 && height != 0 {
+                Some(
+                    self.renderer
+                        .upload_sprite(image.into_raw().into_boxed_slice(), width as _, height as _, 0, 0)
+                        .map_err(|e| gml::Error::FunctionError("background_replace_background".into(), e.into()))?,
+                )
+            } else {
+                None
+            };
+            // the old image is only deleted once the new one is uploaded, so a failure leaves the background intact
+            if let Some(background) = self.assets.backgrounds.get_asset_mut(background_id) {
+                if let Some(old_atlas_ref) = std::mem::replace(&mut background.atlas_ref, atlas_ref) {
+                    self.renderer.delete_sprite(old_atlas_ref);
+                }
+                background.width = width;
+                background.height = height;
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::FunctionError(
+                "background_replace_background".into(),
+                "Trying to replace non-existing background.".into(),
+            ))
+        }
     }
 
     pub fn background_delete(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -9228,6 +9655,7 @@ impl Game {
             if let Some(atlas_ref) = background.atlas_ref {
                 // get RGBA
                 if let Err(e) = file::save_image(
+                    &self.vfs,
                     file::to_path(&fname).as_ref(),
                     RgbaImage::from_vec(
                         background.width,
@@ -9277,7 +9705,7 @@ impl Game {
     pub fn sound_add(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (fname, kind, preload) = expect_args!(args, [string, int, bool])?;
         let path_buf = std::path::PathBuf::from(fname.as_ref());
-        let data = match std::fs::read(&path_buf) {
+        let data = match self.vfs.read(fname.as_ref()) {
             Ok(b) => b.into_boxed_slice(),
             Err(_) => return Ok((-1).into()),
         };
@@ -9287,10 +9715,14 @@ impl Game {
                 Some(x) => asset::sound::FileType::Mp3(x),
                 None => return Ok((-1).into()),
             },
-            Some("wav") => match self.audio.add_wav(data, sound_id as i32, 1.0, kind == 2, kind >= 3) {
+            Some("wav") => match self.audio.add_wav(data, sound_id as i32, 1.0, 0, kind == 2, kind >= 3) {
                 Some(x) => asset::sound::FileType::Wav(x),
                 None => return Ok((-1).into()),
             },
+            Some("mid" | "midi") => match self.audio.add_midi(data, sound_id as i32) {
+                Some(x) => asset::sound::FileType::Midi(x),
+                None => return Ok((-1).into()),
+            },
             _ => return Ok((-1).into()),
         };
         self.assets.sounds.push(Some(Box::new(asset::Sound {
@@ -9311,7 +9743,7 @@ impl Game {
 
             if matches!(sound.handle, asset::sound::FileType::None) {
                 let path_buf = std::path::PathBuf::from(fname.as_ref());
-                let data = match std::fs::read(&path_buf) {
+                let data = match self.vfs.read(fname.as_ref()) {
                     Ok(b) => b.into_boxed_slice(),
                     Err(_) => return Ok(0.into()),
                 };
@@ -9320,10 +9752,14 @@ impl Game {
                         Some(x) => asset::sound::FileType::Mp3(x),
                         None => return Ok(0.into()),
                     },
-                    Some("wav") => match self.audio.add_wav(data, sound_id as i32, 1.0, kind == 2, kind >= 3) {
+                    Some("wav") => match self.audio.add_wav(data, sound_id as i32, 1.0, 0, kind == 2, kind >= 3) {
                         Some(x) => asset::sound::FileType::Wav(x),
                         None => return Ok(0.into()),
                     },
+                    Some("mid" | "midi") => match self.audio.add_midi(data, sound_id as i32) {
+                        Some(x) => asset::sound::FileType::Midi(x),
+                        None => return Ok(0.into()),
+                    },
                     _ => return Ok(0.into()),
                 };
                 Ok(1.into())
@@ -9389,16 +9825,59 @@ impl Game {
         Ok(self.assets.fonts.get_asset(id).map(|x| x.last.into()).unwrap_or((-1).into()))
     }
 
-    pub fn font_add(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        println!("Called unimplemented kernel function font_add");
-        Ok(Default::default())
+    pub fn font_add(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sys_name, size, bold, italic, first, last) = expect_args!(args, [bytes, int, bool, bool, int, int])?;
+        let size = if size > 0 { size as u32 } else { 12 };
+        let first = first.clamp(0, 255) as u8;
+        let last = last.clamp(first.into(), 255) as u8;
+        let (chars, tallest_char_height) =
+            asset::font::create_chars_from_default(size, bold, italic, first, last, &mut self.renderer)
+                .map_err(|e| gml::Error::FunctionError("font_add".into(), e))?;
+        let font_id = self.assets.fonts.len();
+        self.assets.fonts.push(Some(Box::new(asset::Font {
+            name: format!("__newfont{}", font_id).into(),
+            sys_name,
+            charset: 1,
+            size,
+            bold,
+            italic,
+            first,
+            last,
+            tallest_char_height,
+            chars,
+            own_graphics: true,
+        })));
+        Ok(font_id.into())
     }
 
-    pub fn font_replace(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        println!("Called unimplemented kernel function font_replace");
-        Ok(Default::default())
+    pub fn font_replace(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (font_id, sys_name, size, bold, italic, first, last) =
+            expect_args!(args, [int, bytes, int, bool, bool, int, int])?;
+        if let Some(font) = self.assets.fonts.get_asset_mut(font_id) {
+            if font.own_graphics {
+                for c in font.chars.iter() {
+                    self.renderer.delete_sprite(c.atlas_ref);
+                }
+            }
+            let size = if size > 0 { size as u32 } else { 12 };
+            let first = first.clamp(0, 255) as u8;
+            let last = last.clamp(first.into(), 255) as u8;
+            let (chars, tallest_char_height) =
+                asset::font::create_chars_from_default(size, bold, italic, first, last, &mut self.renderer)
+                    .map_err(|e| gml::Error::FunctionError("font_replace".into(), e))?;
+            font.sys_name = sys_name;
+            font.size = size;
+            font.bold = bold;
+            font.italic = italic;
+            font.first = first;
+            font.last = last;
+            font.tallest_char_height = tallest_char_height;
+            font.chars = chars;
+            font.own_graphics = true;
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Font, font_id))
+        }
     }
 
     pub fn font_add_sprite(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -9432,8 +9911,7 @@ impl Game {
         if let Some(font) = self.assets.fonts.get_asset_mut(font_id) {
             if let Some(sprite) = self.assets.sprites.get_asset(sprite_id) {
                 if font.own_graphics {
-                    // font_add isn't in yet but atm for ttfs all characters are on the same texture
-                    if let Some(c) = font.get_char(font.first) {
+                    for c in font.chars.iter() {
                         self.renderer.delete_sprite(c.atlas_ref);
                     }
                 }
@@ -9459,8 +9937,7 @@ impl Game {
         let font_id = expect_args!(args, [int])?;
         if let Some(font) = self.assets.fonts.get_asset(font_id) {
             if font.own_graphics {
-                // font_add isn't in yet but atm for ttfs all characters are on the same texture
-                if let Some(c) = font.get_char(font.first) {
+                for c in font.chars.iter() {
                     self.renderer.delete_sprite(c.atlas_ref);
                 }
             }
@@ -9496,7 +9973,10 @@ impl Game {
                     *dest = src.clone();
                 }
                 let mut new_context = Context::copy_with_args(context, new_args, args.len() - 1);
-                self.execute(&instructions, &mut new_context)?;
+                self.profiler.enter(ProfileKey::Script(script_id as usize));
+                let result = self.execute(&instructions, &mut new_context);
+                self.profiler.exit();
+                result?;
                 Ok(new_context.return_value)
             } else {
                 Err(gml::Error::NonexistentAsset(asset::Type::Script, script_id))
@@ -11480,16 +11960,30 @@ impl Game {
         }
     }
 
-    pub fn ds_queue_write(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function ds_queue_write");
-        Ok(Default::default())
+    pub fn ds_queue_write(&self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        match self.queues.get(id) {
+            Some(queue) => Ok(ds::write_queue(queue).into()),
+            None => Err(gml::Error::FunctionError("ds_queue_write".into(), ds::Error::NonexistentStructure(id).into())),
+        }
     }
 
-    pub fn ds_queue_read(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function ds_queue_read");
-        Ok(Default::default())
+    pub fn ds_queue_read(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, hex_data) = expect_args!(args, [int, string])?;
+        match self.queues.get_mut(id) {
+            Some(old_queue) => {
+                match hex::decode(hex_data.as_ref()) {
+                    Ok(data) => {
+                        if let Some(queue) = ds::read_queue(data.as_slice()) {
+                            *old_queue = queue;
+                        }
+                    },
+                    Err(e) => eprintln!("Warning (ds_queue_read): {}", e),
+                }
+                Ok(Default::default())
+            },
+            None => Err(gml::Error::FunctionError("ds_queue_read".into(), ds::Error::NonexistentStructure(id).into())),
+        }
     }
 
     pub fn ds_list_create(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -12330,16 +12824,34 @@ impl Game {
         }
     }
 
-    pub fn ds_grid_add_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        println!("Called unimplemented kernel function ds_grid_add_region");
-        Ok(Default::default())
+    pub fn ds_grid_add_region(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, x1, y1, x2, y2, val) = expect_args!(args, [int, int, int, int, int, any])?;
+        if let Some(grid) = self.grids.get_mut(id) {
+            for cell in grid.region_mut(x1, y1, x2, y2) {
+                if cell.add_assign(val.clone()).is_err() {
+                    *cell = val.clone();
+                }
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_add_region".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
-    pub fn ds_grid_multiply_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        println!("Called unimplemented kernel function ds_grid_multiply_region");
-        Ok(Default::default())
+    pub fn ds_grid_multiply_region(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, x1, y1, x2, y2, val) = expect_args!(args, [int, int, int, int, int, any])?;
+        if let Some(grid) = self.grids.get_mut(id) {
+            if let Value::Real(fac) = val {
+                for cell in grid.region_mut(x1, y1, x2, y2) {
+                    if let Value::Real(cell) = cell {
+                        *cell *= fac;
+                    }
+                }
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_multiply_region".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
     pub fn ds_grid_set_disk(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -12354,34 +12866,82 @@ impl Game {
         }
     }
 
-    pub fn ds_grid_add_disk(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        println!("Called unimplemented kernel function ds_grid_add_disk");
-        Ok(Default::default())
+    pub fn ds_grid_add_disk(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, xm, ym, r, val) = expect_args!(args, [int, real, real, real, any])?;
+        if let Some(grid) = self.grids.get_mut(id) {
+            for cell in grid.disk_mut(xm, ym, r) {
+                if cell.add_assign(val.clone()).is_err() {
+                    *cell = val.clone();
+                }
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_add_disk".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
-    pub fn ds_grid_multiply_disk(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        println!("Called unimplemented kernel function ds_grid_multiply_disk");
-        Ok(Default::default())
+    pub fn ds_grid_multiply_disk(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, xm, ym, r, val) = expect_args!(args, [int, real, real, real, any])?;
+        if let Some(grid) = self.grids.get_mut(id) {
+            if let Value::Real(fac) = val {
+                for cell in grid.disk_mut(xm, ym, r) {
+                    if let Value::Real(cell) = cell {
+                        *cell *= fac;
+                    }
+                }
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_multiply_disk".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
-    pub fn ds_grid_set_grid_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 8
-        println!("Called unimplemented kernel function ds_grid_set_grid_region");
-        Ok(Default::default())
+    /// Applies an operation to a region of a grid, using the values from a region of another (or the same) grid.
+    fn ds_grid_grid_region(
+        &mut self,
+        function: &str,
+        args: &[Value],
+        op: impl Fn(&mut Value, Value),
+    ) -> gml::Result<Value> {
+        let (id, source, x1, y1, x2, y2, xpos, ypos) = expect_args!(args, [int, int, int, int, int, int, int, int])?;
+        // the source is copied first, since it might be the same grid
+        let values = match self.grids.get(source) {
+            Some(grid) => grid.region_moved(x1, y1, x2, y2, xpos, ypos),
+            None => {
+                return Err(gml::Error::FunctionError(function.into(), ds::Error::NonexistentStructure(source).into()))
+            },
+        };
+        match self.grids.get_mut(id) {
+            Some(grid) => {
+                for ((x, y), val) in values {
+                    if let Some(cell) = grid.get_mut(x, y) {
+                        op(cell, val);
+                    }
+                }
+                Ok(Default::default())
+            },
+            None => Err(gml::Error::FunctionError(function.into(), ds::Error::NonexistentStructure(id).into())),
+        }
     }
 
-    pub fn ds_grid_add_grid_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 8
-        println!("Called unimplemented kernel function ds_grid_add_grid_region");
-        Ok(Default::default())
+    pub fn ds_grid_set_grid_region(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.ds_grid_grid_region("ds_grid_set_grid_region", args, |cell, val| *cell = val)
     }
 
-    pub fn ds_grid_multiply_grid_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 8
-        println!("Called unimplemented kernel function ds_grid_multiply_grid_region");
-        Ok(Default::default())
+    pub fn ds_grid_add_grid_region(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.ds_grid_grid_region("ds_grid_add_grid_region", args, |cell, val| {
+            if cell.add_assign(val.clone()).is_err() {
+                *cell = val;
+            }
+        })
+    }
+
+    pub fn ds_grid_multiply_grid_region(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.ds_grid_grid_region("ds_grid_multiply_grid_region", args, |cell, val| {
+            if let (Value::Real(cell), Value::Real(fac)) = (cell, val) {
+                *cell *= fac;
+            }
+        })
     }
 
     pub fn ds_grid_get(&self, args: &[Value]) -> gml::Result<Value> {
@@ -12449,28 +13009,54 @@ impl Game {
         }
     }
 
-    pub fn ds_grid_get_disk_sum(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        println!("Called unimplemented kernel function ds_grid_get_disk_sum");
-        Ok(Default::default())
+    pub fn ds_grid_get_disk_sum(&self, args: &[Value]) -> gml::Result<Value> {
+        let (id, xm, ym, r) = expect_args!(args, [int, real, real, real])?;
+        if let Some(grid) = self.grids.get(id) {
+            Ok(grid.disk(xm, ym, r).filter_map(Value::as_real).sum::<Real>().into())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_get_disk_sum".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
-    pub fn ds_grid_get_disk_max(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        println!("Called unimplemented kernel function ds_grid_get_disk_max");
-        Ok(Default::default())
+    pub fn ds_grid_get_disk_max(&self, args: &[Value]) -> gml::Result<Value> {
+        let (id, xm, ym, r) = expect_args!(args, [int, real, real, real])?;
+        if let Some(grid) = self.grids.get(id) {
+            // weird fold needed due to NaN nonsense
+            Ok(grid
+                .disk(xm, ym, r)
+                .filter_map(Value::as_real)
+                .fold(Real::from(-100000000), |acc, val| if val >= acc { val } else { acc })
+                .into())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_get_disk_max".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
-    pub fn ds_grid_get_disk_min(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        println!("Called unimplemented kernel function ds_grid_get_disk_min");
-        Ok(Default::default())
+    pub fn ds_grid_get_disk_min(&self, args: &[Value]) -> gml::Result<Value> {
+        let (id, xm, ym, r) = expect_args!(args, [int, real, real, real])?;
+        if let Some(grid) = self.grids.get(id) {
+            // weird fold needed due to NaN nonsense
+            Ok(grid
+                .disk(xm, ym, r)
+                .filter_map(Value::as_real)
+                .fold(Real::from(100000000), |acc, val| if val <= acc { val } else { acc })
+                .into())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_get_disk_min".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
-    pub fn ds_grid_get_disk_mean(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        println!("Called unimplemented kernel function ds_grid_get_disk_mean");
-        Ok(Default::default())
+    pub fn ds_grid_get_disk_mean(&self, args: &[Value]) -> gml::Result<Value> {
+        let (id, xm, ym, r) = expect_args!(args, [int, real, real, real])?;
+        if let Some(grid) = self.grids.get(id) {
+            let (count, sum) = grid
+                .disk(xm, ym, r)
+                .filter_map(Value::as_real)
+                .fold((0, Real::from(0)), |(count, sum), val| (count + 1, sum + val));
+            Ok(if count > 0 { sum / Real::from(count) } else { Real::from(0) }.into())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_get_disk_mean".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
     pub fn ds_grid_value_exists(&self, args: &[Value]) -> gml::Result<Value> {
@@ -12550,10 +13136,23 @@ impl Game {
         }
     }
 
-    pub fn ds_grid_shuffle(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        println!("Called unimplemented kernel function ds_grid_shuffle");
-        Ok(Default::default())
+    pub fn ds_grid_shuffle(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        match self.grids.get_mut(id) {
+            Some(grid) => {
+                // same as ds_list_shuffle, going through each column
+                let size = grid.width() * grid.height();
+                for _ in 1..size {
+                    let id1 = self.rand.next_int(size as u32 - 1);
+                    let id2 = self.rand.next_int(size as u32 - 1);
+                    grid.swap(id1 as usize, id2 as usize);
+                }
+                Ok(Default::default())
+            },
+            None => {
+                Err(gml::Error::FunctionError("ds_grid_shuffle".into(), ds::Error::NonexistentStructure(id).into()))
+            },
+        }
     }
 
     pub fn ds_grid_write(&self, args: &[Value]) -> gml::Result<Value> {
@@ -12613,6 +13212,7 @@ impl Game {
             match &sound.handle {
                 FileType::Mp3(handle) => self.audio.play_mp3(handle, nanos),
                 FileType::Wav(handle) => self.audio.play_wav(handle, nanos),
+                FileType::Midi(handle) => self.audio.play_midi(handle, nanos),
                 FileType::None => (),
             }
             Ok(Default::default())
@@ -12628,6 +13228,7 @@ impl Game {
             match &sound.handle {
                 FileType::Mp3(handle) => self.audio.loop_mp3(handle),
                 FileType::Wav(handle) => self.audio.loop_wav(handle),
+                FileType::Midi(handle) => self.audio.loop_midi(handle),
                 FileType::None => (),
             }
             Ok(Default::default())
@@ -12655,35 +13256,51 @@ impl Game {
 
     pub fn sound_volume(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (sound_id, volume) = expect_args!(args, [int, real])?;
-        if let Some(sound) = self.assets.sounds.get_asset(sound_id) {
-            // Deliberately written in a way that will produce an error when Kind::Midi is added
+        if let Some(sound) = self.assets.sounds.get_asset_mut(sound_id) {
             use asset::sound::FileType;
-            match &sound.handle {
+            match &mut sound.handle {
                 FileType::Wav(handle) => handle.set_volume(volume.into()),
-                FileType::Mp3(_) => (),
+                FileType::Mp3(handle) => handle.set_volume(volume.into()),
+                FileType::Midi(handle) => handle.set_volume(volume.into()),
                 FileType::None => (),
             }
+            self.audio.cancel_fade(sound_id);
             Ok(Default::default())
         } else {
             Err(gml::Error::NonexistentAsset(asset::Type::Sound, sound_id))
         }
     }
 
-    pub fn sound_fade(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function sound_fade");
-        Ok(Default::default())
+    pub fn sound_fade(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, volume, time) = expect_args!(args, [int, real, int])?;
+        if let Some(sound) = self.assets.sounds.get_asset(sound_id) {
+            use asset::sound::FileType;
+            let current = match &sound.handle {
+                FileType::Wav(handle) => handle.volume(),
+                FileType::Mp3(handle) => handle.volume(),
+                FileType::Midi(handle) => handle.volume(),
+                FileType::None => return Ok(Default::default()),
+            };
+            let nanos = self.spoofed_time_nanos.unwrap_or_else(|| datetime::now_as_nanos());
+            self.audio.fade(sound_id, current, volume.into(), time.max(0) as u32, nanos);
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Sound, sound_id))
+        }
     }
 
-    pub fn sound_pan(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function sound_pan");
+    pub fn sound_pan(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, pan) = expect_args!(args, [int, real])?;
+        if let Some(handle) = self.sound_wav_mut(sound_id)? {
+            handle.set_spatial(|s| s.pan = pan.into());
+        }
         Ok(Default::default())
     }
 
-    pub fn sound_background_tempo(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        // Does nothing unless the sound is a midi, which we don't support yet
+    pub fn sound_background_tempo(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let factor = expect_args!(args, [real])?;
+        let nanos = self.spoofed_time_nanos.unwrap_or_else(|| datetime::now_as_nanos());
+        self.audio.set_midi_tempo(factor.into(), nanos);
         Ok(Default::default())
     }
 
@@ -12699,75 +13316,166 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn sound_effect_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        println!("Called unimplemented kernel function sound_effect_set");
+    /// Gets a sound's wav handle for the functions that change how it plays. Only wav sounds have any of that.
+    fn sound_wav_mut(&mut self, sound_id: i32) -> gml::Result<Option<&mut audio::WavHandle>> {
+        if let Some(sound) = self.assets.sounds.get_asset_mut(sound_id) {
+            use asset::sound::FileType;
+            match &mut sound.handle {
+                FileType::Wav(handle) => Ok(Some(handle)),
+                FileType::Mp3(_) | FileType::Midi(_) | FileType::None => Ok(None),
+            }
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Sound, sound_id))
+        }
+    }
+
+    pub fn sound_effect_set(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, effect) = expect_args!(args, [int, int])?;
+        if let Some(handle) = self.sound_wav_mut(sound_id)? {
+            handle.effects_mut().enabled = effect as u32 & 0x7F;
+        }
         Ok(Default::default())
     }
 
-    pub fn sound_effect_chorus(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 8
-        println!("Called unimplemented kernel function sound_effect_chorus");
+    pub fn sound_effect_chorus(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, wet_dry, depth, feedback, frequency, wave, delay, phase) =
+            expect_args!(args, [int, real, real, real, real, int, real, int])?;
+        if let Some(handle) = self.sound_wav_mut(sound_id)? {
+            handle.effects_mut().chorus = audio::effects::Chorus {
+                wet_dry: wet_dry.into(),
+                depth: depth.into(),
+                feedback: feedback.into(),
+                frequency: frequency.into(),
+                sine: wave != 0,
+                delay: delay.into(),
+                phase,
+            }
+            .clamped();
+        }
         Ok(Default::default())
     }
 
-    pub fn sound_effect_compressor(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        println!("Called unimplemented kernel function sound_effect_compressor");
+    pub fn sound_effect_compressor(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, gain, attack, release, threshold, ratio, delay) =
+            expect_args!(args, [int, real, real, real, real, real, real])?;
+        if let Some(handle) = self.sound_wav_mut(sound_id)? {
+            handle.effects_mut().compressor = audio::effects::Compressor {
+                gain: gain.into(),
+                attack: attack.into(),
+                release: release.into(),
+                threshold: threshold.into(),
+                ratio: ratio.into(),
+                delay: delay.into(),
+            }
+            .clamped();
+        }
         Ok(Default::default())
     }
 
-    pub fn sound_effect_echo(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        println!("Called unimplemented kernel function sound_effect_echo");
+    pub fn sound_effect_echo(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, wet_dry, feedback, left_delay, right_delay, pan_delay) =
+            expect_args!(args, [int, real, real, real, real, bool])?;
+        if let Some(handle) = self.sound_wav_mut(sound_id)? {
+            handle.effects_mut().echo = audio::effects::Echo {
+                wet_dry: wet_dry.into(),
+                feedback: feedback.into(),
+                left_delay: left_delay.into(),
+                right_delay: right_delay.into(),
+                pan_delay,
+            }
+            .clamped();
+        }
         Ok(Default::default())
     }
 
-    pub fn sound_effect_flanger(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 8
-        println!("Called unimplemented kernel function sound_effect_flanger");
+    pub fn sound_effect_flanger(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, wet_dry, depth, feedback, frequency, wave, delay, phase) =
+            expect_args!(args, [int, real, real, real, real, int, real, int])?;
+        if let Some(handle) = self.sound_wav_mut(sound_id)? {
+            handle.effects_mut().flanger = audio::effects::Flanger {
+                wet_dry: wet_dry.into(),
+                depth: depth.into(),
+                feedback: feedback.into(),
+                frequency: frequency.into(),
+                sine: wave != 0,
+                delay: delay.into(),
+                phase,
+            }
+            .clamped();
+        }
         Ok(Default::default())
     }
 
-    pub fn sound_effect_gargle(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function sound_effect_gargle");
+    pub fn sound_effect_gargle(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, rate, wave) = expect_args!(args, [int, real, int])?;
+        if let Some(handle) = self.sound_wav_mut(sound_id)? {
+            handle.effects_mut().gargle = audio::effects::Gargle { rate: rate.into(), square: wave != 0 }.clamped();
+        }
         Ok(Default::default())
     }
 
-    pub fn sound_effect_equalizer(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        println!("Called unimplemented kernel function sound_effect_equalizer");
+    pub fn sound_effect_equalizer(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, center, bandwidth, gain) = expect_args!(args, [int, real, real, real])?;
+        if let Some(handle) = self.sound_wav_mut(sound_id)? {
+            handle.effects_mut().equalizer =
+                audio::effects::Equalizer { center: center.into(), bandwidth: bandwidth.into(), gain: gain.into() }
+                    .clamped();
+        }
         Ok(Default::default())
     }
 
-    pub fn sound_effect_reverb(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        println!("Called unimplemented kernel function sound_effect_reverb");
+    pub fn sound_effect_reverb(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, gain, mix, time, ratio) = expect_args!(args, [int, real, real, real, real])?;
+        if let Some(handle) = self.sound_wav_mut(sound_id)? {
+            handle.effects_mut().reverb = audio::effects::Reverb {
+                gain: gain.into(),
+                mix: mix.into(),
+                time: time.into(),
+                hf_ratio: ratio.into(),
+            }
+            .clamped();
+        }
         Ok(Default::default())
     }
 
-    pub fn sound_3d_set_sound_position(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        println!("Called unimplemented kernel function sound_3d_set_sound_position");
+    pub fn sound_3d_set_sound_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, x, y, z) = expect_args!(args, [int, real, real, real])?;
+        if let Some(handle) = self.sound_wav_mut(sound_id)? {
+            handle.set_spatial(|s| s.position = [x.into(), y.into(), z.into()]);
+        }
         Ok(Default::default())
     }
 
-    pub fn sound_3d_set_sound_velocity(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        println!("Called unimplemented kernel function sound_3d_set_sound_velocity");
+    pub fn sound_3d_set_sound_velocity(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, x, y, z) = expect_args!(args, [int, real, real, real])?;
+        if let Some(handle) = self.sound_wav_mut(sound_id)? {
+            handle.set_spatial(|s| s.velocity = [x.into(), y.into(), z.into()]);
+        }
         Ok(Default::default())
     }
 
-    pub fn sound_3d_set_sound_distance(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        println!("Called unimplemented kernel function sound_3d_set_sound_distance");
+    pub fn sound_3d_set_sound_distance(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, min_distance, max_distance) = expect_args!(args, [int, real, real])?;
+        if let Some(handle) = self.sound_wav_mut(sound_id)? {
+            handle.set_spatial(|s| {
+                s.min_distance = f64::from(min_distance).max(0.0);
+                s.max_distance = f64::from(max_distance).max(s.min_distance);
+            });
+        }
         Ok(Default::default())
     }
 
-    pub fn sound_3d_set_sound_cone(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        println!("Called unimplemented kernel function sound_3d_set_sound_cone");
+    pub fn sound_3d_set_sound_cone(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, x, y, z, inside, outside, outside_volume) =
+            expect_args!(args, [int, real, real, real, real, real, real])?;
+        if let Some(handle) = self.sound_wav_mut(sound_id)? {
+            handle.set_spatial(|s| {
+                s.cone_orientation = [x.into(), y.into(), z.into()];
+                s.cone_inside = f64::from(inside).clamp(0.0, 360.0);
+                s.cone_outside = f64::from(outside).clamp(0.0, 360.0);
+                s.cone_outside_volume = f64::from(outside_volume).clamp(-10000.0, 0.0);
+            });
+        }
         Ok(Default::default())
     }
 
@@ -13648,8 +14356,8 @@ impl Game {
 
     pub fn d3d_model_load(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (model_id, fname) = expect_args!(args, [int, string])?;
-        fn load_model(fname: &str) -> Result<model::Model, Box<dyn std::error::Error>> {
-            let mut file = std::io::BufReader::new(std::fs::File::open(file::to_path(&fname).as_ref())?);
+        fn load_model(fs: &vfs::FileSystem, fname: &str) -> Result<model::Model, Box<dyn std::error::Error>> {
+            let mut file = std::io::Cursor::new(fs.read(file::to_path(&fname).as_ref())?);
             let version = file::read_real(&mut file)?;
             if version != 100.0 {
                 return Err("invalid version".into())
@@ -13756,7 +14464,7 @@ impl Game {
             Ok(model::Model { old_draw_colour: None, commands, cache: None })
         }
         if let Some(model) = self.models.get_asset_mut(model_id) {
-            match load_model(&fname) {
+            match load_model(&self.vfs, &fname) {
                 Ok(new_model) => *model = new_model,
                 Err(e) => println!("WARNING: d3d_model_load failed: {}", e),
             }
@@ -13766,8 +14474,8 @@ impl Game {
 
     pub fn d3d_model_save(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (model_id, fname) = expect_args!(args, [int, string])?;
-        fn save_model(model: &model::Model, fname: &str) -> std::io::Result<()> {
-            let mut file = std::io::BufWriter::new(std::fs::File::create(file::to_path(&fname).as_ref())?);
+        fn save_model(fs: &vfs::FileSystem, model: &model::Model, fname: &str) -> std::io::Result<()> {
+            let mut file = Vec::new();
             writeln!(&mut file, "100\r\n{}\r", model.commands.len())?;
             for cmd in &model.commands {
                 let (cmd, args) = cmd.to_line();
@@ -13777,11 +14485,10 @@ impl Game {
                 }
                 writeln!(&mut file, "\r")?;
             }
-            file.flush()?;
-            Ok(())
+            fs.write(file::to_path(&fname).as_ref(), &file)
         }
         if let Some(model) = self.models.get_asset(model_id) {
-            if let Err(e) = save_model(model, &fname) {
+            if let Err(e) = save_model(&self.vfs, model, &fname) {
                 println!("WARNING: d3d_model_save failed: {}", e);
             }
         }
@@ -1,6 +1,6 @@
 use crate::{
     asset,
-    game::{Game, GetAsset, SceneChange, Version},
+    game::{profiler::ProfileKey, Game, GetAsset, SceneChange, Version},
     gml::{
         self,
         datetime::DateTime,
@@ -313,7 +313,10 @@ impl UnaryOperator {
 
 impl Game {
     pub fn invoke(&mut self, function_id: usize, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
-        mappings::FUNCTIONS.index(function_id).unwrap().1.invoke(self, context, args)
+        self.profiler.enter(ProfileKey::Function(function_id));
+        let result = mappings::FUNCTIONS.index(function_id).unwrap().1.invoke(self, context, args);
+        self.profiler.exit();
+        result
     }
 
     pub fn execute(&mut self, instructions: &[Instruction], context: &mut Context) -> gml::Result<ReturnType> {
@@ -596,7 +599,10 @@ impl Game {
                     }
 
                     let mut new_context = Context::copy_with_args(context, arg_values, args.len());
-                    self.execute(&instructions, &mut new_context)?;
+                    self.profiler.enter(ProfileKey::Script(*script_id));
+                    let result = self.execute(&instructions, &mut new_context);
+                    self.profiler.exit();
+                    result?;
                     Ok(new_context.return_value)
                 } else {
                     Err(Error::NonexistentAsset(asset::Type::Script, *script_id as i32))
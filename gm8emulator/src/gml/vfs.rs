@@ -0,0 +1,497 @@
+//! The filesystem seen by the game's file functions.
+//!
+//! Normally this just passes everything through to the host's disk. When sandboxed, all writes go to an in-memory
+//! copy-on-write layer over the host's files instead, so the host's disk is never modified, and the layer is part
+//! of the game state. This means files written by the game are restored when loading a savestate, and games which
+//! write files will replay the same way no matter what was left on disk by earlier runs.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+/// The sandbox layer's contents. Paths are normalised and lowercase, since GM8 games expect Windows' behaviour.
+/// Paths inside the game's directory are stored relative to it, so savestates still work if the game is moved.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sandbox {
+    /// Files which have been written or deleted. Deleted files are None.
+    files: BTreeMap<String, Option<Rc<[u8]>>>,
+    /// Directories which have been created.
+    dirs: BTreeSet<String>,
+    /// The names files and directories were created with, since the keys are lowercase.
+    names: BTreeMap<String, String>,
+}
+
+#[derive(Clone)]
+pub struct FileSystem {
+    root: PathBuf,
+    sandbox: Option<Rc<RefCell<Sandbox>>>,
+}
+
+/// An open file, either on the host's disk or in the sandbox.
+#[derive(Debug)]
+pub enum Stream {
+    Host(File),
+    Virtual(VirtualFile),
+}
+
+/// A file opened in the sandbox. Its contents are written back to the sandbox when it's flushed or dropped.
+pub struct VirtualFile {
+    sandbox: Rc<RefCell<Sandbox>>,
+    key: String,
+    data: Cursor<Vec<u8>>,
+    append: bool,
+    dirty: bool,
+}
+
+/// Mirrors `std::fs::OpenOptions`, but opens files through a FileSystem.
+#[derive(Clone, Copy, Default)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+}
+
+impl FileSystem {
+    /// Creates a FileSystem which passes everything through to the host. `root` should be the game's directory.
+    pub fn new(root: PathBuf) -> Self {
+        Self { root, sandbox: None }
+    }
+
+    pub fn is_sandboxed(&self) -> bool {
+        self.sandbox.is_some()
+    }
+
+    /// Starts redirecting all writes to an empty sandbox layer.
+    pub fn enable_sandbox(&mut self) {
+        self.sandbox = Some(Rc::new(RefCell::new(Sandbox::default())));
+    }
+
+    /// Copies every file in the given directory into the sandbox, as if they were in the game's directory.
+    pub fn seed_sandbox(&mut self, snapshot: &Path) -> io::Result<()> {
+        fn walk(vfs: &FileSystem, sandbox: &mut Sandbox, dir: &Path, relative: &Path) -> io::Result<()> {
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let relative = relative.join(entry.file_name());
+                let key = vfs.key(&relative);
+                sandbox.name(&key, &relative);
+                if entry.file_type()?.is_dir() {
+                    sandbox.dirs.insert(key);
+                    walk(vfs, sandbox, &entry.path(), &relative)?;
+                } else {
+                    sandbox.files.insert(key, Some(fs::read(entry.path())?.into()));
+                }
+            }
+            Ok(())
+        }
+        if let Some(sandbox) = &self.sandbox {
+            walk(self, &mut sandbox.borrow_mut(), snapshot, Path::new(""))?;
+        }
+        Ok(())
+    }
+
+    /// Gets a copy of the sandbox layer for a savestate, or None if this isn't sandboxed.
+    pub fn state(&self) -> Option<Sandbox> {
+        self.sandbox.as_ref().map(|s| s.borrow().clone())
+    }
+
+    /// Restores the sandbox layer from a savestate. Files which are currently open will see the new state.
+    pub fn load_state(&mut self, state: Option<Sandbox>) {
+        match (&self.sandbox, state) {
+            (Some(sandbox), Some(state)) => *sandbox.borrow_mut() = state,
+            (None, Some(state)) => self.sandbox = Some(Rc::new(RefCell::new(state))),
+            (_, None) => self.sandbox = None,
+        }
+    }
+
+    /// Normalises a path into the form used as a key in the sandbox.
+    fn key(&self, path: &Path) -> String {
+        let absolute = if path.is_absolute() { path.to_path_buf() } else { self.root.join(path) };
+        let path = match absolute.strip_prefix(&self.root) {
+            Ok(relative) => relative,
+            Err(_) => absolute.as_path(),
+        };
+        let mut parts: Vec<String> = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::Prefix(p) => parts.push(p.as_os_str().to_string_lossy().into_owned()),
+                Component::RootDir => parts.push(String::new()),
+                Component::CurDir => (),
+                Component::ParentDir => {
+                    parts.pop();
+                },
+                Component::Normal(s) => parts.push(s.to_string_lossy().into_owned()),
+            }
+        }
+        parts.join("/").to_lowercase()
+    }
+
+    /// Gets the sandbox's record of a file: Some(Some) if it was written, Some(None) if it was deleted,
+    /// and None if it's untouched, in which case it should be looked up on the host.
+    fn sandboxed_file(&self, path: &str) -> Option<Option<Rc<[u8]>>> {
+        self.sandbox.as_ref().and_then(|s| s.borrow().files.get(&self.key(Path::new(path))).cloned())
+    }
+
+    pub fn file_exists(&self, path: &str) -> bool {
+        match self.sandboxed_file(path) {
+            Some(file) => file.is_some(),
+            None => Path::new(path).is_file(),
+        }
+    }
+
+    pub fn dir_exists(&self, path: &str) -> bool {
+        match &self.sandbox {
+            Some(sandbox) => {
+                let key = self.key(Path::new(path));
+                let prefix = format!("{}/", key);
+                let sandbox = sandbox.borrow();
+                sandbox.dirs.contains(&key)
+                    || sandbox.files.range(prefix.clone()..).next().filter(|(k, _)| k.starts_with(&prefix)).is_some()
+                    || Path::new(path).is_dir()
+            },
+            None => Path::new(path).is_dir(),
+        }
+    }
+
+    pub fn dir_create(&self, path: &str) -> io::Result<()> {
+        match &self.sandbox {
+            Some(sandbox) => {
+                let mut sandbox = sandbox.borrow_mut();
+                let mut dir = Path::new(path);
+                loop {
+                    let key = self.key(dir);
+                    sandbox.name(&key, dir);
+                    sandbox.dirs.insert(key);
+                    match dir.parent() {
+                        Some(parent) if parent != Path::new("") => dir = parent,
+                        _ => break Ok(()),
+                    }
+                }
+            },
+            None => fs::create_dir_all(path),
+        }
+    }
+
+    /// Reads a whole file.
+    pub fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        match self.sandboxed_file(path) {
+            Some(Some(data)) => Ok(data.to_vec()),
+            Some(None) => Err(io::ErrorKind::NotFound.into()),
+            None => fs::read(path),
+        }
+    }
+
+    /// Replaces a whole file, creating it if it doesn't exist.
+    pub fn write(&self, path: &str, data: &[u8]) -> io::Result<()> {
+        match &self.sandbox {
+            Some(sandbox) => {
+                let mut sandbox = sandbox.borrow_mut();
+                let key = self.key(Path::new(path));
+                sandbox.name(&key, Path::new(path));
+                sandbox.files.insert(key, Some(data.into()));
+                Ok(())
+            },
+            None => fs::write(path, data),
+        }
+    }
+
+    /// Deletes a file. Does nothing if it doesn't exist.
+    pub fn delete(&self, path: &str) -> io::Result<()> {
+        match &self.sandbox {
+            Some(sandbox) => {
+                if self.file_exists(path) {
+                    sandbox.borrow_mut().files.insert(self.key(Path::new(path)), None);
+                }
+                Ok(())
+            },
+            None => {
+                if Path::new(path).exists() {
+                    fs::remove_file(path)?;
+                }
+                Ok(())
+            },
+        }
+    }
+
+    /// Renames a file. Does nothing if the destination already exists.
+    pub fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        match &self.sandbox {
+            Some(_) => {
+                if !self.file_exists(to) {
+                    let data = self.read(from)?;
+                    self.write(to, &data)?;
+                    self.delete(from)?;
+                }
+                Ok(())
+            },
+            None => {
+                if !Path::new(to).exists() {
+                    fs::rename(from, to)?;
+                }
+                Ok(())
+            },
+        }
+    }
+
+    pub fn copy(&self, from: &str, to: &str) -> io::Result<()> {
+        match &self.sandbox {
+            Some(_) => self.write(to, &self.read(from)?),
+            None => fs::copy(from, to).map(|_| ()),
+        }
+    }
+
+    /// Lists the files matching a pattern such as `saves/*.sav`, like Windows' FindFirstFile. As on Windows, only
+    /// the last part of the path can contain wildcards. Directories and read-only files are left out unless asked for.
+    /// Files in the sandbox are listed as well as the host's, and files deleted in the sandbox are left out.
+    pub fn find(
+        &self,
+        pattern: &str,
+        directories: bool,
+        read_only: bool,
+    ) -> Result<Vec<String>, glob::PatternError> {
+        let pattern = Path::new(pattern);
+        let dir = pattern.parent().unwrap_or_else(|| Path::new(""));
+        let name_pattern = glob::Pattern::new(&pattern.file_name().unwrap_or_default().to_string_lossy())?;
+        let options = glob::MatchOptions { case_sensitive: false, ..Default::default() };
+
+        // maps lowercase names to the names which will be returned, so the sandbox can override the host
+        let mut found = BTreeMap::new();
+        if let Ok(entries) = fs::read_dir(if dir == Path::new("") { Path::new(".") } else { dir }) {
+            for entry in entries.filter_map(Result::ok) {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let is_dir = match entry.metadata() {
+                    // apparently directories are read only
+                    Ok(md) if md.is_dir() => true,
+                    Ok(md) if read_only || !md.permissions().readonly() => false,
+                    _ => continue,
+                };
+                if (directories || !is_dir) && name_pattern.matches_with(&name, options) {
+                    found.insert(name.to_lowercase(), name);
+                }
+            }
+        }
+
+        if let Some(sandbox) = &self.sandbox {
+            let sandbox = sandbox.borrow();
+            let dir_key = self.key(dir);
+            let prefix = if dir_key.is_empty() { dir_key } else { format!("{}/", dir_key) };
+            let in_dir = |key: &str| key.strip_prefix(prefix.as_str()).map(|name| name.to_string());
+            let display_name = |name: &str| {
+                sandbox.names.get(&format!("{}{}", prefix, name)).cloned().unwrap_or_else(|| name.to_string())
+            };
+            for (key, file) in sandbox.files.iter() {
+                let name = match in_dir(key.as_str()) {
+                    Some(name) => name,
+                    None => continue,
+                };
+                match name.split_once('/') {
+                    // files in subdirectories mean the subdirectory exists
+                    Some((subdir, _)) => {
+                        if directories && file.is_some() && name_pattern.matches_with(subdir, options) {
+                            found.entry(subdir.to_string()).or_insert_with(|| display_name(subdir));
+                        }
+                    },
+                    None if name_pattern.matches_with(&name, options) => match file {
+                        Some(_) => {
+                            found.entry(name.clone()).or_insert_with(|| display_name(&name));
+                        },
+                        None => {
+                            found.remove(&name);
+                        },
+                    },
+                    None => (),
+                }
+            }
+            if directories {
+                for name in sandbox.dirs.iter().filter_map(|key| in_dir(key.as_str())) {
+                    if !name.contains('/') && name_pattern.matches_with(&name, options) {
+                        found.entry(name.clone()).or_insert_with(|| display_name(&name));
+                    }
+                }
+            }
+        }
+
+        Ok(found.into_values().collect())
+    }
+
+    fn open(&self, path: &str, options: &OpenOptions) -> io::Result<Stream> {
+        match &self.sandbox {
+            Some(sandbox) => {
+                let key = self.key(Path::new(path));
+                let existing = match self.sandboxed_file(path) {
+                    Some(file) => file,
+                    None => match fs::read(path) {
+                        Ok(data) => Some(data.into()),
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                        Err(e) => return Err(e),
+                    },
+                };
+                let data = match &existing {
+                    Some(_) if options.create_new => return Err(io::ErrorKind::AlreadyExists.into()),
+                    Some(_) if options.truncate => Vec::new(),
+                    Some(data) => data.to_vec(),
+                    None if options.create || options.create_new => Vec::new(),
+                    None => return Err(io::ErrorKind::NotFound.into()),
+                };
+                let mut data = Cursor::new(data);
+                if options.append {
+                    data.seek(SeekFrom::End(0))?;
+                }
+                let mut file =
+                    VirtualFile { sandbox: sandbox.clone(), key, data, append: options.append, dirty: false };
+                if existing.is_none() {
+                    sandbox.borrow_mut().name(&file.key, Path::new(path));
+                }
+                // make sure newly created or truncated files exist straight away
+                if existing.is_none() || options.truncate {
+                    file.commit();
+                }
+                Ok(Stream::Virtual(file))
+            },
+            None => fs::OpenOptions::new()
+                .read(options.read)
+                .write(options.write)
+                .append(options.append)
+                .truncate(options.truncate)
+                .create(options.create)
+                .create_new(options.create_new)
+                .open(path)
+                .map(Stream::Host),
+        }
+    }
+}
+
+impl Sandbox {
+    /// Remembers the name a file or directory was created with, unless it already has one.
+    fn name(&mut self, key: &str, path: &Path) {
+        if let Some(name) = path.file_name() {
+            self.names.entry(key.to_string()).or_insert_with(|| name.to_string_lossy().into_owned());
+        }
+    }
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+
+    pub fn open(&self, fs: &FileSystem, path: &str) -> io::Result<Stream> {
+        fs.open(path, self)
+    }
+}
+
+impl Stream {
+    /// Truncates or extends the file, like `File::set_len`.
+    pub fn set_len(&mut self, size: u64) -> io::Result<()> {
+        match self {
+            Self::Host(f) => f.set_len(size),
+            Self::Virtual(f) => {
+                f.data.get_mut().resize(size as usize, 0);
+                f.dirty = true;
+                Ok(())
+            },
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Host(f) => f.read(buf),
+            Self::Virtual(f) => f.data.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Host(f) => f.write(buf),
+            Self::Virtual(f) => {
+                if f.append {
+                    f.data.seek(SeekFrom::End(0))?;
+                }
+                f.dirty = true;
+                f.data.write(buf)
+            },
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Host(f) => f.flush(),
+            Self::Virtual(f) => {
+                f.commit();
+                Ok(())
+            },
+        }
+    }
+}
+
+impl Seek for Stream {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::Host(f) => f.seek(pos),
+            Self::Virtual(f) => f.data.seek(pos),
+        }
+    }
+}
+
+impl VirtualFile {
+    fn commit(&mut self) {
+        self.sandbox.borrow_mut().files.insert(self.key.clone(), Some(self.data.get_ref().as_slice().into()));
+        self.dirty = false;
+    }
+}
+
+impl Drop for VirtualFile {
+    fn drop(&mut self) {
+        if self.dirty {
+            self.commit();
+        }
+    }
+}
+
+impl std::fmt::Debug for VirtualFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VirtualFile").field("key", &self.key).field("len", &self.data.get_ref().len()).finish()
+    }
+}
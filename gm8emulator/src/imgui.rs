@@ -230,6 +230,19 @@ impl Frame<'_> {
         unsafe { c::igTableSetupColumn(self.cstr(), flags, init_width_or_weight, 0) };
     }
 
+    /// Gets the column index the current table is sorted by, and whether it's sorted in ascending order.
+    pub fn table_sort_column(&self) -> Option<(usize, bool)> {
+        unsafe {
+            let specs = c::igTableGetSortSpecs();
+            if specs.is_null() || (*specs).SpecsCount < 1 || (*specs).Specs.is_null() {
+                return None;
+            }
+            let spec = &*(*specs).Specs;
+            let ascending = spec.SortDirection() == c::ImGuiSortDirection__ImGuiSortDirection_Ascending as _;
+            Some((spec.ColumnIndex as usize, ascending))
+        }
+    }
+
     pub fn same_line(&self, offset_from_start_x: f32, spacing: f32) {
         unsafe { c::igSameLine(offset_from_start_x, spacing) };
     }
@@ -400,6 +413,11 @@ impl Frame<'_> {
         unsafe { c::igCheckbox(self.cstr(), value as _) }
     }
 
+    pub fn slider_float(&mut self, label: &str, value: &mut f32, min: f32, max: f32) -> bool {
+        self.cstr_store(label);
+        unsafe { c::igSliderFloat(self.cstr(), value as _, min, max, b"%.3f\0".as_ptr().cast(), 0) }
+    }
+
     pub fn text(&mut self, text: &str) {
         self.cstr_store(text);
         unsafe { c::igText(self.cstr()) };
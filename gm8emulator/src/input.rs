@@ -16,6 +16,17 @@ const MB_NONE: i8 = 0;
 const VK_NOKEY: u8 = 0; // TODO: dont redefine
 const VK_ANYKEY: u8 = 1; // TODO: dont redefine
 
+/// How many joysticks GM8 supports.
+pub const JOYSTICK_COUNT: usize = 2;
+/// How many axes GM8 reads from a joystick: X, Y, Z, R, U and V.
+pub const JOYSTICK_AXES: usize = 6;
+/// How many buttons GM8 reads from a joystick.
+pub const JOYSTICK_BUTTONS: u8 = 32;
+/// The raw position of an axis at rest, out of 0 to 65535.
+pub const AXIS_CENTRE: u16 = 0x7FFF;
+/// The raw value of a POV hat that isn't pointing anywhere, like winmm's JOY_POVCENTERED.
+pub const POV_CENTRED: u16 = 0xFFFF;
+
 #[derive(Copy, Clone, Debug, Deserialize, Serialize)]
 #[repr(u8)]
 pub enum Button {
@@ -721,6 +732,66 @@ const fn gen_default_keymap() -> [u8; KEY_MAX] {
 }
 const DEFAULT_KEYMAP: [u8; KEY_MAX] = gen_default_keymap();
 
+/// The state of a joystick, in the same raw units winmm gives them to GM8.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Joystick {
+    pub connected: bool,
+    pub name: String,
+    pub axis_count: u8,
+    pub button_count: u8,
+    pub has_pov: bool,
+    pub axes: [u16; JOYSTICK_AXES],
+    pub buttons: u32,
+    pub pov: u16, // hundredths of a degree clockwise from forwards, or POV_CENTRED
+}
+
+impl Joystick {
+    pub const fn new() -> Self {
+        Self {
+            connected: false,
+            name: String::new(),
+            axis_count: 0,
+            button_count: 0,
+            has_pov: false,
+            axes: [AXIS_CENTRE; JOYSTICK_AXES],
+            buttons: 0,
+            pov: POV_CENTRED,
+        }
+    }
+
+    /// The position of an axis from -1 to 1, or 0 if the joystick doesn't have it.
+    pub fn axis(&self, axis: usize) -> f64 {
+        match self.axes.get(axis) {
+            Some(&position) if self.connected && axis < usize::from(self.axis_count) => {
+                f64::from(position) / 32767.5 - 1.0
+            },
+            _ => 0.0,
+        }
+    }
+
+    /// Whether a button is held, numbered from 0.
+    pub fn button(&self, button: u8) -> bool {
+        self.connected && button < self.button_count.min(JOYSTICK_BUTTONS) && self.buttons & (1 << button) != 0
+    }
+
+    /// The direction of the POV hat in degrees, or -1 if it's centred.
+    pub fn pov_degrees(&self) -> f64 {
+        if self.connected && self.has_pov && self.pov != POV_CENTRED {
+            f64::from(self.pov) / 100.0
+        } else {
+            -1.0
+        }
+    }
+
+    /// Which way the X and Y axes are pushed, as the numpad key in that direction, so 101 is centred.
+    pub fn direction(&self) -> u8 {
+        let (x, y) = (self.axis(0), self.axis(1));
+        let column = if x < -0.5 { 0 } else if x > 0.5 { 2 } else { 1 };
+        let row = if y > 0.5 { 0 } else if y < -0.5 { 2 } else { 1 };
+        Button::Keypad1 as u8 + row * 3 + column
+    }
+}
+
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Input {
     // basic state
@@ -730,6 +801,7 @@ pub struct Input {
     button_state_release: ArraySerde<bool, KEY_MAX>,
     mouse_position: (i32, i32),
     mouse_wheel: (bool, bool),
+    joysticks: [Joystick; JOYSTICK_COUNT],
 
     // gamemaker weirdness
     key_current: u8,
@@ -749,6 +821,7 @@ impl Input {
             button_state_release: ArraySerde([false; KEY_MAX]),
             mouse_position: (0, 0),
             mouse_wheel: (false, false),
+            joysticks: [Joystick::new(), Joystick::new()],
             key_current: 0,
             key_previous: 0,
             mouse_current: 0,
@@ -813,6 +886,68 @@ impl Input {
         self.mouse_wheel.1 = true;
     }
 
+    /// Plugs in a joystick, or changes the capabilities of one that's already plugged in.
+    pub fn joystick_connect(&mut self, id: usize, name: String, axis_count: u8, button_count: u8, has_pov: bool) {
+        if let Some(joystick) = self.joysticks.get_mut(id) {
+            if !joystick.connected {
+                *joystick = Joystick::new();
+            }
+            joystick.connected = true;
+            joystick.name = name;
+            joystick.axis_count = axis_count.min(JOYSTICK_AXES as u8);
+            joystick.button_count = button_count.min(JOYSTICK_BUTTONS);
+            joystick.has_pov = has_pov;
+        }
+    }
+
+    pub fn joystick_disconnect(&mut self, id: usize) {
+        if let Some(joystick) = self.joysticks.get_mut(id) {
+            *joystick = Joystick::new();
+        }
+    }
+
+    /// Replaces the whole state of a joystick, as read from the real thing.
+    pub fn joystick_set(&mut self, id: usize, state: Joystick) {
+        if let Some(joystick) = self.joysticks.get_mut(id) {
+            *joystick = state;
+        }
+    }
+
+    pub fn joystick_button_press(&mut self, id: usize, button: u8) {
+        if let Some(joystick) = self.joysticks.get_mut(id) {
+            joystick.buttons |= 1u32.checked_shl(button.into()).unwrap_or(0);
+        }
+    }
+
+    pub fn joystick_button_release(&mut self, id: usize, button: u8) {
+        if let Some(joystick) = self.joysticks.get_mut(id) {
+            joystick.buttons &= !1u32.checked_shl(button.into()).unwrap_or(0);
+        }
+    }
+
+    pub fn joystick_move_axis(&mut self, id: usize, axis: usize, position: u16) {
+        if let Some(slot) = self.joysticks.get_mut(id).and_then(|j| j.axes.get_mut(axis)) {
+            *slot = position;
+        }
+    }
+
+    pub fn joystick_move_pov(&mut self, id: usize, pov: u16) {
+        if let Some(joystick) = self.joysticks.get_mut(id) {
+            joystick.pov = pov;
+        }
+    }
+
+    /// Gets a joystick by its index from 0, which is one less than its GML id.
+    #[inline]
+    pub fn joystick(&self, id: usize) -> Option<&Joystick> {
+        self.joysticks.get(id)
+    }
+
+    #[inline]
+    pub fn joysticks(&self) -> &[Joystick; JOYSTICK_COUNT] {
+        &self.joysticks
+    }
+
     // == GameMaker Mappings ==
 
     fn keyboard_check_any_internal_indirect(&self, state: &[bool; KEY_MAX]) -> bool {
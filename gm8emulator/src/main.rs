@@ -14,7 +14,10 @@ mod types;
 mod util;
 
 use game::{
+    dump::{self, DumpSettings},
+    external::{stub, ExternalManager, ResultMode},
     savestate::{self, SaveState},
+    screenshot::{ScreenshotSettings, Screenshotter},
     Game, PlayType, Replay,
 };
 use std::{
@@ -39,6 +42,103 @@ fn help(argv0: &str, opts: getopts::Options) {
     );
 }
 
+/// Makes a path given on the command line absolute, since launching a game changes the working directory.
+fn absolute(path: impl Into<PathBuf>) -> PathBuf {
+    let path = path.into();
+    match env::current_dir() {
+        Ok(dir) if path.is_relative() => dir.join(path),
+        _ => path,
+    }
+}
+
+/// The --dump-* options with values, as their names minus "dump-", descriptions and hints. Giving any of them
+/// turns dumping on, like --dump-video.
+const DUMP_OPTIONS: &[(&str, &str, &str)] = &[
+    ("backend", "'builtin' for PNG frames and a WAV, or 'ffmpeg' (default) for one video", "NAME"),
+    ("path", "where to dump to (default: 'dump' dir or 'tas recording.mkv')", "PATH"),
+    ("frames", "only dump these frames, as START, START-END or -END", "RANGE"),
+    ("fps", "dump at this framerate (default 50), or 'game' for one frame per game frame", "FPS"),
+    ("scale", "scale dumped frames by this factor (nearest neighbour)", "FACTOR"),
+    ("codec", "ffmpeg video codec (default libx264rgb)", "CODEC"),
+    ("codec-args", "extra ffmpeg video codec arguments (default '-preset veryslow -qp 0')", "ARGS"),
+    ("audio-codec", "ffmpeg audio codec (default flac)", "CODEC"),
+    ("container", "ffmpeg container format (default: guessed from the dump path)", "FORMAT"),
+    ("mux", "combine ffmpeg audio and video into one file (default yes)", "yes|no"),
+    ("resize", "fit frames to the first frame's size by 'scale' (default) or 'pad'", "MODE"),
+    ("config", "read any of the --dump-* settings from the [dump] section of this ini", "FILE"),
+];
+
+/// Builds the settings for dumping audio and video out of the --dump-* options and the --dump-config file.
+/// The file is an ini with a [dump] section, whose keys are the names of the options minus "dump-".
+fn parse_dump_settings(matches: &getopts::Matches) -> Result<DumpSettings, String> {
+    let config = match matches.opt_str("dump-config") {
+        Some(path) => Some(ini::Ini::load_from_file(&path).map_err(|e| format!("couldn't load '{}': {}", path, e))?),
+        None => None,
+    };
+    let option = |name: &str| {
+        matches
+            .opt_str(&format!("dump-{}", name))
+            .or_else(|| config.as_ref().and_then(|c| c.get_from(Some("dump"), name)).map(String::from))
+    };
+
+    let backend = match option("backend").as_deref() {
+        Some("builtin") => dump::Backend::Builtin,
+        Some("ffmpeg") | None => dump::Backend::Ffmpeg,
+        Some(other) => return Err(format!("unknown dump backend '{}', expected 'builtin' or 'ffmpeg'", other)),
+    };
+    let mut settings = DumpSettings::new(backend);
+    // the game's directory becomes the working directory once it launches, so resolve this beforehand
+    settings.path = absolute(option("path").map(PathBuf::from).unwrap_or(settings.path));
+    if let Some(range) = option("frames") {
+        (settings.first_frame, settings.last_frame) = dump::parse_frames(&range)?;
+    }
+    if let Some(fps) = option("fps") {
+        settings.framerate = dump::parse_framerate(&fps)?;
+    }
+    if let Some(scale) = option("scale") {
+        settings.scale = match scale.parse::<f64>() {
+            Ok(scale) if scale > 0.0 => scale,
+            _ => return Err(format!("invalid dump scale '{}'", scale)),
+        };
+    }
+    if let Some(codec) = option("codec") {
+        settings.ffmpeg.video_codec = codec;
+    }
+    if let Some(args) = option("codec-args") {
+        settings.ffmpeg.video_args = args.split_whitespace().map(String::from).collect();
+    }
+    if let Some(codec) = option("audio-codec") {
+        settings.ffmpeg.audio_codec = codec;
+    }
+    settings.ffmpeg.container = option("container");
+    if let Some(mux) = option("mux") {
+        settings.ffmpeg.mux = match mux.as_str() {
+            "yes" | "true" | "1" => true,
+            "no" | "false" | "0" => false,
+            _ => return Err(format!("invalid dump mux setting '{}', expected 'yes' or 'no'", mux)),
+        };
+    }
+    if let Some(resize) = option("resize") {
+        settings.ffmpeg.resize = dump::parse_resize(&resize)?;
+    }
+    Ok(settings)
+}
+
+/// Builds the settings for capturing frames of a replay out of the --screenshot-* options.
+fn parse_screenshot_settings(matches: &getopts::Matches) -> Result<ScreenshotSettings, String> {
+    let mut settings = ScreenshotSettings::default();
+    if let Some(range) = matches.opt_str("screenshot-frames") {
+        (settings.first_frame, settings.last_frame) = dump::parse_frames(&range)?;
+    }
+    settings.dir = absolute(matches.opt_str("screenshot-dir").map(PathBuf::from).unwrap_or(settings.dir));
+    settings.reference_dir = matches.opt_str("screenshot-compare").map(absolute);
+    if let Some(tolerance) = matches.opt_str("screenshot-tolerance") {
+        settings.tolerance =
+            tolerance.parse().map_err(|_| format!("invalid screenshot tolerance '{}', expected 0-255", tolerance))?;
+    }
+    Ok(settings)
+}
+
 fn main() {
     process::exit(xmain());
 }
@@ -54,11 +154,26 @@ fn xmain() -> i32 {
     opts.optflag("v", "verbose", "enables verbose logging");
     opts.optflag("r", "realtime", "disables clock spoofing");
     opts.optflag("d", "dump-video", "dump audiovideo encode on replay");
+    for (name, description, hint) in DUMP_OPTIONS {
+        opts.optopt("", &format!("dump-{}", name), description, hint);
+    }
+    opts.optopt("", "screenshot-frames", "save these replay frames as PNGs, as FRAME, START-END or -END", "RANGE");
+    opts.optopt("", "screenshot-dir", "where to save screenshots (default: 'screenshots')", "DIR");
+    opts.optopt("", "screenshot-compare", "fail if screenshots differ from the same-named images in DIR", "DIR");
+    opts.optopt("", "screenshot-tolerance", "how far a colour channel can be from the reference (default 0)", "N");
     opts.optflagopt("l", "no-framelimit-until", "disables the frame-limiter until specified frame", "FRAME");
     opts.optopt("n", "project-name", "name of TAS project to create or load", "NAME");
     opts.optopt("f", "replay-file", "path to savestate file to replay", "FILE");
     opts.optopt("o", "output-file", "output savestate name in replay mode", "FILE.bin");
     opts.optmulti("a", "game-arg", "argument to pass to the game", "ARG");
+    opts.optflag("", "sandbox", "keep files written by the game in memory (always on when recording or replaying)");
+    opts.optopt("", "sandbox-seed", "start the sandbox with the files in DIR, as if they were in the game dir", "DIR");
+    opts.optopt("", "registry", "load the game's registry from FILE (and save it there in normal play)", "FILE");
+    opts.optopt("", "trace", "write every event and instance creation/destruction to FILE", "FILE");
+    opts.optopt("", "soundfont", "play MIDI music with this SoundFont (default: soundfont.sf2 by the emulator)", "FILE");
+    opts.optopt("", "externals", "stub external functions as set out in FILE (recordings use the project's)", "FILE");
+    opts.optflag("", "list-extensions", "print every extension function and how it will be called, then exit");
+    opts.optopt("", "profile", "write a GML profile to FILE after a replay (plus FILE.folded)", "FILE");
     opts.optflagopt("p", "start-save", "Either loads the savestate specified after this parameter or starts at the first frame. If a .gmtas is specified by -f this will start the replay from this savestate instead", "savestate");
 
     let matches = match opts.parse(&args[1..]) {
@@ -84,7 +199,30 @@ fn xmain() -> i32 {
     let strict = matches.opt_present("s");
     let multithread = !matches.opt_present("t");
     let spoof_time = !matches.opt_present("r");
-    let dump_audiovideo = matches.opt_present("d");
+    let dump_settings = if matches.opt_present("d")
+        || DUMP_OPTIONS.iter().any(|(name, ..)| matches.opt_present(&format!("dump-{}", name)))
+    {
+        match parse_dump_settings(&matches) {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                eprintln!("{}", e);
+                return EXIT_FAILURE;
+            },
+        }
+    } else {
+        None
+    };
+    let screenshot_settings = if matches.opt_present("screenshot-frames") || matches.opt_present("screenshot-compare") {
+        match parse_screenshot_settings(&matches) {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                eprintln!("{}", e);
+                return EXIT_FAILURE;
+            },
+        }
+    } else {
+        None
+    };
     let frame_limit_at = matches
         .opt_str("l")
         .map(|frame| match frame.parse::<usize>() {
@@ -97,6 +235,15 @@ fn xmain() -> i32 {
     let frame_limiter = !matches.opt_present("l");
     let verbose = matches.opt_present("v");
     let output_bin = matches.opt_str("o").map(PathBuf::from);
+    let profile_path = matches.opt_str("profile").map(absolute);
+    let trace_path = matches.opt_str("trace").map(absolute);
+    let externals_path = matches.opt_str("externals").map(absolute);
+    let registry_path = matches.opt_str("registry").map(absolute);
+    let soundfont_path = matches.opt_str("soundfont").map(absolute).or_else(|| {
+        env::current_exe().ok().map(|exe| exe.with_file_name("soundfont.sf2")).filter(|path| path.is_file())
+    });
+    let sandbox_seed = matches.opt_str("sandbox-seed").map(absolute);
+    let sandbox = matches.opt_present("sandbox") || sandbox_seed.is_some();
     let pause = matches.opt_present("p");
     let start_save_path = matches.opt_str("p").map(PathBuf::from);
     let project_path = matches.opt_str("n").map(|name| {
@@ -234,6 +381,55 @@ fn xmain() -> i32 {
         PlayType::Normal
     };
 
+    // registry changes are only written back when playing normally, in the other modes they live in savestates
+    // replays start with the registry they were recorded with, so they can't be given a different one
+    if play_type == PlayType::Replay && registry_path.is_some() {
+        eprintln!("--registry doesn't apply to replays, they use the registry they were recorded with");
+        return EXIT_FAILURE;
+    }
+    // likewise for the sandbox's starting files
+    if play_type == PlayType::Replay && sandbox_seed.is_some() {
+        eprintln!("--sandbox-seed doesn't apply to replays, they use the files they were recorded with");
+        return EXIT_FAILURE;
+    }
+    let registry_path = registry_path.or_else(|| match play_type {
+        PlayType::Normal => Some(absolute_path.with_extension("registry")),
+        PlayType::Record => project_path.as_ref().map(|p| p.join("registry.reg")),
+        PlayType::Replay => None,
+    });
+
+    // stubs are given to the game as it launches so they apply to its extensions, even ones whose DLLs can't load
+    // recordings take their stubs from the project and replays from the replay file, so they always match
+    if play_type != PlayType::Normal && externals_path.is_some() {
+        eprintln!("--externals only applies to normal play, put an externals.ini in the project instead");
+        return EXIT_FAILURE;
+    }
+    let externals_path = externals_path.or_else(|| match play_type {
+        PlayType::Record => project_path.as_ref().map(|p| p.join("externals.ini")).filter(|p| p.exists()),
+        PlayType::Normal | PlayType::Replay => None,
+    });
+    let stubs = match (&replay, &externals_path) {
+        (Some(replay), _) => replay.external_stubs.clone(),
+        (None, Some(path)) => match stub::load(path) {
+            Ok(stubs) => stubs,
+            Err(e) => {
+                eprintln!("failed to load external stubs: {}", e);
+                return EXIT_FAILURE;
+            },
+        },
+        (None, None) => Vec::new(),
+    };
+    // plugin paths are relative to where the stubs came from, which for replays is wherever the replay file is,
+    // though plugins aren't loaded at all when the replay has their results
+    let plugin_dir = match &replay {
+        Some(_) => matches.opt_str("f").map(absolute),
+        None => externals_path.clone(),
+    }
+    .and_then(|path| path.parent().map(Path::to_path_buf))
+    .unwrap_or_default();
+    let result_mode = replay.as_ref().map(|r| r.external_result_mode(play_type)).unwrap_or(ResultMode::Live);
+    let externals = ExternalManager::new(play_type == PlayType::Record, stubs, plugin_dir, result_mode);
+
     let mut components = match Game::launch(
         assets,
         absolute_path,
@@ -242,8 +438,9 @@ fn xmain() -> i32 {
         encoding,
         frame_limiter,
         frame_limit_at,
-        dump_audiovideo,
+        dump_settings,
         play_type,
+        externals,
     ) {
         Ok(g) => g,
         Err(e) => {
@@ -252,11 +449,67 @@ fn xmain() -> i32 {
         },
     };
 
+    components.profiler.set_enabled(profile_path.is_some());
+    if let Some(settings) = screenshot_settings {
+        match Screenshotter::new(settings) {
+            Ok(screenshotter) => components.screenshotter = Some(screenshotter),
+            Err(e) => {
+                eprintln!("failed to create screenshot directory: {}", e);
+                return EXIT_FAILURE;
+            },
+        }
+    }
+    if sandbox || play_type != PlayType::Normal {
+        components.vfs.enable_sandbox();
+        if let Some(dir) = &sandbox_seed {
+            if let Err(e) = components.vfs.seed_sandbox(dir) {
+                eprintln!("failed to copy '{}' into the sandbox: {}", dir.to_string_lossy(), e);
+                return EXIT_FAILURE;
+            }
+        }
+        if let Some(state) = replay.as_ref().and_then(|r| r.sandbox.clone()) {
+            components.vfs.load_state(Some(state));
+        }
+    }
+    if let Some(path) = &registry_path {
+        if let Err(e) = components.load_registry(path, play_type == PlayType::Normal) {
+            eprintln!("failed to load registry file '{}': {}", path.to_string_lossy(), e);
+            return EXIT_FAILURE;
+        }
+    }
+    if let Some(path) = &soundfont_path {
+        let soundfont = fs::read(path).map_err(|e| e.to_string());
+        if let Err(e) = soundfont.and_then(|data| components.audio.load_soundfont(&data)) {
+            eprintln!("failed to load SoundFont '{}': {}", path.to_string_lossy(), e);
+            return EXIT_FAILURE;
+        }
+    }
+    if let Some(path) = &trace_path {
+        if let Err(e) = components.tracer.start(path) {
+            eprintln!("failed to create trace file '{}': {}", path.to_string_lossy(), e);
+            return EXIT_FAILURE;
+        }
+    }
+    if matches.opt_present("list-extensions") {
+        for line in components.extension_function_report() {
+            println!("{}", line);
+        }
+        return EXIT_SUCCESS;
+    }
+
+    // recordings store the sandbox as it was before this, since playback exports the included files again
+    let starting_files = components.vfs.state();
+    // this happens once the sandbox is set up so that the files go into it
+    if let Err(e) = components.export_included_files() {
+        eprintln!("failed to export included files: {}", e);
+        return EXIT_FAILURE;
+    }
+
     let time_now = gml::datetime::now_as_nanos();
 
     if let Err(err) = if let Some(path) = project_path {
         components.spoofed_time_nanos = Some(time_now);
-        components.record(path, pause, start_save_path.as_ref());
+        components.record(path, pause, start_save_path.as_ref(), starting_files);
         Ok(())
     } else {
         // cache temp_dir and included files because the other functions take ownership
@@ -271,14 +524,16 @@ fn xmain() -> i32 {
             .filter(|i| i.remove_at_end)
             .map(|i| PathBuf::from(components.decode_str(i.name.as_ref()).into_owned()))
             .collect::<Vec<_>>();
+        // included files are exported through the sandbox, if there is one, so they must be removed through it too
+        let vfs = components.vfs.clone();
         let result = if let Some(replay) = replay {
-            components.replay(replay, output_bin, start_save_path.as_ref())
+            components.replay(replay, output_bin, start_save_path.as_ref(), profile_path)
         } else {
             components.spoofed_time_nanos = if spoof_time { Some(time_now) } else { None };
             components.run()
         };
         for file in files_to_delete.into_iter() {
-            std::fs::remove_file(file).ok();
+            vfs.delete(&file.to_string_lossy()).ok();
         }
         if let Some(temp_dir) = temp_dir {
             std::fs::remove_dir_all(temp_dir).ok();